@@ -0,0 +1,78 @@
+-- Tables written by the `clickhouse` channel output.
+
+CREATE TABLE IF NOT EXISTS pnl_reports (
+    chain UInt64,
+    block UInt64,
+    timestamp DateTime,
+    wallet String,
+    address String,
+    txs Array(String),
+    pnl Int256,
+    builder_reward UInt256,
+    validator_bribe UInt256
+) ENGINE = MergeTree ORDER BY (chain, address, block);
+
+CREATE TABLE IF NOT EXISTS pnl_token_changes (
+    chain UInt64,
+    block UInt64,
+    timestamp DateTime,
+    wallet String,
+    address String,
+    token String,
+    change Int256
+) ENGINE = MergeTree ORDER BY (chain, address, token, block);
+
+-- Dashboard views. Amounts are converted to Float64 in native units (1e18)
+-- so Grafana can plot them directly; use the raw tables for exact values.
+
+CREATE VIEW IF NOT EXISTS pnl_daily AS
+SELECT
+    toDate(timestamp) AS day,
+    chain,
+    wallet,
+    address,
+    count() AS reports,
+    sum(length(txs)) AS txs,
+    sum(pnl) / 1e18 AS pnl,
+    sum(builder_reward) / 1e18 AS builder_reward,
+    sum(validator_bribe) / 1e18 AS validator_bribe
+FROM pnl_reports
+GROUP BY day, chain, wallet, address;
+
+CREATE VIEW IF NOT EXISTS pnl_cumulative AS
+SELECT
+    day,
+    chain,
+    wallet,
+    address,
+    sum(pnl) OVER (PARTITION BY chain, address ORDER BY day) AS cumulative_pnl
+FROM pnl_daily;
+
+-- Raw token units: decimals are not known to the database.
+CREATE VIEW IF NOT EXISTS token_exposure_daily AS
+SELECT
+    day,
+    chain,
+    wallet,
+    address,
+    token,
+    sum(change) OVER (PARTITION BY chain, address, token ORDER BY day) AS balance_change
+FROM (
+    SELECT toDate(timestamp) AS day, chain, wallet, address, token, sum(change) AS change
+    FROM pnl_token_changes
+    GROUP BY day, chain, wallet, address, token
+);
+
+-- Hour of day (UTC) by day of week, 1 for Monday. The `stats` command reads
+-- the same aggregation in any timezone.
+CREATE VIEW IF NOT EXISTS pnl_heatmap AS
+SELECT
+    chain,
+    wallet,
+    address,
+    toDayOfWeek(timestamp) AS day_of_week,
+    toHour(timestamp) AS hour,
+    count() AS reports,
+    sum(pnl) / 1e18 AS pnl
+FROM pnl_reports
+GROUP BY chain, wallet, address, day_of_week, hour;
@@ -0,0 +1,121 @@
+use {
+    crate::storage::{Backfill, Checkpoint, StoredReport},
+    eyre::{bail, ensure, Context, ContextCompat},
+    serde::{Deserialize, Serialize},
+    std::{
+        fs::File,
+        io::{BufRead, BufReader, BufWriter, Write},
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// Bumped when the archive format changes
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// A line of an archive, JSON lines written by `state export` and by
+/// retention before pruning. The first one is always the header
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Entry {
+    Header {
+        version: u32,
+        created_at: u64,
+    },
+    Report(StoredReport),
+    Backfill(Backfill),
+    Checkpoint(Checkpoint),
+    /// A redis key as `DUMP` serializes it, with its remaining time to live
+    Redis {
+        key: String,
+        ttl_ms: Option<u64>,
+        dump: String,
+    },
+}
+
+enum Sink {
+    Plain(BufWriter<File>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+/// Writes entries to a new archive, compressed with zstd when its path ends
+/// in `.zst`. The header is written on creation
+pub struct ArchiveWriter {
+    sink: Sink,
+}
+
+impl ArchiveWriter {
+    pub fn create(path: &str) -> eyre::Result<Self> {
+        let file = BufWriter::new(File::create(path).with_context(|| format!("Failed to create archive {path}"))?);
+        let sink = if path.ends_with(".zst") {
+            Sink::Zstd(zstd::Encoder::new(file, 0).context("Failed to start compressing archive")?)
+        } else {
+            Sink::Plain(file)
+        };
+
+        let mut writer = Self { sink };
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        writer.write(&Entry::Header {
+            version: ARCHIVE_VERSION,
+            created_at,
+        })?;
+
+        Ok(writer)
+    }
+
+    pub fn write(&mut self, entry: &Entry) -> eyre::Result<()> {
+        let out: &mut dyn Write = match &mut self.sink {
+            Sink::Plain(out) => out,
+            Sink::Zstd(out) => out,
+        };
+        serde_json::to_writer(&mut *out, entry).context("Failed to serialize entry")?;
+        writeln!(out).context("Failed to write archive")
+    }
+
+    /// Flush the archive. What was written may be incomplete until then
+    pub fn finish(self) -> eyre::Result<()> {
+        let mut file = match self.sink {
+            Sink::Plain(file) => file,
+            Sink::Zstd(encoder) => encoder.finish().context("Failed to compress archive")?,
+        };
+        file.flush().context("Failed to write archive")
+    }
+}
+
+/// The entries of the archive at `path` after its header, decompressed when
+/// the path ends in `.zst`, and when it was created
+pub fn read(path: &str) -> eyre::Result<(u64, impl Iterator<Item = eyre::Result<Entry>>)> {
+    let file = File::open(path).with_context(|| format!("Failed to open archive {path}"))?;
+    let reader: Box<dyn BufRead> = if path.ends_with(".zst") {
+        Box::new(BufReader::new(
+            zstd::Decoder::new(file).context("Failed to decompress archive")?,
+        ))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+    let mut lines = reader.lines();
+
+    let header = lines.next().context("The archive is empty")??;
+    let created_at = match serde_json::from_str(&header).context("Invalid archive header")? {
+        Entry::Header { version, created_at } => {
+            ensure!(
+                version == ARCHIVE_VERSION,
+                "Archive version {version} is not supported, expected {ARCHIVE_VERSION}"
+            );
+            created_at
+        }
+        _ => bail!("The archive doesn't start with a header"),
+    };
+
+    let entries = lines.enumerate().map(|(index, line)| {
+        let line = line.context("Failed to read archive")?;
+        match serde_json::from_str(&line).with_context(|| format!("Invalid entry on line {}", index + 2))? {
+            Entry::Header { .. } => bail!("Unexpected header on line {}", index + 2),
+            entry => Ok(entry),
+        }
+    });
+
+    Ok((created_at, entries))
+}
@@ -0,0 +1,113 @@
+use {
+    crate::executor::Action,
+    burberry::Executor,
+    eyre::Context,
+    serde::{Deserialize, Serialize},
+    std::{
+        fs::{File, OpenOptions},
+        io::{BufRead, BufReader, Write},
+        sync::{Arc, Mutex},
+        time::{SystemTime, UNIX_EPOCH},
+    },
+    tracing::warn,
+};
+
+/// A notification as it was sent, one JSON line of the audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Unix seconds the send finished at
+    pub timestamp: u64,
+    /// Output it went through, e.g. `telegram`
+    pub output: String,
+    /// Chat, phone numbers or host it went to
+    pub target: String,
+    /// Text exactly as rendered for the output
+    pub text: String,
+    pub delivered: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Append-only JSON lines file of every notification sent
+pub struct AuditLog {
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    pub fn open(path: &str) -> eyre::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open audit log {path}"))?;
+
+        Ok(Self { file: Mutex::new(file) })
+    }
+
+    pub fn record(&self, entry: &AuditEntry) -> eyre::Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        // One write per line, so lines of concurrent sends don't interleave
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes()).context("Failed to write audit log")
+    }
+}
+
+/// Read every entry of an audit log, oldest first
+pub fn read_entries(path: &str) -> eyre::Result<Vec<AuditEntry>> {
+    let file = File::open(path).with_context(|| format!("Failed to open audit log {path}"))?;
+
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|(i, line)| {
+            let line = line.context("Failed to read audit log")?;
+            serde_json::from_str(&line).with_context(|| format!("Invalid entry on line {}", i + 1))
+        })
+        .collect()
+}
+
+/// Records the notifications of one output's executor, with whether they got
+/// through. Every executor sees every action, so only those of `output` are
+/// recorded.
+pub struct Audited<E> {
+    output: &'static str,
+    inner: E,
+    log: Arc<AuditLog>,
+}
+
+impl<E> Audited<E> {
+    pub fn new(output: &'static str, inner: E, log: Arc<AuditLog>) -> Self {
+        Self { output, inner, log }
+    }
+}
+
+#[burberry::async_trait]
+impl<E: Executor<Action> + Send + Sync> Executor<Action> for Audited<E> {
+    async fn execute(&self, action: Action) -> eyre::Result<()> {
+        let notification = action.notification().filter(|(output, ..)| *output == self.output);
+        let result = self.inner.execute(action).await;
+
+        if let Some((output, target, text)) = notification {
+            let entry = AuditEntry {
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                output: output.to_string(),
+                target,
+                text,
+                delivered: result.is_ok(),
+                error: result.as_ref().err().map(|err| format!("{err:#}")),
+            };
+
+            if let Err(err) = self.log.record(&entry) {
+                warn!(output, "Failed to record notification: {err:#}");
+            }
+        }
+
+        result
+    }
+}
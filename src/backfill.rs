@@ -0,0 +1,261 @@
+use {
+    crate::{
+        chain_adapter::ChainRules,
+        config::{TraceConfig, WalletWithContext},
+        executor::ReportEvent,
+        price::PriceOracle,
+        processor,
+        storage::{Storage, StoredReport},
+        utils,
+    },
+    alloy::{primitives::Address, providers::Provider, transports::Transport},
+    alloy_chains::Chain,
+    eyre::{Context, ContextCompat},
+    std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+        time::{SystemTime, UNIX_EPOCH},
+    },
+    tokio::task::JoinSet,
+    tracing::{info, warn},
+};
+
+/// Blocks fetched at once while backfilling next to the live engine
+const BACKFILL_CONCURRENCY: usize = 4;
+
+/// Wallets being onboarded, by chain id and address, so that a config reload
+/// doesn't start their backfill again
+static IN_FLIGHT: Mutex<HashSet<(u64, Address)>> = Mutex::new(HashSet::new());
+
+/// Releases the wallets of an onboarding from [`IN_FLIGHT`] when it ends,
+/// however it ends
+struct InFlight(Vec<(u64, Address)>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut in_flight = IN_FLIGHT.lock().unwrap();
+        for key in &self.0 {
+            in_flight.remove(key);
+        }
+    }
+}
+
+/// Reports of the wallets in a past block
+pub async fn scan_block<T: Clone + Transport>(
+    chain: Chain,
+    rules: &ChainRules,
+    provider: &dyn Provider<T>,
+    number: u64,
+    trace_config: &TraceConfig,
+    wallets: &[WalletWithContext],
+    lenient: bool,
+) -> eyre::Result<Vec<ReportEvent>> {
+    let mut block = provider
+        .get_block_by_number(number.into(), false)
+        .await
+        .context("Failed to get block")?
+        .context("Block not found")?;
+    utils::fill_block_producer(provider, chain, &mut block.header).await?;
+
+    let reports = if lenient {
+        let (receipt_and_traces, warnings) = utils::get_receipt_and_trace_lenient(provider, number, trace_config, &[])
+            .await
+            .context("Failed to get receipt and traces")?;
+        processor::process_block_lenient(chain, rules, &block.header, &receipt_and_traces, wallets, warnings)
+    } else {
+        let receipt_and_traces = utils::get_receipt_and_trace(provider, number, trace_config, &[])
+            .await
+            .context("Failed to get receipt and traces")?;
+        processor::process_block(chain, rules, &block.header, &receipt_and_traces, wallets)
+    }
+    .context("Failed to generate balance changes")?;
+
+    Ok(wallets
+        .iter()
+        .zip(reports)
+        .filter_map(|(wallet, report)| report.map(|report| ReportEvent::new(chain, &block, wallet, report)))
+        .collect())
+}
+
+/// Save the report of `event`, returning whether it was saved
+pub async fn save(storage: &dyn Storage, event: ReportEvent) -> bool {
+    let stored = StoredReport::from(event);
+    match storage.save(&stored).await {
+        Ok(()) => true,
+        Err(err) => {
+            warn!(block = stored.block, wallet = %stored.wallet, "Failed to save report: {err:#}");
+            false
+        }
+    }
+}
+
+/// Save the last `days` of reports of the wallets the store has never
+/// backfilled, e.g. ones just added to the config, so their history doesn't
+/// start today, valued in USD at the prices of their block with `prices`.
+/// Runs until done, next to the live engine. Wallets already being backfilled
+/// are skipped, and a backfill is only recorded when every block of it was
+/// saved, so a failed one is done again on the next start or reload
+#[allow(clippy::too_many_arguments)]
+pub async fn onboard<T: Clone + Transport>(
+    chain: Chain,
+    rules: ChainRules,
+    provider: Arc<dyn Provider<T>>,
+    wallets: Vec<WalletWithContext>,
+    days: u32,
+    trace_config: TraceConfig,
+    lenient: bool,
+    storage: Arc<dyn Storage>,
+    mut prices: Option<PriceOracle<T>>,
+) -> eyre::Result<()> {
+    let mut new_wallets = vec![];
+    for wallet in wallets {
+        if !storage.backfilled(chain.id(), wallet.address).await? {
+            new_wallets.push(wallet);
+        }
+    }
+
+    let _in_flight = {
+        let mut in_flight = IN_FLIGHT.lock().unwrap();
+        new_wallets.retain(|wallet| in_flight.insert((chain.id(), wallet.address)));
+        InFlight(new_wallets.iter().map(|wallet| (chain.id(), wallet.address)).collect())
+    };
+    if new_wallets.is_empty() {
+        return Ok(());
+    }
+
+    let to_block = provider
+        .get_block_number()
+        .await
+        .context("Failed to get latest block")?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let from_block = block_at(provider.as_ref(), now.saturating_sub(days as u64 * 86400), to_block).await?;
+
+    let names = new_wallets
+        .iter()
+        .map(|w| w.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    info!(%chain, from_block, to_block, wallets = %names, "Backfilling new wallets");
+
+    let wallets: Arc<[WalletWithContext]> = new_wallets.into();
+    let rules = Arc::new(rules);
+    let mut tasks = JoinSet::new();
+    let mut failed = vec![];
+
+    for number in from_block..=to_block {
+        if tasks.len() >= BACKFILL_CONCURRENCY {
+            if let Err(block) = join_next(&mut tasks, storage.as_ref(), &mut prices).await {
+                failed.push(block);
+            }
+        }
+
+        let provider = Arc::clone(&provider);
+        let wallets = Arc::clone(&wallets);
+        let rules = Arc::clone(&rules);
+        tasks.spawn(async move {
+            let result = scan_block(
+                chain,
+                &rules,
+                provider.as_ref(),
+                number,
+                &trace_config,
+                &wallets,
+                lenient,
+            )
+            .await;
+            (number, result)
+        });
+    }
+    while !tasks.is_empty() {
+        if let Err(block) = join_next(&mut tasks, storage.as_ref(), &mut prices).await {
+            failed.push(block);
+        }
+    }
+
+    if !failed.is_empty() {
+        failed.sort_unstable();
+        let blocks = failed
+            .iter()
+            .map(|block| block.map_or("?".to_string(), |b| b.to_string()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        warn!(
+            %chain,
+            wallets = %names,
+            failed = failed.len(),
+            "Backfill incomplete, not recording it so it runs again on the next start. Failed blocks: {blocks}"
+        );
+        return Ok(());
+    }
+
+    for wallet in wallets.iter() {
+        storage
+            .record_backfill(chain.id(), wallet.address, from_block, to_block)
+            .await?;
+    }
+    info!(%chain, wallets = %names, "Backfill done");
+
+    Ok(())
+}
+
+/// Save the reports of the next block done, valued with `prices`. Fails with
+/// the block when it or one of its reports failed, unknown if its task
+/// panicked
+async fn join_next<T: Clone + Transport>(
+    tasks: &mut JoinSet<(u64, eyre::Result<Vec<ReportEvent>>)>,
+    storage: &dyn Storage,
+    prices: &mut Option<PriceOracle<T>>,
+) -> Result<(), Option<u64>> {
+    match tasks.join_next().await {
+        Some(Ok((number, Ok(events)))) => {
+            let mut saved = true;
+            for mut event in events {
+                if let Some(prices) = prices.as_mut() {
+                    prices
+                        .value_report(event.block, event.timestamp, &mut event.report)
+                        .await;
+                }
+                saved &= save(storage, event).await;
+            }
+            if saved {
+                Ok(())
+            } else {
+                Err(Some(number))
+            }
+        }
+        Some(Ok((number, Err(err)))) => {
+            warn!(block = number, "Failed to backfill block: {err:#}");
+            Err(Some(number))
+        }
+        Some(Err(err)) => {
+            warn!("Backfill task failed: {err:#}");
+            Err(None)
+        }
+        None => Ok(()),
+    }
+}
+
+/// First block at or after `timestamp`, by binary search up to `latest`
+async fn block_at<T: Clone + Transport>(provider: &dyn Provider<T>, timestamp: u64, latest: u64) -> eyre::Result<u64> {
+    let (mut low, mut high) = (0, latest);
+
+    while low < high {
+        let mid = low + (high - low) / 2;
+        let block = provider
+            .get_block_by_number(mid.into(), false)
+            .await
+            .context("Failed to get block")?
+            .context("Block not found")?;
+
+        if block.header.timestamp < timestamp {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    Ok(low)
+}
@@ -1,7 +1,11 @@
 use {
-    crate::config::NATIVE_TOKEN,
+    crate::{
+        chain_adapter::{self, ChainRules},
+        config::NATIVE_TOKEN,
+    },
     alloy::primitives::{Address, I256, U256},
     alloy_chains::Chain,
+    schemars::JsonSchema,
     serde::{Deserialize, Serialize},
     std::{
         collections::HashMap,
@@ -108,8 +112,8 @@ impl std::fmt::Debug for BalanceChanges {
 }
 
 /// Token to balance changes
-#[derive(Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
-pub struct BalanceChange(HashMap<Address, I256>);
+#[derive(Clone, Default, Serialize, Deserialize, JsonSchema, Eq, PartialEq)]
+pub struct BalanceChange(#[schemars(with = "HashMap<String, String>")] HashMap<Address, I256>);
 
 impl BalanceChange {
     pub fn is_empty(&self) -> bool {
@@ -122,24 +126,41 @@ impl BalanceChange {
         }
     }
 
-    /// Extract ether from the balance change, including WETH
-    pub fn extract_ether(&mut self, chain: Chain) -> I256 {
-        let weth = chain
-            .named()
-            .and_then(|n| n.wrapped_native_token())
-            .and_then(|weth| self.remove(&weth))
-            .unwrap_or(I256::ZERO);
+    /// Extract ether from the balance change, including wrapped ether
+    pub fn extract_ether(&mut self, chain: Chain, rules: &ChainRules) -> I256 {
+        let weth = chain_adapter::wrapped_natives(chain, rules)
+            .iter()
+            .filter_map(|token| self.remove(token))
+            .fold(I256::ZERO, |acc, change| acc + change);
 
         let eth = self.remove(&NATIVE_TOKEN).unwrap_or(I256::ZERO);
 
         eth + weth
     }
 
+    /// Sum of the stablecoin changes at one USD each, in 18 decimals. The
+    /// stablecoins themselves are kept.
+    pub fn sum_stables(&self, stables: &HashMap<Address, u8>) -> I256 {
+        self.iter()
+            .filter_map(|(token, change)| stables.get(token).map(|decimals| scale_to_18(*change, *decimals)))
+            .fold(I256::ZERO, |acc, change| acc + change)
+    }
+
     pub fn retain_non_zero(&mut self) {
         self.retain(|_, v| !v.is_zero());
     }
 }
 
+fn scale_to_18(value: I256, decimals: u8) -> I256 {
+    let ten = I256::from_raw(U256::from(10));
+
+    match decimals.cmp(&18) {
+        std::cmp::Ordering::Less => value * ten.pow(U256::from(18 - decimals)),
+        std::cmp::Ordering::Equal => value,
+        std::cmp::Ordering::Greater => value / ten.pow(U256::from(decimals - 18)),
+    }
+}
+
 impl Deref for BalanceChange {
     type Target = HashMap<Address, I256>;
 
@@ -185,3 +206,136 @@ impl std::fmt::Debug for BalanceChange {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        proptest::{collection::vec, prelude::*},
+    };
+
+    const TOKENS: [Address; 3] = [
+        NATIVE_TOKEN,
+        Address::with_last_byte(0xa1),
+        Address::with_last_byte(0xa2),
+    ];
+
+    /// A transfer of one of [`TOKENS`] between a few accounts, the first of
+    /// which is the zero address that mints and burns
+    fn transfer() -> impl Strategy<Value = (Address, Address, Address, U256)> {
+        (0..TOKENS.len(), 0u8..6, 0u8..6, any::<u64>()).prop_map(|(token, from, to, value)| {
+            (
+                TOKENS[token],
+                Address::with_last_byte(from),
+                Address::with_last_byte(to),
+                U256::from(value),
+            )
+        })
+    }
+
+    fn apply(transfers: &[(Address, Address, Address, U256)]) -> BalanceChanges {
+        let mut bcs = BalanceChanges::default();
+        for (token, from, to, value) in transfers {
+            bcs.append_transfer(*token, *from, *to, *value);
+        }
+        bcs
+    }
+
+    fn total(bcs: &BalanceChanges, token: Address) -> I256 {
+        bcs.values()
+            .filter_map(|bc| bc.get(&token))
+            .fold(I256::ZERO, |sum, change| sum + *change)
+    }
+
+    proptest! {
+        #[test]
+        fn changes_add_up_to_minted_less_burnt(transfers in vec(transfer(), 0..32)) {
+            let bcs = apply(&transfers);
+
+            prop_assert!(!bcs.contains_key(&Address::ZERO));
+            for token in TOKENS {
+                let net = transfers
+                    .iter()
+                    .filter(|(t, from, to, _)| *t == token && from.is_zero() != to.is_zero())
+                    .fold(I256::ZERO, |sum, (_, from, _, value)| {
+                        let value = I256::from_raw(*value);
+                        if from.is_zero() { sum + value } else { sum - value }
+                    });
+                prop_assert_eq!(total(&bcs, token), net);
+            }
+        }
+
+        #[test]
+        fn order_of_transfers_does_not_matter(
+            (transfers, shuffled) in vec(transfer(), 0..32)
+                .prop_flat_map(|transfers| (Just(transfers.clone()), Just(transfers).prop_shuffle()))
+        ) {
+            let mut bcs = apply(&transfers);
+            let mut shuffled = apply(&shuffled);
+            bcs.retain_non_zero();
+            shuffled.retain_non_zero();
+
+            prop_assert_eq!(&*bcs, &*shuffled);
+        }
+
+        #[test]
+        fn retain_non_zero_only_drops_zeros(transfers in vec(transfer(), 0..32)) {
+            let all = apply(&transfers);
+            let mut retained = all.clone();
+            retained.retain_non_zero();
+
+            prop_assert!(retained.values().all(|bc| !bc.is_empty() && bc.values().all(|change| !change.is_zero())));
+            for (account, bc) in all.iter() {
+                for (token, change) in bc.iter() {
+                    let kept = retained.get(account).and_then(|bc| bc.get(token));
+                    prop_assert_eq!(kept.copied().unwrap_or(I256::ZERO), *change);
+                }
+            }
+        }
+
+        #[test]
+        fn extend_sums_by_token(
+            a in vec((0..TOKENS.len(), any::<i64>()), 0..8),
+            b in vec((0..TOKENS.len(), any::<i64>()), 0..8),
+        ) {
+            let change = |entries: &[(usize, i64)]| {
+                BalanceChange(
+                    entries
+                        .iter()
+                        .map(|(token, value)| (TOKENS[*token], I256::try_from(*value).unwrap()))
+                        .collect(),
+                )
+            };
+            let (a, b) = (change(&a), change(&b));
+            let mut sum = a.clone();
+            sum.extend(&b);
+
+            for token in TOKENS {
+                let expected =
+                    a.get(&token).copied().unwrap_or(I256::ZERO) + b.get(&token).copied().unwrap_or(I256::ZERO);
+                prop_assert_eq!(sum.get(&token).copied().unwrap_or(I256::ZERO), expected);
+            }
+        }
+
+        #[test]
+        fn extract_ether_takes_native_and_wrapped(
+            native in any::<i64>(),
+            wrapped in any::<i64>(),
+            other in any::<i64>(),
+        ) {
+            let chain = Chain::mainnet();
+            let weth = chain_adapter::wrapped_natives(chain, &ChainRules::default())[0];
+            let mut bc = BalanceChange(HashMap::from([
+                (NATIVE_TOKEN, I256::try_from(native).unwrap()),
+                (weth, I256::try_from(wrapped).unwrap()),
+                (TOKENS[1], I256::try_from(other).unwrap()),
+            ]));
+
+            let ether = bc.extract_ether(chain, &ChainRules::default());
+
+            prop_assert_eq!(ether, I256::try_from(native).unwrap() + I256::try_from(wrapped).unwrap());
+            prop_assert_eq!(bc.len(), 1);
+            prop_assert_eq!(bc.get(&TOKENS[1]).copied(), Some(I256::try_from(other).unwrap()));
+        }
+    }
+}
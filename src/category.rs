@@ -0,0 +1,92 @@
+use {
+    crate::{
+        chain_adapter::{self, ChainRules},
+        config::WalletWithContext,
+        contract::ERC20,
+        exchanges,
+        staking::StakingFlow,
+    },
+    alloy::{
+        primitives::{address, Address},
+        rpc::types::{trace::geth::CallFrame, AnyTransactionReceipt},
+        sol_types::SolCall,
+    },
+    alloy_chains::{Chain, NamedChain},
+    schemars::JsonSchema,
+    serde::{Deserialize, Serialize},
+};
+
+/// What part of a wallet's activity a share of its PnL comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PnlCategory {
+    /// Swaps and any other contract call
+    Trading,
+    /// Fees of the txs the wallet sent
+    Gas,
+    /// Txs with a staking flow, less what was staked or claimed
+    Staking,
+    /// Txs to a bridge, and deposits bridged in
+    Bridging,
+    /// Plain transfers, and txs of others moving the wallet's funds
+    Transfers,
+    /// Builder, validator or miner rewards
+    BlockProduction,
+}
+
+/// Which category the PnL of a tx counts under. Gas and block production
+/// aren't tied to a tx and are never returned
+pub fn classify(
+    chain: Chain,
+    rules: &ChainRules,
+    receipt: &AnyTransactionReceipt,
+    call_trace: &CallFrame,
+    wallet: &WalletWithContext,
+    staking: &[StakingFlow],
+) -> PnlCategory {
+    if !staking.is_empty() {
+        return PnlCategory::Staking;
+    }
+
+    let to = receipt.to.unwrap_or_default();
+    if chain_adapter::for_chain(chain).is_deposit_tx(receipt) || is_bridge(chain, rules, &to) {
+        return PnlCategory::Bridging;
+    }
+
+    let plain_transfer = call_trace.input.len() < 4 || call_trace.input.starts_with(&ERC20::transferCall::SELECTOR);
+    if !wallet.involved_wallets().contains(&receipt.from) || plain_transfer || exchanges::all().contains_key(&to) {
+        return PnlCategory::Transfers;
+    }
+
+    PnlCategory::Trading
+}
+
+/// Whether `address` is a canonical bridge of the chain, or one from the config
+fn is_bridge(chain: Chain, rules: &ChainRules, address: &Address) -> bool {
+    known_bridges(chain).contains(address) || rules.bridges.contains(address)
+}
+
+fn known_bridges(chain: Chain) -> Vec<Address> {
+    match chain.named() {
+        Some(NamedChain::Mainnet) => vec![
+            // Optimism and Base standard bridges and portals
+            address!("99C9fc46f92E8a1c0deC1b1747d010903E884bE1"),
+            address!("bEb5Fc579115071764c7423A4f12eDde41f106Ed"),
+            address!("3154Cf16ccdb4C6d922629664174b904d80F2C35"),
+            address!("49048044D57e1C92A77f79988d21Fa8fAF74E97e"),
+            // Arbitrum delayed inbox and gateway router
+            address!("4Dbd4fc535Ac27206064B68FfCf827b0A60BAB3f"),
+            address!("72Ce9c846789fdB6fC1f34aC4AD25Dd9ef7031ef"),
+            // zkSync Era
+            address!("32400084C286CF3E17e7B677ea9583e60a000324"),
+            // Polygon PoS
+            address!("A0c68C638235ee32657e8f720a23ceC1bFc77C77"),
+        ],
+        Some(NamedChain::Optimism | NamedChain::Base) => vec![address!("4200000000000000000000000000000000000010")],
+        Some(NamedChain::Arbitrum) => vec![
+            address!("5288c571Fd7aD117beA99bF60FE0846C4E84F933"),
+            address!("0000000000000000000000000000000000000064"),
+        ],
+        _ => vec![],
+    }
+}
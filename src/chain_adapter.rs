@@ -0,0 +1,386 @@
+use {
+    alloy::{
+        primitives::{address, Address, U256},
+        rpc::types::{AnyTransactionReceipt, Header},
+    },
+    alloy_chains::{Chain, NamedChain},
+    eyre::eyre,
+    serde::{Deserialize, Serialize},
+    std::collections::HashMap,
+};
+
+/// What the config says about a chain beyond what its adapter knows. Passed
+/// along with the chain to whatever applies its rules, so that every command
+/// reading the same config processes blocks the same way
+#[derive(Debug, Clone, Default)]
+pub struct ChainRules {
+    /// Wrapped native tokens on top of the known one
+    pub wrapped_natives: Vec<Address>,
+    /// Stablecoins to their decimals
+    pub stables: HashMap<Address, u8>,
+    /// The adapter's fee mode if unset
+    pub fee_mode: Option<FeeMode>,
+    pub skip_deposit_txs: bool,
+    /// The adapter's subsidy if unset
+    pub block_subsidy: Option<BlockSubsidy>,
+    /// Bridge contracts on top of the known ones
+    pub bridges: Vec<Address>,
+}
+
+/// How gas is priced, and so how much of it reaches the block producer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeMode {
+    /// EIP-1559: the base fee is burnt and the producer gets the rest
+    Eip1559,
+    /// No base fee: the producer gets the whole gas price
+    Legacy,
+}
+
+/// How the producer of a block is paid, and so what a wallet producing it earns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProducerModel {
+    /// Producing blocks isn't accounted for
+    None,
+    /// Proposer-builder separation: the builder earns the priority fees and
+    /// pays the proposer out of them
+    Builder,
+    /// The validator produces the block itself and keeps the priority fees
+    Validator,
+    /// Proof of work: the coinbase keeps the priority fees and is minted the
+    /// block subsidy
+    Miner,
+}
+
+/// Reward minted to the miner of every block, on top of the fees
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct BlockSubsidy {
+    /// In whole native tokens, e.g. 5.0
+    pub reward: f64,
+
+    /// Blocks per era, after each of which the reward is multiplied by
+    /// `era_factor`. A single era if unset
+    #[serde(default)]
+    pub era_blocks: Option<u64>,
+
+    #[serde(default = "BlockSubsidy::default_era_factor")]
+    pub era_factor: f64,
+}
+
+impl BlockSubsidy {
+    fn default_era_factor() -> f64 {
+        1.0
+    }
+
+    fn at(&self, number: u64) -> U256 {
+        let era = self
+            .era_blocks
+            .map_or(0, |blocks| number.saturating_sub(1) / blocks.max(1));
+        let reward = self.reward * self.era_factor.powi(era.min(i32::MAX as u64) as i32);
+        U256::from((reward * 1e18) as u128)
+    }
+}
+
+/// Where the balance changes of a tx are read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferSource {
+    /// Token transfer logs and value transfers of the call trace
+    Trace,
+    /// `Transfer` logs of the receipt, with the ones emitted by `native_token`
+    /// counted as the native token. Fees show up as transfers too.
+    ReceiptLogs { native_token: Address },
+}
+
+/// Chain specific behaviour of the processor. Anything that differs between
+/// chains goes here rather than into the processor.
+pub trait ChainAdapter: Send + Sync {
+    fn wrapped_native_token(&self, chain: Chain) -> Option<Address> {
+        chain
+            .named()
+            .and_then(|c| c.wrapped_native_token())
+            .map(|t| t.0 .0.into())
+    }
+
+    /// Whether the wrapped native token emits WETH9 `Deposit`/`Withdrawal`
+    /// events instead of mint and burn transfers
+    fn is_weth9(&self) -> bool {
+        false
+    }
+
+    fn transfer_source(&self) -> TransferSource {
+        TransferSource::Trace
+    }
+
+    /// Fee paid on top of `gas_used * effective_gas_price`
+    fn extra_fee(&self, _receipt: &AnyTransactionReceipt) -> eyre::Result<U256> {
+        Ok(U256::ZERO)
+    }
+
+    fn producer_model(&self) -> ProducerModel {
+        ProducerModel::None
+    }
+
+    fn fee_mode(&self) -> FeeMode {
+        FeeMode::Eip1559
+    }
+
+    /// Native tokens minted to the producer of block `number`
+    fn block_subsidy(&self, _number: u64) -> U256 {
+        U256::ZERO
+    }
+
+    /// Fee-less txs inserted by the chain itself, which don't belong to any
+    /// wallet's PnL
+    fn is_system_tx(&self, _header: &Header, _receipt: &AnyTransactionReceipt) -> bool {
+        false
+    }
+
+    /// Txs bridged in from another chain, e.g. OP stack deposits. They pay
+    /// no gas here and tip no one, but still move funds for the wallet
+    fn is_deposit_tx(&self, _receipt: &AnyTransactionReceipt) -> bool {
+        false
+    }
+
+    /// RPC method returning the producer of a block, for chains leaving the
+    /// coinbase empty
+    fn block_author_method(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// All wrapped native tokens of a chain, counted as the native token in PnL
+pub fn wrapped_natives(chain: Chain, rules: &ChainRules) -> Vec<Address> {
+    let known = for_chain(chain).wrapped_native_token(chain);
+    collect_wrapped_natives(rules, known)
+}
+
+/// Wrapped native tokens whose deposits and withdrawals are decoded from WETH9
+/// events rather than mint and burn transfers
+pub fn weth9_tokens(chain: Chain, rules: &ChainRules) -> Vec<Address> {
+    let adapter = for_chain(chain);
+    let known = adapter.wrapped_native_token(chain).filter(|_| adapter.is_weth9());
+    collect_wrapped_natives(rules, known)
+}
+
+fn collect_wrapped_natives(rules: &ChainRules, known: Option<Address>) -> Vec<Address> {
+    let mut tokens = known.into_iter().collect::<Vec<_>>();
+    tokens.extend(rules.wrapped_natives.iter().filter(|t| Some(**t) != known));
+    tokens
+}
+
+pub fn fee_mode(chain: Chain, rules: &ChainRules) -> FeeMode {
+    rules.fee_mode.unwrap_or_else(|| for_chain(chain).fee_mode())
+}
+
+pub fn block_subsidy(chain: Chain, rules: &ChainRules, number: u64) -> U256 {
+    match rules.block_subsidy {
+        Some(subsidy) => subsidy.at(number),
+        None => for_chain(chain).block_subsidy(number),
+    }
+}
+
+/// Chains whose adapter accounts for no producer are treated as mined when
+/// the rules pay a subsidy
+pub fn producer_model(chain: Chain, rules: &ChainRules) -> ProducerModel {
+    match for_chain(chain).producer_model() {
+        ProducerModel::None if rules.block_subsidy.is_some() => ProducerModel::Miner,
+        model => model,
+    }
+}
+
+/// Whether a tx is left out of every wallet's report: system txs always,
+/// deposit txs if so configured
+pub fn is_excluded_tx(chain: Chain, rules: &ChainRules, header: &Header, receipt: &AnyTransactionReceipt) -> bool {
+    let adapter = for_chain(chain);
+    adapter.is_system_tx(header, receipt) || (rules.skip_deposit_txs && adapter.is_deposit_tx(receipt))
+}
+
+/// Base fee per gas burnt rather than paid to the block producer. Zero on
+/// legacy chains, and for blocks without a base fee
+pub fn burnt_base_fee(chain: Chain, rules: &ChainRules, header: &Header) -> u128 {
+    match fee_mode(chain, rules) {
+        FeeMode::Eip1559 => header.base_fee_per_gas.map(u128::from).unwrap_or_default(),
+        FeeMode::Legacy => 0,
+    }
+}
+
+pub fn for_chain(chain: Chain) -> &'static dyn ChainAdapter {
+    match chain.named() {
+        Some(NamedChain::Mainnet) => &Ethereum,
+        Some(NamedChain::BinanceSmartChain) => &Bnb,
+        Some(NamedChain::Polygon) => &Polygon,
+        Some(NamedChain::Arbitrum | NamedChain::ArbitrumNova) => &Arbitrum,
+        Some(NamedChain::ZkSync) => &ZkSync,
+        Some(NamedChain::Classic) => &EthereumClassic { era_blocks: 5_000_000 },
+        Some(NamedChain::Mordor) => &EthereumClassic { era_blocks: 2_000_000 },
+        _ if chain.is_optimism() => &OpStack,
+        _ => &Generic,
+    }
+}
+
+pub struct Generic;
+
+impl ChainAdapter for Generic {}
+
+pub struct Ethereum;
+
+impl ChainAdapter for Ethereum {
+    fn is_weth9(&self) -> bool {
+        true
+    }
+
+    fn producer_model(&self) -> ProducerModel {
+        ProducerModel::Builder
+    }
+}
+
+/// Type of OP stack deposit txs
+const OP_DEPOSIT_TX_TYPE: u8 = 0x7e;
+
+pub struct OpStack;
+
+impl ChainAdapter for OpStack {
+    fn is_weth9(&self) -> bool {
+        true
+    }
+
+    /// The L1 data fee is charged separately from the L2 gas, and so is the
+    /// operator fee since Isthmus. Deposit txs have neither.
+    fn extra_fee(&self, receipt: &AnyTransactionReceipt) -> eyre::Result<U256> {
+        if self.is_deposit_tx(receipt) {
+            return Ok(U256::ZERO);
+        }
+
+        let l1_fee = receipt_field(receipt, "l1Fee")?;
+
+        let operator_fee = match (
+            receipt_field(receipt, "operatorFeeScalar")?,
+            receipt_field(receipt, "operatorFeeConstant")?,
+        ) {
+            (scalar, constant) if scalar.is_zero() && constant.is_zero() => U256::ZERO,
+            (scalar, constant) => U256::from(receipt.gas_used) * scalar / U256::from(1_000_000) + constant,
+        };
+
+        Ok(l1_fee + operator_fee)
+    }
+
+    /// The deposit setting the L1 block attributes, first in every block
+    fn is_system_tx(&self, _header: &Header, receipt: &AnyTransactionReceipt) -> bool {
+        const L1_ATTRIBUTES_DEPOSITOR: Address = address!("DeaDDEaDDeAdDeAdDEAdDEaddeAddEAdDEAd0001");
+        self.is_deposit_tx(receipt) && receipt.from == L1_ATTRIBUTES_DEPOSITOR
+    }
+
+    fn is_deposit_tx(&self, receipt: &AnyTransactionReceipt) -> bool {
+        receipt.inner.inner.r#type == OP_DEPOSIT_TX_TYPE
+    }
+}
+
+/// A hex quantity of a chain specific receipt field, zero if missing
+fn receipt_field(receipt: &AnyTransactionReceipt, name: &str) -> eyre::Result<U256> {
+    let value = receipt
+        .other
+        .get(name)
+        .and_then(|v| v.as_str())
+        .map(|s| s.trim_start_matches("0x").to_string())
+        .unwrap_or_default();
+
+    U256::from_str_radix(&value, 16).map_err(|_| eyre!("Failed to parse {name} {value}"))
+}
+
+/// `gas_used` already includes the L1 data cost, so the plain fee formula holds
+pub struct Arbitrum;
+
+impl ChainAdapter for Arbitrum {
+    /// ArbOS internal txs, e.g. the one starting each block
+    fn is_system_tx(&self, _header: &Header, receipt: &AnyTransactionReceipt) -> bool {
+        const ARBOS: Address = address!("00000000000000000000000000000000000a4b05");
+        receipt.from == ARBOS
+    }
+}
+
+pub struct Bnb;
+
+impl ChainAdapter for Bnb {
+    fn is_weth9(&self) -> bool {
+        true
+    }
+
+    fn producer_model(&self) -> ProducerModel {
+        ProducerModel::Validator
+    }
+
+    /// Blocks before the London fork have no base fee, and the ones after burn
+    /// none of it
+    fn fee_mode(&self) -> FeeMode {
+        FeeMode::Legacy
+    }
+
+    /// Validators hand the block's fees over to the validator set contract in
+    /// zero gas price txs at the end of the block. Those fees are already
+    /// counted as the validator's reward.
+    fn is_system_tx(&self, header: &Header, receipt: &AnyTransactionReceipt) -> bool {
+        receipt.from == header.miner && receipt.effective_gas_price == 0
+    }
+}
+
+pub struct Polygon;
+
+impl ChainAdapter for Polygon {
+    fn is_weth9(&self) -> bool {
+        true
+    }
+
+    fn producer_model(&self) -> ProducerModel {
+        ProducerModel::Validator
+    }
+
+    /// State syncs from L1
+    fn is_system_tx(&self, _header: &Header, receipt: &AnyTransactionReceipt) -> bool {
+        receipt.from.is_zero()
+    }
+
+    /// Bor leaves the coinbase empty and pays the fees to the block signer
+    fn block_author_method(&self) -> Option<&'static str> {
+        Some("bor_getAuthor")
+    }
+}
+
+/// Ethereum Classic and its Mordor testnet, still mined. The subsidy follows
+/// ECIP-1017: 5 ETC, cut by a fifth every era. Rewards for including uncles
+/// aren't counted.
+pub struct EthereumClassic {
+    era_blocks: u64,
+}
+
+impl ChainAdapter for EthereumClassic {
+    fn producer_model(&self) -> ProducerModel {
+        ProducerModel::Miner
+    }
+
+    fn fee_mode(&self) -> FeeMode {
+        FeeMode::Legacy
+    }
+
+    fn block_subsidy(&self, number: u64) -> U256 {
+        let era = number.saturating_sub(1) / self.era_blocks;
+        let mut reward = U256::from(5_000_000_000_000_000_000_u128);
+        for _ in 0..era {
+            reward = reward * U256::from(4) / U256::from(5);
+        }
+        reward
+    }
+}
+
+/// ETH on zkSync Era lives in the `L2BaseToken` system contract, which emits a
+/// `Transfer` for every movement of it, fees and refunds included, even when
+/// the tx failed. Its tracer doesn't return logs, so receipt logs are used.
+pub struct ZkSync;
+
+impl ChainAdapter for ZkSync {
+    fn transfer_source(&self) -> TransferSource {
+        TransferSource::ReceiptLogs {
+            native_token: address!("000000000000000000000000000000000000800a"),
+        }
+    }
+}
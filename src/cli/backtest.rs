@@ -1,15 +1,29 @@
 use {
+    super::{fees, rules::RulesArgs},
     crate::{
-        config::WalletWithContext,
+        balance_changes::BalanceChange,
+        chain_adapter::{self, ChainRules, TransferSource},
+        config::{TraceConfig, WalletWithContext},
+        migration,
         processor::{self, PnlReport},
         utils::{self, new_provider},
     },
-    alloy::{primitives::Address, providers::Provider, transports::Transport},
+    alloy::{
+        primitives::{Address, I256, U256},
+        providers::Provider,
+        transports::Transport,
+    },
     alloy_chains::Chain,
     clap::Parser,
     eyre::{ensure, eyre, Context, ContextCompat},
     serde::{Deserialize, Serialize},
-    std::{fs::File, sync::Arc, time::Duration},
+    std::{
+        collections::{BTreeMap, BTreeSet, HashMap},
+        fs::File,
+        hash::Hash,
+        sync::Arc,
+        time::Duration,
+    },
     tokio::{
         sync::{mpsc::unbounded_channel, Semaphore},
         time::Instant,
@@ -26,6 +40,23 @@ pub struct Args {
 
     #[arg(long, help = "Append to existing backtest data")]
     generate: bool,
+
+    #[arg(
+        long,
+        default_value_t = 0,
+        help = "Amount differences up to this many wei still pass. Overridden by `tolerance_wei` of a case"
+    )]
+    tolerance_wei: u64,
+
+    #[arg(
+        long,
+        conflicts_with = "generate",
+        help = "Also check the fee of every tx in the cases' blocks against the state diff, like check-fees"
+    )]
+    fees: bool,
+
+    #[command(flatten)]
+    rules: RulesArgs,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,10 +73,19 @@ pub struct TestCase {
     pub other_addresses: Vec<Address>,
     #[serde(default, skip_serializing_if = "is_false")]
     pub include_recipient: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tolerance_wei: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 
     // Output
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub report: Option<PnlReport>,
+
+    /// Whether `report` was generated before `pnl_by_category`, which then
+    /// isn't checked
+    #[serde(skip)]
+    pub uncategorized: bool,
 }
 
 impl std::fmt::Display for TestCase {
@@ -62,14 +102,22 @@ pub struct RunResult {
 }
 
 impl Args {
-    pub async fn run(self) {
-        let file = File::open(self.test_data.clone()).expect("Failed to open test data file");
-        let test_cases: Vec<TestCase> = serde_yaml::from_reader(file).expect("Failed to parse test data");
+    pub async fn run(self) -> eyre::Result<()> {
+        let test_cases = load_test_cases(&self.test_data)?;
+        let rules = self.rules.load()?;
 
-        let provider = new_provider(&self.rpc_url).await.expect("Failed to create provider");
+        let provider = new_provider(&self.rpc_url).await.context("Failed to create provider")?;
 
-        let chain = provider.get_chain_id().await.expect("Failed to get chain").into();
+        let chain: Chain = provider.get_chain_id().await.context("Failed to get chain")?.into();
+        if self.fees {
+            ensure!(
+                chain_adapter::for_chain(chain).transfer_source() == TransferSource::Trace,
+                "Fees of {chain} are read from transfer logs, not calculated"
+            );
+        }
+        let blocks = test_cases.iter().map(|case| case.block).collect::<BTreeSet<_>>();
         let rpc_url = self.rpc_url.clone();
+        let case_rules = Arc::new(rules.clone());
 
         let (sender, mut receiver) = unbounded_channel::<RunResult>();
 
@@ -79,11 +127,12 @@ impl Args {
                 let permit = semaphore.clone().acquire_owned().await.unwrap();
                 let sender = sender.clone();
                 let rpc_url = rpc_url.clone();
+                let rules = Arc::clone(&case_rules);
 
                 tokio::spawn(async move {
                     let start = Instant::now();
                     let report = match new_provider(&rpc_url).await {
-                        Ok(p) => worker(chain, p.as_ref(), &test_case).await,
+                        Ok(p) => worker(chain, &rules, p.as_ref(), &test_case).await,
                         Err(e) => Err(eyre!("Failed to create provider: {e:#}")),
                     };
                     let elapsed = start.elapsed();
@@ -104,6 +153,8 @@ impl Args {
         let mut generated_test_case = Vec::new();
         let mut unmatched_cases = Vec::new();
         let mut failed_cases = Vec::new();
+        let mut summary = Summary::default();
+        let mut uncategorized_cases = 0;
 
         while let Some(result) = receiver.recv().await {
             if let Some(err) = result.report.as_ref().err() {
@@ -112,6 +163,7 @@ impl Args {
                     result.test_case, result.elapsed, err
                 );
                 failed_cases.push(result.test_case.clone());
+                summary.record(&result.test_case, result.elapsed, Verdict::Failed);
             }
 
             if self.generate {
@@ -134,17 +186,34 @@ impl Args {
                 continue;
             };
 
-            let passed = report == result.test_case.report;
-            if !passed {
-                unmatched_cases.push((result.test_case.clone(), report));
+            if result.test_case.uncategorized {
+                uncategorized_cases += 1;
             }
 
-            println!(
-                "[{}] Elapsed: {:?}, {}",
-                result.test_case,
-                result.elapsed,
-                if passed { "Passed" } else { "Unmatched" }
-            );
+            let tolerance = U256::from(result.test_case.tolerance_wei.unwrap_or(self.tolerance_wei));
+            let outcome = if result.test_case.uncategorized {
+                compare(
+                    without_categories(result.test_case.report.as_ref()).as_ref(),
+                    without_categories(report.as_ref()).as_ref(),
+                    tolerance,
+                )
+            } else {
+                compare(result.test_case.report.as_ref(), report.as_ref(), tolerance)
+            };
+
+            let (status, verdict) = match outcome {
+                Outcome::Passed => ("Passed".to_string(), Verdict::Passed),
+                Outcome::WithinTolerance(delta) => {
+                    (format!("Passed (within tolerance, delta {delta} wei)"), Verdict::Passed)
+                }
+                Outcome::Unmatched => {
+                    unmatched_cases.push((result.test_case.clone(), report));
+                    ("Unmatched".to_string(), Verdict::Unmatched)
+                }
+            };
+            summary.record(&result.test_case, result.elapsed, verdict);
+
+            println!("[{}] Elapsed: {:?}, {}", result.test_case, result.elapsed, status);
         }
 
         if self.generate {
@@ -152,17 +221,12 @@ impl Args {
             generated_test_case.sort_by_key(|(i, _)| *i);
             let ordered_test_cases: Vec<_> = generated_test_case.into_iter().map(|(_, tc)| tc).collect();
 
-            let file = File::create(self.test_data).expect("Failed to create test data file");
-            serde_yaml::to_writer(file, &ordered_test_cases).expect("Failed to write test data");
-            return;
+            let file = File::create(self.test_data).context("Failed to create test data file")?;
+            serde_yaml::to_writer(file, &ordered_test_cases).context("Failed to write test data")?;
+            return Ok(());
         }
 
-        if unmatched_cases.is_empty() {
-            println!("All tests passed!");
-            return;
-        }
-
-        for (i, (test_case, report)) in unmatched_cases.into_iter().enumerate() {
+        for (i, (test_case, report)) in unmatched_cases.iter().enumerate() {
             let mut cmd = format!(
                 "RUST_LOG=wallet_watcher=trace cargo run run-block {} {}",
                 test_case.block, test_case.address
@@ -196,32 +260,106 @@ impl Args {
             println!("{report:#?}");
         }
 
-        println!("=== Failed Case ===");
-        for test_case in failed_cases {
-            println!("{test_case}");
+        if !failed_cases.is_empty() {
+            println!("=== Failed Case ===");
+            for test_case in &failed_cases {
+                println!("{test_case}");
+            }
+        }
+
+        summary.print();
+
+        let fee_mismatches = if self.fees {
+            println!("=== Fees ===");
+            let mut by_type = BTreeMap::new();
+            for block in blocks {
+                fees::check_block(
+                    provider.as_ref(),
+                    chain,
+                    &rules,
+                    block,
+                    U256::from(self.tolerance_wei),
+                    &mut by_type,
+                )
+                .await
+                .with_context(|| format!("Failed to check the fees of block {block}"))?;
+            }
+            fees::print_summary(&by_type)
+        } else {
+            0
+        };
+
+        if uncategorized_cases > 0 {
+            println!(
+                "{uncategorized_cases} cases predate pnl_by_category and didn't check it, regenerate them with --generate"
+            );
         }
+
+        ensure!(
+            unmatched_cases.is_empty() && failed_cases.is_empty() && fee_mismatches == 0,
+            "{} cases unmatched, {} failed and {fee_mismatches} fees mismatched",
+            unmatched_cases.len(),
+            failed_cases.len()
+        );
+        println!("All tests passed!");
+
+        Ok(())
     }
 }
 
+/// Test cases of a file, with their reports upgraded to the current version
+fn load_test_cases(path: &str) -> eyre::Result<Vec<TestCase>> {
+    let file = File::open(path).context("Failed to open test data file")?;
+    let raw: Vec<serde_json::Value> = serde_yaml::from_reader(file).context("Failed to parse test data")?;
+
+    raw.into_iter()
+        .enumerate()
+        .map(|(i, mut value)| {
+            let report = value
+                .as_object_mut()
+                .and_then(|case| case.remove("report"))
+                .filter(|report| !report.is_null());
+            let uncategorized = report
+                .as_ref()
+                .is_some_and(|report| report.get("pnl_by_category").is_none());
+            let report = report
+                .map(migration::migrate_report)
+                .transpose()
+                .with_context(|| format!("Failed to migrate the report of test case #{i}"))?;
+
+            let test_case: TestCase =
+                serde_json::from_value(value).with_context(|| format!("Failed to parse test case #{i}"))?;
+            Ok(TestCase {
+                report,
+                uncategorized,
+                ..test_case
+            })
+        })
+        .collect()
+}
+
 async fn worker<T: Clone + Transport>(
     chain: Chain,
+    rules: &ChainRules,
     provider: &dyn Provider<T>,
     test_case: &TestCase,
 ) -> eyre::Result<Option<PnlReport>> {
     println!("[{test_case}] Running");
 
-    let receipt_and_traces = utils::get_receipt_and_trace(provider, test_case.block)
+    let receipt_and_traces = utils::get_receipt_and_trace(provider, test_case.block, &TraceConfig::default(), &[])
         .await
         .context("Failed to get receipt and traces")?;
 
-    let block = provider
+    let mut block = provider
         .get_block_by_number(test_case.block.into(), false)
         .await
         .context("Failed to get block")?
         .context("Block not found")?;
+    utils::fill_block_producer(provider, chain, &mut block.header).await?;
 
     let reports = processor::process_block(
         chain,
+        rules,
         &block.header,
         &receipt_and_traces,
         &[WalletWithContext::new(
@@ -239,6 +377,204 @@ async fn worker<T: Clone + Transport>(
     Ok(reports.into_iter().next().unwrap())
 }
 
+enum Outcome {
+    Passed,
+    WithinTolerance(U256),
+    Unmatched,
+}
+
+fn compare(expected: Option<&PnlReport>, actual: Option<&PnlReport>, tolerance: U256) -> Outcome {
+    if expected == actual {
+        return Outcome::Passed;
+    }
+
+    let (Some(expected), Some(actual)) = (expected, actual) else {
+        return Outcome::Unmatched;
+    };
+
+    match max_amount_delta(expected, actual) {
+        Some(delta) if delta <= tolerance => Outcome::WithinTolerance(delta),
+        _ => Outcome::Unmatched,
+    }
+}
+
+/// The largest absolute difference across all amounts of two reports, or
+/// `None` if they differ in anything else: txs, warnings, new tokens, which
+/// vaults, perps, staking flows and deposits there are, and so on
+fn max_amount_delta(expected: &PnlReport, actual: &PnlReport) -> Option<U256> {
+    if without_amounts(expected) != without_amounts(actual) {
+        return None;
+    }
+
+    let abs_diff = |a: U256, b: U256| if a > b { a - b } else { b - a };
+    let signed = |a: I256, b: I256| (a - b).unsigned_abs();
+
+    let mut deltas = vec![
+        signed(expected.pnl, actual.pnl),
+        signed(expected.stable_pnl, actual.stable_pnl),
+        signed(expected.pnl_usd.unwrap_or_default(), actual.pnl_usd.unwrap_or_default()),
+        abs_diff(expected.builder_reward, actual.builder_reward),
+        abs_diff(expected.validator_bribe, actual.validator_bribe),
+    ];
+
+    deltas.extend(map_deltas(expected.token_changes.iter(), actual.token_changes.iter()));
+    deltas.extend(map_deltas(&expected.pnl_by_category, &actual.pnl_by_category));
+    deltas.extend(map_deltas(&expected.token_values_usd, &actual.token_values_usd));
+
+    // Same length and order, as checked above
+    let vault_assets = expected.vault_assets.iter().zip(&actual.vault_assets);
+    deltas.extend(vault_assets.map(|(e, a)| signed(e.assets, a.assets)));
+    for (e, a) in expected.perps.iter().zip(&actual.perps) {
+        deltas.push(signed(e.realized_pnl, a.realized_pnl));
+        deltas.push(signed(e.fees, a.fees));
+    }
+    let staking = expected.staking.iter().zip(&actual.staking);
+    deltas.extend(staking.map(|(e, a)| abs_diff(e.amount, a.amount)));
+    let cex_deposits = expected.cex_deposits.iter().zip(&actual.cex_deposits);
+    deltas.extend(cex_deposits.map(|(e, a)| signed(e.amount, a.amount)));
+
+    deltas.into_iter().max()
+}
+
+/// Differences of the amounts of two maps by key, a missing key counting as 0
+fn map_deltas<'a, K: Eq + Hash + 'a>(
+    expected: impl IntoIterator<Item = (&'a K, &'a I256)>,
+    actual: impl IntoIterator<Item = (&'a K, &'a I256)>,
+) -> Vec<U256> {
+    let mut amounts: HashMap<&K, (I256, I256)> = HashMap::new();
+    for (key, amount) in expected {
+        amounts.entry(key).or_insert((I256::ZERO, I256::ZERO)).0 = *amount;
+    }
+    for (key, amount) in actual {
+        amounts.entry(key).or_insert((I256::ZERO, I256::ZERO)).1 = *amount;
+    }
+
+    amounts.into_values().map(|(e, a)| (e - a).unsigned_abs()).collect()
+}
+
+/// The report without `pnl_by_category`, for cases generated before it that
+/// can't be checked against it
+fn without_categories(report: Option<&PnlReport>) -> Option<PnlReport> {
+    let mut report = report?.clone();
+    report.pnl_by_category.clear();
+    Some(report)
+}
+
+/// The report with every amount zeroed and the maps of amounts emptied, to
+/// compare what is left exactly
+fn without_amounts(report: &PnlReport) -> PnlReport {
+    let mut report = report.clone();
+
+    report.pnl = I256::ZERO;
+    report.stable_pnl = I256::ZERO;
+    report.pnl_usd = report.pnl_usd.map(|_| I256::ZERO);
+    report.builder_reward = U256::ZERO;
+    report.validator_bribe = U256::ZERO;
+    report.token_changes = BalanceChange::default();
+    report.pnl_by_category.clear();
+    report.token_values_usd.clear();
+    for vault_assets in &mut report.vault_assets {
+        vault_assets.assets = I256::ZERO;
+    }
+    for perps in &mut report.perps {
+        perps.realized_pnl = I256::ZERO;
+        perps.fees = I256::ZERO;
+    }
+    for flow in &mut report.staking {
+        flow.amount = U256::ZERO;
+    }
+    for deposit in &mut report.cex_deposits {
+        deposit.amount = I256::ZERO;
+    }
+
+    report
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Verdict {
+    Passed,
+    Unmatched,
+    Failed,
+}
+
+#[derive(Debug, Default)]
+struct Tally {
+    passed: usize,
+    unmatched: usize,
+    failed: usize,
+}
+
+impl Tally {
+    fn add(&mut self, verdict: Verdict) {
+        match verdict {
+            Verdict::Passed => self.passed += 1,
+            Verdict::Unmatched => self.unmatched += 1,
+            Verdict::Failed => self.failed += 1,
+        }
+    }
+}
+
+impl std::fmt::Display for Tally {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} passed, {} unmatched, {} failed",
+            self.passed, self.unmatched, self.failed
+        )
+    }
+}
+
+/// Pass/fail counts per tag and the latency of every case, printed once the
+/// run is over.
+#[derive(Debug, Default)]
+struct Summary {
+    total: Tally,
+    by_tag: BTreeMap<String, Tally>,
+    latencies: Vec<Duration>,
+}
+
+impl Summary {
+    fn record(&mut self, test_case: &TestCase, elapsed: Duration, verdict: Verdict) {
+        self.total.add(verdict);
+        self.latencies.push(elapsed);
+
+        if test_case.tags.is_empty() {
+            self.by_tag.entry("(untagged)".to_string()).or_default().add(verdict);
+        }
+
+        for tag in &test_case.tags {
+            self.by_tag.entry(tag.clone()).or_default().add(verdict);
+        }
+    }
+
+    fn print(mut self) {
+        println!("=== Summary ===");
+        println!("Total: {}", self.total);
+        for (tag, tally) in &self.by_tag {
+            println!("  {tag}: {tally}");
+        }
+
+        if self.latencies.is_empty() {
+            return;
+        }
+
+        self.latencies.sort();
+        let percentile = |p: usize| {
+            // nearest-rank
+            let rank = (self.latencies.len() * p).div_ceil(100).max(1);
+            self.latencies[rank - 1]
+        };
+
+        println!(
+            "Latency: p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+            percentile(50),
+            percentile(90),
+            percentile(99),
+            self.latencies[self.latencies.len() - 1]
+        );
+    }
+}
+
 fn is_false(v: &bool) -> bool {
     !v
 }
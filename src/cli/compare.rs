@@ -0,0 +1,253 @@
+use {
+    super::rules::RulesArgs,
+    crate::{
+        chain_adapter::ChainRules,
+        config::{ExplorerApi, TraceConfig, TraceMode, WalletWithContext},
+        explorer::{self, ExplorerClient},
+        processor::{self, PnlReport},
+        utils::{fill_block_producer, get_receipt_and_trace, new_provider},
+    },
+    alloy::{
+        primitives::{Address, I256},
+        providers::Provider,
+    },
+    alloy_chains::Chain,
+    clap::{Parser, ValueEnum},
+    eyre::{ensure, Context, ContextCompat},
+    serde_json::Value,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        sync::Arc,
+    },
+};
+
+/// Process a block twice, against two RPC endpoints (e.g. different clients)
+/// or in two trace modes, and print where the resulting reports differ
+#[derive(Debug, Clone, Parser)]
+pub struct Args {
+    block: u64,
+
+    #[arg(help = "The address of the wallet to monitor")]
+    address: Address,
+
+    #[arg(short, long, env = "ETH_RPC_URL", help = "Baseline RPC URL")]
+    rpc_url: String,
+
+    #[arg(long, help = "RPC URL to compare against the baseline, the baseline's if unset")]
+    against: Option<String>,
+
+    #[arg(
+        long,
+        alias = "trace-mode",
+        value_enum,
+        default_value_t = Mode::Block,
+        help = "Trace mode of the baseline"
+    )]
+    mode: Mode,
+
+    #[arg(
+        long,
+        alias = "against-trace-mode",
+        value_enum,
+        help = "Trace mode to compare against the baseline, the baseline's if unset"
+    )]
+    against_mode: Option<Mode>,
+
+    #[arg(long, help = "Etherscan-compatible API of the explorer mode")]
+    explorer_api: Option<String>,
+
+    #[arg(long, env = "EXPLORER_API_KEY", help = "Key of the explorer API")]
+    explorer_api_key: Option<String>,
+
+    #[arg(short, long, help = "The builder address of the wallet")]
+    builder: Option<Address>,
+
+    #[arg(
+        short = 'a',
+        long = "address",
+        help = "Other addresses to include in PnL calculations",
+        value_delimiter = ','
+    )]
+    other_addresses: Vec<Address>,
+
+    #[arg(long, help = "If true, the recipient will be included in PnL calculations")]
+    include_recipient: bool,
+
+    #[command(flatten)]
+    rules: RulesArgs,
+}
+
+/// The trace modes of the config, see [`TraceMode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "snake_case")]
+enum Mode {
+    Block,
+    PerTx,
+    LogsOnly,
+    Explorer,
+}
+
+impl From<Mode> for TraceMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Block => TraceMode::Block,
+            Mode::PerTx => TraceMode::PerTx,
+            Mode::LogsOnly => TraceMode::LogsOnly,
+            Mode::Explorer => TraceMode::Explorer,
+        }
+    }
+}
+
+impl Args {
+    pub async fn run(self) -> eyre::Result<()> {
+        let against = self.against.clone().unwrap_or_else(|| self.rpc_url.clone());
+        let against_mode = self.against_mode.unwrap_or(self.mode);
+        ensure!(
+            against != self.rpc_url || against_mode != self.mode,
+            "Both sides are the same, set --against or --against-mode"
+        );
+
+        let explorer = self.explorer_api.clone().map(|url| {
+            ExplorerClient::new(ExplorerApi {
+                url,
+                api_key: self.explorer_api_key.clone(),
+            })
+        });
+        ensure!(
+            explorer.is_some() || (self.mode != Mode::Explorer && against_mode != Mode::Explorer),
+            "The explorer mode needs --explorer-api"
+        );
+
+        let rules = self.rules.load()?;
+        let wallet = WalletWithContext::new(
+            "Unnamed".to_string(),
+            self.address,
+            self.builder,
+            self.other_addresses.clone(),
+            self.include_recipient,
+            Arc::default(),
+        );
+
+        let (a, b) = tokio::join!(
+            process(&self.rpc_url, self.mode, explorer.as_ref(), &rules, self.block, &wallet),
+            process(&against, against_mode, explorer.as_ref(), &rules, self.block, &wallet)
+        );
+
+        let a = a.context("Failed to process block against baseline")?;
+        let b = b.context("Failed to process block against comparison")?;
+
+        println!("Block {} · {}", self.block, self.address);
+        println!("A: {} · {:?}", self.rpc_url, self.mode);
+        println!("B: {against} · {against_mode:?}");
+
+        let lines = diff(a.as_ref(), b.as_ref())?;
+        if lines.is_empty() {
+            println!("Reports are identical");
+        }
+        for line in lines {
+            println!("{line}");
+        }
+
+        Ok(())
+    }
+}
+
+/// A line per field of the serialized reports that differs between them, with
+/// the delta of amounts. `report` when one of them is missing
+fn diff(a: Option<&PnlReport>, b: Option<&PnlReport>) -> eyre::Result<Vec<String>> {
+    let mut a_fields = BTreeMap::new();
+    flatten(&serde_json::to_value(a)?, "", &mut a_fields);
+    let mut b_fields = BTreeMap::new();
+    flatten(&serde_json::to_value(b)?, "", &mut b_fields);
+
+    let paths = a_fields.keys().chain(b_fields.keys()).collect::<BTreeSet<_>>();
+
+    let show = |value: Option<&Value>| match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+        None => "(none)".to_string(),
+    };
+    let amount = |value: Option<&Value>| value?.as_str()?.parse::<I256>().ok();
+
+    Ok(paths
+        .into_iter()
+        .filter(|path| a_fields.get(*path) != b_fields.get(*path))
+        .map(|path| {
+            let (a, b) = (a_fields.get(path), b_fields.get(path));
+            let field = if path.is_empty() { "report" } else { path };
+            let mut line = format!("{field}: A {} · B {}", show(a), show(b));
+            if let (Some(a), Some(b)) = (amount(a), amount(b)) {
+                line.push_str(&format!(" · Δ {}", b - a));
+            }
+            line
+        })
+        .collect())
+}
+
+/// Leaves of `value` by their path, e.g. `token_changes.0x…` or `txs[0]`
+fn flatten(value: &Value, path: &str, fields: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                flatten(value, &path, fields);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, value) in items.iter().enumerate() {
+                flatten(value, &format!("{path}[{i}]"), fields);
+            }
+        }
+        _ => {
+            fields.insert(path.to_string(), value.clone());
+        }
+    }
+}
+
+async fn process(
+    rpc_url: &str,
+    mode: Mode,
+    explorer: Option<&ExplorerClient>,
+    rules: &ChainRules,
+    block: u64,
+    wallet: &WalletWithContext,
+) -> eyre::Result<Option<PnlReport>> {
+    let provider = new_provider(rpc_url).await.context("Failed to create provider")?;
+    let chain: Chain = provider.get_chain_id().await.context("Failed to get chain id")?.into();
+
+    let config = TraceConfig {
+        mode: mode.into(),
+        ..Default::default()
+    };
+    let mut receipt_and_traces = get_receipt_and_trace(provider.as_ref(), block, &config, &[])
+        .await
+        .context("Failed to get receipt and traces")?;
+    if let (Mode::Explorer, Some(explorer)) = (mode, explorer) {
+        explorer::add_internal_txs(explorer, block, wallet.involved_wallets(), &mut receipt_and_traces)
+            .await
+            .context("Failed to get internal txs from the explorer")?;
+    }
+
+    let mut block = provider
+        .get_block_by_number(block.into(), false)
+        .await
+        .context("Failed to get block")?
+        .context("Block not found")?;
+    fill_block_producer(provider.as_ref(), chain, &mut block.header).await?;
+
+    let reports = processor::process_block(
+        chain,
+        rules,
+        &block.header,
+        &receipt_and_traces,
+        std::slice::from_ref(wallet),
+    )
+    .context("Failed to generate report")?;
+
+    ensure!(reports.len() == 1, "Expected exactly one report");
+    Ok(reports.into_iter().next().unwrap())
+}
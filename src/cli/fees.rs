@@ -0,0 +1,161 @@
+use {
+    super::rules::RulesArgs,
+    crate::{
+        chain_adapter::{self, ChainRules, TransferSource},
+        config::{TraceConfig, NATIVE_TOKEN},
+        processor, state_diff,
+        utils::{fill_block_producer, get_receipt_and_trace, new_provider},
+    },
+    alloy::{
+        primitives::{TxHash, I256, U256},
+        providers::Provider,
+        rpc::types::{trace::geth::CallFrame, AnyTransactionReceipt, Header},
+    },
+    alloy_chains::Chain,
+    clap::Parser,
+    eyre::{bail, ensure, Context, ContextCompat},
+    std::collections::{BTreeMap, HashSet},
+};
+
+/// Check `calculate_tx_fee` against the fee each sender actually paid, per the
+/// node's state diff, for every tx in a range of blocks. Needs an archive node
+/// with the prestate tracer. Exits with an error when a fee doesn't match.
+#[derive(Debug, Clone, Parser)]
+pub struct Args {
+    #[arg(help = "First block to check")]
+    from: u64,
+
+    #[arg(help = "Last block to check, the first one if unset")]
+    to: Option<u64>,
+
+    #[arg(short, long, env = "ETH_RPC_URL")]
+    rpc_url: String,
+
+    #[arg(long, default_value_t = 0, help = "Fee differences up to this many wei still pass")]
+    tolerance_wei: u64,
+
+    #[command(flatten)]
+    rules: RulesArgs,
+}
+
+/// Fee checks of one tx type
+#[derive(Debug, Default)]
+pub struct Tally {
+    passed: usize,
+    mismatched: Vec<(TxHash, I256, I256)>,
+}
+
+impl Args {
+    pub async fn run(self) -> eyre::Result<()> {
+        let rules = self.rules.load()?;
+        let provider = new_provider(&self.rpc_url).await.context("Failed to create provider")?;
+        let chain: Chain = provider.get_chain_id().await.context("Failed to get chain id")?.into();
+        ensure!(
+            chain_adapter::for_chain(chain).transfer_source() == TransferSource::Trace,
+            "Fees of {chain} are read from transfer logs, not calculated"
+        );
+        let tolerance = U256::from(self.tolerance_wei);
+
+        let mut by_type: BTreeMap<u8, Tally> = BTreeMap::new();
+        for number in self.from..=self.to.unwrap_or(self.from) {
+            let txs = check_block(provider.as_ref(), chain, &rules, number, tolerance, &mut by_type).await?;
+            println!("Block {number}: {txs} txs");
+        }
+
+        println!("=== Summary ===");
+        let mismatched = print_summary(&by_type);
+        if mismatched > 0 {
+            bail!("{mismatched} fees mismatched");
+        }
+        println!("All fees match!");
+
+        Ok(())
+    }
+}
+
+/// Check the fee of every tx of block `number` into `by_type`, returning how
+/// many txs the block has
+pub async fn check_block(
+    provider: &dyn Provider,
+    chain: Chain,
+    rules: &ChainRules,
+    number: u64,
+    tolerance: U256,
+    by_type: &mut BTreeMap<u8, Tally>,
+) -> eyre::Result<usize> {
+    let mut block = provider
+        .get_block_by_number(number.into(), false)
+        .await
+        .context("Failed to get block")?
+        .context("Block not found")?;
+    fill_block_producer(provider, chain, &mut block.header).await?;
+
+    let receipt_and_traces = get_receipt_and_trace(provider, number, &TraceConfig::default(), &[])
+        .await
+        .context("Failed to get receipt and traces")?;
+
+    let deltas = state_diff::native_deltas(provider, receipt_and_traces.iter().map(|(r, _)| r.transaction_hash))
+        .await
+        .context("Failed to get state diffs")?;
+
+    for ((receipt, call_trace), state) in receipt_and_traces.iter().zip(deltas) {
+        let sender_delta = state.get(&receipt.from).copied().unwrap_or_default();
+        let paid = paid_fee(chain, rules, &block.header, receipt, call_trace, sender_delta)?;
+        let calculated = processor::calculate_tx_fee(chain, receipt)?;
+
+        let tally = by_type.entry(receipt.inner.inner.r#type).or_default();
+        if (paid - calculated).unsigned_abs() <= tolerance {
+            tally.passed += 1;
+        } else {
+            tally.mismatched.push((receipt.transaction_hash, paid, calculated));
+        }
+    }
+
+    Ok(receipt_and_traces.len())
+}
+
+/// Print the checks by tx type with every mismatch, returning how many there
+/// are
+pub fn print_summary(by_type: &BTreeMap<u8, Tally>) -> usize {
+    for (tx_type, tally) in by_type {
+        println!(
+            "Type {tx_type:#04x}: {} passed, {} mismatched",
+            tally.passed,
+            tally.mismatched.len()
+        );
+        for (hash, paid, calculated) in &tally.mismatched {
+            println!("  {hash}: paid {paid}, calculated {calculated}");
+        }
+    }
+
+    by_type.values().map(|tally| tally.mismatched.len()).sum()
+}
+
+/// Fee the sender of a tx paid: what it lost in the state diff beyond what it
+/// sent in call frames. A sender producing the block gets its own tip back.
+fn paid_fee(
+    chain: Chain,
+    rules: &ChainRules,
+    header: &Header,
+    receipt: &AnyTransactionReceipt,
+    call_trace: &CallFrame,
+    sender_delta: I256,
+) -> eyre::Result<I256> {
+    let sender = HashSet::from([receipt.from]);
+    let sent = processor::generate_pnl(chain, rules, receipt, call_trace, Some(&sender))?
+        .get(&receipt.from)
+        .and_then(|bc| bc.get(&NATIVE_TOKEN))
+        .copied()
+        .unwrap_or_default();
+
+    let mut paid = sent - sender_delta;
+    if receipt.from == header.miner {
+        paid += I256::from_raw(state_diff::tip(chain, rules, header, receipt));
+    }
+    if chain_adapter::for_chain(chain).is_deposit_tx(receipt) {
+        // ETH minted to the sender from L1 isn't in the call frames
+        paid = paid.max(I256::ZERO);
+    }
+
+    Ok(paid)
+}
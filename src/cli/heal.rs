@@ -0,0 +1,193 @@
+use {
+    super::start::executors,
+    crate::{
+        audit::AuditLog,
+        config::{AlertTo, Config},
+        executor::Action,
+        heal, storage,
+        strategy::WalletWatcher,
+        utils::new_pubsub_provider,
+    },
+    alloy::{providers::Provider, pubsub::PubSubFrontend},
+    alloy_chains::Chain,
+    burberry::ActionSubmitter,
+    clap::Parser,
+    eyre::{bail, ensure, Context, ContextCompat},
+    std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+    tracing::{info, warn},
+};
+
+/// Process again the blocks of a chain that `start` recorded in the config's
+/// `storage` as failed, or never recorded while running, and send their
+/// alerts marked as late
+#[derive(Debug, Clone, Parser)]
+pub struct Args {
+    #[arg(help = "Chain to heal, by its name in the config")]
+    chain: String,
+
+    /// The path to the config file
+    #[arg(default_value = "config.toml", help = "The path to the config file")]
+    config: String,
+
+    #[arg(long, conflicts_with = "last_n_blocks", help = "First block to look at")]
+    from_block: Option<u64>,
+
+    #[arg(long, help = "Last block to look at, the latest one if unset")]
+    to_block: Option<u64>,
+
+    #[arg(long, help = "Look at this many blocks up to --to-block")]
+    last_n_blocks: Option<u64>,
+
+    #[arg(long, help = "Only list the gaps")]
+    dry_run: bool,
+}
+
+/// Collects the actions of the strategy instead of handing them to an engine
+#[derive(Default)]
+struct Collector(Mutex<Vec<Action>>);
+
+impl ActionSubmitter<Action> for Collector {
+    fn submit(&self, action: Action) {
+        self.0.lock().unwrap().push(action);
+    }
+}
+
+impl Args {
+    pub async fn run(self) -> eyre::Result<()> {
+        tracing_subscriber::fmt::init();
+
+        let config = Config::from_file(&self.config).context("Failed to parse config")?;
+        let chain_config = config
+            .chains
+            .get(&self.chain)
+            .with_context(|| format!("Chain {} not found", self.chain))?
+            .clone();
+        let url = config
+            .storage
+            .as_ref()
+            .context("No storage in the config, processed blocks aren't recorded anywhere")?;
+        let storage = storage::connect(url).await.context("Failed to connect to storage")?;
+
+        let provider: Arc<dyn Provider<PubSubFrontend>> = new_pubsub_provider(&chain_config.rpc)
+            .await
+            .context("Failed to create provider")?
+            .into();
+        let chain: Chain = provider.get_chain_id().await.context("Failed to get chain id")?.into();
+
+        let to_block = match self.to_block {
+            Some(block) => block,
+            None => provider
+                .get_block_number()
+                .await
+                .context("Failed to get latest block")?,
+        };
+        let from_block = match (self.from_block, self.last_n_blocks) {
+            (Some(block), _) => block,
+            (None, Some(n)) => to_block.saturating_sub(n.saturating_sub(1)),
+            (None, None) => bail!("Set --from-block or --last-n-blocks"),
+        };
+        ensure!(from_block <= to_block, "--from-block is after --to-block");
+
+        let gaps = heal::find_gaps(
+            storage.as_ref(),
+            chain.id(),
+            chain_config.block_source,
+            from_block,
+            to_block,
+        )
+        .await?;
+        info!(%chain, from_block, to_block, gaps = gaps.len(), "Gaps found");
+        if self.dry_run || gaps.is_empty() {
+            for block in gaps {
+                println!("{block}");
+            }
+            return Ok(());
+        }
+
+        // Late alerts go out one by one rather than rolled up
+        let mut wallets = config
+            .to_wallet_with_context_by_chain()
+            .remove(&self.chain)
+            .unwrap_or_default();
+        for wallet in &mut wallets {
+            wallet.throttle = None;
+        }
+        let clickhouse_flush = wallets
+            .iter()
+            .filter_map(|wallet| match wallet.alert_to.as_ref() {
+                AlertTo::Clickhouse(target) => Some(target.flush_interval),
+                _ => None,
+            })
+            .max();
+
+        let mut strategy = WalletWatcher::new(chain, Arc::clone(&provider), wallets)
+            .with_chain_config(&chain_config)
+            .with_lenient_traces(config.lenient_traces)
+            .with_trace_limits(config.trace_limits)
+            .with_cex_alert(config.cex.as_ref().is_some_and(|cex| cex.alert))
+            .with_storage(Arc::clone(&storage));
+        // An aggregation window would hold the reports back until it closes
+        strategy.aggregate = None;
+        if let Some(cache) = &config.redis_cache {
+            let cache = crate::executor::redis::connect(cache)
+                .await
+                .context("Failed to connect to redis cache")?;
+            // Reports delivered before their block failed aren't sent again
+            strategy = strategy.with_delivery_store(cache.clone()).with_token_cache(cache);
+        }
+        if let Some(permalink) = config.permalink.clone() {
+            strategy = strategy.with_permalink(permalink);
+        }
+        if let Some(token_risk) = config.token_risk.clone() {
+            strategy = strategy.with_token_risk(token_risk);
+        }
+        if let Some(prices) = &config.prices {
+            strategy = strategy.with_prices(prices, &chain_config);
+        }
+
+        let audit_log = match &config.audit_log {
+            Some(path) => Some(Arc::new(AuditLog::open(path)?)),
+            None => None,
+        };
+        let executors = executors(config.error_channel.clone(), &audit_log);
+
+        let (mut healed, mut failed) = (0, 0);
+        for number in gaps {
+            let block = provider
+                .get_block_by_number(number.into(), false)
+                .await
+                .with_context(|| format!("Failed to get block {number}"))?
+                .with_context(|| format!("Block {number} not found"))?;
+
+            let collector = Arc::new(Collector::default());
+            if let Err(err) = strategy.heal_block(block, collector.clone()).await {
+                warn!(block = number, "Block failed again: {err:#}");
+                failed += 1;
+                continue;
+            }
+
+            let actions = std::mem::take(&mut *collector.0.lock().unwrap());
+            for action in actions {
+                for executor in &executors {
+                    if let Err(err) = executor.execute(action.clone()).await {
+                        warn!(block = number, "Failed to send late alert: {err:#}");
+                    }
+                }
+            }
+            healed += 1;
+        }
+
+        if let Some(flush_interval) = clickhouse_flush {
+            // Rows are inserted by a background flush
+            info!("Waiting {}s for the ClickHouse flush", flush_interval);
+            tokio::time::sleep(Duration::from_secs(flush_interval + 1)).await;
+        }
+
+        info!(%chain, healed, failed, "Heal done");
+
+        Ok(())
+    }
+}
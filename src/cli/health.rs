@@ -0,0 +1,166 @@
+use {
+    crate::{audit, config::Config, storage},
+    clap::Parser,
+    eyre::{Context, ContextCompat},
+    std::{
+        collections::BTreeMap,
+        fmt::Write,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// Summarize how `start` has been doing over the last days, from the blocks
+/// and reports kept in the config's `storage` and the notifications in its
+/// `audit_log`: blocks processed and given up on, processing time, errors by
+/// step, reports by wallet and alerts sent by output
+#[derive(Debug, Clone, Parser)]
+pub struct Args {
+    /// The path to the config file
+    #[arg(default_value = "config.toml", help = "The path to the config file")]
+    config: String,
+
+    #[arg(long, default_value_t = 1, help = "Days to include, counting back from now")]
+    days: u32,
+
+    #[arg(long, help = "Only this chain id")]
+    chain: Option<u64>,
+}
+
+impl Args {
+    pub async fn run(self) -> eyre::Result<()> {
+        let config = Config::from_file(&self.config).context("Failed to parse config")?;
+        let url = config
+            .storage
+            .as_ref()
+            .context("No storage in the config, processing isn't recorded anywhere")?;
+        let storage = storage::connect(url).await.context("Failed to connect to storage")?;
+
+        let to = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let from = to.saturating_sub(self.days as u64 * 86400);
+        let in_chain = |chain: u64| self.chain.map_or(true, |c| c == chain);
+
+        let mut out = String::new();
+
+        let chains = storage
+            .chain_stats(from, to)
+            .await?
+            .into_iter()
+            .filter(|stats| in_chain(stats.chain))
+            .map(|stats| {
+                // Blocks in the range that were never recorded, e.g. while the
+                // watcher was down. Chains on transfer logs only record the
+                // blocks with a transfer, so most of theirs are
+                let span = stats.last_block - stats.first_block + 1;
+                let unrecorded = span.saturating_sub(stats.processed + stats.failed);
+                vec![
+                    stats.chain.to_string(),
+                    format!("{}-{}", stats.first_block, stats.last_block),
+                    stats.processed.to_string(),
+                    stats.failed.to_string(),
+                    unrecorded.to_string(),
+                    stats.retries.to_string(),
+                    format!("{:.0}", stats.avg_elapsed_ms),
+                    stats.max_elapsed_ms.to_string(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let _ = writeln!(out, "Blocks of the last {} day(s)", self.days);
+        out.push_str(&table(
+            &[
+                "chain",
+                "blocks",
+                "processed",
+                "failed",
+                "unrecorded",
+                "retries",
+                "avg_ms",
+                "max_ms",
+            ],
+            &chains,
+        ));
+
+        let errors = storage
+            .block_errors(from, to)
+            .await?
+            .into_iter()
+            .filter(|(chain, ..)| in_chain(*chain))
+            .map(|(chain, error, count)| vec![chain.to_string(), count.to_string(), error])
+            .collect::<Vec<_>>();
+        let _ = writeln!(out, "\nFailed blocks by error");
+        out.push_str(&table(&["chain", "blocks", "error"], &errors));
+
+        let reports = storage
+            .report_counts(from, to)
+            .await?
+            .into_iter()
+            .filter(|(chain, ..)| in_chain(*chain))
+            .map(|(chain, wallet, count)| vec![chain.to_string(), wallet, count.to_string()])
+            .collect::<Vec<_>>();
+        let _ = writeln!(out, "\nReports by wallet");
+        out.push_str(&table(&["chain", "wallet", "reports"], &reports));
+
+        // The audit log doesn't say which chain an alert was about
+        if let Some(path) = &config.audit_log {
+            let mut outputs: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+            for entry in audit::read_entries(path)?
+                .into_iter()
+                .filter(|e| (from..=to).contains(&e.timestamp))
+            {
+                let (delivered, failed) = outputs.entry(entry.output).or_default();
+                if entry.delivered {
+                    *delivered += 1;
+                } else {
+                    *failed += 1;
+                }
+            }
+
+            let alerts = outputs
+                .into_iter()
+                .map(|(output, (delivered, failed))| vec![output, delivered.to_string(), failed.to_string()])
+                .collect::<Vec<_>>();
+            let _ = writeln!(out, "\nAlerts by output, all chains");
+            out.push_str(&table(&["output", "delivered", "failed"], &alerts));
+        }
+
+        print!("{out}");
+
+        Ok(())
+    }
+}
+
+/// Rows aligned under `headers`, or a note when there are none
+fn table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    if rows.is_empty() {
+        return "  none\n".to_string();
+    }
+
+    let widths = rows.iter().fold(
+        headers.iter().map(|h| h.len()).collect::<Vec<_>>(),
+        |mut widths, row| {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+            widths
+        },
+    );
+    let line = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(widths.iter().copied())
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut out = String::new();
+    let _ = writeln!(out, "  {}", line(headers.to_vec()));
+    for row in rows {
+        let _ = writeln!(out, "  {}", line(row.iter().map(String::as_str).collect()));
+    }
+    out
+}
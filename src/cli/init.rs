@@ -0,0 +1,85 @@
+use {
+    clap::Parser,
+    eyre::{bail, Context, ContextCompat},
+    std::io::{BufRead, Write},
+};
+
+const EXAMPLE_CONFIG: &str = include_str!("../../config.example.yaml");
+
+/// Write an annotated example config to start from
+#[derive(Debug, Clone, Parser)]
+pub struct Args {
+    #[arg(default_value = "config.yaml", help = "Where to write the config")]
+    path: String,
+
+    #[arg(short, long, help = "Prompt for the RPC URL and Telegram bot details")]
+    interactive: bool,
+
+    #[arg(long, help = "Overwrite the file if it exists")]
+    force: bool,
+}
+
+impl Args {
+    pub async fn run(self) -> eyre::Result<()> {
+        if !self.force && std::path::Path::new(&self.path).exists() {
+            bail!("{} already exists, pass --force to overwrite it", self.path);
+        }
+
+        let mut config = EXAMPLE_CONFIG.to_string();
+
+        if self.interactive {
+            for (section, placeholder, question) in [
+                (
+                    "chains:",
+                    "<RPC URL to Ethereum node, string>",
+                    "Ethereum RPC URL (ws://, wss:// or file://)",
+                ),
+                ("channels:", "<Token, string>", "Telegram bot token"),
+                ("channels:", "<Chat ID, string>", "Telegram chat ID"),
+            ] {
+                let answer = prompt(question)?;
+                if !answer.is_empty() {
+                    config = fill_in(&config, section, placeholder, &answer)?;
+                }
+            }
+        }
+
+        std::fs::write(&self.path, config).context("Failed to write config")?;
+
+        println!("Config written to {}", self.path);
+        println!(
+            "Fill in the remaining <...> fields, then run: wallet-watcher start {}",
+            self.path
+        );
+
+        Ok(())
+    }
+}
+
+/// Replace the first `placeholder` after the top-level `section` key, so the
+/// commented examples before it keep theirs
+fn fill_in(config: &str, section: &str, placeholder: &str, answer: &str) -> eyre::Result<String> {
+    let start = std::iter::once(0)
+        .chain(config.match_indices('\n').map(|(i, _)| i + 1))
+        .find(|&line| config[line..].starts_with(section))
+        .with_context(|| format!("No {section} in the example config"))?;
+    let (head, tail) = config.split_at(start);
+    // Serialized as a YAML scalar, so quotes, colons or a leading `#` in the
+    // answer can't break the document
+    let value = serde_yaml::to_string(answer).context("Failed to serialize answer")?;
+
+    Ok(format!("{head}{}", tail.replacen(placeholder, value.trim_end(), 1)))
+}
+
+fn prompt(question: &str) -> eyre::Result<String> {
+    print!("{question} (leave empty to skip): ");
+    std::io::stdout().flush().context("Failed to flush stdout")?;
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .context("Failed to read answer")?;
+
+    Ok(answer.trim().to_string())
+}
@@ -0,0 +1,280 @@
+use {
+    super::rules::RulesArgs,
+    crate::{
+        chain_adapter,
+        config::{TraceConfig, NATIVE_TOKEN},
+        message::MessageGenerator,
+        processor::{self, decode_transfer, to_primitive_log, trace_options},
+        utils::{format_ether_trimmed, format_token_amount, format_units, new_provider},
+    },
+    alloy::{
+        network::ReceiptResponse,
+        primitives::{Address, TxHash, I256, U256, U64},
+        providers::Provider,
+        rpc::{
+            client::BatchRequest,
+            types::{
+                trace::geth::{CallFrame, GethTrace},
+                AnyTransactionReceipt,
+            },
+        },
+    },
+    alloy_chains::Chain,
+    clap::Parser,
+    eyre::{Context, ContextCompat},
+    std::{
+        collections::{BTreeSet, HashMap},
+        fmt::Write,
+        sync::Arc,
+    },
+};
+
+/// Print everything that goes into the PnL of a transaction: the call tree
+/// with decoded token transfers, fee math and resulting balance changes
+#[derive(Debug, Clone, Parser)]
+pub struct Args {
+    hash: TxHash,
+
+    #[arg(short, long, env = "ETH_RPC_URL")]
+    rpc_url: String,
+
+    #[command(flatten)]
+    rules: RulesArgs,
+}
+
+impl Args {
+    pub async fn run(self) -> eyre::Result<()> {
+        let rules = self.rules.load()?;
+        let provider: Arc<dyn Provider> = new_provider(&self.rpc_url)
+            .await
+            .context("Failed to create provider")?
+            .into();
+
+        let mut batch = BatchRequest::new(provider.client());
+
+        let chain = batch.add_call::<_, U64>("eth_chainId", &()).unwrap();
+        let receipt = batch
+            .add_call::<_, AnyTransactionReceipt>("eth_getTransactionReceipt", &(self.hash,))
+            .unwrap();
+        let trace = batch
+            .add_call::<_, GethTrace>(
+                "debug_traceTransaction",
+                &(self.hash, trace_options(&TraceConfig::default())),
+            )
+            .unwrap();
+
+        batch.await.context("Failed to send batch request")?;
+
+        let chain: Chain = chain.await.context("Failed to get chain id")?.to::<u64>().into();
+        let receipt = receipt.await.context("Failed to get transaction receipt")?;
+        let call_trace = trace
+            .await
+            .context("Failed to trace transaction")?
+            .try_into_call_frame()
+            .context("Failed to convert trace to call frame")?;
+
+        let block_number = receipt.block_number.context("Transaction is pending")?;
+        let block = provider
+            .get_block_by_number(block_number.into(), false)
+            .await
+            .context("Failed to get block")?
+            .context("Block not found")?;
+
+        let weth9_tokens = chain_adapter::weth9_tokens(chain, &rules);
+
+        // Token metadata is loaded upfront so the tree can be printed synchronously
+        let mut message_generator = MessageGenerator::new(chain, Arc::clone(&provider));
+        let mut tokens = BTreeSet::new();
+        collect_tokens(&weth9_tokens, &call_trace, &mut tokens);
+
+        let mut token_info = HashMap::new();
+        for token in tokens {
+            if let Ok((symbol, decimals)) = message_generator.load_symbol_and_decimal(&token).await {
+                token_info.insert(token, (symbol.clone(), *decimals));
+            }
+        }
+
+        let printer = Printer {
+            chain,
+            weth9_tokens,
+            token_info,
+        };
+
+        let mut out = String::new();
+
+        writeln!(
+            out,
+            "Tx {hash} · block {block_number} · index {index} · {status}",
+            hash = receipt.transaction_hash,
+            index = receipt.transaction_index.unwrap_or_default(),
+            status = if receipt.status() { "✓ success" } else { "✗ failed" },
+        )
+        .unwrap();
+        writeln!(out, "From {}", receipt.from).unwrap();
+        if let Some(to) = receipt.to {
+            writeln!(out, "To   {to}").unwrap();
+        }
+
+        writeln!(out, "\nFee").unwrap();
+        let gas_used = U256::from(receipt.gas_used);
+        let gas_price = U256::from(receipt.effective_gas_price);
+        writeln!(out, "├─ gas used        {gas_used}").unwrap();
+        writeln!(out, "├─ effective price {} gwei", format_units(gas_price, 9, 9)).unwrap();
+        if let Some(base_fee) = block.header.base_fee_per_gas.map(U256::from) {
+            writeln!(out, "│  ├─ base fee     {} gwei", format_units(base_fee, 9, 9)).unwrap();
+            writeln!(
+                out,
+                "│  └─ priority fee {} gwei",
+                format_units(gas_price.saturating_sub(base_fee), 9, 9)
+            )
+            .unwrap();
+        }
+        if let Some(l1_fee) = receipt.other.get("l1Fee").and_then(|v| v.as_str()) {
+            writeln!(out, "├─ l1 fee          {l1_fee}").unwrap();
+        }
+        match processor::calculate_tx_fee(chain, &receipt) {
+            Ok(fee) => writeln!(
+                out,
+                "└─ total           {} {}",
+                format_ether_trimmed(&fee.into_raw()),
+                printer.native_symbol()
+            ),
+            Err(err) => writeln!(out, "└─ total           error: {err:#}"),
+        }
+        .unwrap();
+
+        writeln!(out, "\nCall tree").unwrap();
+        printer.print_frame(&mut out, &call_trace, "", true);
+
+        writeln!(out, "\nBalance changes").unwrap();
+        match processor::generate_pnl(chain, &rules, &receipt, &call_trace, None) {
+            Ok(bcs) => {
+                for (i, (account, bc)) in bcs.iter().enumerate() {
+                    let last_account = i == bcs.len() - 1;
+                    writeln!(out, "{}{account}", if last_account { "└─ " } else { "├─ " }).unwrap();
+
+                    for (j, (token, change)) in bc.iter().enumerate() {
+                        writeln!(
+                            out,
+                            "{}{}{}: {}",
+                            if last_account { "   " } else { "│  " },
+                            if j == bc.len() - 1 { "└─ " } else { "├─ " },
+                            printer.token_label(token),
+                            printer.amount(token, change),
+                        )
+                        .unwrap();
+                    }
+                }
+            }
+            Err(err) => writeln!(out, "error: {err:#}").unwrap(),
+        }
+
+        print!("{out}");
+
+        Ok(())
+    }
+}
+
+struct Printer {
+    chain: Chain,
+    weth9_tokens: Vec<Address>,
+    token_info: HashMap<Address, (String, u8)>,
+}
+
+impl Printer {
+    fn native_symbol(&self) -> &'static str {
+        self.chain
+            .named()
+            .and_then(|c| c.native_currency_symbol())
+            .unwrap_or("ETH")
+    }
+
+    fn token_label(&self, token: &Address) -> String {
+        if *token == NATIVE_TOKEN {
+            return self.native_symbol().to_string();
+        }
+
+        match self.token_info.get(token) {
+            Some((symbol, _)) => format!("{symbol} ({token})"),
+            None => token.to_string(),
+        }
+    }
+
+    fn amount(&self, token: &Address, change: &I256) -> String {
+        let decimals = self.token_info.get(token).map(|(_, d)| *d).unwrap_or(18);
+        format_token_amount(change, decimals, 18)
+    }
+
+    fn print_frame(&self, out: &mut String, frame: &CallFrame, prefix: &str, last: bool) {
+        let branch = if last { "└─ " } else { "├─ " };
+        let child_prefix = format!("{prefix}{}", if last { "   " } else { "│  " });
+
+        let mut line = format!(
+            "{prefix}{branch}{typ} {from} → {to}",
+            typ = frame.typ,
+            from = frame.from,
+            to = frame.to.map(|a| a.to_string()).unwrap_or_else(|| "?".to_string()),
+        );
+
+        if let Some(value) = frame.value.filter(|v| !v.is_zero()) {
+            write!(line, " · {} {}", format_ether_trimmed(&value), self.native_symbol()).unwrap();
+        }
+
+        if let Some(error) = &frame.error {
+            write!(line, " · ✗ {error}").unwrap();
+            if let Some(reason) = &frame.revert_reason {
+                write!(line, " ({reason})").unwrap();
+            }
+            line.push_str(" · ignored");
+        }
+
+        writeln!(out, "{line}").unwrap();
+
+        let children = frame.logs.len() + frame.calls.len();
+        let mut i = 0;
+
+        for log in &frame.logs {
+            i += 1;
+            let branch = if i == children { "└─ " } else { "├─ " };
+
+            let decoded = to_primitive_log(log)
+                .ok()
+                .and_then(|log| decode_transfer(&self.weth9_tokens, &log));
+
+            match decoded {
+                Some((token, from, to, value)) => writeln!(
+                    out,
+                    "{child_prefix}{branch}⇢ {token} {from} → {to} · {amount}",
+                    token = self.token_label(&token),
+                    amount = self.amount(&token, &I256::from_raw(value)),
+                ),
+                None => writeln!(
+                    out,
+                    "{child_prefix}{branch}⇢ log {} (not a transfer)",
+                    log.address.map(|a| a.to_string()).unwrap_or_default()
+                ),
+            }
+            .unwrap();
+        }
+
+        for call in &frame.calls {
+            i += 1;
+            self.print_frame(out, call, &child_prefix, i == children);
+        }
+    }
+}
+
+fn collect_tokens(weth9_tokens: &[Address], frame: &CallFrame, tokens: &mut BTreeSet<Address>) {
+    for log in &frame.logs {
+        if let Some((token, ..)) = to_primitive_log(log)
+            .ok()
+            .and_then(|log| decode_transfer(weth9_tokens, &log))
+        {
+            tokens.insert(token);
+        }
+    }
+
+    for call in &frame.calls {
+        collect_tokens(weth9_tokens, call, tokens);
+    }
+}
@@ -0,0 +1,103 @@
+use {
+    crate::audit::{self, AuditEntry},
+    chrono::{DateTime, NaiveDate},
+    clap::Parser,
+    eyre::{Context, ContextCompat},
+};
+
+/// Query the notifications recorded in the audit log set by `audit_log`
+#[derive(Debug, Clone, Parser)]
+pub struct Args {
+    #[arg(help = "The path to the audit log")]
+    log: String,
+
+    #[arg(long, help = "Only messages sent through this output, e.g. telegram")]
+    output: Option<String>,
+
+    #[arg(long, help = "Only messages to targets containing this, e.g. a chat id")]
+    target: Option<String>,
+
+    #[arg(long, help = "Only messages whose text contains this")]
+    contains: Option<String>,
+
+    #[arg(long, value_parser = parse_time, help = "Only messages sent at or after this date or RFC 3339 time (UTC)")]
+    since: Option<u64>,
+
+    #[arg(long, value_parser = parse_time, help = "Only messages sent before this date or RFC 3339 time (UTC)")]
+    until: Option<u64>,
+
+    #[arg(long, help = "Only messages that failed to be delivered")]
+    failed: bool,
+
+    #[arg(
+        short = 'n',
+        long,
+        default_value_t = 50,
+        help = "Show the latest this many matches, 0 for all"
+    )]
+    limit: usize,
+
+    #[arg(long, help = "Print the matching entries as JSON lines")]
+    json: bool,
+}
+
+impl Args {
+    pub async fn run(self) -> eyre::Result<()> {
+        let entries = audit::read_entries(&self.log)?;
+        let matches = entries.iter().filter(|e| self.matches(e)).collect::<Vec<_>>();
+        let skip = match self.limit {
+            0 => 0,
+            limit => matches.len().saturating_sub(limit),
+        };
+
+        for entry in &matches[skip..] {
+            if self.json {
+                println!("{}", serde_json::to_string(entry)?);
+                continue;
+            }
+
+            let time = DateTime::from_timestamp(entry.timestamp as i64, 0)
+                .context("Invalid timestamp")?
+                .format("%Y-%m-%d %H:%M:%S");
+            let status = match &entry.error {
+                _ if entry.delivered => "delivered".to_string(),
+                Some(error) => format!("failed: {error}"),
+                None => "failed".to_string(),
+            };
+
+            println!("[{time}] {} {} ({status})", entry.output, entry.target);
+            for line in entry.text.lines() {
+                println!("    {line}");
+            }
+        }
+
+        if !self.json {
+            println!("{} of {} messages", matches.len() - skip, matches.len());
+        }
+
+        Ok(())
+    }
+
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.output
+            .as_ref()
+            .map_or(true, |o| entry.output.eq_ignore_ascii_case(o)) &&
+            self.target.as_ref().map_or(true, |t| entry.target.contains(t.as_str())) &&
+            self.contains.as_ref().map_or(true, |c| entry.text.contains(c.as_str())) &&
+            self.since.map_or(true, |since| entry.timestamp >= since) &&
+            self.until.map_or(true, |until| entry.timestamp < until) &&
+            (!self.failed || !entry.delivered)
+    }
+}
+
+/// Unix seconds of a `YYYY-MM-DD` date or an RFC 3339 time
+fn parse_time(s: &str) -> eyre::Result<u64> {
+    let time = match NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        Ok(date) => date.and_hms_opt(0, 0, 0).context("Invalid date")?.and_utc().timestamp(),
+        Err(_) => DateTime::parse_from_rfc3339(s)
+            .with_context(|| format!("Invalid time {s}"))?
+            .timestamp(),
+    };
+
+    Ok(time.max(0) as u64)
+}
@@ -1,8 +1,23 @@
 use clap::{Parser, Subcommand};
 
 mod backtest;
+mod compare;
+mod fees;
+mod heal;
+mod health;
+mod init;
+mod inspect;
+mod messages;
+mod report;
+mod rules;
 mod run;
+mod scan;
+mod schema;
 mod start;
+mod state;
+mod stats;
+mod test_alert;
+mod watch;
 
 #[derive(Debug, Parser)]
 pub struct Cli {
@@ -12,21 +27,47 @@ pub struct Cli {
 
 #[derive(Debug, Subcommand)]
 pub enum Command {
+    Init(init::Args),
     Start(start::Args),
     RunTx(run::TxArgs),
     RunBlock(run::BlockArgs),
+    Scan(scan::Args),
+    InspectTx(inspect::Args),
+    CompareBlock(compare::Args),
+    CheckFees(fees::Args),
     Backtest(backtest::Args),
+    Watch(watch::Args),
+    Schema(schema::Args),
+    Messages(messages::Args),
+    Stats(stats::Args),
+    Health(health::Args),
+    Heal(heal::Args),
+    Report(report::Args),
+    State(state::Args),
+    TestAlert(test_alert::Args),
 }
 
 impl Cli {
     pub async fn run(self) -> eyre::Result<()> {
         match self.command {
+            Command::Init(args) => args.run().await,
             Command::Start(args) => args.run().await,
             Command::RunTx(args) => args.run().await,
             Command::RunBlock(args) => args.run().await,
+            Command::Scan(args) => args.run().await,
+            Command::InspectTx(args) => args.run().await,
+            Command::CompareBlock(args) => args.run().await,
+            Command::CheckFees(args) => args.run().await,
             Command::Backtest(args) => args.run().await,
-        };
-
-        Ok(())
+            Command::Watch(args) => args.run().await,
+            Command::Schema(args) => args.run().await,
+            Command::Messages(args) => args.run().await,
+            Command::Stats(args) => args.run().await,
+            Command::Health(args) => args.run().await,
+            Command::Heal(args) => args.run().await,
+            Command::Report(args) => args.run().await,
+            Command::State(args) => args.run().await,
+            Command::TestAlert(args) => args.run().await,
+        }
     }
 }
@@ -0,0 +1,495 @@
+use {
+    crate::{
+        config::Config,
+        storage::{self, ReportQuery, StoredReport},
+        utils::format_units,
+    },
+    alloy::primitives::{Address, I256},
+    chrono::{DateTime, Datelike, Days, NaiveDate},
+    clap::{Parser, ValueEnum},
+    eyre::{Context, ContextCompat},
+    serde::Serialize,
+    std::{
+        collections::{BTreeMap, HashMap, HashSet},
+        fmt::Write,
+        str::FromStr,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// Size of the charts of the Markdown and HTML reports
+const CHART_WIDTH: f64 = 480.0;
+const CHART_HEIGHT: f64 = 120.0;
+const CHART_PADDING: f64 = 8.0;
+
+/// Sum the PnL of each wallet per day, week or month from the reports kept in
+/// the config's `storage`
+#[derive(Debug, Clone, Parser)]
+pub struct Args {
+    /// The path to the config file
+    #[arg(default_value = "config.toml", help = "The path to the config file")]
+    config: String,
+
+    #[arg(long, help = "Only this wallet address")]
+    wallet: Option<Address>,
+
+    #[arg(
+        long,
+        help = "Only this chain id. Block numbers in --from and --to are of this chain"
+    )]
+    chain: Option<u64>,
+
+    #[arg(long, help = "First block number or date (YYYY-MM-DD, UTC) to include")]
+    from: Option<Bound>,
+
+    #[arg(long, help = "Last block number or date (YYYY-MM-DD, UTC) to include")]
+    to: Option<Bound>,
+
+    #[arg(long, value_enum, default_value_t = Period::Day, help = "What a row sums up")]
+    period: Period,
+
+    #[arg(long, help = "Write the rows as CSV to this path too")]
+    csv: Option<String>,
+
+    #[arg(long, help = "Write the rows as JSON to this path too")]
+    json: Option<String>,
+
+    #[arg(
+        long,
+        help = "Write a Markdown report to this path too, with the rows and a chart of each wallet by channel"
+    )]
+    markdown: Option<String>,
+
+    #[arg(long, help = "Write the same report as an HTML page to this path too")]
+    html: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Bound {
+    Block(u64),
+    Date(NaiveDate),
+}
+
+impl FromStr for Bound {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(block) = s.parse() {
+            return Ok(Bound::Block(block));
+        }
+
+        NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .map(Bound::Date)
+            .map_err(|_| format!("expected a block number or a YYYY-MM-DD date, got {s}"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Period {
+    Day,
+    /// Weeks start on Monday
+    Week,
+    Month,
+}
+
+impl Period {
+    fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => date,
+            Period::Week => date - Days::new(date.weekday().num_days_from_monday() as u64),
+            Period::Month => date.with_day(1).unwrap_or(date),
+        }
+    }
+}
+
+/// Sums of a wallet's reports on a chain in a period
+#[derive(Default)]
+struct Totals {
+    reports: u32,
+    pnl: I256,
+    stable_pnl: I256,
+    /// Only of the reports valued in USD
+    pnl_usd: Option<I256>,
+}
+
+impl Totals {
+    fn add(&mut self, stored: &StoredReport) {
+        self.reports += 1;
+        self.pnl += stored.report.pnl;
+        self.stable_pnl += stored.report.stable_pnl;
+        if let Some(pnl_usd) = stored.report.pnl_usd {
+            *self.pnl_usd.get_or_insert(I256::ZERO) += pnl_usd;
+        }
+    }
+}
+
+/// A line of the output, amounts in whole units at 18 decimals
+#[derive(Serialize)]
+struct Row {
+    period: String,
+    wallet: String,
+    #[serde(skip)]
+    address: Address,
+    chain: u64,
+    reports: u32,
+    pnl: String,
+    stable_pnl: String,
+    pnl_usd: Option<String>,
+}
+
+impl Args {
+    pub async fn run(self) -> eyre::Result<()> {
+        let config = Config::from_file(&self.config).context("Failed to parse config")?;
+        let url = config
+            .storage
+            .as_ref()
+            .context("No storage in the config, reports aren't stored anywhere")?;
+        let storage = storage::connect(url).await.context("Failed to connect to storage")?;
+
+        let mut query = ReportQuery {
+            chain: self.chain,
+            address: self.wallet,
+            ..Default::default()
+        };
+        match self.from {
+            Some(Bound::Block(block)) => query.from_block = Some(block),
+            Some(Bound::Date(date)) => query.from_timestamp = Some(start_of_day(date)),
+            None => {}
+        }
+        match self.to {
+            Some(Bound::Block(block)) => query.to_block = Some(block),
+            Some(Bound::Date(date)) => query.to_timestamp = Some(start_of_day(date + Days::new(1)) - 1),
+            None => {}
+        }
+
+        let reports = storage.reports(&query).await?;
+        if reports.is_empty() {
+            println!("No reports in the range");
+            return Ok(());
+        }
+
+        // By address, so a wallet renamed, or disabled and enabled again, keeps
+        // one history
+        let mut totals: BTreeMap<(NaiveDate, Address, u64), Totals> = BTreeMap::new();
+        let mut stored_names = HashMap::new();
+        for stored in &reports {
+            let date = DateTime::from_timestamp(stored.timestamp as i64, 0)
+                .context("Invalid report timestamp")?
+                .date_naive();
+            totals
+                .entry((self.period.start(date), stored.address, stored.chain))
+                .or_default()
+                .add(stored);
+            stored_names.insert(stored.address, stored.wallet.as_str());
+        }
+
+        let rows = totals
+            .into_iter()
+            .map(|((period, address, chain), totals)| Row {
+                period: period.to_string(),
+                wallet: wallet_name(&config, address, stored_names[&address]),
+                address,
+                chain,
+                reports: totals.reports,
+                pnl: format_amount(totals.pnl),
+                stable_pnl: format_amount(totals.stable_pnl),
+                pnl_usd: totals.pnl_usd.map(format_amount),
+            })
+            .collect::<Vec<_>>();
+
+        print!("{}", render_table(&rows));
+
+        if let Some(path) = &self.csv {
+            std::fs::write(path, render_csv(&rows)).context("Failed to write CSV")?;
+            println!("Wrote {} rows to {path}", rows.len());
+        }
+        if let Some(path) = &self.json {
+            let json = serde_json::to_string_pretty(&rows).context("Failed to serialize rows")?;
+            std::fs::write(path, json).context("Failed to write JSON")?;
+            println!("Wrote {} rows to {path}", rows.len());
+        }
+        if let Some(path) = &self.markdown {
+            std::fs::write(path, render_document(&config, &rows, Document::Markdown))
+                .context("Failed to write Markdown report")?;
+            println!("Wrote the report to {path}");
+        }
+        if let Some(path) = &self.html {
+            std::fs::write(path, render_document(&config, &rows, Document::Html))
+                .context("Failed to write HTML report")?;
+            println!("Wrote the report to {path}");
+        }
+
+        Ok(())
+    }
+}
+
+/// Name of the wallet at `address` in the config, marked when it is disabled.
+/// The name it was stored under once it is gone from the config
+fn wallet_name(config: &Config, address: Address, stored: &str) -> String {
+    let wallets = config
+        .channels
+        .iter()
+        .flat_map(|c| &c.wallets)
+        .filter(|w| w.address == address)
+        .collect::<Vec<_>>();
+
+    match (wallets.iter().find(|w| !w.disabled), wallets.first()) {
+        (Some(wallet), _) => wallet.name.clone(),
+        (None, Some(wallet)) => format!("{} (disabled)", wallet.name),
+        (None, None) => stored.to_string(),
+    }
+}
+
+fn start_of_day(date: NaiveDate) -> u64 {
+    date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc().timestamp() as u64
+}
+
+fn format_amount(value: I256) -> String {
+    let (sign, abs) = value.into_sign_and_abs();
+    let sign = if sign.is_negative() { "-" } else { "" };
+    format!("{sign}{}", format_units(abs, 18, 6))
+}
+
+const COLUMNS: [&str; 7] = ["period", "wallet", "chain", "reports", "pnl", "stable_pnl", "pnl_usd"];
+
+fn cells(row: &Row) -> [String; 7] {
+    [
+        row.period.clone(),
+        row.wallet.clone(),
+        row.chain.to_string(),
+        row.reports.to_string(),
+        row.pnl.clone(),
+        row.stable_pnl.clone(),
+        row.pnl_usd.clone().unwrap_or_default(),
+    ]
+}
+
+fn render_table(rows: &[Row]) -> String {
+    let cells = rows.iter().map(cells).collect::<Vec<_>>();
+    let widths = COLUMNS.map(|column| column.len());
+    let widths = cells.iter().fold(widths, |mut widths, row| {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+        widths
+    });
+
+    let mut out = String::new();
+    let line = |values: &[&str]| {
+        values
+            .iter()
+            .zip(widths)
+            .map(|(value, width)| format!("{value:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+    };
+    let _ = writeln!(out, "{}", line(&COLUMNS));
+    for row in &cells {
+        let _ = writeln!(out, "{}", line(&row.each_ref().map(String::as_str)));
+    }
+
+    out
+}
+
+fn render_csv(rows: &[Row]) -> String {
+    let mut out = COLUMNS.join(",");
+    out.push('\n');
+    for row in rows {
+        // Wallet names are the only field that may need quoting
+        let mut cells = cells(row);
+        if cells[1].contains([',', '"', '\n']) {
+            cells[1] = format!("\"{}\"", cells[1].replace('"', "\"\""));
+        }
+        out.push_str(&cells.join(","));
+        out.push('\n');
+    }
+
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Document {
+    Markdown,
+    Html,
+}
+
+/// A report to share with people outside the alert chats: a section per
+/// channel of the config, and in it a chart of the running PnL of each of its
+/// wallets and their rows. Wallets no longer in the config come last. Charts
+/// are inline SVG, which Markdown viewers that drop HTML leave out
+fn render_document(config: &Config, rows: &[Row], format: Document) -> String {
+    let first = rows.first().map_or("", |row| row.period.as_str());
+    let last = rows.last().map_or("", |row| row.period.as_str());
+    let generated = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|now| DateTime::from_timestamp(now.as_secs() as i64, 0))
+        .map(|now| now.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default();
+    let title = format!("PnL from {first} to {last}");
+
+    let mut out = String::new();
+    match format {
+        Document::Markdown => {
+            let _ = writeln!(out, "# {title}\n\nGenerated {generated}\n");
+        }
+        Document::Html => {
+            let _ = writeln!(
+                out,
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+                <style>body {{ font-family: sans-serif; }} table {{ border-collapse: collapse; }} \
+                th, td {{ border: 1px solid #ccc; padding: 2px 8px; text-align: right; }}</style>\n\
+                </head>\n<body>\n<h1>{title}</h1>\n<p>Generated {generated}</p>"
+            );
+        }
+    }
+
+    let mut sections = config
+        .channels
+        .iter()
+        .enumerate()
+        .map(|(i, channel)| {
+            let name = channel.name.clone().unwrap_or_else(|| format!("Channel {i}"));
+            (name, channel.wallets.iter().map(|w| w.address).collect::<Vec<_>>())
+        })
+        .collect::<Vec<_>>();
+    let in_config = sections
+        .iter()
+        .flat_map(|(_, addresses)| addresses.iter().copied())
+        .collect::<HashSet<_>>();
+    sections.push((
+        "Other wallets".to_string(),
+        rows.iter()
+            .map(|row| row.address)
+            .filter(|address| !in_config.contains(address))
+            .collect(),
+    ));
+
+    for (name, addresses) in sections {
+        let mut seen = HashSet::new();
+        let wallets = addresses
+            .into_iter()
+            .filter(|address| seen.insert(*address))
+            .map(|address| rows.iter().filter(|row| row.address == address).collect::<Vec<_>>())
+            .filter(|rows| !rows.is_empty())
+            .collect::<Vec<_>>();
+        if wallets.is_empty() {
+            continue;
+        }
+
+        match format {
+            Document::Markdown => {
+                let _ = writeln!(out, "## {}\n", name.replace('|', "\\|"));
+            }
+            Document::Html => {
+                let _ = writeln!(out, "<h2>{}</h2>", escape_html(&name));
+            }
+        }
+
+        for rows in wallets {
+            match format {
+                Document::Markdown => {
+                    let _ = writeln!(out, "### {}\n\n{}\n", rows[0].wallet, chart(&rows));
+                    let _ = writeln!(out, "| period | chain | reports | pnl | stable_pnl | pnl_usd |");
+                    let _ = writeln!(out, "|---|---|--:|--:|--:|--:|");
+                    for row in &rows {
+                        let cells = cells(row);
+                        let _ = writeln!(out, "| {} | {} |", cells[0], cells[2..].join(" | "));
+                    }
+                    out.push('\n');
+                }
+                Document::Html => {
+                    let _ = writeln!(out, "<h3>{}</h3>\n{}", escape_html(&rows[0].wallet), chart(&rows));
+                    let _ = writeln!(
+                        out,
+                        "<table>\n<tr><th>period</th><th>chain</th><th>reports</th><th>pnl</th>\
+                        <th>stable_pnl</th><th>pnl_usd</th></tr>"
+                    );
+                    for row in &rows {
+                        let cells = cells(row);
+                        let _ = writeln!(
+                            out,
+                            "<tr><td>{}</td><td>{}</td></tr>",
+                            cells[0],
+                            cells[2..].join("</td><td>")
+                        );
+                    }
+                    let _ = writeln!(out, "</table>");
+                }
+            }
+        }
+    }
+
+    if format == Document::Html {
+        let _ = writeln!(out, "</body>\n</html>");
+    }
+
+    out
+}
+
+/// Inline SVG of the running PnL of a wallet's rows, summed over chains per
+/// period. In USD when every row has it, in native units otherwise
+fn chart(rows: &[&Row]) -> String {
+    let usd = rows.iter().all(|row| row.pnl_usd.is_some());
+    let mut by_period: BTreeMap<&str, f64> = BTreeMap::new();
+    for row in rows {
+        let amount = match (&row.pnl_usd, usd) {
+            (Some(pnl_usd), true) => pnl_usd,
+            _ => &row.pnl,
+        };
+        *by_period.entry(row.period.as_str()).or_default() += amount.parse::<f64>().unwrap_or_default();
+    }
+
+    let totals = by_period
+        .values()
+        .scan(0.0, |total, amount| {
+            *total += amount;
+            Some(*total)
+        })
+        .collect::<Vec<_>>();
+    let (min, max) = totals.iter().fold((0.0_f64, 0.0_f64), |(min, max), total| {
+        (min.min(*total), max.max(*total))
+    });
+    let span = (max - min).max(f64::EPSILON);
+    let x = |i: usize| match totals.len() {
+        1 => CHART_WIDTH / 2.0,
+        n => CHART_PADDING + i as f64 * (CHART_WIDTH - 2.0 * CHART_PADDING) / (n - 1) as f64,
+    };
+    let y = |value: f64| CHART_PADDING + (max - value) / span * (CHART_HEIGHT - 2.0 * CHART_PADDING);
+
+    let last = totals.last().copied().unwrap_or_default();
+    let color = if last < 0.0 { "#c0392b" } else { "#27ae60" };
+    let points = totals
+        .iter()
+        .enumerate()
+        .map(|(i, total)| (x(i), y(*total)))
+        .collect::<Vec<_>>();
+    let line = points
+        .iter()
+        .map(|(x, y)| format!("{x:.1},{y:.1}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{CHART_WIDTH}\" height=\"{CHART_HEIGHT}\" \
+        viewBox=\"0 0 {CHART_WIDTH} {CHART_HEIGHT}\">\
+        <line x1=\"0\" y1=\"{zero:.1}\" x2=\"{CHART_WIDTH}\" y2=\"{zero:.1}\" stroke=\"#ccc\"/>\
+        <polyline fill=\"none\" stroke=\"{color}\" stroke-width=\"2\" points=\"{line}\"/>",
+        zero = y(0.0),
+    );
+    for (x, y) in points {
+        let _ = write!(svg, "<circle cx=\"{x:.1}\" cy=\"{y:.1}\" r=\"2\" fill=\"{color}\"/>");
+    }
+    let _ = write!(
+        svg,
+        "<text x=\"{CHART_PADDING}\" y=\"{}\" font-size=\"10\" fill=\"#666\">{} {last:.2}</text></svg>",
+        CHART_HEIGHT - 2.0,
+        if usd { "USD" } else { "native" },
+    );
+
+    svg
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
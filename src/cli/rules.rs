@@ -0,0 +1,47 @@
+use {
+    crate::{chain_adapter::ChainRules, config::Config},
+    clap::Args as ClapArgs,
+    eyre::{ensure, Context, ContextCompat},
+};
+
+/// The rules a chain of a config adds to its adapter, e.g. its fee mode, block
+/// subsidy, stables and bridges, for commands processing blocks outside of
+/// `start` to get the same reports. The adapter's alone without a config
+#[derive(Debug, Clone, ClapArgs)]
+pub struct RulesArgs {
+    #[arg(long, help = "Config to apply the chain rules of")]
+    config: Option<String>,
+
+    #[arg(
+        long,
+        requires = "config",
+        help = "Chain to apply the rules of, by its name in the config. Optional with a single chain"
+    )]
+    chain_name: Option<String>,
+}
+
+impl RulesArgs {
+    pub fn load(&self) -> eyre::Result<ChainRules> {
+        let Some(path) = &self.config else {
+            return Ok(ChainRules::default());
+        };
+
+        let config = Config::from_file(path).context("Failed to parse config")?;
+        let chain_name = match &self.chain_name {
+            Some(chain) => chain.clone(),
+            None => {
+                ensure!(
+                    config.chains.len() == 1,
+                    "Several chains are set up, pick one with --chain-name"
+                );
+                config.chains.keys().next().context("No chain is set up")?.clone()
+            }
+        };
+
+        Ok(config
+            .chains
+            .get(&chain_name)
+            .with_context(|| format!("Chain {chain_name} not found"))?
+            .rules())
+    }
+}
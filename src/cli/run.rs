@@ -1,21 +1,33 @@
 use {
+    super::rules::RulesArgs,
     crate::{
-        config::{AlertTo, WalletWithContext},
-        message::MessageGenerator,
+        chain_adapter::ChainRules,
+        config::{TraceConfig, WalletWithContext},
+        message::{MessageFormat, MessageGenerator},
         processor::{self, trace_options},
-        utils::{get_receipt_and_trace, new_provider},
+        utils::{fill_block_producer, get_receipts_and_trace_results, new_provider, zip_traces},
     },
     alloy::{
         primitives::{Address, TxHash, U64},
         providers::Provider,
         rpc::{
             client::BatchRequest,
-            types::{trace::geth::GethTrace, AnyTransactionReceipt},
+            types::{
+                trace::geth::{CallFrame, GethTrace, TraceResult},
+                AnyTransactionReceipt, Block,
+            },
         },
     },
     alloy_chains::Chain,
     clap::Parser,
-    std::{collections::HashSet, sync::Arc},
+    eyre::{bail, ensure, Context, ContextCompat},
+    serde::{de::DeserializeOwned, Serialize},
+    serde_json::Value,
+    std::{
+        collections::HashSet,
+        path::{Path, PathBuf},
+        sync::Arc,
+    },
 };
 
 #[derive(Debug, Clone, Parser)]
@@ -24,13 +36,23 @@ pub struct TxArgs {
 
     #[arg(short, long, env = "ETH_RPC_URL")]
     rpc_url: String,
+
+    #[arg(
+        long,
+        help = "Save the receipt and trace used to this directory, to attach to a bug report"
+    )]
+    dump_inputs: Option<PathBuf>,
+
+    #[command(flatten)]
+    rules: RulesArgs,
 }
 
 impl TxArgs {
-    pub async fn run(self) {
+    pub async fn run(self) -> eyre::Result<()> {
         tracing_subscriber::fmt::init();
 
-        let provider = new_provider(&self.rpc_url).await.expect("Failed to create provider");
+        let rules = self.rules.load()?;
+        let provider = new_provider(&self.rpc_url).await.context("Failed to create provider")?;
 
         let mut batch = BatchRequest::new(provider.client());
 
@@ -39,25 +61,40 @@ impl TxArgs {
             .add_call::<_, AnyTransactionReceipt>("eth_getTransactionReceipt", &(self.hash,))
             .unwrap();
         let trace = batch
-            .add_call::<_, GethTrace>("debug_traceTransaction", &(self.hash, trace_options()))
+            .add_call::<_, GethTrace>(
+                "debug_traceTransaction",
+                &(self.hash, trace_options(&TraceConfig::default())),
+            )
             .unwrap();
 
-        batch.await.expect("Failed to send batch request");
+        batch.await.context("Failed to send batch request")?;
+
+        let chain: Chain = chain.await.context("Failed to get chain id")?.to::<u64>().into();
+        let receipt = receipt.await.context("Failed to get transaction receipt")?;
+        let trace = trace.await.context("Failed to trace transaction")?;
 
-        let chain: Chain = chain.await.expect("Failed to get chain id").to::<u64>().into();
-        let receipt = receipt.await.expect("Failed to get transaction receipt");
-        let trace = trace.await.expect("Failed to trace transaction");
+        if let Some(dir) = &self.dump_inputs {
+            dump(dir, "receipt.json", &receipt)?;
+            dump(dir, "trace.json", &trace)?;
+            eprintln!(
+                "Saved the inputs of tx {} on chain {chain} to {}",
+                self.hash,
+                dir.display()
+            );
+        }
 
         let call_trace = trace
             .try_into_call_frame()
-            .expect("Failed to convert trace to call frame");
+            .context("Failed to convert trace to call frame")?;
 
-        let involved_wallets = HashSet::from([receipt.from, receipt.to.expect("No recipient")]);
+        let involved_wallets = HashSet::from([receipt.from, receipt.to.context("No recipient")?]);
 
-        let bcs = processor::generate_pnl(chain, &receipt, &call_trace, Some(&involved_wallets))
-            .expect("Failed to generate balance changes");
+        let bcs = processor::generate_pnl(chain, &rules, &receipt, &call_trace, Some(&involved_wallets))
+            .context("Failed to generate balance changes")?;
 
         println!("{:#?}", bcs);
+
+        Ok(())
     }
 }
 
@@ -71,8 +108,26 @@ pub struct BlockArgs {
     #[arg(short, long, help = "The builder address of the wallet")]
     builder: Option<Address>,
 
-    #[arg(short, long, env = "ETH_RPC_URL")]
-    rpc_url: String,
+    #[arg(short, long, env = "ETH_RPC_URL", required_unless_present = "from_files")]
+    rpc_url: Option<String>,
+
+    #[arg(
+        long,
+        num_args = 3,
+        value_names = ["RECEIPTS", "TRACES", "HEADER"],
+        help = "Process the results of eth_getBlockReceipts, debug_traceBlockByNumber and eth_getBlockByNumber saved \
+                as JSON instead of calling a node. No message is rendered, as token metadata needs a node"
+    )]
+    from_files: Option<Vec<PathBuf>>,
+
+    #[arg(long, help = "Chain id of the files given with --from-files. Mainnet if unset")]
+    chain: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Save the header, receipts and traces used to this directory, in the format --from-files reads"
+    )]
+    dump_inputs: Option<PathBuf>,
 
     #[arg(
         short = 'a',
@@ -84,39 +139,64 @@ pub struct BlockArgs {
 
     #[arg(long, help = "If true, the recipient will be included in PnL calculations")]
     include_recipient: bool,
+
+    #[command(flatten)]
+    rules: RulesArgs,
 }
 
 impl BlockArgs {
-    pub async fn run(self) {
+    pub async fn run(self) -> eyre::Result<()> {
         tracing_subscriber::fmt::init();
 
-        let provider = new_provider(&self.rpc_url).await.expect("Failed to create provider");
-        let provider: Arc<dyn Provider<_>> = Arc::from(provider);
-        let chain: Chain = provider.get_chain_id().await.expect("Failed to get chain id").into();
-        let block = provider
-            .get_block_by_number(self.block.into(), false)
-            .await
-            .expect("Failed to get block")
-            .expect("Block not found");
-        let receipt_and_traces = get_receipt_and_trace(provider.as_ref(), self.block)
-            .await
-            .expect("Failed to get receipt and trace");
-
         let wallets = vec![WalletWithContext::new(
             "Unnamed".to_string(),
             self.address,
             self.builder,
-            self.other_addresses,
+            self.other_addresses.clone(),
             self.include_recipient,
-            Arc::new(AlertTo {
-                bot_token: "".to_string(),
-                chat_id: "".to_string(),
-                thread_id: None,
-            }),
+            Arc::default(),
         )];
+        let rules = self.rules.load()?;
+
+        if let Some(files) = &self.from_files {
+            return self.run_from_files(files, &rules, &wallets);
+        }
 
-        let report = processor::process_block(chain, &block.header, &receipt_and_traces, &wallets)
-            .expect("Failed to generate balance changes")
+        let rpc_url = self.rpc_url.as_deref().context("No RPC URL")?;
+        let provider = new_provider(rpc_url).await.context("Failed to create provider")?;
+        let provider: Arc<dyn Provider<_>> = Arc::from(provider);
+        let chain: Chain = provider.get_chain_id().await.context("Failed to get chain id")?.into();
+        let mut block = provider
+            .get_block_by_number(self.block.into(), false)
+            .await
+            .context("Failed to get block")?
+            .context("Block not found")?;
+        fill_block_producer(provider.as_ref(), chain, &mut block.header).await?;
+        let (receipts, traces) = get_receipts_and_trace_results(provider.as_ref(), self.block, &TraceConfig::default())
+            .await
+            .context("Failed to get receipt and trace")?;
+
+        if let Some(dir) = &self.dump_inputs {
+            dump(dir, "receipts.json", &receipts)?;
+            dump(dir, "traces.json", &traces)?;
+            dump(dir, "header.json", &block)?;
+            eprintln!(
+                "Saved the inputs to {dir}, replay with: run-block {} {} --chain {} --from-files {dir}/receipts.json \
+                 {dir}/traces.json {dir}/header.json",
+                self.block,
+                self.address,
+                chain.id(),
+                dir = dir.display()
+            );
+        }
+
+        let receipt_and_traces = zip_traces(receipts, traces)?
+            .into_iter()
+            .map(|(receipt, trace)| trace.map(|trace| (receipt, trace)))
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        let report = processor::process_block(chain, &rules, &block.header, &receipt_and_traces, &wallets)
+            .context("Failed to generate balance changes")?
             .first()
             .unwrap()
             .clone();
@@ -127,12 +207,75 @@ impl BlockArgs {
             let mut message_generator = MessageGenerator::new(chain, Arc::clone(&provider));
 
             let message = message_generator
-                .generate(&block, &receipt_and_traces, &report, &wallets[0])
+                .generate(
+                    &block,
+                    &receipt_and_traces,
+                    &report,
+                    &wallets[0],
+                    MessageFormat::Telegram,
+                )
                 .await
-                .expect("Failed to generate message");
+                .context("Failed to generate message")?;
 
             println!("Message:");
             println!("{message}");
         }
+
+        Ok(())
+    }
+
+    /// Process a block from saved RPC results, without any node
+    fn run_from_files(&self, files: &[PathBuf], rules: &ChainRules, wallets: &[WalletWithContext]) -> eyre::Result<()> {
+        let [receipts, traces, header] = files else {
+            bail!("--from-files takes the receipts, traces and header files");
+        };
+
+        let chain = Chain::from(self.chain.unwrap_or(1));
+        let receipts: Vec<AnyTransactionReceipt> = read_result(receipts)?;
+        let traces: Vec<TraceResult> = read_result(traces)?;
+        let block: Block = read_result(header)?;
+        ensure!(
+            block.header.number == self.block,
+            "The header file is of block {}, not {}",
+            block.header.number,
+            self.block
+        );
+
+        let receipt_and_traces = zip_traces(receipts, traces)?
+            .into_iter()
+            .map(|(receipt, trace)| trace.map(|trace| (receipt, trace)))
+            .collect::<eyre::Result<Vec<(AnyTransactionReceipt, CallFrame)>>>()?;
+
+        let report = processor::process_block(chain, rules, &block.header, &receipt_and_traces, wallets)
+            .context("Failed to generate balance changes")?
+            .first()
+            .unwrap()
+            .clone();
+
+        println!("Report: {report:#?}");
+
+        Ok(())
     }
 }
+
+/// A saved RPC result, bare or in its JSON-RPC response as `curl` prints it
+fn read_result<T: DeserializeOwned>(path: &Path) -> eyre::Result<T> {
+    let file = std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut value: Value =
+        serde_json::from_reader(file).with_context(|| format!("{} is not valid JSON", path.display()))?;
+
+    if let Some(result) = value.get_mut("result") {
+        value = result.take();
+    }
+
+    serde_json::from_value(value).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Write a fetched input as pretty JSON
+fn dump(dir: &Path, name: &str, value: &impl Serialize) -> eyre::Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let path = dir.join(name);
+    std::fs::write(&path, serde_json::to_string_pretty(value)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
@@ -0,0 +1,198 @@
+use {
+    crate::{
+        backfill,
+        config::{Config, WalletWithContext},
+        price::PriceOracle,
+        storage,
+        utils::new_provider,
+    },
+    alloy::providers::Provider,
+    alloy_chains::Chain,
+    clap::Parser,
+    eyre::{bail, ensure, Context, ContextCompat},
+    std::{collections::BTreeMap, sync::Arc},
+    tokio::sync::{mpsc::unbounded_channel, Semaphore},
+    tracing::{info, warn},
+};
+
+/// Process a range of past blocks for the wallets of a chain in the config and
+/// print their reports to stdout as JSON lines, in block order. With `prices`
+/// in the config, reports are valued in USD at the prices of their block
+#[derive(Debug, Clone, Parser)]
+pub struct Args {
+    /// The path to the config file
+    #[arg(default_value = "config.toml", help = "The path to the config file")]
+    config: String,
+
+    #[arg(
+        long,
+        help = "Chain to scan, by its name in the config. Optional with a single chain"
+    )]
+    chain: Option<String>,
+
+    #[arg(long, conflicts_with = "last_n_blocks", help = "First block to scan")]
+    from_block: Option<u64>,
+
+    #[arg(long, help = "Last block to scan, the latest one if unset")]
+    to_block: Option<u64>,
+
+    #[arg(long, help = "Scan this many blocks up to --to-block")]
+    last_n_blocks: Option<u64>,
+
+    #[arg(long, help = "Only these wallets, by name")]
+    wallet: Vec<String>,
+
+    #[arg(long, help = "Blocks fetched at once. Twice the CPU count if unset")]
+    concurrency: Option<usize>,
+
+    #[arg(long, help = "Save the reports into the config's storage too")]
+    store: bool,
+}
+
+impl Args {
+    pub async fn run(self) -> eyre::Result<()> {
+        // Reports go to stdout
+        tracing_subscriber::fmt().with_writer(std::io::stderr).init();
+
+        let config = Config::from_file(&self.config).context("Failed to parse config")?;
+        let chain_name = match &self.chain {
+            Some(chain) => chain.clone(),
+            None => {
+                ensure!(
+                    config.chains.len() == 1,
+                    "Several chains are set up, pick one with --chain"
+                );
+                config.chains.keys().next().context("No chain is set up")?.clone()
+            }
+        };
+        let chain_config = config
+            .chains
+            .get(&chain_name)
+            .with_context(|| format!("Chain {chain_name} not found"))?
+            .clone();
+
+        let wallets = config
+            .to_wallet_with_context_by_chain()
+            .remove(&chain_name)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|w| self.wallet.is_empty() || self.wallet.contains(&w.name))
+            .collect::<Vec<_>>();
+        ensure!(!wallets.is_empty(), "No wallet to scan on {chain_name}");
+
+        let storage = match (&config.storage, self.store) {
+            (Some(url), true) => Some(storage::connect(url).await.context("Failed to connect to storage")?),
+            (None, true) => bail!("--store is set but the config has no storage"),
+            _ => None,
+        };
+
+        let provider: Arc<dyn Provider> = new_provider(&chain_config.rpc)
+            .await
+            .context("Failed to create provider")?
+            .into();
+        let chain: Chain = provider.get_chain_id().await.context("Failed to get chain id")?.into();
+        let rules = Arc::new(chain_config.rules());
+
+        let to_block = match self.to_block {
+            Some(block) => block,
+            None => provider
+                .get_block_number()
+                .await
+                .context("Failed to get latest block")?,
+        };
+        let from_block = match (self.from_block, self.last_n_blocks) {
+            (Some(block), _) => block,
+            (None, Some(n)) => to_block.saturating_sub(n.saturating_sub(1)),
+            (None, None) => bail!("Set --from-block or --last-n-blocks"),
+        };
+        ensure!(from_block <= to_block, "--from-block is after --to-block");
+
+        info!(%chain, from_block, to_block, wallets = wallets.len(), "Scanning");
+
+        let mut prices = config
+            .prices
+            .as_ref()
+            .map(|prices| PriceOracle::new(chain, Arc::clone(&provider), prices, &chain_config));
+
+        let wallets: Arc<[WalletWithContext]> = wallets.into();
+        let trace_config = chain_config.trace;
+        let lenient = config.lenient_traces;
+        let concurrency = self.concurrency.unwrap_or_else(|| num_cpus::get() * 2).max(1);
+
+        let (sender, mut receiver) = unbounded_channel();
+        tokio::spawn({
+            let provider = Arc::clone(&provider);
+            let wallets = Arc::clone(&wallets);
+            let rules = Arc::clone(&rules);
+            async move {
+                let semaphore = Arc::new(Semaphore::new(concurrency));
+                for number in from_block..=to_block {
+                    let permit = semaphore.clone().acquire_owned().await.unwrap();
+                    let sender = sender.clone();
+                    let provider = Arc::clone(&provider);
+                    let wallets = Arc::clone(&wallets);
+                    let rules = Arc::clone(&rules);
+
+                    tokio::spawn(async move {
+                        let reports = backfill::scan_block(
+                            chain,
+                            &rules,
+                            provider.as_ref(),
+                            number,
+                            &trace_config,
+                            &wallets,
+                            lenient,
+                        )
+                        .await;
+                        let _ = sender.send((number, reports));
+                        drop(permit);
+                    });
+                }
+            }
+        });
+
+        // Blocks done ahead of the next one to print
+        let mut done = BTreeMap::new();
+        let mut next = from_block;
+        let (mut reports, mut failed) = (0, 0);
+
+        while let Some((number, result)) = receiver.recv().await {
+            done.insert(number, result);
+
+            while let Some(result) = done.remove(&next) {
+                match result {
+                    Ok(events) => {
+                        for mut event in events {
+                            if let Some(prices) = prices.as_mut() {
+                                prices
+                                    .value_report(event.block, event.timestamp, &mut event.report)
+                                    .await;
+                            }
+                            println!(
+                                "{}",
+                                serde_json::to_string(&event).context("Failed to serialize report")?
+                            );
+                            if let Some(storage) = &storage {
+                                backfill::save(storage.as_ref(), event).await;
+                            }
+                            reports += 1;
+                        }
+                    }
+                    Err(err) => {
+                        warn!(block = next, "Failed to scan block: {err:#}");
+                        failed += 1;
+                    }
+                }
+                next += 1;
+            }
+
+            if next > to_block {
+                break;
+            }
+        }
+
+        info!(blocks = to_block - from_block + 1, reports, failed, "Scan done");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use {
+    crate::executor::{mqtt::Summary, push::PushPayload, webhook::WebhookPayload, AlertEvent, ReportEvent},
+    clap::{Parser, ValueEnum},
+    eyre::Context,
+    schemars::schema_for,
+};
+
+/// Print the JSON Schema of a payload the watcher sends, to generate typed
+/// clients from
+#[derive(Debug, Clone, Parser)]
+pub struct Args {
+    #[arg(value_enum, default_value = "report")]
+    payload: Payload,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Payload {
+    /// Reports published to NATS and Redis
+    Report,
+    /// Watch alerts published to NATS and Redis
+    Alert,
+    /// Posts to the push endpoint
+    Push,
+    /// MQTT summaries
+    Mqtt,
+    /// Plain text webhook posts
+    Webhook,
+}
+
+impl Args {
+    pub async fn run(self) -> eyre::Result<()> {
+        let schema = match self.payload {
+            Payload::Report => schema_for!(ReportEvent),
+            Payload::Alert => schema_for!(AlertEvent),
+            Payload::Push => schema_for!(PushPayload),
+            Payload::Mqtt => schema_for!(Summary),
+            Payload::Webhook => schema_for!(WebhookPayload),
+        };
+
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&schema).context("Failed to serialize schema")?
+        );
+
+        Ok(())
+    }
+}
@@ -1,42 +1,173 @@
 use {
-    crate::{config::Config, strategy::WalletWatcher, utils::new_pubsub_provider},
+    crate::{
+        audit::{AuditLog, Audited},
+        backfill,
+        config::{BlockSource, Config, ErrorAlertTo},
+        digest, exchanges,
+        executor::{
+            clickhouse::ClickhouseExporter,
+            discord::DiscordExecutor,
+            dune::DuneExporter,
+            mqtt::MqttPublisher,
+            nats::NatsPublisher,
+            push::PushExecutor,
+            redis::RedisPublisher,
+            signal::SignalExecutor,
+            slack::SlackExecutor,
+            telegram::{self, TelegramExecutor},
+            twilio::TwilioExecutor,
+            webhook::WebhookExecutor,
+            Action, Shared,
+        },
+        message::Renderer,
+        price::PriceOracle,
+        reconnect::ReconnectingCollector,
+        relay::RelayClient,
+        reload, retention,
+        retry::RetryCollector,
+        rpc_metrics,
+        storage::{self, ChainLock, Storage},
+        strategy::WalletWatcher,
+        token_list, tui,
+        utils::new_pubsub_provider,
+    },
     alloy::{providers::Provider, pubsub::PubSubFrontend, rpc::types::Block},
     alloy_chains::Chain,
-    burberry::{
-        collector::BlockCollector,
-        executor::telegram_message::{Message, TelegramMessageDispatcher},
-        Engine,
-    },
+    burberry::{Engine, Executor},
     clap::Parser,
-    std::sync::Arc,
-    tokio::task::JoinHandle,
-    tracing::{error, info},
+    eyre::{ensure, Context},
+    std::{
+        fs::File,
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+    tokio::{
+        sync::watch,
+        task::{JoinHandle, JoinSet},
+    },
+    tracing::{error, info, warn},
 };
 
+/// How often the RPC calls and telegram messages made so far are logged
+const RPC_USAGE_LOG_INTERVAL: Duration = Duration::from_secs(600);
+
+/// How often a standby instance tries to take the lock of a chain, and the
+/// leader checks it still holds it
+const CHAIN_LOCK_INTERVAL: Duration = Duration::from_secs(10);
+
 #[derive(Debug, Clone, Parser)]
 pub struct Args {
     /// The path to the config file
     #[arg(default_value = "config.toml", help = "The path to the config file")]
     config: String,
+
+    #[arg(long, help = "Show a live dashboard in the terminal. Alerts are still sent")]
+    tui: bool,
+
+    #[arg(long, default_value = "wallet-watcher.log", help = "Where logs go when --tui is set")]
+    log_file: String,
 }
 
 impl Args {
-    pub async fn run(self) {
-        tracing_subscriber::fmt::init();
+    pub async fn run(self) -> eyre::Result<()> {
+        let dashboard = if self.tui {
+            let log_file = File::create(&self.log_file).context("Failed to create log file")?;
+            tracing_subscriber::fmt()
+                .with_writer(Mutex::new(log_file))
+                .with_ansi(false)
+                .init();
+
+            Some(tui::spawn())
+        } else {
+            tracing_subscriber::fmt::init();
+            None
+        };
 
-        let config = Config::from_file(&self.config).expect("Failed to parse config");
-        if config.chains.is_empty() {
-            panic!("no chain is set up");
+        let config = Config::from_file(&self.config).context("Failed to parse config")?;
+        ensure!(!config.chains.is_empty(), "no chain is set up");
+
+        for wallet in config.channels.iter().flat_map(|c| &c.wallets).filter(|w| w.disabled) {
+            info!(wallet = %wallet.name, "Wallet is disabled, not watching it");
         }
 
         let wallets_by_chain = config.to_wallet_with_context_by_chain();
+        let token_watches_by_chain = config.to_token_watches_by_chain();
+        let event_watches_by_chain = config.to_event_watches_by_chain();
+        let large_transfer_watches_by_chain = config.to_large_transfer_watches_by_chain();
+        let cluster_watches_by_chain = config.to_cluster_watches_by_chain();
+        let lenient_traces = config.lenient_traces;
+        let error_channel = config.error_channel.clone();
+        let retry = config.retry;
+        let trace_limits = config.trace_limits;
+        let permalink = config.permalink.clone();
+        let token_risk = config.token_risk.clone();
+        let prices = config.prices.clone();
+        let first_seen = config.first_seen;
+        let backfill_days = config.backfill_days;
+        let leader_election = config.leader_election;
+        let daily_digest = config.channels.iter().any(|channel| channel.daily_digest);
+        let cex_alert = config.cex.as_ref().is_some_and(|cex| cex.alert);
+        if let Some(cex) = &config.cex {
+            exchanges::register(&cex.addresses);
+        }
+
+        let token_cache = match &config.redis_cache {
+            Some(url) => Some(
+                crate::executor::redis::connect(url)
+                    .await
+                    .context("Failed to connect to redis cache")?,
+            ),
+            None => None,
+        };
+
+        let audit_log = match &config.audit_log {
+            Some(path) => Some(Arc::new(AuditLog::open(path)?)),
+            None => None,
+        };
+
+        let storage = match &config.storage {
+            Some(url) => Some(storage::connect(url).await.context("Failed to connect to storage")?),
+            None => None,
+        };
+
+        if let (Some(storage), Some(retention)) = (&storage, config.retention.clone()) {
+            tokio::spawn(retention::prune_every(Arc::clone(storage), retention));
+        }
+
+        let listed_tokens = Arc::new(token_list::load(&config.token_lists).await);
+
+        // One set for every chain, so that each output keeps a single
+        // connection, rate limit and buffer per process
+        let shared_executors = executors(error_channel.clone(), &audit_log);
+        tokio::spawn(telegram::log_stats_every(RPC_USAGE_LOG_INTERVAL));
+
+        let (reloads, _) = watch::channel(wallets_by_chain.clone());
+        tokio::spawn(reload::watch_config(
+            self.config.clone(),
+            config.chains.keys().cloned().collect(),
+            reloads.clone(),
+        ));
 
         let mut tasks: Vec<JoinHandle<_>> = vec![];
-        for (name, rpc) in config.chains {
-            let wallets = wallets_by_chain.get(&name).cloned().unwrap_or_default();
+        for (name, chain_config) in config.chains {
+            let rpc = chain_config.rpc.clone();
+            let token_watches = token_watches_by_chain.get(&name).cloned().unwrap_or_default();
+            let event_watches = event_watches_by_chain.get(&name).cloned().unwrap_or_default();
+            let large_transfer_watches = large_transfer_watches_by_chain.get(&name).cloned().unwrap_or_default();
+            let cluster_watches = cluster_watches_by_chain.get(&name).cloned().unwrap_or_default();
+            let token_cache = token_cache.clone();
+            let dashboard = dashboard.clone();
+            let error_channel = error_channel.clone();
+            let permalink = permalink.clone();
+            let token_risk = token_risk.clone();
+            let prices = prices.clone();
+            let storage = storage.clone();
+            let listed_tokens = Arc::clone(&listed_tokens);
+            let shared_executors = shared_executors.clone();
+            let reloads = reloads.subscribe();
             let provider: Arc<dyn Provider<PubSubFrontend>> = new_pubsub_provider(&rpc)
                 .await
-                .expect("Failed to create provider")
+                .context("Failed to create provider")?
                 .into();
 
             let task = tokio::spawn(async move {
@@ -48,14 +179,185 @@ impl Args {
                     }
                 };
 
-                let mut engine = Engine::<Block, Message>::new();
+                rpc_metrics::register_costs(chain, chain_config.rpc_provider, &chain_config.rpc_costs);
+                tokio::spawn(rpc_metrics::log_usage_every(chain, RPC_USAGE_LOG_INTERVAL));
+
+                loop {
+                    let lock = match (leader_election, &storage) {
+                        (true, Some(storage)) => Some(lead(chain, storage.as_ref()).await),
+                        _ => None,
+                    };
+
+                    // Everything the chain runs while this instance leads it,
+                    // stopped together when the lock is lost
+                    let watching = async {
+                        let mut side_tasks = JoinSet::new();
+                        let mut reloads = reloads.clone();
+                        let wallets = reloads.borrow_and_update().get(&name).cloned().unwrap_or_default();
+
+                        // Sent by the leader of the chain alone
+                        if let (true, Some(storage)) = (daily_digest, &storage) {
+                            side_tasks.spawn(digest::send_daily(
+                                chain,
+                                name.clone(),
+                                Arc::clone(storage),
+                                Renderer::new(chain).with_chain_config(&chain_config),
+                                reloads.clone(),
+                                (!chain_config.relays.is_empty()).then(|| RelayClient::new(&chain_config.relays)),
+                                shared_executors.clone(),
+                            ));
+                        }
+
+                        let onboard = {
+                            let provider = provider.clone();
+                            let storage = storage.clone();
+                            let prices = prices.clone();
+                            let chain_config = chain_config.clone();
+                            move |wallets: Vec<_>| {
+                                let (Some(days), Some(storage)) = (backfill_days, storage.clone()) else {
+                                    return None;
+                                };
+                                let prices = prices
+                                    .as_ref()
+                                    .map(|prices| PriceOracle::new(chain, provider.clone(), prices, &chain_config));
+                                let onboarding = backfill::onboard(
+                                    chain,
+                                    chain_config.rules(),
+                                    provider.clone(),
+                                    wallets,
+                                    days,
+                                    chain_config.trace,
+                                    lenient_traces,
+                                    storage,
+                                    prices,
+                                );
+                                Some(async move {
+                                    if let Err(err) = onboarding.await {
+                                        error!(%chain, "Failed to backfill new wallets: {err:#}");
+                                    }
+                                })
+                            }
+                        };
+                        if let Some(onboarding) = onboard(wallets.clone()) {
+                            side_tasks.spawn(onboarding);
+                        }
 
-                engine.add_collector(Box::new(BlockCollector::new(provider.clone())));
-                engine.add_strategy(Box::new(WalletWatcher::new(chain, provider.clone(), wallets)));
-                engine.add_executor(Box::new(TelegramMessageDispatcher::new(None, None, None)));
+                        let (wallet_updates, wallet_receiver) = watch::channel(wallets.clone());
+                        let transfer_logs = chain_config.block_source == BlockSource::TransferLogs;
+                        let name = name.clone();
+                        side_tasks.spawn(async move {
+                            let mut onboardings = JoinSet::new();
+                            while reloads.changed().await.is_ok() {
+                                let wallets = reloads.borrow_and_update().get(&name).cloned().unwrap_or_default();
+                                if transfer_logs {
+                                    warn!(
+                                        %chain,
+                                        "Transfer logs of reloaded wallets are only subscribed to on restart"
+                                    );
+                                }
+                                if let Some(onboarding) = onboard(wallets.clone()) {
+                                    onboardings.spawn(onboarding);
+                                }
+                                if wallet_updates.send(wallets).is_err() {
+                                    break;
+                                }
+                            }
+                        });
 
-                info!(%chain, %rpc, "Start monitoring");
-                let _ = engine.run_and_join().await;
+                        let mut engine = Engine::<Block, Action>::new();
+
+                        let addresses = match chain_config.block_source {
+                            BlockSource::Blocks => vec![],
+                            BlockSource::TransferLogs => wallets
+                                .iter()
+                                .flat_map(|w| w.involved_wallets().iter().copied())
+                                .collect(),
+                        };
+                        let checkpoint = match &storage {
+                            Some(storage) => storage.checkpoint(chain.id()).await.unwrap_or_else(|err| {
+                                warn!(%chain, "Failed to read checkpoint, not catching up: {err:#}");
+                                None
+                            }),
+                            None => None,
+                        };
+                        let (provider_updates, provider_receiver) = watch::channel(provider.clone());
+                        engine.add_collector(Box::new(
+                            ReconnectingCollector::new(
+                                chain,
+                                rpc.clone(),
+                                provider.clone(),
+                                chain_config.block_source,
+                                addresses,
+                                provider_updates,
+                            )
+                            .with_checkpoint(checkpoint),
+                        ));
+                        let (retries, retry_collector) = RetryCollector::new();
+                        engine.add_collector(Box::new(retry_collector));
+                        let mut strategy = WalletWatcher::new(chain, provider.clone(), wallets)
+                            .with_chain_config(&chain_config)
+                            .with_lenient_traces(lenient_traces)
+                            .with_retry(retry, retries)
+                            .with_trace_limits(trace_limits)
+                            .with_cex_alert(cex_alert)
+                            .with_token_watches(token_watches.clone())
+                            .with_event_watches(event_watches.clone())
+                            .with_large_transfer_watches(large_transfer_watches.clone())
+                            .with_cluster_watches(cluster_watches.clone())
+                            .with_wallet_updates(wallet_receiver)
+                            .with_provider_updates(provider_receiver);
+                        if let Some(first_seen) = first_seen {
+                            strategy = strategy.with_first_seen(first_seen, token_cache.clone());
+                        }
+                        if let Some(cache) = token_cache.clone() {
+                            strategy = strategy.with_delivery_store(cache.clone()).with_token_cache(cache);
+                        }
+                        if let Some(tokens) = listed_tokens.get(&chain.id()) {
+                            strategy = strategy.with_listed_tokens(tokens);
+                        }
+                        if let Some(dashboard) = dashboard.clone() {
+                            strategy = strategy.with_dashboard(dashboard);
+                        }
+                        if let Some(channel) = error_channel.clone() {
+                            strategy = strategy.with_error_channel(channel);
+                        }
+                        if let Some(permalink) = permalink.clone() {
+                            strategy = strategy.with_permalink(permalink);
+                        }
+                        if let Some(token_risk) = token_risk.clone() {
+                            strategy = strategy.with_token_risk(token_risk);
+                        }
+                        if let Some(storage) = storage.clone() {
+                            strategy = strategy.with_storage(storage);
+                        }
+                        if let Some(prices) = &prices {
+                            strategy = strategy.with_prices(prices, &chain_config);
+                        }
+
+                        engine.add_strategy(Box::new(strategy));
+                        for executor in &shared_executors {
+                            engine.add_executor(Box::new(Shared::new(Arc::clone(executor))));
+                        }
+
+                        info!(%chain, %rpc, "Start monitoring");
+                        let _ = engine.run_and_join().await;
+                    };
+
+                    let lost_lead = match lock {
+                        Some(lock) => tokio::select! {
+                            _ = watching => false,
+                            _ = keep_lead(chain, lock) => true,
+                        },
+                        None => {
+                            watching.await;
+                            false
+                        }
+                    };
+                    if !lost_lead {
+                        break;
+                    }
+                    warn!(%chain, "Stopped watching the chain, standing by for its lock");
+                }
 
                 error!(%chain, "Engine stopped");
             });
@@ -68,5 +370,78 @@ impl Args {
             let _ = task.await;
             break;
         }
+
+        Ok(())
+    }
+}
+
+/// Wait until this instance holds the lock of `chain`, so it's the only one
+/// watching it
+async fn lead(chain: Chain, storage: &dyn Storage) -> ChainLock {
+    let mut standing_by = false;
+
+    loop {
+        match storage.lock_chain(chain.id()).await {
+            Ok(Some(lock)) => {
+                info!(%chain, "Took the chain lock, watching it");
+                return lock;
+            }
+            Ok(None) if !standing_by => {
+                info!(%chain, "Another instance watches the chain, standing by");
+                standing_by = true;
+            }
+            Ok(None) => {}
+            Err(err) => warn!(%chain, "Failed to take the chain lock: {err:#}"),
+        }
+
+        tokio::time::sleep(CHAIN_LOCK_INTERVAL).await;
+    }
+}
+
+/// Return once the lock of `chain` is lost, since a standby instance may take
+/// over and both would send the same alerts
+async fn keep_lead(chain: Chain, mut lock: ChainLock) {
+    loop {
+        tokio::time::sleep(CHAIN_LOCK_INTERVAL).await;
+
+        if !lock.held().await {
+            error!(%chain, "Lost the chain lock");
+            return;
+        }
+    }
+}
+
+/// An executor for every output. Each one only acts on the actions of its
+/// own output
+pub fn executors(
+    error_channel: Option<ErrorAlertTo>,
+    audit_log: &Option<Arc<AuditLog>>,
+) -> Vec<Arc<dyn Executor<Action>>> {
+    vec![
+        audited("telegram", TelegramExecutor::new(error_channel), audit_log),
+        Arc::new(ClickhouseExporter::new()),
+        Arc::new(DuneExporter::new()),
+        Arc::new(NatsPublisher::default()),
+        Arc::new(RedisPublisher::default()),
+        Arc::new(MqttPublisher::default()),
+        audited("signal", SignalExecutor::default(), audit_log),
+        audited("twilio", TwilioExecutor::default(), audit_log),
+        audited("discord", DiscordExecutor::default(), audit_log),
+        audited("slack", SlackExecutor::default(), audit_log),
+        Arc::new(PushExecutor::default()),
+        audited("webhook", WebhookExecutor::default(), audit_log),
+    ]
+}
+
+/// The executor of a notification output, recording what it sends when the
+/// audit log is on
+fn audited<E: Executor<Action> + Send + Sync + 'static>(
+    output: &'static str,
+    executor: E,
+    audit_log: &Option<Arc<AuditLog>>,
+) -> Arc<dyn Executor<Action>> {
+    match audit_log {
+        Some(log) => Arc::new(Audited::new(output, executor, Arc::clone(log))),
+        None => Arc::new(executor),
     }
 }
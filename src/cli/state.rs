@@ -0,0 +1,238 @@
+use {
+    crate::{
+        archive::{self, ArchiveWriter, Entry},
+        config::Config,
+        storage::{self, ReportQuery, Storage},
+    },
+    alloy::hex,
+    clap::{Args as ClapArgs, Parser, Subcommand},
+    eyre::{ensure, Context},
+    redis::{aio::ConnectionManager, AsyncCommands},
+    std::sync::Arc,
+    tracing::info,
+};
+
+/// Reports read from storage at once while exporting
+const EXPORT_PAGE_SIZE: u64 = 1000;
+
+/// Keys the watcher keeps in `redis_cache`: token metadata, tokens seen per
+/// wallet and delivered reports
+const REDIS_KEYS: &str = "wallet-watcher:*";
+
+/// Move the watcher's state to another host, or keep it for recovery: the
+/// reports, backfills and checkpoints in `storage`, and the token metadata
+/// cache and delivered reports in `redis_cache`
+#[derive(Debug, Clone, Parser)]
+pub struct Args {
+    #[command(subcommand)]
+    command: StateCommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum StateCommand {
+    /// Write the state of the config's stores into an archive
+    Export(ExportArgs),
+    /// Load an archive into the config's stores, replacing what they hold under
+    /// the same keys
+    Import(ImportArgs),
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+struct ExportArgs {
+    /// The path to the config file
+    #[arg(default_value = "config.toml", help = "The path to the config file")]
+    config: String,
+
+    #[arg(
+        short,
+        long,
+        default_value = "wallet-watcher-state.jsonl",
+        help = "The archive to write, compressed with zstd when it ends in .zst"
+    )]
+    out: String,
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+struct ImportArgs {
+    #[arg(help = "The archive to load, e.g. one written by export or by retention before pruning")]
+    archive: String,
+
+    /// The path to the config file
+    #[arg(default_value = "config.toml", help = "The path to the config file")]
+    config: String,
+}
+
+/// The stores of the config
+struct Stores {
+    storage: Option<Arc<dyn Storage>>,
+    cache: Option<ConnectionManager>,
+}
+
+impl Stores {
+    async fn connect(config_path: &str) -> eyre::Result<Self> {
+        let config = Config::from_file(config_path).context("Failed to parse config")?;
+        ensure!(
+            config.storage.is_some() || config.redis_cache.is_some(),
+            "The config has neither storage nor redis_cache, there is no state"
+        );
+
+        let storage = match &config.storage {
+            Some(url) => Some(storage::connect(url).await.context("Failed to connect to storage")?),
+            None => None,
+        };
+        let cache = match &config.redis_cache {
+            Some(url) => Some(
+                crate::executor::redis::connect(url)
+                    .await
+                    .context("Failed to connect to redis cache")?,
+            ),
+            None => None,
+        };
+
+        Ok(Self { storage, cache })
+    }
+}
+
+impl Args {
+    pub async fn run(self) -> eyre::Result<()> {
+        tracing_subscriber::fmt::init();
+
+        match self.command {
+            StateCommand::Export(args) => args.run().await,
+            StateCommand::Import(args) => args.run().await,
+        }
+    }
+}
+
+impl ExportArgs {
+    async fn run(self) -> eyre::Result<()> {
+        let stores = Stores::connect(&self.config).await?;
+        let mut out = ArchiveWriter::create(&self.out)?;
+
+        let (mut reports, mut backfills, mut checkpoints, mut keys) = (0, 0, 0, 0);
+
+        if let Some(storage) = &stores.storage {
+            loop {
+                let page = storage
+                    .reports(&ReportQuery {
+                        limit: Some(EXPORT_PAGE_SIZE),
+                        offset: Some(reports),
+                        ..Default::default()
+                    })
+                    .await?;
+                let last_page = (page.len() as u64) < EXPORT_PAGE_SIZE;
+
+                for report in page {
+                    out.write(&Entry::Report(report))?;
+                    reports += 1;
+                }
+                if last_page {
+                    break;
+                }
+            }
+            for backfill in storage.backfills().await? {
+                out.write(&Entry::Backfill(backfill))?;
+                backfills += 1;
+            }
+            for checkpoint in storage.checkpoints().await? {
+                out.write(&Entry::Checkpoint(checkpoint))?;
+                checkpoints += 1;
+            }
+        }
+
+        if let Some(mut cache) = stores.cache {
+            let mut names = vec![];
+            let mut iter = cache
+                .scan_match::<_, String>(REDIS_KEYS)
+                .await
+                .context("Failed to list redis keys")?;
+            while let Some(key) = iter.next_item().await {
+                names.push(key);
+            }
+            drop(iter);
+
+            for key in names {
+                let dump: Option<Vec<u8>> = redis::cmd("DUMP")
+                    .arg(&key)
+                    .query_async(&mut cache)
+                    .await
+                    .with_context(|| format!("Failed to dump {key}"))?;
+                let ttl: i64 = cache
+                    .pttl(&key)
+                    .await
+                    .with_context(|| format!("Failed to get the TTL of {key}"))?;
+
+                // Expired since it was listed
+                let (Some(dump), false) = (dump, ttl == -2) else {
+                    continue;
+                };
+
+                out.write(&Entry::Redis {
+                    key,
+                    ttl_ms: u64::try_from(ttl).ok(),
+                    dump: hex::encode(dump),
+                })?;
+                keys += 1;
+            }
+        }
+
+        out.finish()?;
+        info!(reports, backfills, checkpoints, redis_keys = keys, path = %self.out, "State exported");
+
+        Ok(())
+    }
+}
+
+impl ImportArgs {
+    async fn run(self) -> eyre::Result<()> {
+        let mut stores = Stores::connect(&self.config).await?;
+        let (created_at, entries) = archive::read(&self.archive)?;
+        info!(created_at, "Importing state");
+
+        let (mut reports, mut backfills, mut checkpoints, mut keys, mut skipped) = (0, 0, 0, 0, 0);
+
+        for entry in entries {
+            match (entry?, &stores.storage, stores.cache.as_mut()) {
+                (Entry::Report(report), Some(storage), _) => {
+                    storage.save(&report).await?;
+                    reports += 1;
+                }
+                (Entry::Backfill(backfill), Some(storage), _) => {
+                    storage
+                        .record_backfill(backfill.chain, backfill.address, backfill.from_block, backfill.to_block)
+                        .await?;
+                    backfills += 1;
+                }
+                (Entry::Checkpoint(checkpoint), Some(storage), _) => {
+                    storage.save_checkpoint(checkpoint.chain, checkpoint.block).await?;
+                    checkpoints += 1;
+                }
+                (Entry::Redis { key, ttl_ms, dump }, _, Some(cache)) => {
+                    let dump = hex::decode(&dump).with_context(|| format!("Invalid dump of {key}"))?;
+                    redis::cmd("RESTORE")
+                        .arg(&key)
+                        .arg(ttl_ms.unwrap_or(0))
+                        .arg(dump)
+                        .arg("REPLACE")
+                        .query_async::<()>(cache)
+                        .await
+                        .with_context(|| format!("Failed to restore {key}"))?;
+                    keys += 1;
+                }
+                // The config has no store for it
+                _ => skipped += 1,
+            }
+        }
+
+        info!(
+            reports,
+            backfills,
+            checkpoints,
+            redis_keys = keys,
+            skipped,
+            "State imported"
+        );
+
+        Ok(())
+    }
+}
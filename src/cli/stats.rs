@@ -0,0 +1,265 @@
+use {
+    crate::{
+        config::{AlertTo, Config},
+        executor::clickhouse,
+        markup::Markup,
+        message::MessageFormat,
+    },
+    chrono_tz::Tz,
+    clap::{Parser, ValueEnum},
+    eyre::{eyre, Context, ContextCompat},
+    serde::Deserialize,
+    std::{collections::BTreeMap, fmt::Write},
+};
+
+const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Shades of a text cell, from least to most
+const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+/// Print an hour-of-day × day-of-week heatmap of each wallet's reports, read
+/// from the ClickHouse tables of the first `clickhouse` channel in the config
+#[derive(Debug, Clone, Parser)]
+pub struct Args {
+    /// The path to the config file
+    #[arg(default_value = "config.toml", help = "The path to the config file")]
+    config: String,
+
+    #[arg(
+        long,
+        default_value_t = 30,
+        help = "Days of reports to include, counting back from now"
+    )]
+    days: u32,
+
+    #[arg(long, help = "Only this wallet, by name")]
+    wallet: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = Metric::Pnl, help = "What a cell shows")]
+    metric: Metric,
+
+    #[arg(long, default_value = "UTC", help = "IANA timezone hours and days are counted in")]
+    timezone: String,
+
+    #[arg(
+        long,
+        help = "Write the heatmaps as an HTML page to this path instead of printing them"
+    )]
+    html: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Metric {
+    /// Sum of native PnL
+    Pnl,
+    /// Number of blocks with a report
+    Activity,
+}
+
+#[derive(Debug, Deserialize)]
+struct Row {
+    wallet: String,
+    /// 1 for Monday to 7 for Sunday
+    day: u8,
+    hour: u8,
+    reports: u32,
+    pnl: f64,
+}
+
+/// Cells of one wallet by day of week, then hour
+type Heatmap = [[f64; 24]; 7];
+
+impl Args {
+    pub async fn run(self) -> eyre::Result<()> {
+        let config = Config::from_file(&self.config).context("Failed to parse config")?;
+        let target = config
+            .channels
+            .iter()
+            .find_map(|channel| match &channel.alert {
+                AlertTo::Clickhouse(target) => Some(target),
+                _ => None,
+            })
+            .context("No clickhouse channel in the config, reports aren't stored anywhere")?;
+        self.timezone
+            .parse::<Tz>()
+            .map_err(|err| eyre!("Invalid timezone {}: {err}", self.timezone))?;
+
+        // Counts are cast down so JSON output doesn't quote them
+        let sql = format!(
+            "SELECT wallet, toDayOfWeek(timestamp, 0, {{tz:String}}) AS day, toHour(timestamp, {{tz:String}}) AS hour, \
+             toUInt32(count()) AS reports, sum(pnl) / 1e18 AS pnl \
+             FROM {} \
+             WHERE timestamp >= now() - INTERVAL {{days:UInt32}} DAY AND ({{wallet:String}} = '' OR wallet = {{wallet:String}}) \
+             GROUP BY wallet, day, hour \
+             FORMAT JSONEachRow",
+            clickhouse::qualified_table(target, &target.reports_table)
+        );
+        let params = [
+            ("tz", self.timezone.clone()),
+            ("days", self.days.to_string()),
+            ("wallet", self.wallet.clone().unwrap_or_default()),
+        ];
+
+        let body = clickhouse::query(&reqwest::Client::new(), target, &sql, &params).await?;
+
+        let mut heatmaps: BTreeMap<String, Heatmap> = BTreeMap::new();
+        for line in body.lines().filter(|line| !line.is_empty()) {
+            let row: Row = serde_json::from_str(line).context("Failed to parse query result")?;
+            let value = match self.metric {
+                Metric::Pnl => row.pnl,
+                Metric::Activity => row.reports as f64,
+            };
+            heatmaps.entry(row.wallet).or_insert([[0.0; 24]; 7])[(row.day as usize - 1) % 7][row.hour as usize % 24] =
+                value;
+        }
+
+        if heatmaps.is_empty() {
+            println!("No reports in the last {} days", self.days);
+            return Ok(());
+        }
+
+        match &self.html {
+            Some(path) => {
+                std::fs::write(path, self.render_html(&heatmaps)).context("Failed to write HTML")?;
+                println!("Wrote {} heatmaps to {path}", heatmaps.len());
+            }
+            None => print!("{}", self.render_text(&heatmaps)),
+        }
+
+        Ok(())
+    }
+
+    fn render_text(&self, heatmaps: &BTreeMap<String, Heatmap>) -> String {
+        let mut out = String::new();
+
+        for (wallet, heatmap) in heatmaps {
+            let max = max_abs(heatmap);
+
+            let _ = writeln!(
+                out,
+                "{wallet} ({}, last {} days, {})",
+                self.metric_name(),
+                self.days,
+                self.timezone
+            );
+            let _ = writeln!(out, "     {}", (0..24).map(|h| format!("{h:<2}")).collect::<String>());
+            for (day, cells) in DAYS.iter().zip(heatmap) {
+                let row = cells.iter().map(|value| text_cell(*value, max)).collect::<String>();
+                let _ = writeln!(out, "{day}  {row}  {}", format_value(cells.iter().sum()));
+            }
+
+            let (best, worst) = best_and_worst(heatmap);
+            if let Some((day, hour, value)) = best {
+                let _ = write!(out, "Best: {} {hour:02}:00 ({})", DAYS[day], format_value(value));
+            }
+            if let Some((day, hour, value)) = worst {
+                let _ = write!(out, ", worst: {} {hour:02}:00 ({})", DAYS[day], format_value(value));
+            }
+            let _ = writeln!(out, "\n");
+        }
+
+        if self.metric == Metric::Pnl {
+            out.push_str(
+                "Green is profit and red is loss, darker cells are larger relative to the wallet's largest hour\n",
+            );
+        }
+
+        out
+    }
+
+    fn render_html(&self, heatmaps: &BTreeMap<String, Heatmap>) -> String {
+        let mut out = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Wallet heatmaps</title>\n<style>\n\
+             body { font-family: sans-serif; }\n\
+             table { border-collapse: collapse; margin-bottom: 2em; }\n\
+             td, th { width: 2.2em; height: 1.6em; text-align: center; font-size: 0.75em; }\n\
+             </style>\n</head>\n<body>\n",
+        );
+
+        for (wallet, heatmap) in heatmaps {
+            let max = max_abs(heatmap);
+            let mut title = Markup::new(MessageFormat::Html);
+            title.text(wallet);
+
+            let _ = writeln!(
+                out,
+                "<h2>{}</h2>\n<p>{}, last {} days, {}</p>\n<table>",
+                title.finish(),
+                self.metric_name(),
+                self.days,
+                self.timezone
+            );
+            let _ = writeln!(
+                out,
+                "<tr><th></th>{}</tr>",
+                (0..24).map(|h| format!("<th>{h}</th>")).collect::<String>()
+            );
+            for (day, cells) in DAYS.iter().zip(heatmap) {
+                let row = cells
+                    .iter()
+                    .map(|value| {
+                        let alpha = if max > 0.0 { value.abs() / max } else { 0.0 };
+                        let color = if *value < 0.0 { "220, 38, 38" } else { "22, 163, 74" };
+                        format!(
+                            "<td style=\"background: rgba({color}, {alpha:.2})\" title=\"{}\"></td>",
+                            format_value(*value)
+                        )
+                    })
+                    .collect::<String>();
+                let _ = writeln!(out, "<tr><th>{day}</th>{row}</tr>");
+            }
+            out.push_str("</table>\n");
+        }
+
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+
+    fn metric_name(&self) -> &'static str {
+        match self.metric {
+            Metric::Pnl => "PnL",
+            Metric::Activity => "blocks with a report",
+        }
+    }
+}
+
+fn max_abs(heatmap: &Heatmap) -> f64 {
+    heatmap.iter().flatten().fold(0.0, |max, value| value.abs().max(max))
+}
+
+/// The hours with the highest and lowest value, by day and hour
+type Extreme = Option<(usize, usize, f64)>;
+
+fn best_and_worst(heatmap: &Heatmap) -> (Extreme, Extreme) {
+    let cells = heatmap
+        .iter()
+        .enumerate()
+        .flat_map(|(day, hours)| hours.iter().enumerate().map(move |(hour, value)| (day, hour, *value)))
+        .filter(|(.., value)| *value != 0.0);
+
+    let best = cells.clone().max_by(|a, b| a.2.total_cmp(&b.2));
+    let worst = cells
+        .min_by(|a, b| a.2.total_cmp(&b.2))
+        .filter(|(.., value)| *value < 0.0);
+
+    (best, worst)
+}
+
+/// Two columns wide, shaded by magnitude and colored by sign
+fn text_cell(value: f64, max: f64) -> String {
+    if value == 0.0 || max == 0.0 {
+        return format!("{} ", SHADES[0]);
+    }
+
+    let shade = SHADES[1 + ((value.abs() / max) * 3.0).round() as usize];
+    let color = if value < 0.0 { 31 } else { 32 };
+    format!("\x1b[{color}m{shade}{shade}\x1b[0m")
+}
+
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{value}")
+    } else {
+        format!("{value:.4}")
+    }
+}
@@ -0,0 +1,195 @@
+use {
+    super::start::executors,
+    crate::{
+        audit::AuditLog,
+        config::{AlertTo, Config},
+        executor::Action,
+        processor::{PnlReport, REPORT_VERSION},
+        strategy::WalletWatcher,
+        utils::new_pubsub_provider,
+    },
+    alloy::{
+        primitives::{I256, U256},
+        providers::Provider,
+        pubsub::PubSubFrontend,
+    },
+    alloy_chains::Chain,
+    burberry::ActionSubmitter,
+    clap::Parser,
+    eyre::{bail, Context, ContextCompat},
+    std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+};
+
+/// Send a made-up report of a channel's first wallet through the same
+/// rendering, throttling, routing and executors as `start`, to check the
+/// channel's wiring
+#[derive(Debug, Clone, Parser)]
+pub struct Args {
+    /// The path to the config file
+    #[arg(default_value = "config.toml", help = "The path to the config file")]
+    config: String,
+
+    #[arg(long, help = "Name of the channel, or its position in the config counting from 0")]
+    channel: String,
+
+    #[arg(long, help = "Chain the report is made on. The wallet's first chain if unset")]
+    chain: Option<String>,
+}
+
+/// Collects the actions of the strategy instead of handing them to an engine
+#[derive(Default)]
+struct Collector(Mutex<Vec<Action>>);
+
+impl ActionSubmitter<Action> for Collector {
+    fn submit(&self, action: Action) {
+        self.0.lock().unwrap().push(action);
+    }
+}
+
+impl Args {
+    pub async fn run(self) -> eyre::Result<()> {
+        let config = Config::from_file(&self.config).context("Failed to parse config")?;
+
+        let channel = config
+            .channels
+            .iter()
+            .enumerate()
+            .find(|(i, c)| c.name.as_deref() == Some(self.channel.as_str()) || i.to_string() == self.channel)
+            .map(|(_, c)| c)
+            .with_context(|| {
+                let names = config
+                    .channels
+                    .iter()
+                    .filter_map(|c| c.name.as_deref())
+                    .collect::<Vec<_>>();
+                format!(
+                    "Channel {} not found, named channels: {}",
+                    self.channel,
+                    names.join(", ")
+                )
+            })?;
+        let wallet = channel
+            .wallets
+            .iter()
+            .find(|w| !w.disabled)
+            .with_context(|| format!("Channel {} has no enabled wallets to report on", self.channel))?;
+
+        let chain_name = match (&self.chain, wallet.chains.first()) {
+            (Some(chain), _) => chain.clone(),
+            (None, Some(chain)) => chain.clone(),
+            (None, None) => {
+                let mut chains = config.chains.keys().collect::<Vec<_>>();
+                chains.sort();
+                chains.first().context("No chain is set up")?.to_string()
+            }
+        };
+        let chain_config = config
+            .chains
+            .get(&chain_name)
+            .with_context(|| format!("Chain {chain_name} not found"))?;
+
+        // The wallet as `start` sets it up, from this channel alone
+        let mut scoped = config.clone();
+        scoped.channels = vec![channel.clone()];
+        let wallets = scoped
+            .to_wallet_with_context_by_chain()
+            .remove(&chain_name)
+            .unwrap_or_default();
+        let wallet_index = wallets
+            .iter()
+            .position(|w| w.name == wallet.name && w.address == wallet.address)
+            .with_context(|| format!("Wallet {} isn't watched on {chain_name}", wallet.name))?;
+
+        let provider: Arc<dyn Provider<PubSubFrontend>> = new_pubsub_provider(&chain_config.rpc)
+            .await
+            .context("Failed to create provider")?
+            .into();
+        let chain: Chain = provider.get_chain_id().await.context("Failed to get chain id")?.into();
+        let block = provider
+            .get_block_by_number(Default::default(), false)
+            .await
+            .context("Failed to get block")?
+            .context("Block not found")?;
+
+        let report = PnlReport {
+            version: REPORT_VERSION,
+            txs: vec![],
+            // 0.0123 of the native token at 18 decimals
+            pnl: I256::from_raw(U256::from(12_300_000_000_000_000_u64)),
+            pnl_by_category: Default::default(),
+            stable_pnl: I256::ZERO,
+            pnl_usd: None,
+            token_values_usd: Default::default(),
+            builder_reward: U256::ZERO,
+            validator_bribe: U256::ZERO,
+            token_changes: Default::default(),
+            new_tokens: vec![],
+            airdrop_claims: vec![],
+            vaults: vec![],
+            vault_assets: vec![],
+            perps: vec![],
+            staking: vec![],
+            cex_deposits: vec![],
+            warnings: vec!["Test alert from wallet-watcher, not real activity".to_string()],
+            truncated: false,
+        };
+
+        let mut strategy = WalletWatcher::new(chain, provider, wallets).with_chain_config(chain_config);
+        // An aggregation window would hold the report back until it closes
+        strategy.aggregate = None;
+        if let Some(permalink) = config.permalink.clone() {
+            strategy = strategy.with_permalink(permalink);
+        }
+
+        let collector = Collector::default();
+        strategy
+            .send_test_report(wallet_index, &block, report, &collector)
+            .await
+            .context("Failed to generate the test report")?;
+
+        let actions = std::mem::take(&mut *collector.0.lock().unwrap());
+        if actions.is_empty() {
+            bail!("Nothing was sent, the wallet's throttle held the report back");
+        }
+
+        let audit_log = match &config.audit_log {
+            Some(path) => Some(Arc::new(AuditLog::open(path)?)),
+            None => None,
+        };
+        let executors = executors(config.error_channel.clone(), &audit_log);
+
+        for action in actions {
+            for executor in &executors {
+                executor
+                    .execute(action.clone())
+                    .await
+                    .with_context(|| format!("Failed to send to channel {}", self.channel))?;
+            }
+        }
+
+        if let AlertTo::Clickhouse(target) = &channel.alert {
+            // Rows are inserted by a background flush
+            println!(
+                "Waiting {}s for the ClickHouse flush, failures are logged",
+                target.flush_interval
+            );
+            tokio::time::sleep(Duration::from_secs(target.flush_interval + 1)).await;
+        }
+        if let AlertTo::Dune(target) = &channel.alert {
+            // Too long to wait for
+            println!(
+                "Dune rows are uploaded every {}s by a running watcher, not by a test alert",
+                target.flush_interval
+            );
+        }
+
+        println!(
+            "Sent a test report of {} on {chain} to channel {}",
+            wallet.name, self.channel
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use {
+    super::rules::RulesArgs,
+    crate::{
+        chain_adapter::ChainRules,
+        config::{TraceConfig, WalletWithContext},
+        message::MessageGenerator,
+        processor::{self, PnlReport},
+        utils::{
+            fill_block_producer, format_ether_trimmed, format_token_amount, get_receipt_and_trace, new_pubsub_provider,
+        },
+    },
+    alloy::{primitives::Address, providers::Provider, pubsub::PubSubFrontend, rpc::types::Block},
+    alloy_chains::Chain,
+    burberry::{collector::BlockCollector, Collector},
+    clap::Parser,
+    eyre::Context,
+    futures::StreamExt,
+    std::sync::Arc,
+};
+
+/// Print a line for every block the address has PnL in, until interrupted
+#[derive(Debug, Clone, Parser)]
+pub struct Args {
+    #[arg(help = "The address of the wallet to watch")]
+    address: Address,
+
+    #[arg(short, long, env = "ETH_RPC_URL", help = "WebSocket or IPC RPC URL")]
+    rpc_url: String,
+
+    #[arg(short, long, help = "The builder address of the wallet")]
+    builder: Option<Address>,
+
+    #[arg(
+        short = 'a',
+        long = "address",
+        help = "Other addresses to include in PnL calculations",
+        value_delimiter = ','
+    )]
+    other_addresses: Vec<Address>,
+
+    #[arg(long, help = "If true, the recipient will be included in PnL calculations")]
+    include_recipient: bool,
+
+    #[command(flatten)]
+    rules: RulesArgs,
+}
+
+impl Args {
+    pub async fn run(self) -> eyre::Result<()> {
+        let rules = self.rules.load()?;
+        let provider: Arc<dyn Provider<PubSubFrontend>> = new_pubsub_provider(&self.rpc_url)
+            .await
+            .context("Failed to create provider")?
+            .into();
+        let chain: Chain = provider.get_chain_id().await.context("Failed to get chain id")?.into();
+
+        let wallets = [WalletWithContext::new(
+            "Unnamed".to_string(),
+            self.address,
+            self.builder,
+            self.other_addresses,
+            self.include_recipient,
+            Arc::default(),
+        )];
+
+        let mut message_generator = MessageGenerator::new(chain, Arc::clone(&provider));
+
+        let collector = BlockCollector::new(Arc::clone(&provider));
+        let mut blocks = collector
+            .get_event_stream()
+            .await
+            .context("Failed to subscribe to new blocks")?;
+
+        eprintln!("Watching {} on {chain}", self.address);
+
+        while let Some(block) = blocks.next().await {
+            let report = match process(chain, &rules, provider.as_ref(), &block, &wallets).await {
+                Ok(Some(report)) => report,
+                Ok(None) => continue,
+                Err(err) => {
+                    eprintln!("{} failed: {err:#}", block.header.number);
+                    continue;
+                }
+            };
+
+            let mut line = format!(
+                "{block} {pnl:>24} {symbol} {txs:>3} tx",
+                block = block.header.number,
+                pnl = format_signed_ether(&report),
+                symbol = chain.named().and_then(|c| c.native_currency_symbol()).unwrap_or("ETH"),
+                txs = report.txs.len(),
+            );
+
+            for (token, change) in report.token_changes.iter() {
+                let (symbol, decimals) = match message_generator.load_symbol_and_decimal(token).await {
+                    Ok((symbol, decimals)) => (symbol.clone(), *decimals),
+                    Err(_) => (token.to_string(), 18),
+                };
+
+                line.push_str(&format!(" {symbol} {}", format_token_amount(change, decimals, 8)));
+            }
+
+            if !report.builder_reward.is_zero() {
+                line.push_str(" [B]");
+            }
+
+            println!("{line}");
+        }
+
+        eprintln!("Block subscription ended");
+
+        Ok(())
+    }
+}
+
+async fn process(
+    chain: Chain,
+    rules: &ChainRules,
+    provider: &dyn Provider<PubSubFrontend>,
+    block: &Block,
+    wallets: &[WalletWithContext],
+) -> eyre::Result<Option<PnlReport>> {
+    let receipt_and_traces = get_receipt_and_trace(provider, block.header.number, &TraceConfig::default(), &[])
+        .await
+        .context("Failed to get receipt and traces")?;
+
+    let mut header = block.header.clone();
+    fill_block_producer(provider, chain, &mut header).await?;
+
+    let mut reports = processor::process_block(chain, rules, &header, &receipt_and_traces, wallets)
+        .context("Failed to generate report")?;
+
+    Ok(reports.pop().flatten())
+}
+
+fn format_signed_ether(report: &PnlReport) -> String {
+    let (sign, pnl) = report.pnl.into_sign_and_abs();
+    format!(
+        "{}{}",
+        if sign.is_positive() { "+" } else { "-" },
+        format_ether_trimmed(&pnl)
+    )
+}
@@ -0,0 +1,112 @@
+use {
+    crate::{config::ClusterWatchWithContext, token_watch::to_min_value},
+    alloy::primitives::I256,
+    schemars::JsonSchema,
+    serde::Serialize,
+    std::collections::{BTreeMap, VecDeque},
+    tracing::warn,
+};
+
+/// Which threshold of a cluster was crossed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusterTrigger {
+    Loss,
+    Profit,
+}
+
+/// A cluster whose wallets together crossed a threshold within its window
+pub struct ClusterAlert {
+    pub cluster: ClusterWatchWithContext,
+    pub trigger: ClusterTrigger,
+    pub total: I256,
+    /// Native PnL within the window by wallet name
+    pub by_wallet: BTreeMap<String, I256>,
+}
+
+/// Native PnL of the wallets of each cluster over its sliding window, by
+/// block time
+#[derive(Default)]
+pub struct ClusterTracker {
+    clusters: Vec<ClusterWatchWithContext>,
+    /// By cluster index: block timestamp, wallet name and PnL of each report
+    windows: Vec<VecDeque<(u64, String, I256)>>,
+}
+
+impl ClusterTracker {
+    pub fn new(clusters: Vec<ClusterWatchWithContext>) -> Self {
+        Self {
+            windows: vec![VecDeque::new(); clusters.len()],
+            clusters,
+        }
+    }
+
+    /// Add the PnL of a wallet's report to the clusters it is in
+    pub fn record(&mut self, wallet: &str, timestamp: u64, pnl: I256) {
+        for (cluster, window) in self.clusters.iter().zip(&mut self.windows) {
+            if cluster.wallets.iter().any(|w| w == wallet) {
+                window.push_back((timestamp, wallet.to_string(), pnl));
+            }
+        }
+    }
+
+    /// Clusters that crossed a threshold as of `timestamp`. A cluster's window
+    /// starts over once it is alerted, so the same reports don't alert twice.
+    pub fn check(&mut self, timestamp: u64, native_decimals: u8) -> Vec<ClusterAlert> {
+        let mut alerts = vec![];
+
+        for (cluster, window) in self.clusters.iter().zip(&mut self.windows) {
+            let start = timestamp.saturating_sub(cluster.window.as_secs());
+            while window.front().is_some_and(|(t, ..)| *t <= start) {
+                window.pop_front();
+            }
+            if window.is_empty() {
+                continue;
+            }
+
+            let total = window.iter().map(|(.., pnl)| *pnl).fold(I256::ZERO, |a, b| a + b);
+            let trigger = match threshold_crossed(cluster, total, native_decimals) {
+                Ok(Some(trigger)) => trigger,
+                Ok(None) => continue,
+                Err(err) => {
+                    warn!(cluster = %cluster.name, "Failed to read the thresholds of the cluster: {err:#}");
+                    continue;
+                }
+            };
+
+            let mut by_wallet: BTreeMap<String, I256> = BTreeMap::new();
+            for (_, wallet, pnl) in window.drain(..) {
+                *by_wallet.entry(wallet).or_default() += pnl;
+            }
+
+            alerts.push(ClusterAlert {
+                cluster: cluster.clone(),
+                trigger,
+                total,
+                by_wallet,
+            });
+        }
+
+        alerts
+    }
+}
+
+fn threshold_crossed(
+    cluster: &ClusterWatchWithContext,
+    total: I256,
+    native_decimals: u8,
+) -> eyre::Result<Option<ClusterTrigger>> {
+    if let Some(max_loss) = cluster.max_loss {
+        if total.is_negative() && total.unsigned_abs() >= to_min_value(max_loss, native_decimals)? {
+            return Ok(Some(ClusterTrigger::Loss));
+        }
+    }
+
+    if let Some(min_profit) = cluster.min_profit {
+        if total.is_positive() && total.unsigned_abs() >= to_min_value(min_profit, native_decimals)? {
+            return Ok(Some(ClusterTrigger::Profit));
+        }
+    }
+
+    Ok(None)
+}
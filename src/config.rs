@@ -1,34 +1,926 @@
 use {
-    alloy::primitives::Address,
+    crate::{
+        chain_adapter::{BlockSubsidy, ChainRules, FeeMode},
+        event_watch,
+        i18n::Language,
+        perps::PerpsProtocol,
+        rpc_metrics::RpcProvider,
+        utils::{
+            self,
+            format::{AmountFormat, NumberFormat, ThousandsSeparator},
+        },
+    },
+    alloy::{
+        json_abi::Event,
+        primitives::{Address, B256, U256},
+    },
+    alloy_chains::Chain,
+    chrono_tz::Tz,
     clap::Parser,
     eyre::{ensure, Context},
     serde::{Deserialize, Serialize},
-    std::{collections::HashMap, sync::Arc},
+    serde_with::{serde_as, DisplayFromStr, PickFirst},
+    std::{collections::HashMap, convert::Infallible, str::FromStr, sync::Arc, time::Duration},
 };
 
 pub const NATIVE_TOKEN: Address = Address::ZERO;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct Config {
-    pub chains: HashMap<String, String>,
+    #[serde_as(as = "HashMap<_, PickFirst<(_, DisplayFromStr)>>")]
+    pub chains: HashMap<String, ChainConfig>,
     pub channels: Vec<Channel>,
+
+    /// Redis URL used to share token metadata between replicas, and to
+    /// remember delivered reports across restarts
+    #[serde(default)]
+    pub redis_cache: Option<String>,
+
+    /// Uniswap-style token lists, URLs or file paths, loaded on start so the
+    /// tokens in them need no RPC calls for their symbol and decimals
+    #[serde(default)]
+    pub token_lists: Vec<String>,
+
+    /// Skip txs with malformed traces instead of failing the whole block. The
+    /// reports of such blocks carry a warning for each skipped tx
+    #[serde(default)]
+    pub lenient_traces: bool,
+
+    /// Where block processing failures are reported, so gaps in the reports
+    /// don't go unnoticed
+    #[serde(default)]
+    pub error_channel: Option<ErrorAlertTo>,
+
+    #[serde(default)]
+    pub retry: RetryConfig,
+
+    /// Write each Telegram report as a static HTML page with the full details,
+    /// linked from the message
+    #[serde(default)]
+    pub permalink: Option<PermalinkConfig>,
+
+    /// Check tokens the first time they show up and mark the ones that look
+    /// like honeypots with ⚠️
+    #[serde(default)]
+    pub token_risk: Option<TokenRiskConfig>,
+
+    /// Value reports in USD at the prices of their block
+    #[serde(default)]
+    pub prices: Option<PriceConfig>,
+
+    /// Mark tokens a wallet never held before with NEW. What was held is kept
+    /// in `redis_cache` when it is set, and in memory for this run otherwise.
+    #[serde(default)]
+    pub first_seen: Option<FirstSeenConfig>,
+
+    /// Caps on the call traces walked for PnL, for blocks with huge MEV
+    /// bundles. Reports of blocks whose traces were cut are marked truncated
+    #[serde(default)]
+    pub trace_limits: TraceLimits,
+
+    /// Transfers to exchange addresses are tagged as CEX deposits. Known hot
+    /// wallets are tagged without this
+    #[serde(default)]
+    pub cex: Option<CexConfig>,
+
+    /// Append every Telegram, Signal, Twilio and webhook notification, with
+    /// whether it was delivered, to this JSON lines file. Query it with the
+    /// `messages` command
+    #[serde(default)]
+    pub audit_log: Option<String>,
+
+    /// Keep every report in this database, `sqlite://<path>` or
+    /// `postgres://...`, for historical PnL
+    #[serde(default)]
+    pub storage: Option<String>,
+
+    /// Save this many days of past reports into `storage` for the wallets it
+    /// has no history of yet, e.g. ones just added, while watching live
+    #[serde(default)]
+    pub backfill_days: Option<u32>,
+
+    /// Delete what `storage` keeps of blocks older than this, archiving the
+    /// reports to files first
+    #[serde(default)]
+    pub retention: Option<RetentionConfig>,
+
+    /// Run as one of several instances sharing a Postgres `storage`: each
+    /// chain is watched by the instance holding its lock, the others stand by
+    /// and take over when it goes away
+    #[serde(default)]
+    pub leader_election: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RetentionConfig {
+    /// Reports and processed blocks of the last this many days are kept,
+    /// counting whole UTC days
+    pub days: u32,
+
+    /// Where the reports are written before they are deleted, one
+    /// zstd-compressed archive per pruning. Without it they are only deleted
+    #[serde(default)]
+    pub archive_dir: Option<String>,
+
+    /// Older days are deleted too, oldest first, while the store takes more
+    /// than this many GB. The current day is always kept
+    #[serde(default)]
+    pub max_size_gb: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct CexConfig {
+    /// More exchange addresses to the exchange's name, e.g. the wallets' own
+    /// deposit addresses
+    #[serde(default)]
+    pub addresses: HashMap<Address, String>,
+
+    /// Also send a separate alert for each report with a CEX deposit
+    #[serde(default)]
+    pub alert: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct TraceLimits {
+    /// Calls nested deeper than this are dropped, the top-level call being 1
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+
+    /// Frames of a tx's trace kept, in call order
+    #[serde(default)]
+    pub max_frames: Option<usize>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub struct Channel {
-    #[serde(flatten)]
-    pub alert: AlertTo,
-    pub wallets: Vec<Wallet>,
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct FirstSeenConfig {
+    /// Also send a separate alert when a wallet receives a new token
+    #[serde(default)]
+    pub alert: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct TokenRiskConfig {
+    /// Wrapped native tokens a token's main pool must hold, e.g. 1.5 for 1.5
+    /// WETH
+    #[serde(default = "TokenRiskConfig::default_min_liquidity")]
+    pub min_liquidity: f64,
+}
+
+impl TokenRiskConfig {
+    fn default_min_liquidity() -> f64 {
+        1.0
+    }
+
+    pub fn min_liquidity_wei(&self) -> U256 {
+        U256::from((self.min_liquidity * 1e18) as u128)
+    }
+}
+
+/// Prices are read from Chainlink feeds at the block first: the known one of
+/// the native token and the chains' `price_feeds`. The native token and the
+/// chains' `coingecko_ids` are then looked up on CoinGecko around the block
+/// time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct PriceConfig {
+    #[serde(default = "PriceConfig::default_coingecko_url")]
+    pub coingecko_url: String,
+
+    /// Sent as a demo key, or a pro one when `coingecko_url` is the pro API
+    #[serde(default)]
+    pub coingecko_api_key: Option<String>,
+
+    /// Requests made to CoinGecko at most, across chains
+    #[serde(default = "PriceConfig::default_coingecko_requests_per_minute")]
+    pub coingecko_requests_per_minute: u32,
+}
+
+impl PriceConfig {
+    fn default_coingecko_url() -> String {
+        "https://api.coingecko.com/api/v3".to_string()
+    }
+
+    fn default_coingecko_requests_per_minute() -> u32 {
+        30
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct PermalinkConfig {
+    /// Directory the pages are written to, e.g. the root of a web server
+    pub dir: String,
+
+    /// URL the directory is served at
+    pub base_url: String,
+
+    /// Keys the page names, so that a page can't be found from the chain,
+    /// block and wallet it is about
+    pub secret: String,
+}
+
+/// How failed blocks are retried before giving up on them
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RetryConfig {
+    /// Retries after the first failure. 0 disables retrying
+    #[serde(default = "RetryConfig::default_attempts")]
+    pub attempts: u32,
+
+    /// Delay before the first retry, doubled for each following one
+    #[serde(default = "RetryConfig::default_delay_secs")]
+    pub delay_secs: u64,
+}
+
+impl RetryConfig {
+    fn default_attempts() -> u32 {
+        3
+    }
+
+    fn default_delay_secs() -> u64 {
+        5
+    }
+
+    /// Delay before retry number `attempt`, starting from 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        Duration::from_secs(self.delay_secs.saturating_mul(1 << attempt.saturating_sub(1).min(16)))
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: Self::default_attempts(),
+            delay_secs: Self::default_delay_secs(),
+        }
+    }
+}
+
+/// A chain to monitor. Written either as the RPC URL alone, or as a map for
+/// chains whose details aren't known to the watcher.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ChainConfig {
+    /// Should be either websocket ws://,wss://, or IPC file file://<PATH>
+    pub rpc: String,
+
+    /// Symbol of the native currency, ETH if unknown
+    #[serde(default)]
+    pub native_symbol: Option<String>,
+
+    /// Decimals of the native currency, 18 if unknown
+    #[serde(default)]
+    pub native_decimals: Option<u8>,
+
+    /// Base URL of an Etherscan-compatible explorer, e.g. `https://explorer.example.com`
+    #[serde(default)]
+    pub explorer: Option<String>,
+
+    /// More wrapped native tokens next to the well-known one, counted as the
+    /// native token. They must emit WETH9 `Deposit`/`Withdrawal` events
+    #[serde(default)]
+    pub wrapped_natives: Vec<Address>,
+
+    /// Stablecoins to their decimals. Their changes are summed at one USD
+    /// each into `stable_pnl`
+    #[serde(default)]
+    pub stables: HashMap<Address, u8>,
+
+    /// Send one message per wallet per window of blocks rather than per
+    /// block, for chains with very fast blocks. Data outputs still get every
+    /// report
+    #[serde(default)]
+    pub aggregate: Option<AggregateConfig>,
+
+    /// Links shown next to each tx of a message, in order. Phalcon if unset
+    #[serde(default)]
+    pub tx_links: Option<Vec<TxLink>>,
+
+    /// How blocks are traced on the node
+    #[serde(default)]
+    pub trace: TraceConfig,
+
+    /// `legacy` for chains without EIP-1559, where the block producer gets
+    /// the whole gas price. `eip1559` unless the chain is known otherwise
+    #[serde(default)]
+    pub fee_mode: Option<FeeMode>,
+
+    /// Reward minted to the miner of every block, for proof of work chains
+    /// the watcher doesn't know. Wallets mining to their address or builder
+    /// earn it with the fees of the block
+    #[serde(default)]
+    pub block_subsidy: Option<BlockSubsidy>,
+
+    /// Perps protocols whose realized PnL and fees are shown in reports
+    #[serde(default)]
+    pub perps: Vec<PerpsProtocol>,
+
+    /// Leave txs bridged in from L1, e.g. OP stack deposits, out of reports.
+    /// System txs of the chain are always left out
+    #[serde(default)]
+    pub skip_deposit_txs: bool,
+
+    /// Provider of `rpc`, to estimate the compute units of the calls made
+    #[serde(default)]
+    pub rpc_provider: Option<RpcProvider>,
+
+    /// Compute units of a call by method, over the `rpc_provider` prices
+    #[serde(default)]
+    pub rpc_costs: HashMap<String, u64>,
+
+    #[serde(default)]
+    pub block_source: BlockSource,
+
+    /// Etherscan-compatible API read by the `explorer` trace mode
+    #[serde(default)]
+    pub explorer_api: Option<ExplorerApi>,
+
+    /// Chainlink USD feeds by token, with `prices` set. The native token's
+    /// feed is known on the main chains
+    #[serde(default)]
+    pub price_feeds: HashMap<Address, Address>,
+
+    /// CoinGecko coin ids by token, with `prices` set, for tokens without a
+    /// feed. Other tokens aren't priced
+    #[serde(default)]
+    pub coingecko_ids: HashMap<Address, String>,
+
+    /// Bridge contracts besides the canonical ones of the main L2s. Txs to
+    /// them count as bridging in the PnL breakdown
+    #[serde(default)]
+    pub bridges: Vec<Address>,
+
+    /// Data APIs of MEV-Boost relays, e.g. `https://boost-relay.flashbots.net`,
+    /// asked for the bids of wallets with `builder_pubkeys` to put the blocks
+    /// they lost in daily digests
+    #[serde(default)]
+    pub relays: Vec<String>,
+}
+
+impl ChainConfig {
+    /// What the processor applies on top of the chain's adapter
+    pub fn rules(&self) -> ChainRules {
+        ChainRules {
+            wrapped_natives: self.wrapped_natives.clone(),
+            stables: self.stables.clone(),
+            fee_mode: self.fee_mode,
+            skip_deposit_txs: self.skip_deposit_txs,
+            block_subsidy: self.block_subsidy,
+            bridges: self.bridges.clone(),
+        }
+    }
+}
+
+/// An Etherscan-compatible API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ExplorerApi {
+    /// Endpoint of the API, with any query parameter it needs, e.g.
+    /// `https://api.etherscan.io/v2/api?chainid=1`
+    pub url: String,
+
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+/// Which blocks of a chain are processed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockSource {
+    /// Every new block
+    #[default]
+    Blocks,
+    /// Only blocks with an ERC20 `Transfer` from or to a watched wallet, from
+    /// a logs subscription. Blocks where a wallet only sends or receives the
+    /// native token, or only builds, are missed
+    TransferLogs,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct TraceConfig {
+    /// Timeout of a block's trace on the node. Geth gives up after 5 seconds
+    /// by default, which very large blocks can exceed
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+
+    /// Trace with a minimal JS tracer recording only value calls and token
+    /// transfer logs, for nodes where the call tracer with logs is too slow
+    #[serde(default)]
+    pub js_tracer: bool,
+
+    /// Check the native balance changes of each reported tx against the
+    /// node's state diff, and go with the state diff where they disagree.
+    /// Costs an extra trace per reported tx
+    #[serde(default)]
+    pub verify_state_diff: bool,
+
+    #[serde(default)]
+    pub mode: TraceMode,
+
+    /// Trace only the txs whose receipt mentions a watched wallet, see
+    /// [`crate::utils::may_involve`]. Blocks produced by a watched builder
+    /// are still traced in full
+    #[serde(default)]
+    pub wallet_scoped: bool,
+
+    /// Skip blocks whose logs bloom and txs name no watched wallet, token or
+    /// contract without fetching their receipts or traces. Costs an
+    /// `eth_getBlockByNumber` per block the bloom rules out, to check for
+    /// plain transfers
+    #[serde(default)]
+    pub bloom_prescreen: bool,
+
+    /// Pick a lighter mode than `mode` for large blocks or a slow node
+    #[serde(default)]
+    pub adaptive: Option<AdaptiveTrace>,
+}
+
+/// How the call traces of a block are fetched
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceMode {
+    /// One `debug_traceBlockByNumber` for the whole block
+    #[default]
+    Block,
+    /// One `debug_traceTransaction` per tx, sent as a batch, for nodes that
+    /// time out on whole blocks
+    PerTx,
+    /// No traces, each tx is read as a top-level call with its receipt's logs.
+    /// Native transfers made by contracts are missed
+    LogsOnly,
+    /// Like `logs_only`, with the native transfers made by contracts to and
+    /// from watched wallets taken from the chain's `explorer_api`, for nodes
+    /// without the debug namespace. Reports are approximate
+    Explorer,
+}
+
+/// When a block is fetched in a lighter mode. Each threshold is off when
+/// unset
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct AdaptiveTrace {
+    /// Blocks using at least this much gas are traced tx by tx
+    #[serde(default)]
+    pub per_tx_gas: Option<u64>,
+
+    /// Blocks with at least this many txs are traced tx by tx
+    #[serde(default)]
+    pub per_tx_txs: Option<usize>,
+
+    /// Blocks using at least this much gas are read from logs only
+    #[serde(default)]
+    pub logs_only_gas: Option<u64>,
+
+    /// Blocks with at least this many txs are read from logs only
+    #[serde(default)]
+    pub logs_only_txs: Option<usize>,
+
+    /// Go one mode lighter still while recent blocks took longer than this
+    /// to fetch on average
+    #[serde(default)]
+    pub max_latency_ms: Option<u64>,
+}
+
+/// A tx viewer linked next to each tx of a message, besides the explorer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxLink {
+    Phalcon,
+    Tenderly,
+    /// Base URL of a Blockscout instance, e.g. `https://eth.blockscout.com`
+    Blockscout(String),
+    /// `url` with `{hash}` and `{chain_id}` filled in
+    Custom {
+        name: String,
+        url: String,
+    },
+}
+
+impl TxLink {
+    pub fn label(&self) -> &str {
+        match self {
+            TxLink::Phalcon => "Phalcon",
+            TxLink::Tenderly => "Tenderly",
+            TxLink::Blockscout(_) => "Blockscout",
+            TxLink::Custom { name, .. } => name,
+        }
+    }
+
+    pub fn url(&self, chain: Chain, hash: &B256) -> String {
+        match self {
+            TxLink::Phalcon => utils::phalcon_tx_url(chain, hash),
+            TxLink::Tenderly => utils::tenderly_tx_url(chain, hash),
+            TxLink::Blockscout(base) => utils::tx_url(base.trim_end_matches('/'), hash),
+            TxLink::Custom { url, .. } => url
+                .replace("{hash}", &hash.to_string())
+                .replace("{chain_id}", &chain.id().to_string()),
+        }
+    }
+}
+
+/// Window reports are merged over. It closes at whichever limit is hit first
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct AggregateConfig {
+    #[serde(default)]
+    pub blocks: Option<u64>,
+
+    #[serde(default)]
+    pub seconds: Option<u64>,
+}
+
+impl AggregateConfig {
+    pub fn is_due(&self, blocks: u64, elapsed: Duration) -> bool {
+        self.blocks.is_some_and(|limit| blocks >= limit) ||
+            self.seconds.is_some_and(|limit| elapsed >= Duration::from_secs(limit))
+    }
+}
+
+impl FromStr for ChainConfig {
+    type Err = Infallible;
+
+    fn from_str(rpc: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            rpc: rpc.to_string(),
+            ..Default::default()
+        })
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Channel {
+    /// Refers to the channel in commands, e.g. `test-alert`
+    #[serde(default)]
+    pub name: Option<String>,
+
+    #[serde(flatten)]
+    pub alert: AlertTo,
+    #[serde(default)]
+    pub wallets: Vec<Wallet>,
+
+    /// Tokens whose large transfers are alerted, whoever makes them
+    #[serde(default)]
+    pub tokens: Vec<TokenWatch>,
+
+    /// Contract events alerted with their decoded fields
+    #[serde(default)]
+    pub events: Vec<EventWatch>,
+
+    /// Contracts whose ownership, admin and role changes are alerted
+    #[serde(default)]
+    pub contracts: Vec<ContractWatch>,
+
+    /// Alert on any large transfer on a chain, whoever makes it
+    #[serde(default)]
+    pub large_transfers: Vec<LargeTransferWatch>,
+
+    /// Groups of wallets whose PnL is alerted on as a whole
+    #[serde(default)]
+    pub clusters: Vec<ClusterWatch>,
+
+    /// Language of the messages sent to this channel
+    #[serde(default)]
+    pub language: Language,
+
+    /// IANA timezone block times are shown in, e.g. `Asia/Shanghai`. UTC if
+    /// unset
+    #[serde(default)]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub timezone: Option<Tz>,
+
+    /// How token amounts are written in the messages of this channel, and
+    /// in the text of its push notifications and MQTT summaries
+    #[serde(default)]
+    pub amount_format: AmountFormat,
+
+    /// Group the digits of amounts by thousands
+    #[serde(default)]
+    pub thousands_separator: Option<ThousandsSeparator>,
+
+    /// Shorten amounts of a thousand and more with K, M, B or T, e.g. `1.2M`
+    #[serde(default)]
+    pub unit_suffixes: bool,
+
+    /// Send at most one message per wallet every this many minutes. Reports
+    /// in between are rolled up into the next message. Data outputs get every
+    /// report regardless
+    #[serde(default)]
+    pub throttle_minutes: Option<u64>,
+
+    /// Send the reports of all wallets of a block as one message rather than
+    /// one per wallet. Combined messages aren't throttled or aggregated
+    #[serde(default)]
+    pub combine_wallets: bool,
+
+    /// Overrides the chains' `tx_links` for this channel
+    #[serde(default)]
+    pub tx_links: Option<Vec<TxLink>>,
+
+    /// After each UTC day, send the day's PnL of every wallet by chain from
+    /// `storage`, with a chart of it over the day. Telegram channels only
+    #[serde(default)]
+    pub daily_digest: bool,
+}
+
+impl Channel {
+    pub fn number_format(&self) -> NumberFormat {
+        NumberFormat {
+            amounts: self.amount_format,
+            separator: self.thousands_separator,
+            suffixes: self.unit_suffixes,
+        }
+    }
+}
+
+/// Where the reports of a channel are delivered. Telegram fields are written
+/// flat on the channel for backward compatibility, other outputs are keyed by
+/// their name, e.g. `clickhouse: { ... }`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertTo {
+    Clickhouse(ClickhouseTarget),
+    Nats(NatsTarget),
+    Redis(RedisTarget),
+    Mqtt(MqttTarget),
+    Signal(SignalTarget),
+    Twilio(TwilioTarget),
+    Discord(DiscordTarget),
+    Slack(SlackTarget),
+    Push(PushTarget),
+    Dune(DuneTarget),
+    #[serde(untagged)]
+    Telegram(TelegramTarget),
+}
+
+/// Destination of error notifications. Like [`AlertTo`], Telegram fields are
+/// written flat.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorAlertTo {
+    Webhook(WebhookTarget),
+    #[serde(untagged)]
+    Telegram(TelegramTarget),
+}
+
+impl AlertTo {
+    /// Whether the output gets rendered messages rather than the report
+    /// itself
+    pub fn is_chat(&self) -> bool {
+        matches!(
+            self,
+            AlertTo::Telegram(_) | AlertTo::Signal(_) | AlertTo::Twilio(_) | AlertTo::Discord(_) | AlertTo::Slack(_)
+        )
+    }
+}
+
+impl Default for AlertTo {
+    fn default() -> Self {
+        Self::Telegram(TelegramTarget::default())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct TelegramTarget {
+    pub bot_token: String,
+    pub chat_id: String,
+    pub thread_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct WebhookTarget {
+    /// Receives a JSON POST of `{"text": "..."}`
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub struct ClickhouseTarget {
+    /// HTTP interface of the ClickHouse server, e.g. `http://localhost:8123`
+    pub url: String,
+
+    #[serde(default)]
+    pub user: Option<String>,
+
+    #[serde(default)]
+    pub password: Option<String>,
+
+    #[serde(default = "ClickhouseTarget::default_database")]
+    pub database: String,
+
+    /// Table receiving one row per report
+    #[serde(default = "ClickhouseTarget::default_reports_table")]
+    pub reports_table: String,
+
+    /// Table receiving one row per token balance change of a report
+    #[serde(default = "ClickhouseTarget::default_token_changes_table")]
+    pub token_changes_table: String,
+
+    /// Seconds between two inserts. Rows are buffered in memory meanwhile.
+    #[serde(default = "ClickhouseTarget::default_flush_interval")]
+    pub flush_interval: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct NatsTarget {
+    /// Server address, e.g. `nats://localhost:4222`
+    pub url: String,
+
+    /// Subject each report is published to as JSON. Watch alerts go to
+    /// `<subject>.alerts`
+    pub subject: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RedisTarget {
+    /// e.g. `redis://localhost:6379`
+    pub url: String,
+
+    /// Pub/sub channel each report is published to as JSON. Watch alerts go
+    /// to `<channel>:alerts`
+    pub channel: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub struct MqttTarget {
+    pub host: String,
+
+    #[serde(default = "MqttTarget::default_port")]
+    pub port: u16,
+
+    #[serde(default)]
+    pub username: Option<String>,
+
+    #[serde(default)]
+    pub password: Option<String>,
+
+    #[serde(default = "MqttTarget::default_client_id")]
+    pub client_id: String,
+
+    /// Summaries are published to `<topic_prefix>/<chain id>/<wallet name>`
+    #[serde(default = "MqttTarget::default_topic_prefix")]
+    pub topic_prefix: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SignalTarget {
+    /// Base URL of a signal-cli REST API instance
+    pub url: String,
+
+    /// The registered number messages are sent from
+    pub number: String,
+
+    /// Phone numbers or group IDs to send to
+    pub recipients: Vec<String>,
+}
+
+/// A Discord channel or thread through an incoming webhook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct DiscordTarget {
+    /// `https://discord.com/api/webhooks/<id>/<token>`
+    pub webhook_url: String,
+
+    /// Thread of the webhook's channel to post in
+    #[serde(default)]
+    pub thread_id: Option<String>,
+}
+
+/// A Slack channel, posted to by a bot with the `chat:write` scope
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SlackTarget {
+    /// `xoxb-...`
+    pub bot_token: String,
+
+    /// ID of the channel, e.g. `C0123456789`. The bot must be a member
+    pub channel_id: String,
+}
+
+/// SMS or WhatsApp through Twilio. For WhatsApp, prefix the numbers with
+/// `whatsapp:`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct TwilioTarget {
+    pub account_sid: String,
+    pub auth_token: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+/// Mobile push notifications. Reports are posted to a companion endpoint that
+/// holds the FCM / APNs credentials and relays them to the listed devices.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct PushTarget {
+    pub url: String,
+
+    /// Sent as a bearer token to the companion endpoint
+    #[serde(default)]
+    pub auth_token: Option<String>,
+
+    #[serde(default)]
+    pub fcm_tokens: Vec<String>,
+
+    #[serde(default)]
+    pub apns_tokens: Vec<String>,
+
+    /// Only push reports whose absolute PnL in native token is at least this
+    #[serde(default)]
+    pub min_pnl: f64,
+}
+
+/// Rows of reports and of the transfers of watched wallets, uploaded to Dune
+/// tables or appended to CSV files in their format, every `flush_interval`
+/// seconds
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub struct DuneTarget {
+    /// Uploads to Dune when set, along with `namespace`
+    #[serde(default)]
+    pub api_key: Option<String>,
+
+    /// Dune user or team owning the tables
+    #[serde(default)]
+    pub namespace: Option<String>,
+
+    /// Directory the CSV files are appended to, one per table
+    #[serde(default)]
+    pub csv_dir: Option<String>,
+
+    #[serde(default = "DuneTarget::default_reports_table")]
+    pub reports_table: String,
+
+    #[serde(default = "DuneTarget::default_transfers_table")]
+    pub transfers_table: String,
+
+    /// Tables created by the watcher are private
+    #[serde(default)]
+    pub private: bool,
+
+    #[serde(default = "DuneTarget::default_flush_interval")]
+    pub flush_interval: u64,
+}
+
+impl DuneTarget {
+    fn default_reports_table() -> String {
+        "wallet_watcher_reports".to_string()
+    }
+
+    fn default_transfers_table() -> String {
+        "wallet_watcher_transfers".to_string()
+    }
+
+    fn default_flush_interval() -> u64 {
+        3600
+    }
+}
+
+impl MqttTarget {
+    fn default_port() -> u16 {
+        1883
+    }
+
+    fn default_client_id() -> String {
+        "wallet-watcher".to_string()
+    }
+
+    fn default_topic_prefix() -> String {
+        "wallet-watcher".to_string()
+    }
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub struct AlertTo {
-    pub bot_token: String,
-    pub chat_id: String,
-    pub thread_id: Option<String>,
+impl ClickhouseTarget {
+    fn default_database() -> String {
+        "default".to_string()
+    }
+
+    fn default_reports_table() -> String {
+        "pnl_reports".to_string()
+    }
+
+    fn default_token_changes_table() -> String {
+        "pnl_token_changes".to_string()
+    }
+
+    fn default_flush_interval() -> u64 {
+        60
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Parser)]
@@ -37,16 +929,42 @@ pub struct Wallet {
     /// The name of the wallet
     pub name: String,
 
+    /// Shown before the name wherever the wallet is shown, e.g. `🦊`
+    #[serde(default)]
+    pub emoji: Option<String>,
+
+    /// Shown instead of the name in messages, the dashboard and push
+    /// notifications, e.g. `J`
+    #[serde(default)]
+    pub short_name: Option<String>,
+
     /// The address of the wallet
     pub address: Address,
 
-    /// The builder address of the wallet
+    /// The builder address of the wallet. On BSC and Polygon, the address the
+    /// validator signs blocks with
     pub builder: Option<Address>,
 
+    /// Other fee recipients the builder rotates through. Blocks with any of
+    /// them as coinbase count as built by the wallet, like `builder`
+    #[serde(default)]
+    pub builders: Vec<Address>,
+
     /// Additional addresses to include in the PnL calculations.
     #[serde(default = "Vec::new")]
     pub other_addresses: Vec<Address>,
 
+    /// Addresses the builder pays proposers from, e.g. a payment contract or
+    /// rotating senders. ETH they send outside the wallet in a built block is
+    /// the validator bribe. The block's coinbase if empty
+    #[serde(default)]
+    pub payment_addresses: Vec<Address>,
+
+    /// BLS public keys the builder bids to relays with, to find the blocks it
+    /// lost in the chains' `relays`
+    #[serde(default)]
+    pub builder_pubkeys: Vec<String>,
+
     /// Chains this wallet is listening on. Leave empty to listen on all chains.
     #[serde(default = "Vec::new")]
     pub chains: Vec<String>,
@@ -54,6 +972,126 @@ pub struct Wallet {
     /// If true, the recipient will be included in PnL calculations
     #[serde(default = "Default::default")]
     pub include_recipient: bool,
+
+    /// Overrides the channel's `throttle_minutes` for this wallet. 0 disables
+    /// throttling
+    #[serde(default)]
+    pub throttle_minutes: Option<u64>,
+
+    /// Order in combined messages, higher first. Wallets below 0 are folded
+    /// under a "show more" line
+    #[serde(default)]
+    pub priority: i32,
+
+    /// Stop watching the wallet without removing it. Its stored reports are
+    /// kept and still show in `report`, so it can be turned back on later
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+/// Transfers of a token to alert on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct TokenWatch {
+    /// Shown in the alerts
+    pub name: String,
+
+    pub token: Address,
+
+    /// Smallest transfer alerted, in whole tokens, e.g. 10000.5
+    #[serde(default)]
+    pub min_amount: f64,
+
+    /// Chains the token is watched on. Leave empty to watch on all chains.
+    #[serde(default)]
+    pub chains: Vec<String>,
+}
+
+/// An event of a contract to alert on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct EventWatch {
+    /// Shown in the alerts
+    pub name: String,
+
+    pub address: Address,
+
+    /// Human-readable ABI of the event, e.g.
+    /// `event Paused(address account)`
+    pub event: String,
+
+    /// Chains the event is watched on. Leave empty to watch on all chains.
+    #[serde(default)]
+    pub chains: Vec<String>,
+}
+
+/// A contract to alert on when its ownership, proxy admin, implementation or
+/// roles change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ContractWatch {
+    /// Shown in the alerts
+    pub name: String,
+
+    pub address: Address,
+
+    /// Chains the contract is watched on. Leave empty to watch on all chains.
+    #[serde(default)]
+    pub chains: Vec<String>,
+}
+
+/// Any transfer on a chain of at least `min_native` of the native token or
+/// its wrapped version, or `min_usd` of a stablecoin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct LargeTransferWatch {
+    /// Shown in the alerts
+    pub name: String,
+
+    #[serde(default)]
+    pub min_native: Option<f64>,
+
+    #[serde(default)]
+    pub min_usd: Option<f64>,
+
+    /// Chains scanned. Leave empty to scan all chains.
+    #[serde(default)]
+    pub chains: Vec<String>,
+}
+
+/// Wallets whose native PnL is summed over a sliding window, e.g. to catch a
+/// drain spread across several hot wallets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ClusterWatch {
+    /// Shown in the alerts
+    pub name: String,
+
+    /// Names of the wallets, in any channel
+    pub wallets: Vec<String>,
+
+    #[serde(default = "ClusterWatch::default_window_minutes")]
+    pub window_minutes: u64,
+
+    /// Alert when the wallets together lose at least this much native token
+    /// within the window
+    #[serde(default)]
+    pub max_loss: Option<f64>,
+
+    /// Alert when the wallets together make at least this much native token
+    /// within the window
+    #[serde(default)]
+    pub min_profit: Option<f64>,
+
+    /// Chains summed over. Leave empty to sum on all chains, each on its own.
+    #[serde(default)]
+    pub chains: Vec<String>,
+}
+
+impl ClusterWatch {
+    fn default_window_minutes() -> u64 {
+        10
+    }
 }
 
 impl Config {
@@ -69,10 +1107,174 @@ impl Config {
 
     /// Validate
     ///   1. Chain exists for wallet
-    ///   2. Each channel has at least one wallet
+    ///   2. Each channel has at least one wallet or watch
+    ///   3. Cluster members exist and are enabled
     pub fn validate(&self) -> eyre::Result<()> {
+        ensure!(
+            self.backfill_days.is_none() || self.storage.is_some(),
+            "backfill_days is set but there is no storage to backfill",
+        );
+        ensure!(
+            self.retention.is_none() || self.storage.is_some(),
+            "retention is set but there is no storage to prune",
+        );
+        ensure!(
+            self.retention.as_ref().map_or(true, |retention| retention.days > 0),
+            "retention days must be at least 1",
+        );
+        ensure!(
+            self.retention
+                .as_ref()
+                .and_then(|retention| retention.max_size_gb)
+                .map_or(true, |max_size_gb| max_size_gb > 0.0),
+            "retention max_size_gb must be positive",
+        );
+        ensure!(
+            !self.leader_election ||
+                self.storage
+                    .as_deref()
+                    .is_some_and(|url| url.starts_with("postgres://") || url.starts_with("postgresql://")),
+            "leader_election needs a Postgres storage to hold the chain locks",
+        );
+
+        for (name, chain) in &self.chains {
+            ensure!(
+                chain.trace.mode != TraceMode::Explorer || chain.explorer_api.is_some(),
+                "Chain {name} traces from the explorer but has no explorer_api",
+            );
+        }
+
         for (i, channel) in self.channels.iter().enumerate() {
-            ensure!(!channel.wallets.is_empty(), "Channel #{i} has no wallets",);
+            let has_watches = !channel.tokens.is_empty() ||
+                !channel.events.is_empty() ||
+                !channel.contracts.is_empty() ||
+                !channel.large_transfers.is_empty() ||
+                !channel.clusters.is_empty();
+            ensure!(
+                !channel.wallets.is_empty() || has_watches,
+                "Channel #{i} has no wallets or watches",
+            );
+            ensure!(
+                !has_watches ||
+                    channel.alert.is_chat() ||
+                    matches!(channel.alert, AlertTo::Nats(_) | AlertTo::Redis(_)),
+                "Channel #{i} has watches, which only chat, NATS and Redis channels alert on",
+            );
+
+            ensure!(
+                !channel.daily_digest || matches!(channel.alert, AlertTo::Telegram(_)),
+                "Channel #{i} has a daily_digest but isn't a Telegram channel",
+            );
+            ensure!(
+                !channel.daily_digest || self.storage.is_some(),
+                "Channel #{i} has a daily_digest but there is no storage to read the reports from",
+            );
+
+            if let AlertTo::Dune(target) = &channel.alert {
+                ensure!(
+                    target.api_key.is_some() == target.namespace.is_some(),
+                    "Channel #{i} needs both api_key and namespace to upload to Dune",
+                );
+                ensure!(
+                    target.api_key.is_some() || target.csv_dir.is_some(),
+                    "Channel #{i} has neither a Dune api_key nor a csv_dir",
+                );
+            }
+
+            for cluster in &channel.clusters {
+                ensure!(
+                    cluster.max_loss.is_some() || cluster.min_profit.is_some(),
+                    "Cluster {} has neither max_loss nor min_profit",
+                    cluster.name
+                );
+                ensure!(
+                    cluster.window_minutes > 0,
+                    "Cluster {} has an empty window",
+                    cluster.name
+                );
+
+                for wallet in &cluster.wallets {
+                    let member = self
+                        .channels
+                        .iter()
+                        .flat_map(|c| &c.wallets)
+                        .find(|w| w.name == *wallet);
+                    ensure!(
+                        member.is_some(),
+                        "Wallet {} not found for cluster {}",
+                        wallet,
+                        cluster.name
+                    );
+                    // A disabled wallet has no reports, so the cluster would never sum it
+                    ensure!(
+                        member.is_some_and(|w| !w.disabled),
+                        "Wallet {} of cluster {} is disabled",
+                        wallet,
+                        cluster.name
+                    );
+                }
+
+                for chain in &cluster.chains {
+                    ensure!(
+                        self.chains.contains_key(chain),
+                        "Chain {} not found for cluster {}",
+                        chain,
+                        cluster.name
+                    );
+                }
+            }
+
+            for watch in &channel.large_transfers {
+                ensure!(
+                    watch.min_native.is_some() || watch.min_usd.is_some(),
+                    "Large transfer watch {} has neither min_native nor min_usd",
+                    watch.name
+                );
+
+                for chain in &watch.chains {
+                    ensure!(
+                        self.chains.contains_key(chain),
+                        "Chain {} not found for large transfer watch {}",
+                        chain,
+                        watch.name
+                    );
+                }
+            }
+
+            for watch in &channel.contracts {
+                for chain in &watch.chains {
+                    ensure!(
+                        self.chains.contains_key(chain),
+                        "Chain {} not found for contract watch {}",
+                        chain,
+                        watch.name
+                    );
+                }
+            }
+
+            for watch in &channel.events {
+                Event::parse(&watch.event).with_context(|| format!("Invalid event of event watch {}", watch.name))?;
+
+                for chain in &watch.chains {
+                    ensure!(
+                        self.chains.contains_key(chain),
+                        "Chain {} not found for event watch {}",
+                        chain,
+                        watch.name
+                    );
+                }
+            }
+
+            for watch in &channel.tokens {
+                for chain in &watch.chains {
+                    ensure!(
+                        self.chains.contains_key(chain),
+                        "Chain {} not found for token watch {}",
+                        chain,
+                        watch.name
+                    );
+                }
+            }
 
             for wallet in &channel.wallets {
                 for chain in &wallet.chains {
@@ -94,10 +1296,11 @@ impl Config {
 
         let all_chains = self.chains.keys().cloned().collect::<Vec<_>>();
 
-        for channel in &self.channels {
+        for (channel_index, channel) in self.channels.iter().enumerate() {
             let alert = Arc::new(channel.alert.clone());
+            let tx_links: Option<Arc<[TxLink]>> = channel.tx_links.as_deref().map(Into::into);
 
-            for wallet in &channel.wallets {
+            for wallet in channel.wallets.iter().filter(|w| !w.disabled) {
                 let supported_chains = if wallet.chains.is_empty() {
                     all_chains.iter()
                 } else {
@@ -111,7 +1314,26 @@ impl Config {
                     wallet.other_addresses.clone(),
                     wallet.include_recipient,
                     Arc::clone(&alert),
-                );
+                )
+                .with_emoji(wallet.emoji.clone())
+                .with_short_name(wallet.short_name.clone())
+                .with_language(channel.language)
+                .with_timezone(channel.timezone.unwrap_or(Tz::UTC))
+                .with_number_format(channel.number_format())
+                .with_throttle(
+                    wallet
+                        .throttle_minutes
+                        .or(channel.throttle_minutes)
+                        .filter(|minutes| *minutes > 0)
+                        .map(|minutes| Duration::from_secs(minutes * 60)),
+                )
+                .with_combined_channel(channel.combine_wallets.then_some(channel_index))
+                .with_digest_channel(channel.daily_digest.then_some(channel_index))
+                .with_priority(wallet.priority)
+                .with_tx_links(tx_links.clone())
+                .with_payment_addresses(wallet.payment_addresses.clone())
+                .with_builder_pubkeys(wallet.builder_pubkeys.clone())
+                .with_builders(wallet.builders.clone());
 
                 for chain in supported_chains {
                     result.entry(chain.to_owned()).or_default().push(wallet.clone());
@@ -121,15 +1343,211 @@ impl Config {
 
         result
     }
+
+    pub fn to_token_watches_by_chain(&self) -> HashMap<String, Vec<TokenWatchWithContext>> {
+        let mut result: HashMap<String, Vec<TokenWatchWithContext>> = HashMap::new();
+
+        for channel in &self.channels {
+            let alert = Arc::new(channel.alert.clone());
+
+            for watch in &channel.tokens {
+                let chains = self.watched_chains(&watch.chains);
+
+                let watch = TokenWatchWithContext {
+                    name: watch.name.clone(),
+                    token: watch.token,
+                    min_amount: watch.min_amount,
+                    alert_to: Arc::clone(&alert),
+                    language: channel.language,
+                };
+
+                for chain in chains {
+                    result.entry(chain).or_default().push(watch.clone());
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Event watches by chain. Events must have been validated
+    pub fn to_event_watches_by_chain(&self) -> HashMap<String, Vec<EventWatchWithContext>> {
+        let mut result: HashMap<String, Vec<EventWatchWithContext>> = HashMap::new();
+
+        for channel in &self.channels {
+            let alert = Arc::new(channel.alert.clone());
+
+            for watch in &channel.events {
+                let chains = self.watched_chains(&watch.chains);
+
+                let watch = EventWatchWithContext {
+                    name: watch.name.clone(),
+                    address: watch.address,
+                    event: Event::parse(&watch.event).expect("event is validated"),
+                    alert_to: Arc::clone(&alert),
+                    language: channel.language,
+                    high_severity: false,
+                };
+
+                for chain in chains {
+                    result.entry(chain).or_default().push(watch.clone());
+                }
+            }
+
+            for watch in &channel.contracts {
+                let chains = self.watched_chains(&watch.chains);
+
+                for event in event_watch::admin_events() {
+                    let watch = EventWatchWithContext {
+                        name: watch.name.clone(),
+                        address: watch.address,
+                        event,
+                        alert_to: Arc::clone(&alert),
+                        language: channel.language,
+                        high_severity: true,
+                    };
+
+                    for chain in &chains {
+                        result.entry(chain.clone()).or_default().push(watch.clone());
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    pub fn to_large_transfer_watches_by_chain(&self) -> HashMap<String, Vec<LargeTransferWatchWithContext>> {
+        let mut result: HashMap<String, Vec<LargeTransferWatchWithContext>> = HashMap::new();
+
+        for channel in &self.channels {
+            let alert = Arc::new(channel.alert.clone());
+
+            for watch in &channel.large_transfers {
+                let chains = self.watched_chains(&watch.chains);
+
+                let watch = LargeTransferWatchWithContext {
+                    name: watch.name.clone(),
+                    min_native: watch.min_native,
+                    min_usd: watch.min_usd,
+                    alert_to: Arc::clone(&alert),
+                    language: channel.language,
+                };
+
+                for chain in chains {
+                    result.entry(chain).or_default().push(watch.clone());
+                }
+            }
+        }
+
+        result
+    }
+
+    pub fn to_cluster_watches_by_chain(&self) -> HashMap<String, Vec<ClusterWatchWithContext>> {
+        let mut result: HashMap<String, Vec<ClusterWatchWithContext>> = HashMap::new();
+
+        for channel in &self.channels {
+            let alert = Arc::new(channel.alert.clone());
+
+            for cluster in &channel.clusters {
+                let chains = self.watched_chains(&cluster.chains);
+
+                let cluster = ClusterWatchWithContext {
+                    name: cluster.name.clone(),
+                    wallets: cluster.wallets.clone(),
+                    window: Duration::from_secs(cluster.window_minutes * 60),
+                    max_loss: cluster.max_loss,
+                    min_profit: cluster.min_profit,
+                    alert_to: Arc::clone(&alert),
+                    language: channel.language,
+                    number_format: channel.number_format(),
+                };
+
+                for chain in chains {
+                    result.entry(chain).or_default().push(cluster.clone());
+                }
+            }
+        }
+
+        result
+    }
+
+    /// `chains` of a watch, or all chains if it names none
+    fn watched_chains(&self, chains: &[String]) -> Vec<String> {
+        if chains.is_empty() {
+            self.chains.keys().cloned().collect()
+        } else {
+            chains.to_vec()
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct EventWatchWithContext {
+    pub name: String,
+    pub address: Address,
+    pub event: Event,
+    pub alert_to: Arc<AlertTo>,
+    pub language: Language,
+    /// Ownership and admin changes, which may be a takeover
+    pub high_severity: bool,
+}
+
+#[derive(Clone)]
+pub struct LargeTransferWatchWithContext {
+    pub name: String,
+    pub min_native: Option<f64>,
+    pub min_usd: Option<f64>,
+    pub alert_to: Arc<AlertTo>,
+    pub language: Language,
+}
+
+#[derive(Clone)]
+pub struct ClusterWatchWithContext {
+    pub name: String,
+    pub wallets: Vec<String>,
+    pub window: Duration,
+    pub max_loss: Option<f64>,
+    pub min_profit: Option<f64>,
+    pub alert_to: Arc<AlertTo>,
+    pub language: Language,
+    pub number_format: NumberFormat,
+}
+
+#[derive(Clone)]
+pub struct TokenWatchWithContext {
+    pub name: String,
+    pub token: Address,
+    pub min_amount: f64,
+    pub alert_to: Arc<AlertTo>,
+    pub language: Language,
 }
 
 #[derive(Clone)]
 pub struct WalletWithContext {
     pub name: String,
+    pub emoji: Option<String>,
+    pub short_name: Option<String>,
     pub address: Address,
     pub builder: Option<Address>,
+    /// Fee recipients the builder rotates through, besides `builder`
+    pub builders: Vec<Address>,
     pub include_recipient: bool,
     pub alert_to: Arc<AlertTo>,
+    pub language: Language,
+    pub timezone: Tz,
+    pub number_format: NumberFormat,
+    pub throttle: Option<Duration>,
+    /// Index of the channel when its wallets share one message per block
+    pub combined_channel: Option<usize>,
+    /// Index of the channel when it gets a daily digest
+    pub digest_channel: Option<usize>,
+    pub priority: i32,
+    /// Tx links of the channel, if it overrides the chain's
+    pub tx_links: Option<Arc<[TxLink]>>,
+    /// Where proposer payments are sent from, the coinbase if empty
+    pub payment_addresses: Vec<Address>,
+    pub builder_pubkeys: Vec<String>,
 
     involved_wallets: Vec<Address>,
 }
@@ -152,14 +1570,109 @@ impl WalletWithContext {
 
         Self {
             name,
+            emoji: None,
+            short_name: None,
             address,
             builder,
+            builders: vec![],
             include_recipient,
             alert_to,
+            language: Language::default(),
+            timezone: Tz::UTC,
+            number_format: NumberFormat::default(),
+            throttle: None,
+            combined_channel: None,
+            digest_channel: None,
+            priority: 0,
+            tx_links: None,
+            payment_addresses: vec![],
+            builder_pubkeys: vec![],
             involved_wallets,
         }
     }
 
+    pub fn with_emoji(mut self, emoji: Option<String>) -> Self {
+        self.emoji = emoji;
+        self
+    }
+
+    pub fn with_short_name(mut self, short_name: Option<String>) -> Self {
+        self.short_name = short_name;
+        self
+    }
+
+    pub fn with_language(mut self, language: Language) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// The short name, or the name, after the emoji
+    pub fn display_name(&self) -> String {
+        utils::display_name(self.emoji.as_deref(), self.short_name.as_deref().unwrap_or(&self.name))
+    }
+
+    pub fn with_timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    pub fn with_number_format(mut self, number_format: NumberFormat) -> Self {
+        self.number_format = number_format;
+        self
+    }
+
+    pub fn with_throttle(mut self, throttle: Option<Duration>) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    pub fn with_combined_channel(mut self, channel: Option<usize>) -> Self {
+        self.combined_channel = channel;
+        self
+    }
+
+    pub fn with_digest_channel(mut self, channel: Option<usize>) -> Self {
+        self.digest_channel = channel;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_tx_links(mut self, tx_links: Option<Arc<[TxLink]>>) -> Self {
+        self.tx_links = tx_links;
+        self
+    }
+
+    pub fn with_payment_addresses(mut self, addresses: Vec<Address>) -> Self {
+        self.payment_addresses = addresses;
+        self
+    }
+
+    pub fn with_builder_pubkeys(mut self, pubkeys: Vec<String>) -> Self {
+        self.builder_pubkeys = pubkeys;
+        self
+    }
+
+    /// Add fee recipients the builder rotates through. They are involved
+    /// in the wallet's PnL like `builder`
+    pub fn with_builders(mut self, builders: Vec<Address>) -> Self {
+        for builder in &builders {
+            if !self.involved_wallets.contains(builder) {
+                self.involved_wallets.push(*builder);
+            }
+        }
+        self.builders = builders;
+        self
+    }
+
+    /// Whether the wallet built a block with `coinbase`
+    pub fn is_builder(&self, coinbase: Address) -> bool {
+        self.builder == Some(coinbase) || self.builders.contains(&coinbase)
+    }
+
     pub fn involved_wallets(&self) -> &[Address] {
         &self.involved_wallets
     }
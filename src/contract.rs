@@ -21,6 +21,69 @@ sol!(
         function skim(address to) external;
     }
 
+    #[sol(rpc)]
+    contract FactoryV2 {
+        function getPair(address tokenA, address tokenB) external view returns (address pair);
+    }
+
+    /// Common claim functions of airdrop distributors, Merkle ones mostly
+    interface AirdropDistributor {
+        function claim(uint256 index, address account, uint256 amount, bytes32[] calldata merkleProof) external;
+        function claim(address account, uint256 amount, bytes32[] calldata merkleProof) external;
+        function claim(uint256 amount, bytes32[] calldata merkleProof) external;
+        function claim() external;
+    }
+
+    #[sol(rpc)]
+    contract ERC4626 {
+        function asset() external view returns (address);
+        function convertToAssets(uint256 shares) external view returns (uint256);
+
+        event Deposit(address indexed sender, address indexed owner, uint256 assets, uint256 shares);
+        event Withdraw(address indexed sender, address indexed receiver, address indexed owner, uint256 assets, uint256 shares);
+    }
+
+    /// GMX v1 vault, holding every position
+    contract GmxVault {
+        event IncreasePosition(bytes32 key, address account, address collateralToken, address indexToken, uint256 collateralDelta, uint256 sizeDelta, bool isLong, uint256 price, uint256 fee);
+        event DecreasePosition(bytes32 key, address account, address collateralToken, address indexToken, uint256 collateralDelta, uint256 sizeDelta, bool isLong, uint256 price, uint256 fee);
+        event LiquidatePosition(bytes32 key, address account, address collateralToken, address indexToken, bool isLong, uint256 size, uint256 collateral, uint256 reserveAmount, int256 realisedPnl, uint256 markPrice);
+        event UpdatePnl(bytes32 key, bool hasProfit, uint256 delta);
+    }
+
+    #[sol(rpc)]
+    contract Ownable {
+        function owner() public view returns (address);
+    }
+
+    /// Lido stETH
+    interface Lido {
+        event Submitted(address indexed sender, uint256 amount, address referral);
+    }
+
+    /// Lido withdrawal queue, turning stETH back into ETH
+    interface LidoWithdrawalQueue {
+        event WithdrawalRequested(uint256 indexed requestId, address indexed requestor, address indexed owner, uint256 amountOfStETH, uint256 amountOfShares);
+        event WithdrawalClaimed(uint256 indexed requestId, address indexed owner, address indexed receiver, uint256 amountOfETH);
+    }
+
+    /// EigenLayer strategy manager, before the slashing upgrade
+    interface EigenLayerStrategyManager {
+        event Deposit(address staker, address token, address strategy, uint256 shares);
+    }
+
+    /// EigenLayer strategy manager, since the slashing upgrade
+    interface EigenLayerStrategyManagerV2 {
+        event Deposit(address staker, address strategy, uint256 shares);
+    }
+
+    /// Chainlink price feed
+    #[sol(rpc)]
+    interface AggregatorV3 {
+        function decimals() external view returns (uint8);
+        function latestRoundData() external view returns (uint80 roundId, int256 answer, uint256 startedAt, uint256 updatedAt, uint80 answeredInRound);
+    }
+
     #[sol(rpc)]
     interface WETH9 {
         event Approval(address indexed src, address indexed guy, uint wad);
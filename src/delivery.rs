@@ -0,0 +1,95 @@
+use {
+    alloy::primitives::{keccak256, Address, B256},
+    alloy_chains::Chain,
+    redis::{aio::ConnectionManager, AsyncCommands, ExistenceCheck, SetExpiry, SetOptions},
+    std::collections::{HashSet, VecDeque},
+    tracing::warn,
+};
+
+/// Ids remembered in memory, on top of the store
+const KEPT_IDS: usize = 10_000;
+
+/// How long the store remembers a delivery
+const STORE_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Deterministic id of a wallet's report for a block, the same on every retry
+/// and restart. Consumers can use it to drop duplicates.
+pub fn delivery_id(chain: Chain, block_hash: B256, wallet: Address) -> B256 {
+    keccak256([&chain.id().to_be_bytes()[..], block_hash.as_slice(), wallet.as_slice()].concat())
+}
+
+/// Reports already delivered, so a block processed again, e.g. on retry,
+/// doesn't send them twice. They are kept in redis when a store is set up, so
+/// they survive restarts.
+#[derive(Default)]
+pub struct Deliveries {
+    recent: HashSet<B256>,
+    order: VecDeque<B256>,
+    store: Option<ConnectionManager>,
+    /// Claimed but not submitted yet
+    unconfirmed: HashSet<B256>,
+}
+
+impl Deliveries {
+    pub fn new(store: Option<ConnectionManager>) -> Self {
+        Self {
+            store,
+            ..Default::default()
+        }
+    }
+
+    /// Mark `id` delivered, returning whether it wasn't already. Until
+    /// [`Self::confirm`]ed, [`Self::release_unconfirmed`] takes it back
+    pub async fn claim(&mut self, id: B256) -> bool {
+        if self.recent.contains(&id) {
+            return false;
+        }
+
+        if let Some(store) = self.store.as_mut() {
+            let options = SetOptions::default()
+                .conditional_set(ExistenceCheck::NX)
+                .with_expiration(SetExpiry::EX(STORE_TTL_SECS));
+
+            match store
+                .set_options::<_, _, Option<String>>(format!("wallet-watcher:delivered:{id:#x}"), 1, options)
+                .await
+            {
+                Ok(None) => return false,
+                Ok(Some(_)) => {}
+                // Better twice than never
+                Err(err) => warn!(%id, "Failed to record delivery: {err:#}"),
+            }
+        }
+
+        self.recent.insert(id);
+        self.unconfirmed.insert(id);
+        self.order.push_back(id);
+        if self.order.len() > KEPT_IDS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.recent.remove(&oldest);
+            }
+        }
+
+        true
+    }
+
+    /// The report of `id` was submitted
+    pub fn confirm(&mut self, id: B256) {
+        self.unconfirmed.remove(&id);
+    }
+
+    /// Forget the claims whose report wasn't submitted, e.g. because the block
+    /// failed halfway, so a retry delivers them
+    pub async fn release_unconfirmed(&mut self) {
+        for id in std::mem::take(&mut self.unconfirmed) {
+            self.recent.remove(&id);
+            self.order.retain(|kept| *kept != id);
+
+            if let Some(store) = self.store.as_mut() {
+                if let Err(err) = store.del::<_, ()>(format!("wallet-watcher:delivered:{id:#x}")).await {
+                    warn!(%id, "Failed to release delivery: {err:#}");
+                }
+            }
+        }
+    }
+}
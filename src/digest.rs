@@ -0,0 +1,340 @@
+use {
+    crate::{
+        config::{AlertTo, WalletWithContext},
+        executor::Action,
+        message::{MessageFormat, Renderer},
+        relay::RelayClient,
+        storage::{ReportQuery, Storage, StoredReport},
+        utils,
+    },
+    alloy::primitives::{I256, U256},
+    alloy_chains::Chain,
+    burberry::Executor,
+    chrono::{DateTime, Utc},
+    eyre::Context,
+    image::{codecs::png::PngEncoder, ColorType, ImageEncoder},
+    plotters::{
+        backend::BitMapBackend,
+        drawing::IntoDrawingArea,
+        element::PathElement,
+        style::{Color, RGBColor, BLACK, WHITE},
+    },
+    std::{
+        collections::{BTreeMap, HashMap},
+        sync::Arc,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tokio::sync::watch,
+    tracing::{error, info, warn},
+};
+
+/// Digests go out this long after midnight UTC, once the last blocks of the
+/// day had time to be processed and saved
+const DIGEST_DELAY: Duration = Duration::from_secs(600);
+
+/// Telegram cuts photo captions longer than this, so longer digests follow
+/// their chart as a message of their own
+const CAPTION_LIMIT: usize = 1024;
+
+const CHART_WIDTH: u32 = 800;
+const CHART_HEIGHT: u32 = 400;
+const CHART_PADDING: i32 = 20;
+
+/// Colors of the wallets' lines on the chart, with the emoji marking each
+/// wallet in the text. Wallets past the last one reuse the colors in order
+const LINE_COLORS: [(RGBColor, &str); 6] = [
+    (RGBColor(0x1f, 0x77, 0xb4), "🟦"),
+    (RGBColor(0xd6, 0x27, 0x28), "🟥"),
+    (RGBColor(0x2c, 0xa0, 0x2c), "🟩"),
+    (RGBColor(0xff, 0x7f, 0x0e), "🟧"),
+    (RGBColor(0x94, 0x67, 0xbd), "🟪"),
+    (RGBColor(0x8c, 0x56, 0x4b), "🟫"),
+];
+
+/// A wallet's reports over a UTC day, oldest first
+pub struct WalletDay {
+    pub wallet: WalletWithContext,
+    pub reports: Vec<StoredReport>,
+    /// Of builders with `builder_pubkeys` on a chain with `relays`
+    pub missed: Option<Missed>,
+}
+
+/// Blocks a builder bid on over a day that another builder won
+pub struct Missed {
+    pub blocks: u64,
+    /// What the winning bids were above its best ones, summed
+    pub outbid_by: U256,
+    /// Profit forgone, estimated at the margin of the blocks it won that day.
+    /// Unknown when it won none at a profit
+    pub profit: Option<U256>,
+    /// Blocks the relays failed to tell about
+    pub unknown: u64,
+}
+
+/// Emoji of the `i`th wallet's line on the chart
+pub fn marker(i: usize) -> &'static str {
+    LINE_COLORS[i % LINE_COLORS.len()].1
+}
+
+/// Whether every report of the days is valued in USD, so the chart can be too
+pub fn in_usd(days: &[WalletDay]) -> bool {
+    days.iter()
+        .flat_map(|day| &day.reports)
+        .all(|r| r.report.pnl_usd.is_some())
+}
+
+/// The USD PnL of `reports`, when every one of them is valued
+pub fn pnl_usd(reports: &[StoredReport]) -> Option<I256> {
+    reports
+        .iter()
+        .try_fold(I256::ZERO, |sum, r| Some(sum + r.report.pnl_usd?))
+}
+
+/// Send the digests of `chain` to the channels that have one after every UTC
+/// day, to the wallets of the chain named `name` in the config at the time.
+/// Failures are logged and the day is skipped
+pub async fn send_daily(
+    chain: Chain,
+    name: String,
+    storage: Arc<dyn Storage>,
+    renderer: Renderer,
+    wallets: watch::Receiver<HashMap<String, Vec<WalletWithContext>>>,
+    relays: Option<RelayClient>,
+    executors: Vec<Arc<dyn Executor<Action>>>,
+) {
+    loop {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let delay = DIGEST_DELAY.as_secs();
+        let next = (now.saturating_sub(delay) / 86400 + 1) * 86400 + delay;
+        tokio::time::sleep(Duration::from_secs(next - now)).await;
+
+        let day = next - delay - 86400;
+        let wallets = wallets.borrow().get(&name).cloned().unwrap_or_default();
+        let actions = match digests(chain, storage.as_ref(), &renderer, &wallets, relays.as_ref(), day).await {
+            Ok(actions) => actions,
+            Err(err) => {
+                error!(%chain, "Failed to make the daily digests: {err:#}");
+                continue;
+            }
+        };
+
+        info!(%chain, messages = actions.len(), "Sending daily digests");
+        for action in actions {
+            for executor in &executors {
+                if let Err(err) = executor.execute(action.clone()).await {
+                    warn!(%chain, "Failed to send daily digest: {err:#}");
+                }
+            }
+        }
+    }
+}
+
+/// The messages of the digests of `chain` for the UTC day starting at `day`,
+/// one or two per channel with one: the chart, and the text as its caption or
+/// after it
+pub async fn digests(
+    chain: Chain,
+    storage: &dyn Storage,
+    renderer: &Renderer,
+    wallets: &[WalletWithContext],
+    relays: Option<&RelayClient>,
+    day: u64,
+) -> eyre::Result<Vec<Action>> {
+    // Blocks of the day, as far as they were processed
+    let blocks = match relays {
+        Some(_) => storage
+            .chain_stats(day, day + 86399)
+            .await?
+            .into_iter()
+            .find(|stats| stats.chain == chain.id())
+            .map(|stats| (stats.first_block, stats.last_block)),
+        None => None,
+    };
+
+    let mut channels: BTreeMap<usize, Vec<WalletDay>> = BTreeMap::new();
+    for wallet in wallets {
+        let Some(channel) = wallet.digest_channel else {
+            continue;
+        };
+
+        let query = ReportQuery {
+            chain: Some(chain.id()),
+            address: Some(wallet.address),
+            from_timestamp: Some(day),
+            to_timestamp: Some(day + 86399),
+            ..Default::default()
+        };
+        // An address watched in several channels is stored once per wallet
+        let mut reports = storage
+            .reports(&query)
+            .await?
+            .into_iter()
+            .filter(|r| r.wallet == wallet.name)
+            .collect::<Vec<_>>();
+        reports.sort_by_key(|r| r.block);
+
+        let missed = match (relays, blocks) {
+            (Some(relays), Some(blocks)) if !wallet.builder_pubkeys.is_empty() => {
+                match missed(relays, wallet, &reports, blocks).await {
+                    Ok(missed) => Some(missed),
+                    Err(err) => {
+                        warn!(%chain, wallet = %wallet.name, "Failed to get the lost blocks: {err:#}");
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        channels.entry(channel).or_default().push(WalletDay {
+            wallet: wallet.clone(),
+            reports,
+            missed,
+        });
+    }
+
+    let date = DateTime::<Utc>::from_timestamp(day as i64, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%d")
+        .to_string();
+    let mut actions = vec![];
+    for days in channels.into_values() {
+        let AlertTo::Telegram(target) = days[0].wallet.alert_to.as_ref() else {
+            continue;
+        };
+
+        let png = match days.iter().any(|day| !day.reports.is_empty()) {
+            true => Some(chart(&days, day, renderer.native_decimals())?),
+            false => None,
+        };
+        let text = renderer.digest(&date, &days, png.is_some(), MessageFormat::Telegram);
+
+        match png {
+            Some(png) if text.chars().count() <= CAPTION_LIMIT => {
+                actions.push(Action::TelegramPhoto(target.clone(), png, text));
+            }
+            Some(png) => {
+                actions.push(Action::TelegramPhoto(target.clone(), png, String::new()));
+                actions.push(Action::Telegram(target.clone(), text));
+            }
+            None => actions.push(Action::Telegram(target.clone(), text)),
+        }
+    }
+
+    Ok(actions)
+}
+
+/// The blocks from `from_block` to `to_block` that `wallet` bid on and lost.
+/// The profit forgone on them is estimated from its best bids, at the ratio
+/// of its PnL on the blocks it won to what it paid their proposers
+async fn missed(
+    relays: &RelayClient,
+    wallet: &WalletWithContext,
+    reports: &[StoredReport],
+    (from_block, to_block): (u64, u64),
+) -> eyre::Result<Missed> {
+    let day = relays
+        .builder_day(&wallet.builder_pubkeys, from_block, to_block)
+        .await?;
+
+    let (profit, paid) = reports
+        .iter()
+        .filter_map(|r| Some((r.report.pnl, *day.won.get(&r.block)?)))
+        .fold((I256::ZERO, U256::ZERO), |(profit, paid), (pnl, value)| {
+            (profit + pnl, paid + value)
+        });
+    let profit = (profit.is_positive() && !paid.is_zero()).then(|| day.lost_bids * profit.unsigned_abs() / paid);
+
+    Ok(Missed {
+        blocks: day.lost,
+        outbid_by: day.outbid_by,
+        profit,
+        unknown: day.unknown,
+    })
+}
+
+/// PNG of the running PnL of each wallet over the day starting at `day`, in
+/// USD when every report has it and in native units otherwise. There is no
+/// text on it, the digest says which line is which
+fn chart(days: &[WalletDay], day: u64, native_decimals: u8) -> eyre::Result<Vec<u8>> {
+    let usd = in_usd(days);
+    let lines = days
+        .iter()
+        .map(|wallet_day| {
+            wallet_day
+                .reports
+                .iter()
+                .scan(0.0, |total, r| {
+                    *total += match (r.report.pnl_usd, usd) {
+                        (Some(pnl_usd), true) => to_f64(pnl_usd, 18),
+                        _ => to_f64(r.report.pnl, native_decimals),
+                    };
+                    Some((r.timestamp, *total))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let (min, max) = lines
+        .iter()
+        .flatten()
+        .fold((0.0_f64, 0.0_f64), |(min, max), (_, total)| {
+            (min.min(*total), max.max(*total))
+        });
+    let span = (max - min).max(f64::EPSILON);
+    let x = |timestamp: u64| {
+        let elapsed = timestamp.saturating_sub(day).min(86400) as f64 / 86400.0;
+        CHART_PADDING + (elapsed * (CHART_WIDTH as i32 - 2 * CHART_PADDING) as f64) as i32
+    };
+    let y =
+        |value: f64| CHART_PADDING + ((max - value) / span * (CHART_HEIGHT as i32 - 2 * CHART_PADDING) as f64) as i32;
+
+    let mut pixels = vec![0; (CHART_WIDTH * CHART_HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut pixels, (CHART_WIDTH, CHART_HEIGHT)).into_drawing_area();
+        root.fill(&WHITE).context("Failed to draw chart")?;
+        root.draw(&PathElement::new(
+            vec![(x(day), y(0.0)), (x(day + 86400), y(0.0))],
+            BLACK.mix(0.2),
+        ))
+        .context("Failed to draw chart")?;
+
+        for (i, points) in lines.iter().enumerate() {
+            // Steps, as the PnL only moves at the wallet's blocks
+            let mut path = vec![(x(day), y(0.0))];
+            let mut last = 0.0;
+            for (timestamp, total) in points {
+                path.push((x(*timestamp), y(last)));
+                path.push((x(*timestamp), y(*total)));
+                last = *total;
+            }
+            path.push((x(day + 86400), y(last)));
+
+            let color = LINE_COLORS[i % LINE_COLORS.len()].0;
+            root.draw(&PathElement::new(path, color.stroke_width(2)))
+                .context("Failed to draw chart")?;
+        }
+        root.present().context("Failed to draw chart")?;
+    }
+
+    let mut png = vec![];
+    PngEncoder::new(&mut png)
+        .write_image(&pixels, CHART_WIDTH, CHART_HEIGHT, ColorType::Rgb8)
+        .context("Failed to encode chart")?;
+
+    Ok(png)
+}
+
+fn to_f64(value: I256, decimals: u8) -> f64 {
+    let abs = utils::format_units(value.unsigned_abs(), decimals, decimals)
+        .parse::<f64>()
+        .unwrap_or_default();
+    if value.is_negative() {
+        -abs
+    } else {
+        abs
+    }
+}
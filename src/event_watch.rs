@@ -0,0 +1,111 @@
+use {
+    crate::processor::TxAndPosition,
+    alloy::{
+        dyn_abi::{DynSolValue, EventExt},
+        hex,
+        json_abi::Event,
+        primitives::Address,
+        rpc::types::{trace::geth::CallFrame, AnyTransactionReceipt},
+    },
+    tracing::warn,
+};
+
+/// Decoded fields of an event, by the event's input names
+pub type EventFields = Vec<(String, String)>;
+
+/// Ownership, EIP-1967 proxy and access control changes. Any of them may be a
+/// takeover of the contract.
+const ADMIN_EVENTS: &[&str] = &[
+    "event OwnershipTransferred(address indexed previousOwner, address indexed newOwner)",
+    "event AdminChanged(address previousAdmin, address newAdmin)",
+    "event Upgraded(address indexed implementation)",
+    "event BeaconUpgraded(address indexed beacon)",
+    "event RoleGranted(bytes32 indexed role, address indexed account, address indexed sender)",
+    "event RoleRevoked(bytes32 indexed role, address indexed account, address indexed sender)",
+];
+
+/// The events watched on a contract watch
+pub fn admin_events() -> Vec<Event> {
+    ADMIN_EVENTS
+        .iter()
+        .map(|e| Event::parse(e).expect("admin events are valid"))
+        .collect()
+}
+
+/// Emissions of `event` by `address` in a block, with their decoded fields
+pub fn find_events(
+    receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
+    address: Address,
+    event: &Event,
+) -> Vec<(TxAndPosition, EventFields)> {
+    let mut found = vec![];
+
+    for (receipt, _) in receipt_and_traces {
+        if !receipt.inner.inner.status() {
+            continue;
+        }
+
+        let tx = TxAndPosition {
+            index: receipt.transaction_index.unwrap_or_default(),
+            hash: receipt.transaction_hash,
+        };
+
+        for log in receipt.inner.inner.logs() {
+            if log.inner.address != address {
+                continue;
+            }
+            if !event.anonymous && log.inner.data.topics().first() != Some(&event.selector()) {
+                continue;
+            }
+
+            let decoded = match event.decode_log(&log.inner.data, true) {
+                Ok(decoded) => decoded,
+                Err(err) => {
+                    warn!(tx = %receipt.transaction_hash, "Failed to decode {}: {err:#}", event.name);
+                    continue;
+                }
+            };
+
+            let mut indexed = decoded.indexed.iter();
+            let mut body = decoded.body.iter();
+            let fields = event
+                .inputs
+                .iter()
+                .enumerate()
+                .filter_map(|(i, input)| {
+                    let value = if input.indexed { indexed.next() } else { body.next() }?;
+                    let name = if input.name.is_empty() {
+                        format!("#{i}")
+                    } else {
+                        input.name.clone()
+                    };
+                    Some((name, format_value(value)))
+                })
+                .collect();
+
+            found.push((tx.clone(), fields));
+        }
+    }
+
+    found
+}
+
+fn format_value(value: &DynSolValue) -> String {
+    match value {
+        DynSolValue::Address(a) => a.to_string(),
+        DynSolValue::Bool(b) => b.to_string(),
+        DynSolValue::Int(i, _) => i.to_string(),
+        DynSolValue::Uint(u, _) => u.to_string(),
+        DynSolValue::FixedBytes(b, size) => hex::encode_prefixed(&b[..*size]),
+        DynSolValue::Bytes(b) => hex::encode_prefixed(b),
+        DynSolValue::String(s) => s.clone(),
+        DynSolValue::Array(values) | DynSolValue::FixedArray(values) => {
+            format!("[{}]", values.iter().map(format_value).collect::<Vec<_>>().join(", "))
+        }
+        DynSolValue::Tuple(values) => {
+            format!("({})", values.iter().map(format_value).collect::<Vec<_>>().join(", "))
+        }
+        // Indexed dynamic values only keep their hash
+        other => format!("{other:?}"),
+    }
+}
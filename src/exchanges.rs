@@ -0,0 +1,33 @@
+use {
+    alloy::primitives::{address, Address},
+    std::{
+        collections::HashMap,
+        sync::{LazyLock, RwLock},
+    },
+};
+
+/// Exchange addresses to the exchange's name, the well-known hot wallets plus
+/// the ones from the config
+static EXCHANGES: LazyLock<RwLock<HashMap<Address, String>>> = LazyLock::new(|| {
+    let known = [
+        (address!("28C6c06298d514Db089934071355E5743bf21d60"), "Binance"),
+        (address!("21a31Ee1afC51d94C2eFcCAa2092aD1028285549"), "Binance"),
+        (address!("DFd5293D8e347dFe59E90eFd55b2956a1343963d"), "Binance"),
+        (address!("71660c4005BA85c37ccec55d0C4493E66Fe775d3"), "Coinbase"),
+        (address!("A9D1e08C7793af67e9d92fe308d5697FB81d3E43"), "Coinbase"),
+        (address!("2910543Af39abA0Cd09dBb2D50200b3E800A63D2"), "Kraken"),
+        (address!("6cC5F688a315f3dC28A7781717a9A798a59fDA7b"), "OKX"),
+    ];
+
+    RwLock::new(known.into_iter().map(|(a, name)| (a, name.to_string())).collect())
+});
+
+/// Label more addresses as belonging to an exchange, e.g. the wallet's own
+/// deposit addresses
+pub fn register(addresses: &HashMap<Address, String>) {
+    EXCHANGES.write().unwrap().extend(addresses.clone());
+}
+
+pub fn all() -> HashMap<Address, String> {
+    EXCHANGES.read().unwrap().clone()
+}
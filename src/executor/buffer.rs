@@ -0,0 +1,60 @@
+use {std::time::Duration, tokio::time::Instant};
+
+/// Rows buffered per table of a target. The oldest are dropped past it while
+/// flushes keep failing
+pub const MAX_BUFFERED_ROWS: usize = 100_000;
+
+/// When the rows of a target were last flushed, and how many flushes failed
+/// in a row since
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Flushes {
+    last: Option<Instant>,
+    failures: u32,
+}
+
+impl Flushes {
+    /// Whether the next flush is due, `interval` after the last one, doubled
+    /// after each failure up to `max_backoff`
+    pub fn is_due(&self, interval: Duration, max_backoff: Duration, now: Instant) -> bool {
+        let wait = match self.failures {
+            0 => interval,
+            failures => interval
+                .saturating_mul(1 << failures.min(16))
+                .min(max_backoff)
+                .max(interval),
+        };
+
+        self.last.map(|t| now.duration_since(t) >= wait).unwrap_or(true)
+    }
+
+    /// These flushes with one started at `now`
+    pub fn started(self, now: Instant) -> Self {
+        Self {
+            last: Some(now),
+            ..self
+        }
+    }
+
+    pub fn succeeded(&mut self) {
+        self.failures = 0;
+    }
+
+    pub fn failed(&mut self) {
+        self.failures += 1;
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+}
+
+/// Put the rows of a failed flush back ahead of the ones buffered since, and
+/// drop the oldest past [`MAX_BUFFERED_ROWS`]. Returns how many were dropped
+pub fn requeue(rows: &mut Vec<String>, mut failed: Vec<String>) -> usize {
+    failed.append(rows);
+    *rows = failed;
+
+    let excess = rows.len().saturating_sub(MAX_BUFFERED_ROWS);
+    rows.drain(..excess);
+    excess
+}
@@ -0,0 +1,275 @@
+use {
+    super::{
+        buffer::{self, Flushes},
+        Action, ReportEvent,
+    },
+    crate::config::ClickhouseTarget,
+    alloy::primitives::Address,
+    burberry::Executor,
+    eyre::Context,
+    serde::Serialize,
+    std::{collections::HashMap, sync::Arc, time::Duration},
+    tokio::{sync::Mutex, time::Instant},
+    tracing::{debug, error, warn},
+};
+
+/// Longest wait between two attempts of a failing insert, doubled from the
+/// flush interval after each failure
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(300);
+
+type Batches = Arc<Mutex<HashMap<ClickhouseTarget, Batch>>>;
+
+/// Buffers reports per target and inserts them into ClickHouse as
+/// `JSONEachRow` every `flush_interval` seconds. Rows of a failed insert are
+/// kept and inserted again, backing off while it keeps failing.
+pub struct ClickhouseExporter {
+    batches: Batches,
+}
+
+impl ClickhouseExporter {
+    pub fn new() -> Self {
+        let batches = Batches::default();
+
+        tokio::spawn(flush_loop(reqwest::Client::new(), Arc::clone(&batches)));
+
+        Self { batches }
+    }
+}
+
+#[burberry::async_trait]
+impl Executor<Action> for ClickhouseExporter {
+    async fn execute(&self, action: Action) -> eyre::Result<()> {
+        let Action::Clickhouse(target, event) = action else {
+            return Ok(());
+        };
+
+        let mut batches = self.batches.lock().await;
+        batches.entry(target).or_default().push(&event)
+    }
+}
+
+#[derive(Default)]
+struct Batch {
+    reports: Vec<String>,
+    token_changes: Vec<String>,
+    flushes: Flushes,
+}
+
+impl Batch {
+    fn push(&mut self, event: &ReportEvent) -> eyre::Result<()> {
+        let report = &event.report;
+
+        self.reports.push(serde_json::to_string(&ReportRow {
+            chain: event.chain,
+            block: event.block,
+            timestamp: event.timestamp,
+            wallet: &event.wallet,
+            address: event.address,
+            txs: report
+                .txs
+                .iter()
+                .map(|tx| format!("{}:{}", tx.hash, tx.index))
+                .collect(),
+            pnl: report.pnl.to_string(),
+            builder_reward: report.builder_reward.to_string(),
+            validator_bribe: report.validator_bribe.to_string(),
+        })?);
+
+        for (token, change) in report.token_changes.iter() {
+            self.token_changes.push(serde_json::to_string(&TokenChangeRow {
+                chain: event.chain,
+                block: event.block,
+                timestamp: event.timestamp,
+                wallet: &event.wallet,
+                address: event.address,
+                token: *token,
+                change: change.to_string(),
+            })?);
+        }
+
+        Ok(())
+    }
+
+    fn is_due(&self, target: &ClickhouseTarget, now: Instant) -> bool {
+        if self.reports.is_empty() && self.token_changes.is_empty() {
+            return false;
+        }
+
+        self.flushes
+            .is_due(Duration::from_secs(target.flush_interval), MAX_RETRY_BACKOFF, now)
+    }
+
+    fn take(&mut self, now: Instant) -> Batch {
+        std::mem::replace(
+            self,
+            Batch {
+                flushes: self.flushes.started(now),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Put back the rows of `failed` that weren't inserted, ahead of the ones
+    /// buffered since
+    fn requeue(&mut self, target: &ClickhouseTarget, failed: Batch) {
+        self.flushes.failed();
+
+        for (table, rows, failed) in [
+            (&target.reports_table, &mut self.reports, failed.reports),
+            (
+                &target.token_changes_table,
+                &mut self.token_changes,
+                failed.token_changes,
+            ),
+        ] {
+            let dropped = buffer::requeue(rows, failed);
+            if dropped > 0 {
+                error!(url = %target.url, %table, dropped, "Too many rows buffered, dropped the oldest");
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ReportRow<'a> {
+    chain: u64,
+    block: u64,
+    timestamp: u64,
+    wallet: &'a str,
+    address: Address,
+    txs: Vec<String>,
+    pnl: String,
+    builder_reward: String,
+    validator_bribe: String,
+}
+
+#[derive(Serialize)]
+struct TokenChangeRow<'a> {
+    chain: u64,
+    block: u64,
+    timestamp: u64,
+    wallet: &'a str,
+    address: Address,
+    token: Address,
+    change: String,
+}
+
+async fn flush_loop(client: reqwest::Client, batches: Batches) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        ticker.tick().await;
+
+        let now = Instant::now();
+        let due = {
+            let mut batches = batches.lock().await;
+            batches
+                .iter_mut()
+                .filter(|(target, batch)| batch.is_due(target, now))
+                .map(|(target, batch)| (target.clone(), batch.take(now)))
+                .collect::<Vec<_>>()
+        };
+
+        for (target, mut batch) in due {
+            // Reports that went in aren't inserted again when only their
+            // token changes failed
+            let result = match insert(&client, &target, &target.reports_table, &batch.reports).await {
+                Ok(()) => {
+                    let reports = std::mem::take(&mut batch.reports).len();
+                    insert(&client, &target, &target.token_changes_table, &batch.token_changes)
+                        .await
+                        .map(|()| reports)
+                }
+                Err(err) => Err(err),
+            };
+
+            let mut batches = batches.lock().await;
+            let buffered = batches.entry(target.clone()).or_default();
+            match result {
+                Ok(rows) => {
+                    buffered.flushes.succeeded();
+                    debug!(url = %target.url, rows, "Exported reports to ClickHouse");
+                }
+                Err(err) => {
+                    buffered.requeue(&target, batch);
+                    warn!(
+                        url = %target.url,
+                        failures = buffered.flushes.failures(),
+                        reports = buffered.reports.len(),
+                        token_changes = buffered.token_changes.len(),
+                        "Failed to export reports to ClickHouse, retrying: {err:#}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+async fn insert(client: &reqwest::Client, target: &ClickhouseTarget, table: &str, rows: &[String]) -> eyre::Result<()> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    let query = format!("INSERT INTO {} FORMAT JSONEachRow", qualified_table(target, table));
+
+    request(client, target)
+        .query(&[("query", query)])
+        .body(rows.join("\n"))
+        .send()
+        .await
+        .context("Failed to send insert request")?
+        .error_for_status()
+        .with_context(|| format!("Insert into {table} was rejected"))?;
+
+    Ok(())
+}
+
+/// `table` of the target's database, quoted so that names needn't be plain
+/// identifiers
+pub fn qualified_table(target: &ClickhouseTarget, table: &str) -> String {
+    format!("{}.{}", quote(&target.database), quote(table))
+}
+
+fn quote(identifier: &str) -> String {
+    format!("`{}`", identifier.replace('\\', "\\\\").replace('`', "\\`"))
+}
+
+/// Run a read query, with `{name:Type}` placeholders bound from `params`, and
+/// return the response body
+pub async fn query(
+    client: &reqwest::Client,
+    target: &ClickhouseTarget,
+    sql: &str,
+    params: &[(&str, String)],
+) -> eyre::Result<String> {
+    let params = params
+        .iter()
+        .map(|(name, value)| (format!("param_{name}"), value.clone()))
+        .collect::<Vec<_>>();
+
+    request(client, target)
+        .query(&params)
+        .body(sql.to_string())
+        .send()
+        .await
+        .context("Failed to send query")?
+        .error_for_status()
+        .context("Query was rejected")?
+        .text()
+        .await
+        .context("Failed to read query result")
+}
+
+fn request(client: &reqwest::Client, target: &ClickhouseTarget) -> reqwest::RequestBuilder {
+    let mut request = client.post(&target.url);
+
+    if let Some(user) = &target.user {
+        request = request.header("X-ClickHouse-User", user);
+    }
+
+    if let Some(password) = &target.password {
+        request = request.header("X-ClickHouse-Key", password);
+    }
+
+    request
+}
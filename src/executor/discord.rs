@@ -0,0 +1,50 @@
+use {
+    super::{split_at_lines, Action},
+    burberry::Executor,
+    eyre::Context,
+    serde_json::json,
+};
+
+/// Longest message content Discord accepts
+const MAX_CONTENT_LEN: usize = 2000;
+
+/// Posts messages through a Discord incoming webhook. Messages too long for
+/// one post are split at line breaks.
+#[derive(Default)]
+pub struct DiscordExecutor {
+    client: reqwest::Client,
+}
+
+#[burberry::async_trait]
+impl Executor<Action> for DiscordExecutor {
+    async fn execute(&self, action: Action) -> eyre::Result<()> {
+        let Action::Discord(target, message) = action else {
+            return Ok(());
+        };
+
+        let mut url = reqwest::Url::parse(&target.webhook_url).context("Invalid Discord webhook URL")?;
+        if let Some(thread_id) = &target.thread_id {
+            url.query_pairs_mut().append_pair("thread_id", thread_id);
+        }
+
+        for content in split_at_lines(&message, MAX_CONTENT_LEN) {
+            // Addresses and symbols are never meant to ping anyone
+            let body = json!({
+                "content": content,
+                "allowed_mentions": { "parse": [] },
+            });
+
+            self.client
+                .post(url.clone())
+                .body(body.to_string())
+                .header("Content-Type", "application/json")
+                .send()
+                .await
+                .context("Failed to send Discord message")?
+                .error_for_status()
+                .context("Discord message was rejected")?;
+        }
+
+        Ok(())
+    }
+}
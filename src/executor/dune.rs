@@ -0,0 +1,419 @@
+use {
+    super::{
+        buffer::{self, Flushes},
+        Action, ReportEvent,
+    },
+    crate::config::DuneTarget,
+    alloy::primitives::{Address, B256, U256},
+    burberry::Executor,
+    chrono::DateTime,
+    eyre::{ensure, Context},
+    serde::Serialize,
+    std::{
+        collections::{HashMap, HashSet},
+        path::Path,
+        sync::Arc,
+        time::Duration,
+    },
+    tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::Mutex, time::Instant},
+    tracing::{debug, error, warn},
+};
+
+/// Longest wait between two attempts of a failing flush, doubled from the
+/// flush interval after each failure
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(6 * 3600);
+
+const API_URL: &str = "https://api.dune.com/api/v1/table";
+
+const REPORT_COLUMNS: &[(&str, &str)] = &[
+    ("chain", "integer"),
+    ("block_number", "integer"),
+    ("block_time", "timestamp"),
+    ("wallet", "varchar"),
+    ("address", "varbinary"),
+    ("tx_hashes", "varchar"),
+    ("pnl", "int256"),
+    ("stable_pnl", "int256"),
+    ("builder_reward", "uint256"),
+    ("validator_bribe", "uint256"),
+];
+
+const TRANSFER_COLUMNS: &[(&str, &str)] = &[
+    ("chain", "integer"),
+    ("block_number", "integer"),
+    ("block_time", "timestamp"),
+    ("wallet", "varchar"),
+    ("address", "varbinary"),
+    ("tx_hash", "varbinary"),
+    ("token", "varbinary"),
+    ("from", "varbinary"),
+    ("to", "varbinary"),
+    ("value", "uint256"),
+];
+
+/// A transfer from or to a wallet in one of the txs of its report
+#[derive(Debug, Clone)]
+pub struct WalletTransfer {
+    pub tx: B256,
+    pub token: Address,
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+}
+
+type Batches = Arc<Mutex<HashMap<DuneTarget, Batch>>>;
+
+/// Buffers report and transfer rows per target as CSV, and uploads them to
+/// Dune or appends them to files every `flush_interval` seconds. Rows that
+/// failed to go out are kept for the next attempt. One exporter is shared by
+/// every chain, so its flush loop is the only writer of the CSV files
+pub struct DuneExporter {
+    batches: Batches,
+}
+
+impl DuneExporter {
+    pub fn new() -> Self {
+        let batches = Batches::default();
+
+        tokio::spawn(flush_loop(reqwest::Client::new(), Arc::clone(&batches)));
+
+        Self { batches }
+    }
+}
+
+#[burberry::async_trait]
+impl Executor<Action> for DuneExporter {
+    async fn execute(&self, action: Action) -> eyre::Result<()> {
+        let Action::Dune(target, event, transfers) = action else {
+            return Ok(());
+        };
+
+        let mut batches = self.batches.lock().await;
+        batches
+            .entry(target.clone())
+            .or_default()
+            .push(&target, &event, &transfers);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Batch {
+    /// Rows waiting to be appended to `csv_dir`
+    csv: Rows,
+    /// Rows waiting to be uploaded to Dune
+    upload: Rows,
+    flushes: Flushes,
+}
+
+#[derive(Default)]
+struct Rows {
+    reports: Vec<String>,
+    transfers: Vec<String>,
+}
+
+impl Rows {
+    fn is_empty(&self) -> bool {
+        self.reports.is_empty() && self.transfers.is_empty()
+    }
+
+    fn extend(&mut self, reports: &[String], transfers: &[String]) {
+        self.reports.extend_from_slice(reports);
+        self.transfers.extend_from_slice(transfers);
+    }
+
+    /// Put `failed` back ahead of the rows buffered since
+    fn requeue(&mut self, failed: Rows) {
+        for (rows, failed) in [
+            (&mut self.reports, failed.reports),
+            (&mut self.transfers, failed.transfers),
+        ] {
+            let dropped = buffer::requeue(rows, failed);
+            if dropped > 0 {
+                error!(dropped, "Too many Dune rows buffered, dropped the oldest");
+            }
+        }
+    }
+}
+
+impl Batch {
+    fn push(&mut self, target: &DuneTarget, event: &ReportEvent, transfers: &[WalletTransfer]) {
+        let report = &event.report;
+        let block_time = DateTime::from_timestamp(event.timestamp as i64, 0)
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+
+        let reports = [csv_row(&[
+            event.chain.to_string(),
+            event.block.to_string(),
+            block_time.clone(),
+            event.wallet.clone(),
+            event.address.to_string(),
+            report
+                .txs
+                .iter()
+                .map(|tx| tx.hash.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+            report.pnl.to_string(),
+            report.stable_pnl.to_string(),
+            report.builder_reward.to_string(),
+            report.validator_bribe.to_string(),
+        ])];
+
+        let transfers = transfers
+            .iter()
+            .map(|transfer| {
+                csv_row(&[
+                    event.chain.to_string(),
+                    event.block.to_string(),
+                    block_time.clone(),
+                    event.wallet.clone(),
+                    event.address.to_string(),
+                    transfer.tx.to_string(),
+                    transfer.token.to_string(),
+                    transfer.from.to_string(),
+                    transfer.to.to_string(),
+                    transfer.value.to_string(),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        if target.csv_dir.is_some() {
+            self.csv.extend(&reports, &transfers);
+        }
+        if target.api_key.is_some() && target.namespace.is_some() {
+            self.upload.extend(&reports, &transfers);
+        }
+    }
+
+    fn is_due(&self, target: &DuneTarget, now: Instant) -> bool {
+        if self.csv.is_empty() && self.upload.is_empty() {
+            return false;
+        }
+
+        self.flushes
+            .is_due(Duration::from_secs(target.flush_interval), MAX_RETRY_BACKOFF, now)
+    }
+
+    fn take(&mut self, now: Instant) -> Batch {
+        std::mem::replace(
+            self,
+            Batch {
+                flushes: self.flushes.started(now),
+                ..Default::default()
+            },
+        )
+    }
+}
+
+/// A CSV line, quoting the fields that need it
+fn csv_row(fields: &[String]) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn csv_header(columns: &[(&str, &str)]) -> String {
+    columns.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(",")
+}
+
+async fn flush_loop(client: reqwest::Client, batches: Batches) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(1));
+    // Tables known to exist, by namespace and name
+    let mut created = HashSet::new();
+
+    loop {
+        ticker.tick().await;
+
+        let now = Instant::now();
+        let due = {
+            let mut batches = batches.lock().await;
+            batches
+                .iter_mut()
+                .filter(|(target, batch)| batch.is_due(target, now))
+                .map(|(target, batch)| (target.clone(), batch.take(now)))
+                .collect::<Vec<_>>()
+        };
+
+        for (target, mut batch) in due {
+            if let Some(dir) = &target.csv_dir {
+                for (table, columns, rows) in [
+                    (&target.reports_table, REPORT_COLUMNS, &mut batch.csv.reports),
+                    (&target.transfers_table, TRANSFER_COLUMNS, &mut batch.csv.transfers),
+                ] {
+                    if rows.is_empty() {
+                        continue;
+                    }
+
+                    match append_csv(Path::new(dir), table, columns, rows).await {
+                        Ok(()) => {
+                            debug!(table, rows = rows.len(), "Appended rows to CSV");
+                            rows.clear();
+                        }
+                        Err(err) => warn!(table, "Failed to append rows to CSV, retrying: {err:#}"),
+                    }
+                }
+            }
+
+            if let (Some(api_key), Some(namespace)) = (&target.api_key, &target.namespace) {
+                for (table, columns, rows) in [
+                    (&target.reports_table, REPORT_COLUMNS, &mut batch.upload.reports),
+                    (&target.transfers_table, TRANSFER_COLUMNS, &mut batch.upload.transfers),
+                ] {
+                    if rows.is_empty() {
+                        continue;
+                    }
+
+                    let result = async {
+                        if !created.contains(&(namespace.clone(), table.clone())) {
+                            create_table(&client, api_key, namespace, table, columns, target.private).await?;
+                            created.insert((namespace.clone(), table.clone()));
+                        }
+                        insert(&client, api_key, namespace, table, columns, rows).await
+                    }
+                    .await;
+
+                    match result {
+                        Ok(()) => {
+                            debug!(table, rows = rows.len(), "Uploaded rows to Dune");
+                            rows.clear();
+                        }
+                        Err(err) => warn!(table, "Failed to upload rows to Dune, retrying: {err:#}"),
+                    }
+                }
+            }
+
+            let mut batches = batches.lock().await;
+            let buffered = batches.entry(target).or_default();
+            if batch.csv.is_empty() && batch.upload.is_empty() {
+                buffered.flushes.succeeded();
+            } else {
+                buffered.flushes.failed();
+                buffered.csv.requeue(batch.csv);
+                buffered.upload.requeue(batch.upload);
+            }
+        }
+    }
+}
+
+/// Append `rows` to `<table>.csv` in `dir` in a single write, starting the
+/// file with the header when it is empty
+async fn append_csv(dir: &Path, table: &str, columns: &[(&str, &str)], rows: &[String]) -> eyre::Result<()> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let path = dir.join(format!("{table}.csv"));
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    let is_new = file.metadata().await?.len() == 0;
+    let mut content = String::new();
+    if is_new {
+        content.push_str(&csv_header(columns));
+        content.push('\n');
+    }
+    for row in rows {
+        content.push_str(row);
+        content.push('\n');
+    }
+
+    file.write_all(content.as_bytes())
+        .await
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    file.flush().await?;
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct CreateTable<'a> {
+    namespace: &'a str,
+    table_name: &'a str,
+    description: &'static str,
+    is_private: bool,
+    schema: Vec<Column<'a>>,
+}
+
+#[derive(Serialize)]
+struct Column<'a> {
+    name: &'a str,
+    #[serde(rename = "type")]
+    typ: &'a str,
+}
+
+/// Create the table unless it exists already
+async fn create_table(
+    client: &reqwest::Client,
+    api_key: &str,
+    namespace: &str,
+    table: &str,
+    columns: &[(&str, &str)],
+    private: bool,
+) -> eyre::Result<()> {
+    let body = CreateTable {
+        namespace,
+        table_name: table,
+        description: "Exported by wallet-watcher",
+        is_private: private,
+        schema: columns.iter().map(|(name, typ)| Column { name, typ }).collect(),
+    };
+
+    let response = client
+        .post(format!("{API_URL}/create"))
+        .header("X-DUNE-API-KEY", api_key)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(&body)?)
+        .send()
+        .await
+        .context("Failed to send create request")?;
+
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+    ensure!(
+        status.is_success() || text.contains("already exist"),
+        "Creating {namespace}.{table} was rejected with {status}: {text}"
+    );
+
+    Ok(())
+}
+
+async fn insert(
+    client: &reqwest::Client,
+    api_key: &str,
+    namespace: &str,
+    table: &str,
+    columns: &[(&str, &str)],
+    rows: &[String],
+) -> eyre::Result<()> {
+    let body = std::iter::once(csv_header(columns))
+        .chain(rows.iter().cloned())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    client
+        .post(format!("{API_URL}/{namespace}/{table}/insert"))
+        .header("X-DUNE-API-KEY", api_key)
+        .header("Content-Type", "text/csv")
+        .body(body)
+        .send()
+        .await
+        .context("Failed to send insert request")?
+        .error_for_status()
+        .with_context(|| format!("Insert into {namespace}.{table} was rejected"))?;
+
+    Ok(())
+}
@@ -0,0 +1,302 @@
+use {
+    crate::{
+        cluster::{ClusterAlert, ClusterTrigger},
+        config::{
+            ClickhouseTarget, DiscordTarget, DuneTarget, MqttTarget, NatsTarget, PushTarget, RedisTarget, SignalTarget,
+            SlackTarget, TelegramTarget, TwilioTarget, WalletWithContext, WebhookTarget,
+        },
+        delivery::delivery_id,
+        event_watch::EventFields,
+        processor::{PnlReport, Transfer, TxAndPosition},
+        utils::{self, format::NumberFormat, U256AsDecimalStr},
+    },
+    alloy::{
+        primitives::{Address, TxHash, B256, I256, U256},
+        rpc::types::Block,
+    },
+    alloy_chains::Chain,
+    burberry::Executor,
+    schemars::JsonSchema,
+    serde::Serialize,
+    serde_with::serde_as,
+    std::{collections::BTreeMap, sync::Arc},
+};
+
+mod buffer;
+pub mod clickhouse;
+pub mod discord;
+pub mod dune;
+pub mod mqtt;
+pub mod nats;
+pub mod push;
+pub mod redis;
+pub mod signal;
+pub mod slack;
+pub mod telegram;
+pub mod twilio;
+pub mod webhook;
+
+/// An executor shared by the engines of every chain, so that connections,
+/// rate limits and buffers are per process rather than per chain
+pub struct Shared(Arc<dyn Executor<Action>>);
+
+impl Shared {
+    pub fn new(executor: Arc<dyn Executor<Action>>) -> Self {
+        Self(executor)
+    }
+}
+
+#[burberry::async_trait]
+impl Executor<Action> for Shared {
+    async fn execute(&self, action: Action) -> eyre::Result<()> {
+        self.0.execute(action).await
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Action {
+    Telegram(TelegramTarget, String),
+    /// A PNG with a MarkdownV2 caption, which may be empty
+    TelegramPhoto(TelegramTarget, Vec<u8>, String),
+    Clickhouse(ClickhouseTarget, ReportEvent),
+    Nats(NatsTarget, ReportEvent),
+    NatsAlert(NatsTarget, AlertEvent),
+    Redis(RedisTarget, ReportEvent),
+    RedisAlert(RedisTarget, AlertEvent),
+    Mqtt(MqttTarget, ReportEvent),
+    Signal(SignalTarget, String),
+    Twilio(TwilioTarget, String),
+    Discord(DiscordTarget, String),
+    Slack(SlackTarget, String),
+    Push(PushTarget, ReportEvent),
+    Webhook(WebhookTarget, String),
+    Dune(DuneTarget, ReportEvent, Vec<dune::WalletTransfer>),
+}
+
+impl Action {
+    /// Output, recipients and text of a notification. Actions carrying a
+    /// report rather than a rendered text have none.
+    pub fn notification(&self) -> Option<(&'static str, String, String)> {
+        match self {
+            Action::Telegram(target, text) | Action::TelegramPhoto(target, _, text) => {
+                let chat = match &target.thread_id {
+                    Some(thread_id) => format!("{}/{thread_id}", target.chat_id),
+                    None => target.chat_id.clone(),
+                };
+                Some(("telegram", chat, text.clone()))
+            }
+            Action::Signal(target, text) => Some(("signal", target.recipients.join(","), text.clone())),
+            Action::Twilio(target, text) => Some(("twilio", target.to.join(","), text.clone())),
+            Action::Slack(target, text) => Some(("slack", target.channel_id.clone(), text.clone())),
+            // Webhook URLs carry their token, so only the thread is kept
+            Action::Discord(target, text) => {
+                Some(("discord", target.thread_id.clone().unwrap_or_default(), text.clone()))
+            }
+            // Webhook URLs often carry a secret, so only the host is kept
+            Action::Webhook(target, text) => {
+                let host = reqwest::Url::parse(&target.url)
+                    .ok()
+                    .and_then(|url| url.host_str().map(str::to_string))
+                    .unwrap_or_default();
+                Some(("webhook", host, text.clone()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A report together with the block and wallet it belongs to. This is what
+/// non-chat outputs receive instead of a rendered message, and its JSON form
+/// is the payload published to message buses.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ReportEvent {
+    /// Same for every delivery of the report, see [`delivery_id`]
+    #[schemars(with = "String")]
+    pub id: B256,
+    pub chain: u64,
+    pub block: u64,
+    pub timestamp: u64,
+    pub wallet: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short_name: Option<String>,
+    #[schemars(with = "String")]
+    pub address: Address,
+
+    #[serde(flatten)]
+    pub report: PnlReport,
+
+    /// Of the wallet's channel, for outputs writing amounts as text
+    #[serde(skip)]
+    pub number_format: NumberFormat,
+
+    /// Of the chain's native token, for outputs writing amounts as text
+    #[serde(skip)]
+    pub native_decimals: u8,
+}
+
+impl ReportEvent {
+    pub fn new(chain: Chain, block: &Block, wallet: &WalletWithContext, report: PnlReport) -> Self {
+        Self {
+            id: delivery_id(chain, block.header.hash, wallet.address),
+            chain: chain.id(),
+            block: block.header.number,
+            timestamp: block.header.timestamp,
+            wallet: wallet.name.clone(),
+            emoji: wallet.emoji.clone(),
+            short_name: wallet.short_name.clone(),
+            address: wallet.address,
+            report,
+            number_format: wallet.number_format,
+            native_decimals: 18,
+        }
+    }
+
+    pub fn with_native_decimals(mut self, decimals: u8) -> Self {
+        self.native_decimals = decimals;
+        self
+    }
+
+    /// The short name, or the name, after the emoji
+    pub fn display_name(&self) -> String {
+        utils::display_name(
+            self.emoji.as_deref(),
+            self.short_name.as_deref().unwrap_or(&self.wallet),
+        )
+    }
+}
+
+/// A watch alert together with the block it fired in. It is what message
+/// buses receive instead of a rendered alert, next to the [`ReportEvent`]s of
+/// the channel's wallets
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct AlertEvent {
+    pub chain: u64,
+    pub block: u64,
+    pub timestamp: u64,
+    /// Name of the watch
+    pub watch: String,
+
+    #[serde(flatten)]
+    pub alert: Alert,
+}
+
+impl AlertEvent {
+    pub fn new(chain: Chain, block: &Block, watch: &str, alert: Alert) -> Self {
+        Self {
+            chain: chain.id(),
+            block: block.header.number,
+            timestamp: block.header.timestamp,
+            watch: watch.to_string(),
+            alert,
+        }
+    }
+}
+
+/// What a watch alerts on, tagged by `kind`
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Alert {
+    /// Transfers of a watched token above its threshold
+    TokenTransfers { transfers: Vec<AlertTransfer> },
+    /// Transfers above the thresholds of a large transfer watch
+    LargeTransfers { transfers: Vec<AlertTransfer> },
+    /// Emissions of a watched event. The ownership, admin and role changes of
+    /// a watched contract are of high severity
+    Events {
+        #[schemars(with = "String")]
+        address: Address,
+        event: String,
+        high_severity: bool,
+        emissions: Vec<Emission>,
+    },
+    /// The wallets of a cluster together crossed a threshold within its
+    /// window, in native units
+    Cluster {
+        trigger: ClusterTrigger,
+        #[schemars(with = "String")]
+        total: I256,
+        #[schemars(with = "BTreeMap<String, String>")]
+        by_wallet: BTreeMap<String, I256>,
+    },
+}
+
+impl Alert {
+    pub fn cluster(alert: &ClusterAlert) -> Self {
+        Self::Cluster {
+            trigger: alert.trigger,
+            total: alert.total,
+            by_wallet: alert.by_wallet.clone(),
+        }
+    }
+}
+
+/// A transfer of a token, or of the native token as the zero address
+#[serde_as]
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct AlertTransfer {
+    #[schemars(with = "String")]
+    pub tx: TxHash,
+    #[schemars(with = "String")]
+    pub token: Address,
+    #[schemars(with = "String")]
+    pub from: Address,
+    #[schemars(with = "String")]
+    pub to: Address,
+    #[serde_as(as = "U256AsDecimalStr")]
+    #[schemars(with = "String")]
+    pub value: U256,
+}
+
+impl From<&(TxAndPosition, Transfer)> for AlertTransfer {
+    fn from((tx, transfer): &(TxAndPosition, Transfer)) -> Self {
+        Self {
+            tx: tx.hash,
+            token: transfer.token,
+            from: transfer.from,
+            to: transfer.to,
+            value: transfer.value,
+        }
+    }
+}
+
+/// An emission of a watched event, with its decoded fields by name
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Emission {
+    #[schemars(with = "String")]
+    pub tx: TxHash,
+    pub fields: BTreeMap<String, String>,
+}
+
+impl From<&(TxAndPosition, EventFields)> for Emission {
+    fn from((tx, fields): &(TxAndPosition, EventFields)) -> Self {
+        Self {
+            tx: tx.hash,
+            fields: fields.iter().cloned().collect(),
+        }
+    }
+}
+
+/// `message` in parts of at most `max_len` characters, cut after a line
+/// break where there is one
+fn split_at_lines(message: &str, max_len: usize) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut rest = message;
+
+    while rest.chars().count() > max_len {
+        let limit = rest.char_indices().nth(max_len).map_or(rest.len(), |(i, _)| i);
+        let end = match rest[..limit].rfind('\n') {
+            Some(newline) if newline > 0 => newline + 1,
+            _ => limit,
+        };
+
+        parts.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    if !rest.is_empty() {
+        parts.push(rest);
+    }
+
+    parts
+}
@@ -0,0 +1,115 @@
+use {
+    super::{Action, ReportEvent},
+    crate::config::MqttTarget,
+    burberry::Executor,
+    eyre::Context,
+    rumqttc::{AsyncClient, MqttOptions, QoS},
+    schemars::JsonSchema,
+    serde::Serialize,
+    std::{
+        collections::{hash_map::Entry, HashMap},
+        time::Duration,
+    },
+    tokio::sync::Mutex,
+    tracing::warn,
+};
+
+/// Publishes a compact summary of each report to
+/// `<topic_prefix>/<chain>/<wallet>`, small enough for home automation and
+/// embedded displays. Summaries are dropped rather than queued up while the
+/// broker is unreachable.
+#[derive(Default)]
+pub struct MqttPublisher {
+    clients: Mutex<HashMap<MqttTarget, AsyncClient>>,
+}
+
+impl MqttPublisher {
+    async fn client(&self, target: &MqttTarget) -> AsyncClient {
+        let mut clients = self.clients.lock().await;
+
+        match clients.entry(target.clone()) {
+            Entry::Occupied(e) => e.get().clone(),
+            Entry::Vacant(e) => e.insert(connect(target)).clone(),
+        }
+    }
+}
+
+#[burberry::async_trait]
+impl Executor<Action> for MqttPublisher {
+    async fn execute(&self, action: Action) -> eyre::Result<()> {
+        let Action::Mqtt(target, event) = action else {
+            return Ok(());
+        };
+
+        let topic = format!("{}/{}/{}", target.topic_prefix, event.chain, topic_level(&event.wallet));
+        let payload = serde_json::to_vec(&Summary::from(&event)).context("Failed to serialize summary")?;
+
+        // Waiting for room in the queue would hold up every other output
+        if let Err(err) = self
+            .client(&target)
+            .await
+            .try_publish(&topic, QoS::AtLeastOnce, false, payload)
+        {
+            warn!(%topic, "Dropped MQTT summary: {err}");
+        }
+
+        Ok(())
+    }
+}
+
+/// `name` as a single topic level, with the level separator and wildcards
+/// replaced
+fn topic_level(name: &str) -> String {
+    name.replace(['/', '+', '#'], "_")
+}
+
+/// Payload of an MQTT message
+#[derive(Serialize, JsonSchema)]
+pub struct Summary {
+    block: u64,
+    timestamp: u64,
+    pnl: String,
+    txs: usize,
+    tokens: usize,
+    builder: bool,
+}
+
+impl From<&ReportEvent> for Summary {
+    fn from(event: &ReportEvent) -> Self {
+        Self {
+            block: event.block,
+            timestamp: event.timestamp,
+            pnl: event
+                .number_format
+                .signed_native(&event.report.pnl, event.native_decimals),
+            txs: event.report.txs.len(),
+            tokens: event.report.token_changes.len(),
+            builder: !event.report.builder_reward.is_zero(),
+        }
+    }
+}
+
+fn connect(target: &MqttTarget) -> AsyncClient {
+    let mut options = MqttOptions::new(&target.client_id, &target.host, target.port);
+    options.set_keep_alive(Duration::from_secs(30));
+
+    if let Some(username) = &target.username {
+        options.set_credentials(username, target.password.clone().unwrap_or_default());
+    }
+
+    let (client, mut event_loop) = AsyncClient::new(options, 64);
+
+    // The event loop drives the connection and reconnects on the next poll
+    // after an error.
+    let host = target.host.clone();
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = event_loop.poll().await {
+                warn!(%host, "MQTT connection error: {err}");
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        }
+    });
+
+    client
+}
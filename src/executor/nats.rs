@@ -0,0 +1,63 @@
+use {
+    super::Action,
+    burberry::Executor,
+    eyre::Context,
+    std::collections::{hash_map::Entry, HashMap},
+    tokio::sync::Mutex,
+};
+
+/// Publishes reports and watch alerts as JSON to NATS subjects. Connections
+/// are opened on first use and shared by all channels pointing to the same
+/// server.
+#[derive(Default)]
+pub struct NatsPublisher {
+    clients: Mutex<HashMap<String, async_nats::Client>>,
+}
+
+impl NatsPublisher {
+    async fn client(&self, url: &str) -> eyre::Result<async_nats::Client> {
+        let mut clients = self.clients.lock().await;
+
+        match clients.entry(url.to_string()) {
+            Entry::Occupied(e) => Ok(e.get().clone()),
+            Entry::Vacant(e) => {
+                let client = async_nats::connect(url)
+                    .await
+                    .with_context(|| format!("Failed to connect to NATS server {url}"))?;
+
+                Ok(e.insert(client).clone())
+            }
+        }
+    }
+}
+
+#[burberry::async_trait]
+impl Executor<Action> for NatsPublisher {
+    async fn execute(&self, action: Action) -> eyre::Result<()> {
+        let (target, subject, payload) = match action {
+            Action::Nats(target, event) => {
+                let payload = serde_json::to_vec(&event).context("Failed to serialize report")?;
+                let subject = target.subject.clone();
+                (target, subject, payload)
+            }
+            Action::NatsAlert(target, event) => {
+                let payload = serde_json::to_vec(&event).context("Failed to serialize alert")?;
+                let subject = alert_subject(&target.subject);
+                (target, subject, payload)
+            }
+            _ => return Ok(()),
+        };
+
+        self.client(&target.url)
+            .await?
+            .publish(subject.clone(), payload.into())
+            .await
+            .with_context(|| format!("Failed to publish to {subject}"))
+    }
+}
+
+/// Subject the watch alerts of a channel are published to, under the one of
+/// its reports so that subscribers of either get one schema
+fn alert_subject(subject: &str) -> String {
+    format!("{subject}.alerts")
+}
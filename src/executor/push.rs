@@ -0,0 +1,103 @@
+use {
+    super::{Action, ReportEvent},
+    alloy::primitives::U256,
+    alloy_chains::Chain,
+    burberry::Executor,
+    eyre::Context,
+    schemars::JsonSchema,
+    serde::Serialize,
+};
+
+/// Posts reports to the companion push endpoint configured in
+/// [`PushTarget`](crate::config::PushTarget). Payload:
+///
+/// ```json
+/// {
+///   "title": "Jared: -1.5 ETH",
+///   "body": "ETH block 21213222, 2 txs",
+///   "tokens": { "fcm": ["..."], "apns": ["..."] },
+///   "report": { ...report payload... }
+/// }
+/// ```
+#[derive(Default)]
+pub struct PushExecutor {
+    client: reqwest::Client,
+}
+
+/// Payload posted to the push endpoint
+#[derive(Serialize, JsonSchema)]
+pub struct PushPayload {
+    pub title: String,
+    pub body: String,
+    pub tokens: PushTokens,
+    pub report: ReportEvent,
+}
+
+/// Device tokens to notify
+#[derive(Serialize, JsonSchema)]
+pub struct PushTokens {
+    pub fcm: Vec<String>,
+    pub apns: Vec<String>,
+}
+
+#[burberry::async_trait]
+impl Executor<Action> for PushExecutor {
+    async fn execute(&self, action: Action) -> eyre::Result<()> {
+        let Action::Push(target, event) = action else {
+            return Ok(());
+        };
+
+        let (sign, pnl) = event.report.pnl.into_sign_and_abs();
+        let min_pnl = U256::from((target.min_pnl * 1e18) as u128);
+        if pnl < min_pnl {
+            return Ok(());
+        }
+
+        let chain = Chain::from_id(event.chain);
+        let symbol = chain.named().and_then(|c| c.native_currency_symbol()).unwrap_or("ETH");
+
+        let payload = PushPayload {
+            title: format!(
+                "{}: {}{} {symbol}",
+                event.display_name(),
+                if sign.is_positive() { "+" } else { "-" },
+                event.number_format.native(&pnl, event.native_decimals),
+            ),
+            body: summary(chain, &event),
+            tokens: PushTokens {
+                fcm: target.fcm_tokens.clone(),
+                apns: target.apns_tokens.clone(),
+            },
+            report: event,
+        };
+
+        let mut request = self
+            .client
+            .post(&target.url)
+            .body(serde_json::to_string(&payload)?)
+            .header("Content-Type", "application/json");
+
+        if let Some(token) = &target.auth_token {
+            request = request.bearer_auth(token);
+        }
+
+        request
+            .send()
+            .await
+            .context("Failed to send push notification")?
+            .error_for_status()
+            .context("Push notification was rejected")?;
+
+        Ok(())
+    }
+}
+
+fn summary(chain: Chain, event: &ReportEvent) -> String {
+    format!(
+        "{chain} block {block}, {txs} tx{plural}",
+        chain = chain.to_string().to_uppercase(),
+        block = event.block,
+        txs = event.report.txs.len(),
+        plural = if event.report.txs.len() == 1 { "" } else { "s" },
+    )
+}
@@ -0,0 +1,66 @@
+use {
+    super::Action,
+    burberry::Executor,
+    eyre::Context,
+    redis::{aio::ConnectionManager, AsyncCommands},
+    std::collections::{hash_map::Entry, HashMap},
+    tokio::sync::Mutex,
+};
+
+/// Publishes reports and watch alerts as JSON to Redis pub/sub channels.
+#[derive(Default)]
+pub struct RedisPublisher {
+    connections: Mutex<HashMap<String, ConnectionManager>>,
+}
+
+impl RedisPublisher {
+    async fn connection(&self, url: &str) -> eyre::Result<ConnectionManager> {
+        let mut connections = self.connections.lock().await;
+
+        match connections.entry(url.to_string()) {
+            Entry::Occupied(e) => Ok(e.get().clone()),
+            Entry::Vacant(e) => Ok(e.insert(connect(url).await?).clone()),
+        }
+    }
+}
+
+#[burberry::async_trait]
+impl Executor<Action> for RedisPublisher {
+    async fn execute(&self, action: Action) -> eyre::Result<()> {
+        let (target, channel, payload) = match action {
+            Action::Redis(target, event) => {
+                let payload = serde_json::to_string(&event).context("Failed to serialize report")?;
+                let channel = target.channel.clone();
+                (target, channel, payload)
+            }
+            Action::RedisAlert(target, event) => {
+                let payload = serde_json::to_string(&event).context("Failed to serialize alert")?;
+                let channel = alert_channel(&target.channel);
+                (target, channel, payload)
+            }
+            _ => return Ok(()),
+        };
+
+        let mut conn = self.connection(&target.url).await?;
+        let _: () = conn
+            .publish(&channel, payload)
+            .await
+            .with_context(|| format!("Failed to publish to {channel}"))?;
+
+        Ok(())
+    }
+}
+
+/// Channel the watch alerts of a channel are published to, next to the one
+/// of its reports
+fn alert_channel(channel: &str) -> String {
+    format!("{channel}:alerts")
+}
+
+pub async fn connect(url: &str) -> eyre::Result<ConnectionManager> {
+    redis::Client::open(url)
+        .with_context(|| format!("Invalid redis url {url}"))?
+        .get_connection_manager()
+        .await
+        .with_context(|| format!("Failed to connect to redis {url}"))
+}
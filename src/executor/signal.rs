@@ -0,0 +1,34 @@
+use {super::Action, burberry::Executor, eyre::Context, serde_json::json};
+
+/// Sends messages through a [signal-cli REST API](https://github.com/bbernhard/signal-cli-rest-api) instance.
+#[derive(Default)]
+pub struct SignalExecutor {
+    client: reqwest::Client,
+}
+
+#[burberry::async_trait]
+impl Executor<Action> for SignalExecutor {
+    async fn execute(&self, action: Action) -> eyre::Result<()> {
+        let Action::Signal(target, message) = action else {
+            return Ok(());
+        };
+
+        let body = json!({
+            "message": message,
+            "number": target.number,
+            "recipients": target.recipients,
+        });
+
+        self.client
+            .post(format!("{}/v2/send", target.url.trim_end_matches('/')))
+            .body(body.to_string())
+            .header("Content-Type", "application/json")
+            .send()
+            .await
+            .context("Failed to send signal message")?
+            .error_for_status()
+            .context("Signal message was rejected")?;
+
+        Ok(())
+    }
+}
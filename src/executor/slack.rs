@@ -0,0 +1,75 @@
+use {
+    super::{split_at_lines, Action},
+    burberry::Executor,
+    eyre::{bail, Context},
+    serde::Deserialize,
+    serde_json::json,
+};
+
+/// Longest text of a section block
+const MAX_SECTION_LEN: usize = 3000;
+
+/// Most blocks in one message
+const MAX_BLOCKS: usize = 50;
+
+/// Posts messages with `chat.postMessage` as Block Kit sections of mrkdwn
+/// text, split at line breaks. The text is sent as the notification fallback
+/// too.
+#[derive(Default)]
+pub struct SlackExecutor {
+    client: reqwest::Client,
+}
+
+/// Slack answers 200 to most failures, with `ok` false
+#[derive(Deserialize)]
+struct SlackResponse {
+    ok: bool,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[burberry::async_trait]
+impl Executor<Action> for SlackExecutor {
+    async fn execute(&self, action: Action) -> eyre::Result<()> {
+        let Action::Slack(target, message) = action else {
+            return Ok(());
+        };
+
+        let sections = split_at_lines(&message, MAX_SECTION_LEN);
+        for sections in sections.chunks(MAX_BLOCKS) {
+            let blocks = sections
+                .iter()
+                .map(|text| json!({ "type": "section", "text": { "type": "mrkdwn", "text": text } }))
+                .collect::<Vec<_>>();
+
+            let body = json!({
+                "channel": target.channel_id,
+                "text": sections.concat(),
+                "blocks": blocks,
+                "unfurl_links": false,
+            });
+
+            let response = self
+                .client
+                .post("https://slack.com/api/chat.postMessage")
+                .bearer_auth(&target.bot_token)
+                .body(body.to_string())
+                .header("Content-Type", "application/json; charset=utf-8")
+                .send()
+                .await
+                .context("Failed to send Slack message")?
+                .error_for_status()
+                .context("Slack message was rejected")?
+                .text()
+                .await
+                .context("Failed to read Slack response")?;
+
+            let response: SlackResponse = serde_json::from_str(&response).context("Invalid Slack response")?;
+            if !response.ok {
+                bail!("Slack message was rejected: {}", response.error.unwrap_or_default());
+            }
+        }
+
+        Ok(())
+    }
+}
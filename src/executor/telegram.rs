@@ -0,0 +1,334 @@
+use {
+    super::{webhook::WebhookPayload, Action},
+    crate::config::{ErrorAlertTo, TelegramTarget},
+    burberry::{executor::telegram_message::escape, Executor},
+    eyre::{bail, Context},
+    reqwest::multipart::{Form, Part},
+    serde::Deserialize,
+    serde_json::json,
+    std::{
+        collections::HashMap,
+        sync::atomic::{AtomicU64, Ordering},
+        time::Duration,
+    },
+    tokio::{sync::Mutex, time::Instant},
+    tracing::{debug, info, warn},
+};
+
+/// Attempts of a message before it is given up on
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Telegram allows about one message per second in a chat
+const CHAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Sends MarkdownV2 messages and photos through the Bot API. Messages to a chat
+/// are spaced out, and a rate limited message waits for as long as Telegram
+/// asks before it is sent again. A message given up on is reported to the error
+/// channel.
+pub struct TelegramExecutor {
+    client: reqwest::Client,
+    /// When the next message may be sent, by chat
+    next_send: Mutex<HashMap<String, Instant>>,
+    error_channel: Option<ErrorAlertTo>,
+}
+
+/// Counters of the messages sent since start, logged with every failure, on
+/// [`log_stats_every`] and on the dashboard
+static STATS: SendStats = SendStats {
+    sent: AtomicU64::new(0),
+    failed: AtomicU64::new(0),
+    rate_limited: AtomicU64::new(0),
+    latency_ms: AtomicU64::new(0),
+};
+
+#[derive(Debug)]
+struct SendStats {
+    sent: AtomicU64,
+    failed: AtomicU64,
+    rate_limited: AtomicU64,
+    /// Sum over sent messages, to average
+    latency_ms: AtomicU64,
+}
+
+/// Telegram messages since start
+#[derive(Debug, Clone, Copy)]
+pub struct TelegramStats {
+    pub sent: u64,
+    pub failed: u64,
+    pub rate_limited: u64,
+    pub avg_latency_ms: u64,
+}
+
+impl std::fmt::Display for TelegramStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "sent {}, failed {}, rate limited {}, avg {}ms",
+            self.sent, self.failed, self.rate_limited, self.avg_latency_ms
+        )
+    }
+}
+
+pub fn stats() -> TelegramStats {
+    let sent = STATS.sent.load(Ordering::Relaxed);
+    TelegramStats {
+        sent,
+        failed: STATS.failed.load(Ordering::Relaxed),
+        rate_limited: STATS.rate_limited.load(Ordering::Relaxed),
+        avg_latency_ms: STATS
+            .latency_ms
+            .load(Ordering::Relaxed)
+            .checked_div(sent)
+            .unwrap_or_default(),
+    }
+}
+
+/// Log the telegram counters every `interval`, once a message was attempted
+pub async fn log_stats_every(interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+
+        let stats = stats();
+        if stats.sent + stats.failed + stats.rate_limited == 0 {
+            continue;
+        }
+
+        info!(
+            sent = stats.sent,
+            failed = stats.failed,
+            rate_limited = stats.rate_limited,
+            avg_latency_ms = stats.avg_latency_ms,
+            "Telegram messages since start"
+        );
+    }
+}
+
+/// Body of a Bot API error
+#[derive(Deserialize)]
+struct ApiError {
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    parameters: Option<ResponseParameters>,
+}
+
+#[derive(Deserialize)]
+struct ResponseParameters {
+    retry_after: Option<u64>,
+}
+
+/// What is sent to a chat
+#[derive(Clone, Copy)]
+enum Content<'a> {
+    Text(&'a str),
+    Photo { png: &'a [u8], caption: &'a str },
+}
+
+enum SendError {
+    RateLimited(Duration),
+    Other(eyre::Report),
+}
+
+impl TelegramExecutor {
+    pub fn new(error_channel: Option<ErrorAlertTo>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            next_send: Mutex::new(HashMap::new()),
+            error_channel,
+        }
+    }
+
+    async fn send_with_retry(&self, target: &TelegramTarget, content: Content<'_>) -> eyre::Result<()> {
+        let mut attempt = 1;
+
+        loop {
+            self.wait_for_chat(&target.chat_id).await;
+
+            let start = Instant::now();
+            match self.send(target, content).await {
+                Ok(()) => {
+                    let elapsed = start.elapsed();
+                    let sent = STATS.sent.fetch_add(1, Ordering::Relaxed) + 1;
+                    let latency_ms = STATS
+                        .latency_ms
+                        .fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed) +
+                        elapsed.as_millis() as u64;
+                    debug!(chat = %target.chat_id, ?elapsed, avg_ms = latency_ms / sent, "Sent telegram message");
+                    return Ok(());
+                }
+
+                Err(SendError::RateLimited(retry_after)) if attempt < MAX_ATTEMPTS => {
+                    STATS.rate_limited.fetch_add(1, Ordering::Relaxed);
+                    warn!(chat = %target.chat_id, attempt, ?retry_after, "Rate limited by telegram");
+                    self.delay_chat(&target.chat_id, retry_after).await;
+                }
+
+                Err(SendError::RateLimited(_)) => bail!("Still rate limited after {attempt} attempts"),
+                Err(SendError::Other(err)) => return Err(err),
+            }
+
+            attempt += 1;
+        }
+    }
+
+    async fn send(&self, target: &TelegramTarget, content: Content<'_>) -> Result<(), SendError> {
+        let request = match content {
+            Content::Text(text) => {
+                let mut body = json!({
+                    "chat_id": target.chat_id,
+                    "text": text,
+                    "parse_mode": "MarkdownV2",
+                    "link_preview_options": { "is_disabled": true },
+                });
+                if let Some(thread_id) = &target.thread_id {
+                    body["message_thread_id"] = thread_id.as_str().into();
+                }
+
+                self.client
+                    .post(format!("https://api.telegram.org/bot{}/sendMessage", target.bot_token))
+                    .body(body.to_string())
+                    .header("Content-Type", "application/json")
+            }
+            Content::Photo { png, caption } => {
+                let photo = Part::bytes(png.to_vec())
+                    .file_name("chart.png")
+                    .mime_str("image/png")
+                    .context("Invalid photo type")
+                    .map_err(SendError::Other)?;
+                let mut form = Form::new().text("chat_id", target.chat_id.clone()).part("photo", photo);
+                if !caption.is_empty() {
+                    form = form
+                        .text("caption", caption.to_string())
+                        .text("parse_mode", "MarkdownV2");
+                }
+                if let Some(thread_id) = &target.thread_id {
+                    form = form.text("message_thread_id", thread_id.clone());
+                }
+
+                self.client
+                    .post(format!("https://api.telegram.org/bot{}/sendPhoto", target.bot_token))
+                    .multipart(form)
+            }
+        };
+
+        let response = request
+            .send()
+            .await
+            .context("Failed to send telegram message")
+            .map_err(SendError::Other)?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let retry_after_header = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        let error = response.json::<ApiError>().await.ok();
+
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = error
+                .as_ref()
+                .and_then(|e| e.parameters.as_ref())
+                .and_then(|p| p.retry_after)
+                .or(retry_after_header)
+                .unwrap_or(1);
+            return Err(SendError::RateLimited(Duration::from_secs(retry_after)));
+        }
+
+        let description = error.map(|e| e.description).unwrap_or_default();
+        Err(SendError::Other(eyre::eyre!(
+            "Telegram message was rejected with {status}: {description}"
+        )))
+    }
+
+    /// Wait until the chat may get another message, and book the slot after
+    async fn wait_for_chat(&self, chat_id: &str) {
+        let wait_until = {
+            let mut next_send = self.next_send.lock().await;
+            let now = Instant::now();
+            let slot = next_send.get(chat_id).copied().filter(|t| *t > now).unwrap_or(now);
+            next_send.insert(chat_id.to_string(), slot + CHAT_INTERVAL);
+            slot
+        };
+
+        tokio::time::sleep_until(wait_until).await;
+    }
+
+    /// Hold off every message to the chat for `delay`
+    async fn delay_chat(&self, chat_id: &str, delay: Duration) {
+        let mut next_send = self.next_send.lock().await;
+        let until = Instant::now() + delay;
+        let slot = next_send.entry(chat_id.to_string()).or_insert(until);
+        *slot = (*slot).max(until);
+    }
+
+    /// Tell the operator a message was dropped. Not retried, so a broken error
+    /// channel doesn't pile up
+    async fn alert_dropped(&self, target: &TelegramTarget, err: &eyre::Report) {
+        let failed = STATS.failed.fetch_add(1, Ordering::Relaxed) + 1;
+        warn!(
+            chat = %target.chat_id,
+            sent = STATS.sent.load(Ordering::Relaxed),
+            failed,
+            rate_limited = STATS.rate_limited.load(Ordering::Relaxed),
+            "Dropped telegram message: {err:#}"
+        );
+
+        let message = format!("Dropped a telegram message to chat {}: {err:#}", target.chat_id);
+        if let Err(err) = self.notify_error_channel(target, message).await {
+            warn!("Failed to alert the error channel: {err:#}");
+        }
+    }
+
+    async fn notify_error_channel(&self, target: &TelegramTarget, message: String) -> eyre::Result<()> {
+        match &self.error_channel {
+            // The error channel itself failing isn't reported again
+            Some(ErrorAlertTo::Telegram(channel)) if channel.chat_id != target.chat_id => {
+                match self.send(channel, Content::Text(&escape(&message))).await {
+                    Ok(()) => {}
+                    Err(SendError::RateLimited(_)) => bail!("Rate limited"),
+                    Err(SendError::Other(err)) => return Err(err),
+                }
+            }
+            Some(ErrorAlertTo::Webhook(channel)) => {
+                self.client
+                    .post(&channel.url)
+                    .body(serde_json::to_string(&WebhookPayload { text: message })?)
+                    .header("Content-Type", "application/json")
+                    .send()
+                    .await
+                    .context("Failed to send webhook")?
+                    .error_for_status()
+                    .context("Webhook was rejected")?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[burberry::async_trait]
+impl Executor<Action> for TelegramExecutor {
+    async fn execute(&self, action: Action) -> eyre::Result<()> {
+        let (target, content) = match &action {
+            Action::Telegram(target, text) => (target, Content::Text(text)),
+            Action::TelegramPhoto(target, png, caption) => (target, Content::Photo { png, caption }),
+            _ => return Ok(()),
+        };
+
+        if let Err(err) = self.send_with_retry(target, content).await {
+            self.alert_dropped(target, &err).await;
+            return Err(err);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use {super::Action, burberry::Executor, eyre::Context};
+
+/// Sends SMS or WhatsApp messages through Twilio's Messages API, one request
+/// per recipient.
+#[derive(Default)]
+pub struct TwilioExecutor {
+    client: reqwest::Client,
+}
+
+#[burberry::async_trait]
+impl Executor<Action> for TwilioExecutor {
+    async fn execute(&self, action: Action) -> eyre::Result<()> {
+        let Action::Twilio(target, message) = action else {
+            return Ok(());
+        };
+
+        let url = format!(
+            "https://api.twilio.com/2010-04-01/Accounts/{}/Messages.json",
+            target.account_sid
+        );
+
+        for to in &target.to {
+            self.client
+                .post(&url)
+                .basic_auth(&target.account_sid, Some(&target.auth_token))
+                .form(&[("From", &target.from), ("To", to), ("Body", &message)])
+                .send()
+                .await
+                .with_context(|| format!("Failed to send message to {to}"))?
+                .error_for_status()
+                .with_context(|| format!("Message to {to} was rejected"))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use {super::Action, burberry::Executor, eyre::Context, schemars::JsonSchema, serde::Serialize};
+
+/// Posts plain text messages as `{"text": "..."}`, the payload Slack-compatible
+/// incoming webhooks accept.
+#[derive(Default)]
+pub struct WebhookExecutor {
+    client: reqwest::Client,
+}
+
+/// Payload of a webhook
+#[derive(Serialize, JsonSchema)]
+pub struct WebhookPayload {
+    pub text: String,
+}
+
+#[burberry::async_trait]
+impl Executor<Action> for WebhookExecutor {
+    async fn execute(&self, action: Action) -> eyre::Result<()> {
+        let Action::Webhook(target, message) = action else {
+            return Ok(());
+        };
+
+        self.client
+            .post(&target.url)
+            .body(serde_json::to_string(&WebhookPayload { text: message })?)
+            .header("Content-Type", "application/json")
+            .send()
+            .await
+            .context("Failed to send webhook")?
+            .error_for_status()
+            .context("Webhook was rejected")?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use {
+    crate::{config::ExplorerApi, utils::U256AsDecimalStr},
+    alloy::{
+        primitives::{Address, B256, U256},
+        rpc::types::{trace::geth::CallFrame, AnyTransactionReceipt},
+    },
+    eyre::{bail, Context},
+    serde::Deserialize,
+    serde_json::Value,
+    serde_with::serde_as,
+    std::collections::HashSet,
+};
+
+/// Answer of an Etherscan-compatible API when nothing matches
+const NO_RECORDS: &str = "No transactions found";
+
+/// Client of an Etherscan-compatible API, the degraded data source of chains
+/// whose nodes can't trace
+pub struct ExplorerClient {
+    client: reqwest::Client,
+    api: ExplorerApi,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    status: String,
+    message: String,
+    result: Value,
+}
+
+/// An internal tx as the explorer reports it, a value call made by a contract
+#[serde_as]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InternalTx {
+    pub hash: B256,
+    pub from: Address,
+    /// Empty for contract creations
+    pub to: String,
+    #[serde_as(as = "U256AsDecimalStr")]
+    pub value: U256,
+    #[serde(default)]
+    pub contract_address: String,
+    #[serde(default)]
+    pub trace_id: String,
+    #[serde(default)]
+    pub is_error: String,
+}
+
+impl ExplorerClient {
+    pub fn new(api: ExplorerApi) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api,
+        }
+    }
+
+    /// Host of the API, to flag the reports it contributed to
+    pub fn host(&self) -> String {
+        reqwest::Url::parse(&self.api.url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_else(|| self.api.url.clone())
+    }
+
+    /// Internal txs from or to `address` in `block`
+    pub async fn internal_txs(&self, address: Address, block: u64) -> eyre::Result<Vec<InternalTx>> {
+        let block = block.to_string();
+        let address = address.to_string();
+        let mut query = vec![
+            ("module", "account"),
+            ("action", "txlistinternal"),
+            ("address", address.as_str()),
+            ("startblock", block.as_str()),
+            ("endblock", block.as_str()),
+        ];
+        if let Some(api_key) = &self.api.api_key {
+            query.push(("apikey", api_key.as_str()));
+        }
+
+        let response: Response = serde_json::from_str(
+            &self
+                .client
+                .get(&self.api.url)
+                .query(&query)
+                .send()
+                .await
+                .context("Failed to query explorer")?
+                .error_for_status()
+                .context("Explorer rejected the query")?
+                .text()
+                .await
+                .context("Failed to read explorer response")?,
+        )
+        .context("Invalid explorer response")?;
+
+        if response.status != "1" {
+            if response.message == NO_RECORDS {
+                return Ok(vec![]);
+            }
+            bail!("Explorer returned {}: {}", response.message, response.result);
+        }
+
+        serde_json::from_value(response.result).context("Invalid internal txs")
+    }
+}
+
+/// Add the internal txs of `addresses` in `block` as calls under the frames
+/// of their txs, so their native transfers count. A transfer between two of
+/// `addresses` is added once.
+pub async fn add_internal_txs(
+    client: &ExplorerClient,
+    block: u64,
+    addresses: &[Address],
+    receipt_and_traces: &mut [(AnyTransactionReceipt, CallFrame)],
+) -> eyre::Result<()> {
+    let mut seen = HashSet::new();
+
+    for address in addresses {
+        for tx in client.internal_txs(*address, block).await? {
+            if !seen.insert((tx.hash, tx.trace_id.clone())) {
+                continue;
+            }
+
+            let Some((_, frame)) = receipt_and_traces
+                .iter_mut()
+                .find(|(receipt, _)| receipt.transaction_hash == tx.hash)
+            else {
+                continue;
+            };
+
+            let to = if tx.to.is_empty() { &tx.contract_address } else { &tx.to };
+            frame.calls.push(CallFrame {
+                from: tx.from,
+                to: to.parse().ok(),
+                value: Some(tx.value),
+                typ: if tx.to.is_empty() { "CREATE" } else { "CALL" }.to_string(),
+                error: (tx.is_error == "1").then(|| "execution reverted".to_string()),
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use {
+    alloy::primitives::Address,
+    alloy_chains::Chain,
+    redis::{aio::ConnectionManager, AsyncCommands},
+    std::collections::{HashMap, HashSet},
+    tracing::warn,
+};
+
+/// Tokens each wallet has held before. They are kept in redis when a cache is
+/// set up, so they survive restarts; otherwise every token is new once per run.
+pub struct SeenTokens {
+    chain: Chain,
+    seen: HashMap<Address, HashSet<Address>>,
+    cache: Option<ConnectionManager>,
+}
+
+impl SeenTokens {
+    pub fn new(chain: Chain, cache: Option<ConnectionManager>) -> Self {
+        Self {
+            chain,
+            seen: HashMap::new(),
+            cache,
+        }
+    }
+
+    /// Record the tokens a wallet's balance changed in, returning the ones it
+    /// never held before
+    pub async fn record(&mut self, wallet: Address, tokens: impl IntoIterator<Item = Address>) -> Vec<Address> {
+        let key = format!("wallet-watcher:seen:{}:{:#x}", self.chain.id(), wallet);
+        let seen = self.seen.entry(wallet).or_default();
+        let mut new_tokens = vec![];
+
+        for token in tokens {
+            if !seen.insert(token) {
+                continue;
+            }
+
+            let is_new = match self.cache.as_mut() {
+                Some(cache) => cache
+                    .sadd::<_, _, bool>(&key, format!("{token:#x}"))
+                    .await
+                    .unwrap_or_else(|err| {
+                        warn!(%token, "Failed to record seen token: {err:#}");
+                        false
+                    }),
+                None => true,
+            };
+
+            if is_new {
+                new_tokens.push(token);
+            }
+        }
+
+        new_tokens
+    }
+}
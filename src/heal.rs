@@ -0,0 +1,39 @@
+use {
+    crate::{config::BlockSource, storage::Storage},
+    std::collections::HashSet,
+};
+
+/// Blocks of `chain` from `from_block` to `to_block` to process again: the
+/// ones that failed, and with `blocks` as the source, the ones never recorded
+/// since the first recorded one. Chains on transfer logs only record the
+/// blocks with a transfer, so only their failures are gaps
+pub async fn find_gaps(
+    storage: &dyn Storage,
+    chain: u64,
+    source: BlockSource,
+    from_block: u64,
+    to_block: u64,
+) -> eyre::Result<Vec<u64>> {
+    let recorded = storage.recorded_blocks(chain, from_block, to_block).await?;
+
+    match source {
+        BlockSource::TransferLogs => Ok(recorded
+            .into_iter()
+            .filter(|(_, failed)| *failed)
+            .map(|(block, _)| block)
+            .collect()),
+        BlockSource::Blocks => {
+            // Blocks before it may predate the watcher
+            let Some(&(first, _)) = recorded.first() else {
+                return Ok(vec![]);
+            };
+            let processed = recorded
+                .into_iter()
+                .filter(|(_, failed)| !*failed)
+                .map(|(block, _)| block)
+                .collect::<HashSet<_>>();
+
+            Ok((first..=to_block).filter(|block| !processed.contains(block)).collect())
+        }
+    }
+}
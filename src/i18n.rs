@@ -0,0 +1,187 @@
+use {
+    crate::category::PnlCategory,
+    serde::{Deserialize, Serialize},
+};
+
+/// Language of the alert messages of a channel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    #[default]
+    En,
+    Zh,
+    Ja,
+}
+
+/// Words used in alert messages. Amounts, symbols and links are the same in
+/// every language.
+pub struct Strings {
+    pub builder_tag: &'static str,
+    pub validator_bribe: &'static str,
+    pub details: &'static str,
+    pub contract_creation: &'static str,
+    pub gas: &'static str,
+    pub blocks: &'static str,
+    pub more_wallets: &'static str,
+    pub reverted: &'static str,
+    pub new_token: &'static str,
+    pub first_receipt: &'static str,
+    pub airdrop_claim: &'static str,
+    pub truncated: &'static str,
+    pub orphaned: &'static str,
+    pub fees: &'static str,
+    pub cex_deposit: &'static str,
+    pub staking_deposit: &'static str,
+    pub staking_withdrawal_request: &'static str,
+    pub staking_claim: &'static str,
+    pub cluster_loss: &'static str,
+    pub cluster_profit: &'static str,
+    pub in_last: &'static str,
+    pub minutes: &'static str,
+    pub category_trading: &'static str,
+    pub category_gas: &'static str,
+    pub category_staking: &'static str,
+    pub category_bridging: &'static str,
+    pub category_transfers: &'static str,
+    pub category_block_production: &'static str,
+    pub daily_digest: &'static str,
+    pub reports: &'static str,
+    pub cumulative_pnl: &'static str,
+    pub missed: &'static str,
+    pub outbid_by: &'static str,
+    pub unknown: &'static str,
+}
+
+const EN: Strings = Strings {
+    builder_tag: "[B]",
+    validator_bribe: "VBribe",
+    details: "Details",
+    contract_creation: "contract creation",
+    gas: "gas",
+    blocks: "blocks",
+    more_wallets: "more wallets",
+    reverted: "Reverted",
+    new_token: "NEW",
+    first_receipt: "First receipt",
+    airdrop_claim: "airdrop claim",
+    truncated: "Trace truncated, transfers may be missing",
+    orphaned: "Orphaned by a reorg of",
+    fees: "fees",
+    cex_deposit: "CEX deposit",
+    staking_deposit: "deposit",
+    staking_withdrawal_request: "withdrawal request",
+    staking_claim: "withdrawal claim",
+    cluster_loss: "Lost",
+    cluster_profit: "Made",
+    in_last: "in the last",
+    minutes: "min",
+    category_trading: "Trading",
+    category_gas: "Gas",
+    category_staking: "Staking",
+    category_bridging: "Bridging",
+    category_transfers: "Transfers",
+    category_block_production: "Block production",
+    daily_digest: "Daily digest",
+    reports: "reports",
+    cumulative_pnl: "Cumulative PnL",
+    missed: "Missed",
+    outbid_by: "outbid by",
+    unknown: "unknown",
+};
+
+const ZH: Strings = Strings {
+    builder_tag: "[出块]",
+    validator_bribe: "验证者贿赂",
+    details: "详情",
+    contract_creation: "合约创建",
+    gas: "Gas",
+    blocks: "个区块",
+    more_wallets: "个其他钱包",
+    reverted: "交易回滚",
+    new_token: "新",
+    first_receipt: "首次收到",
+    airdrop_claim: "空投领取",
+    truncated: "调用追踪已截断，可能缺少转账",
+    orphaned: "已被重组孤立，重组深度",
+    fees: "手续费",
+    cex_deposit: "交易所充值",
+    staking_deposit: "质押",
+    staking_withdrawal_request: "申请提取",
+    staking_claim: "提取到账",
+    cluster_loss: "亏损",
+    cluster_profit: "盈利",
+    in_last: "最近",
+    minutes: "分钟内",
+    category_trading: "交易",
+    category_gas: "Gas",
+    category_staking: "质押",
+    category_bridging: "跨链",
+    category_transfers: "转账",
+    category_block_production: "出块",
+    daily_digest: "每日摘要",
+    reports: "条报告",
+    cumulative_pnl: "累计盈亏",
+    missed: "错失",
+    outbid_by: "出价落后",
+    unknown: "未知",
+};
+
+const JA: Strings = Strings {
+    builder_tag: "[ビルダー]",
+    validator_bribe: "バリデータへの支払い",
+    details: "詳細",
+    contract_creation: "コントラクト作成",
+    gas: "ガス",
+    blocks: "ブロック",
+    more_wallets: "件の他のウォレット",
+    reverted: "リバート",
+    new_token: "新規",
+    first_receipt: "初回受取",
+    airdrop_claim: "エアドロップ受取",
+    truncated: "トレースが切り詰められたため、送金が欠けている可能性があります",
+    orphaned: "リオルグにより孤立、深さ",
+    fees: "手数料",
+    cex_deposit: "取引所への入金",
+    staking_deposit: "ステーキング",
+    staking_withdrawal_request: "出金リクエスト",
+    staking_claim: "出金受取",
+    cluster_loss: "損失",
+    cluster_profit: "利益",
+    in_last: "直近",
+    minutes: "分間",
+    category_trading: "取引",
+    category_gas: "ガス",
+    category_staking: "ステーキング",
+    category_bridging: "ブリッジ",
+    category_transfers: "送金",
+    category_block_production: "ブロック生成",
+    daily_digest: "デイリーダイジェスト",
+    reports: "件のレポート",
+    cumulative_pnl: "累積損益",
+    missed: "機会損失",
+    outbid_by: "入札差",
+    unknown: "不明",
+};
+
+impl Language {
+    pub fn strings(self) -> &'static Strings {
+        match self {
+            Language::En => &EN,
+            Language::Zh => &ZH,
+            Language::Ja => &JA,
+        }
+    }
+}
+
+impl Strings {
+    pub fn category(&self, category: PnlCategory) -> &'static str {
+        match category {
+            PnlCategory::Trading => self.category_trading,
+            PnlCategory::Gas => self.category_gas,
+            PnlCategory::Staking => self.category_staking,
+            PnlCategory::Bridging => self.category_bridging,
+            PnlCategory::Transfers => self.category_transfers,
+            PnlCategory::BlockProduction => self.category_block_production,
+        }
+    }
+}
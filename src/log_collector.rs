@@ -0,0 +1,147 @@
+use {
+    crate::{contract::ERC20, rpc_metrics},
+    alloy::{
+        primitives::Address,
+        providers::Provider,
+        pubsub::PubSubFrontend,
+        rpc::types::{Block, Filter, Log},
+        sol_types::SolEvent,
+    },
+    alloy_chains::Chain,
+    burberry::{async_trait, Collector, CollectorStream},
+    eyre::{ensure, Context},
+    futures::{channel::mpsc, stream, SinkExt, StreamExt},
+    std::{collections::BTreeSet, sync::Arc},
+    tracing::{error, warn},
+};
+
+/// Blocks remembered to drop the second log of a block already emitted
+const RECENT_BLOCKS: usize = 64;
+
+/// Blocks covered by one `eth_getLogs` query when looking up past transfers
+const LOGS_RANGE: u64 = 1000;
+
+/// Emits the blocks with an ERC20 transfer from or to one of `addresses`, as
+/// found by logs subscriptions, instead of every block
+pub struct TransferLogCollector {
+    provider: Arc<dyn Provider<PubSubFrontend>>,
+    addresses: Vec<Address>,
+}
+
+impl TransferLogCollector {
+    pub fn new(provider: Arc<dyn Provider<PubSubFrontend>>, addresses: Vec<Address>) -> Self {
+        Self { provider, addresses }
+    }
+}
+
+#[async_trait]
+impl Collector<Block> for TransferLogCollector {
+    fn name(&self) -> &str {
+        "TransferLogCollector"
+    }
+
+    async fn get_event_stream(&self) -> eyre::Result<CollectorStream<'_, Block>> {
+        // An empty filter topic matches every transfer
+        ensure!(!self.addresses.is_empty(), "No wallet to subscribe to the transfers of");
+
+        let (sent, received) = transfer_filters(&self.addresses);
+        let sent = self.provider.subscribe_logs(&sent).await?.into_stream();
+        let received = self.provider.subscribe_logs(&received).await?.into_stream();
+
+        let (mut tx, rx) = mpsc::unbounded();
+        let provider = Arc::clone(&self.provider);
+
+        tokio::spawn(async move {
+            let mut logs = stream::select(sent, received);
+            let mut recent = BTreeSet::new();
+
+            while let Some(log) = logs.next().await {
+                let Some(number) = block_number(&log) else {
+                    continue;
+                };
+                if !recent.insert(number) {
+                    continue;
+                }
+                if recent.len() > RECENT_BLOCKS {
+                    recent.pop_first();
+                }
+
+                let block = match provider.get_block_by_number(number.into(), false).await {
+                    Ok(Some(block)) => block,
+                    Ok(None) => {
+                        warn!(block = number, "Block of a transfer log not found");
+                        continue;
+                    }
+                    Err(err) => {
+                        warn!(block = number, "Failed to get block of a transfer log: {err:#}");
+                        continue;
+                    }
+                };
+
+                if tx.send(block).await.is_err() {
+                    return;
+                }
+            }
+
+            error!("Transfer log subscriptions ended");
+        });
+
+        Ok(Box::pin(rx))
+    }
+}
+
+/// Filters of the transfers from and to `addresses`. Topics of a filter are
+/// ANDed, so senders and recipients need one each
+fn transfer_filters(addresses: &[Address]) -> (Filter, Filter) {
+    let topics = addresses.iter().map(|a| a.into_word()).collect::<Vec<_>>();
+    let sent = Filter::new()
+        .event_signature(ERC20::Transfer::SIGNATURE_HASH)
+        .topic1(topics.clone());
+    let received = Filter::new()
+        .event_signature(ERC20::Transfer::SIGNATURE_HASH)
+        .topic2(topics);
+
+    (sent, received)
+}
+
+/// Blocks from `from_block` to `to_block` with an ERC20 transfer from or to
+/// one of `addresses`, from `eth_getLogs`
+pub async fn blocks_with_transfers(
+    chain: Chain,
+    provider: &dyn Provider<PubSubFrontend>,
+    addresses: &[Address],
+    from_block: u64,
+    to_block: u64,
+) -> eyre::Result<BTreeSet<u64>> {
+    ensure!(!addresses.is_empty(), "No wallet to look up the transfers of");
+
+    let (sent, received) = transfer_filters(addresses);
+    let mut blocks = BTreeSet::new();
+
+    let mut start = from_block;
+    while start <= to_block {
+        let end = (start + LOGS_RANGE - 1).min(to_block);
+
+        for filter in [&sent, &received] {
+            rpc_metrics::record(chain, "eth_getLogs", 1);
+            let logs = provider
+                .get_logs(&filter.clone().from_block(start).to_block(end))
+                .await
+                .with_context(|| format!("Failed to get transfer logs of blocks {start} to {end}"))?;
+            blocks.extend(logs.iter().filter_map(block_number));
+        }
+
+        start = end + 1;
+    }
+
+    Ok(blocks)
+}
+
+/// Block of a log, unless it was removed by a reorg
+fn block_number(log: &Log) -> Option<u64> {
+    if log.removed {
+        return None;
+    }
+
+    log.block_number
+}
@@ -1,15 +1,52 @@
 use clap::Parser;
 
+mod archive;
+mod audit;
+mod backfill;
 mod balance_changes;
+mod category;
+mod chain_adapter;
 mod cli;
+mod cluster;
 mod config;
 mod contract;
+mod delivery;
+mod digest;
+mod event_watch;
+mod exchanges;
+mod executor;
+mod explorer;
+mod first_seen;
+mod heal;
+mod i18n;
+mod log_collector;
+mod markup;
 mod message;
+mod migration;
+mod perps;
+mod price;
 mod processor;
+mod reconnect;
+mod relay;
+mod reload;
+mod reorg;
+mod retention;
+mod retry;
+mod rpc_metrics;
+mod staking;
+mod state_diff;
+mod storage;
 mod strategy;
+mod throttle;
+mod token_list;
+mod token_risk;
+mod token_watch;
+mod trace_mode;
+mod tui;
 mod utils;
+mod vault;
 
 #[tokio::main]
-async fn main() {
-    cli::Cli::parse().run().await.unwrap()
+async fn main() -> eyre::Result<()> {
+    cli::Cli::parse().run().await
 }
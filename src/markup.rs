@@ -0,0 +1,150 @@
+use {crate::message::MessageFormat, burberry::executor::telegram_message::escape};
+
+/// Characters with a meaning in Discord markdown
+const DISCORD_SPECIAL: [char; 11] = ['\\', '*', '_', '~', '`', '|', '>', '#', '-', '[', ']'];
+
+/// Builds a message span by span. Each span is escaped the way its kind
+/// requires in the target format, so text like token symbols can't break the
+/// markup around it.
+pub struct Markup {
+    format: MessageFormat,
+    content: String,
+}
+
+impl Markup {
+    pub fn new(format: MessageFormat) -> Self {
+        Self {
+            format,
+            content: String::new(),
+        }
+    }
+
+    pub fn text(&mut self, text: &str) -> &mut Self {
+        let escaped = self.escape(text);
+        self.content.push_str(&escaped);
+        self
+    }
+
+    pub fn bold(&mut self, text: &str) -> &mut Self {
+        let escaped = self.escape(text);
+        match self.format {
+            MessageFormat::Telegram => self.push(&format!("*{escaped}*")),
+            MessageFormat::Discord => self.push(&format!("**{escaped}**")),
+            MessageFormat::Slack => self.push(&format!("*{escaped}*")),
+            MessageFormat::Plain => self.push(&escaped),
+            MessageFormat::Html => self.push(&format!("<b>{escaped}</b>")),
+        }
+    }
+
+    /// Monospaced text
+    pub fn code(&mut self, text: &str) -> &mut Self {
+        match self.format {
+            MessageFormat::Telegram => {
+                let escaped = text.replace('\\', "\\\\").replace('`', "\\`");
+                self.push(&format!("`{escaped}`"))
+            }
+            // Backticks can't be escaped in a code span, so they are swapped
+            // for a look-alike
+            MessageFormat::Discord => self.push(&format!("`{}`", text.replace('`', "ˋ"))),
+            MessageFormat::Slack => {
+                let escaped = self.escape(&text.replace('`', "ˋ"));
+                self.push(&format!("`{escaped}`"))
+            }
+            MessageFormat::Plain => self.push(text),
+            MessageFormat::Html => {
+                let escaped = self.escape(text);
+                self.push(&format!("<code>{escaped}</code>"))
+            }
+        }
+    }
+
+    /// A link, or its text alone where links aren't supported
+    pub fn link(&mut self, text: &str, url: &str) -> &mut Self {
+        let escaped = self.escape(text);
+        match self.format {
+            MessageFormat::Telegram => {
+                let url = url.replace('\\', "\\\\").replace(')', "\\)");
+                self.push(&format!("[{escaped}]({url})"))
+            }
+            // Angle brackets keep Discord from embedding a preview of the page
+            MessageFormat::Discord => self.push(&format!("[{escaped}](<{url}>)")),
+            MessageFormat::Slack => {
+                let url = self.escape(url).replace('|', "%7C");
+                self.push(&format!("<{url}|{}>", escaped.replace('|', "¦")))
+            }
+            MessageFormat::Plain => self.push(&escaped),
+            MessageFormat::Html => {
+                let url = self.escape(url);
+                self.push(&format!(r#"<a href="{url}">{escaped}</a>"#))
+            }
+        }
+    }
+
+    /// Whether links are clickable rather than shown by their text alone
+    pub fn has_links(&self) -> bool {
+        self.format != MessageFormat::Plain
+    }
+
+    pub fn newline(&mut self) -> &mut Self {
+        self.push("\n")
+    }
+
+    /// `folded` collapsed under a `summary` line where the service can expand
+    /// it on demand, listed after it otherwise
+    pub fn fold(&mut self, summary: &str, folded: Markup) -> &mut Self {
+        match self.format {
+            // Expandable block quotation
+            MessageFormat::Telegram => {
+                let quoted = folded.content.trim_end().lines().collect::<Vec<_>>().join("\n>");
+                self.text(summary).newline().push(&format!("**>{quoted}||\n"))
+            }
+            // Spoiler, revealed when clicked
+            MessageFormat::Discord => {
+                let content = folded.content.trim_end().to_string();
+                self.text(summary).newline().push(&format!("||{content}||\n"))
+            }
+            MessageFormat::Plain | MessageFormat::Slack => self.text(summary).newline().push(&folded.content),
+            MessageFormat::Html => {
+                let summary = self.escape(summary);
+                self.push(&format!(
+                    "<details><summary>{summary}</summary>{}</details>",
+                    folded.content
+                ))
+            }
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.content
+    }
+
+    fn push(&mut self, markup: &str) -> &mut Self {
+        self.content.push_str(markup);
+        self
+    }
+
+    fn escape(&self, text: &str) -> String {
+        match self.format {
+            MessageFormat::Telegram => escape(text),
+            MessageFormat::Discord => {
+                let mut escaped = String::with_capacity(text.len());
+                for c in text.chars() {
+                    if DISCORD_SPECIAL.contains(&c) {
+                        escaped.push('\\');
+                    }
+                    escaped.push(c);
+                }
+                escaped
+            }
+            // Only the characters Slack parses links and mentions from can be
+            // escaped
+            MessageFormat::Slack => text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;"),
+            MessageFormat::Plain => text.to_string(),
+            MessageFormat::Html => text
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;"),
+        }
+    }
+}
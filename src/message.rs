@@ -1,58 +1,142 @@
 use {
     crate::{
-        config::WalletWithContext,
+        balance_changes::BalanceChange,
+        category::PnlCategory,
+        cluster::{ClusterAlert, ClusterTrigger},
+        config::{
+            ChainConfig, EventWatchWithContext, LargeTransferWatchWithContext, PermalinkConfig, TokenRiskConfig,
+            TokenWatchWithContext, TxLink, WalletWithContext, NATIVE_TOKEN,
+        },
         contract::ERC20::ERC20Instance,
-        processor::PnlReport,
-        utils::{self, format_ether_trimmed, format_short_address, format_token_amount},
+        digest::{self, WalletDay},
+        event_watch::EventFields,
+        markup::Markup,
+        perps::PerpsPnl,
+        processor::{CexDeposit, PnlReport, Transfer, TxAndPosition},
+        reorg::Orphaned,
+        rpc_metrics,
+        staking::{StakingFlow, StakingFlowKind},
+        throttle::RollUp,
+        token_risk,
+        utils::{self, format::NumberFormat, format_short_address},
+        vault::VaultAssets,
     },
     alloy::{
         network::ReceiptResponse,
-        primitives::{address, Address},
+        primitives::{address, hex, keccak256, Address, I256, U256},
         providers::Provider,
         rpc::types::{trace::geth::CallFrame, AnyTransactionReceipt, Block},
         transports::Transport,
     },
     alloy_chains::Chain,
-    burberry::executor::telegram_message::escape,
+    chrono::DateTime,
+    chrono_tz::Tz,
     eyre::{Context, ContextCompat},
+    redis::{aio::ConnectionManager, AsyncCommands},
     std::{
-        collections::{hash_map::Entry, HashMap},
-        fmt::Write,
+        collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
         sync::Arc,
     },
-    tracing::error,
+    tracing::{error, warn},
 };
 
+/// Gathers what a message needs, token metadata mostly, and hands it to the
+/// [`Renderer`]
 pub struct MessageGenerator<T: Clone + Transport> {
-    chain: Chain,
     provider: Arc<dyn Provider<T>>,
+    token_cache: Option<ConnectionManager>,
+    permalink: Option<PermalinkConfig>,
+    token_risk: Option<TokenRiskConfig>,
+    /// Tokens already checked for risk
+    risk_checked: HashSet<Address>,
+    renderer: Renderer,
+}
+
+/// Turns reports into messages. Rendering does no I/O: tokens missing from
+/// `token_info` are shown by address.
+pub struct Renderer {
+    chain: Chain,
+    native_symbol: String,
+    native_decimals: u8,
+    explorer: String,
+    tx_links: Vec<TxLink>,
     token_info: HashMap<Address, (String, u8)>,
+    /// Tokens that look like honeypots, to why
+    token_risk: HashMap<Address, String>,
 }
 
 impl<T: Clone + Transport> MessageGenerator<T> {
     pub fn new(chain: Chain, provider: Arc<dyn Provider<T>>) -> Self {
-        let mut token_info = HashMap::default();
-
-        if chain == Chain::mainnet() {
-            token_info.insert(
-                address!("9f8F72aA9304c8B593d555F12eF6589cC3A579A2"),
-                ("MKR".to_string(), 18),
-            );
-        }
-
         Self {
-            chain,
             provider,
-            token_info,
+            token_cache: None,
+            permalink: None,
+            token_risk: None,
+            risk_checked: HashSet::new(),
+            renderer: Renderer::new(chain),
+        }
+    }
+
+    /// Apply the native currency and explorer set for the chain in the config
+    pub fn with_chain_config(mut self, config: &ChainConfig) -> Self {
+        self.renderer = self.renderer.with_chain_config(config);
+        self
+    }
+
+    /// Make calls through `provider` from now on, e.g. after reconnecting
+    pub fn set_provider(&mut self, provider: Arc<dyn Provider<T>>) {
+        self.provider = provider;
+    }
+
+    /// Share token metadata through redis in addition to the in-memory cache
+    pub fn with_token_cache(mut self, cache: ConnectionManager) -> Self {
+        self.token_cache = Some(cache);
+        self
+    }
+
+    /// Take the symbol and decimals of these tokens as given instead of
+    /// looking them up
+    pub fn with_listed_tokens(mut self, tokens: &HashMap<Address, (String, u8)>) -> Self {
+        for (token, info) in tokens {
+            self.renderer.token_info.entry(*token).or_insert_with(|| info.clone());
         }
+        self
+    }
+
+    /// Write each Telegram, Discord and Slack report as a static HTML page too,
+    /// and link it from the message
+    pub fn with_permalink(mut self, permalink: PermalinkConfig) -> Self {
+        self.permalink = Some(permalink);
+        self
+    }
+
+    /// Check tokens for honeypot signs the first time they show up
+    pub fn with_token_risk(mut self, config: TokenRiskConfig) -> Self {
+        self.token_risk = Some(config);
+        self
     }
 
-    async fn load_symbol_and_decimal(&mut self, token: &Address) -> eyre::Result<&(String, u8)> {
-        let entry = self.token_info.entry(*token);
+    pub async fn load_symbol_and_decimal(&mut self, token: &Address) -> eyre::Result<&(String, u8)> {
+        let chain = self.renderer.chain;
+        let entry = self.renderer.token_info.entry(*token);
 
         match entry {
             Entry::Occupied(e) => Ok(e.into_mut()),
             Entry::Vacant(e) => {
+                let cache_key = format!("wallet-watcher:token:{}:{:#x}", chain.id(), token);
+
+                if let Some(cache) = self.token_cache.as_mut() {
+                    let cached: Option<String> = cache.get(&cache_key).await.unwrap_or_else(|err| {
+                        warn!(%token, "Failed to read token cache: {err:#}");
+                        None
+                    });
+
+                    if let Some(info) = cached.and_then(|s| serde_json::from_str(&s).ok()) {
+                        return Ok(e.insert(info));
+                    }
+                }
+
+                rpc_metrics::record(chain, "eth_call", 2);
                 let erc20 = ERC20Instance::new(*token, self.provider.root());
 
                 let symbol = erc20.symbol().call().await.context("Failed to get symbol for token")?;
@@ -62,7 +146,43 @@ impl<T: Clone + Transport> MessageGenerator<T> {
                     .await
                     .context("Failed to get decimals for token")?;
 
-                Ok(e.insert((symbol._0, decimal._0)))
+                let info = (symbol._0, decimal._0);
+
+                if let Some(cache) = self.token_cache.as_mut() {
+                    let value = serde_json::to_string(&info)?;
+                    if let Err(err) = cache.set::<_, _, ()>(&cache_key, value).await {
+                        warn!(%token, "Failed to write token cache: {err:#}");
+                    }
+                }
+
+                Ok(e.insert(info))
+            }
+        }
+    }
+
+    /// Load the metadata of the changed tokens for rendering. Failures are
+    /// logged and the token is shown by address instead
+    async fn load_tokens(&mut self, token_changes: &BalanceChange) {
+        for token in token_changes.keys() {
+            if let Err(err) = self.load_symbol_and_decimal(token).await {
+                error!(%token, "Failed to load symbol for token: {err:#}");
+            }
+
+            let Some(config) = &self.token_risk else {
+                continue;
+            };
+            if self.risk_checked.contains(token) {
+                continue;
+            }
+
+            match token_risk::check(self.provider.as_ref(), self.renderer.chain, *token, config).await {
+                Ok(risk) => {
+                    self.risk_checked.insert(*token);
+                    if let Some(reason) = risk {
+                        self.renderer.token_risk.insert(*token, reason);
+                    }
+                }
+                Err(err) => warn!(%token, "Failed to check token risk: {err:#}"),
             }
         }
     }
@@ -73,66 +193,286 @@ impl<T: Clone + Transport> MessageGenerator<T> {
         receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
         report: &PnlReport,
         wallet: &WalletWithContext,
+        format: MessageFormat,
     ) -> eyre::Result<String> {
-        let mut message_content = format!(
-            "{address_link} · \\#{chain} · {block_link}{builder_tag}\n",
-            address_link = utils::address_link(self.chain, &wallet.address, Some(escape(&wallet.name))),
-            chain = escape(&self.chain.to_string().to_uppercase()),
-            block_link = utils::block_link(self.chain, block.header.number),
-            builder_tag = if report.builder_reward.is_zero() { "" } else { "\\[B\\]" },
-        );
+        self.load_tokens(&report.token_changes).await;
+        for vault_assets in &report.vault_assets {
+            if let Err(err) = self.load_symbol_and_decimal(&vault_assets.asset).await {
+                error!(token = %vault_assets.asset, "Failed to load symbol for token: {err:#}");
+            }
+        }
+        for flow in &report.staking {
+            if flow.token == NATIVE_TOKEN {
+                continue;
+            }
+            if let Err(err) = self.load_symbol_and_decimal(&flow.token).await {
+                error!(token = %flow.token, "Failed to load symbol for token: {err:#}");
+            }
+        }
 
-        let (sign, pnl) = report.pnl.into_sign_and_abs();
-
-        let currency_symbol = self
-            .chain
-            .named()
-            .and_then(|chain| chain.native_currency_symbol())
-            .unwrap_or("ETH");
-
-        writeln!(
-            &mut message_content,
-            "{symbol}: *{sign}{pnl}*",
-            symbol = escape(currency_symbol),
-            sign = escape(if sign.is_positive() { "" } else { "-" }),
-            pnl = escape(&format_ether_trimmed(&pnl)),
-        )?;
-
-        if !report.token_changes.is_empty() {
-            let chain = self.chain;
-            for (token, change) in report.token_changes.iter() {
-                let (symbol, decimals) = match self.load_symbol_and_decimal(token).await {
-                    Ok((symbol, decimals)) => (TokenName::Symbol(symbol).to_string(), *decimals),
-                    Err(err) => {
-                        error!(%token, "Failed to load symbol for token: {err:#}");
-                        (TokenName::Address(token).to_string(), 18)
-                    }
-                };
+        let mut message = self
+            .renderer
+            .report(block, receipt_and_traces, report, wallet, format)?;
+
+        if let (MessageFormat::Telegram | MessageFormat::Discord | MessageFormat::Slack, Some(permalink)) =
+            (format, &self.permalink)
+        {
+            let page = self
+                .renderer
+                .report(block, receipt_and_traces, report, wallet, MessageFormat::Html)?;
+
+            // The alert is still worth sending without its page
+            match write_page(permalink, self.renderer.chain, block.header.number, wallet, &page).await {
+                Ok(url) => {
+                    let mut link = Markup::new(format);
+                    link.link(wallet.language.strings().details, &url).newline();
+                    message.push_str(&link.finish());
+                }
+                Err(err) => error!("Failed to write report page: {err:#}"),
+            }
+        }
+
+        Ok(message)
+    }
+
+    /// Summarise the reports a throttled wallet held back in one message
+    pub async fn generate_rollup(
+        &mut self,
+        rollup: &RollUp,
+        wallet: &WalletWithContext,
+        format: MessageFormat,
+    ) -> eyre::Result<String> {
+        self.load_tokens(&rollup.token_changes).await;
+        Ok(self.renderer.rollup(rollup, wallet, format))
+    }
+
+    /// Alert for the tokens a wallet received for the first time
+    pub async fn generate_first_seen(
+        &mut self,
+        block: &Block,
+        report: &PnlReport,
+        wallet: &WalletWithContext,
+        format: MessageFormat,
+    ) -> eyre::Result<String> {
+        self.load_tokens(&report.token_changes).await;
+        Ok(self.renderer.first_seen(block, report, wallet, format))
+    }
+
+    /// Alert for the large transfers of a watched token in a block. Addresses
+    /// in `labels` are shown by name
+    pub fn generate_token_transfers(
+        &self,
+        block: &Block,
+        watch: &TokenWatchWithContext,
+        transfers: &[(TxAndPosition, Transfer)],
+        labels: &HashMap<Address, String>,
+        format: MessageFormat,
+    ) -> String {
+        self.renderer
+            .token_transfers(block, &watch.name, transfers, labels, format)
+    }
 
-                writeln!(
-                    &mut message_content,
-                    "{token_link}: {amount}",
-                    token_link =
-                        utils::token_owner_link(chain, token, &wallet.address, Some(escape(&symbol.to_string())),),
-                    amount = escape(&format_token_amount(change, decimals, 8)),
-                )?;
+    /// Alert for the large transfers on the chain in a block, of any token the
+    /// scanner covers
+    pub async fn generate_large_transfers(
+        &mut self,
+        block: &Block,
+        watch: &LargeTransferWatchWithContext,
+        transfers: &[(TxAndPosition, Transfer)],
+        labels: &HashMap<Address, String>,
+        format: MessageFormat,
+    ) -> String {
+        for (_, transfer) in transfers {
+            if transfer.token == NATIVE_TOKEN {
+                continue;
+            }
+            if let Err(err) = self.load_symbol_and_decimal(&transfer.token).await {
+                warn!(token = %transfer.token, "Failed to load token info: {err:#}");
             }
         }
 
+        self.renderer
+            .token_transfers(block, &watch.name, transfers, labels, format)
+    }
+
+    pub fn native_decimals(&self) -> u8 {
+        self.renderer.native_decimals
+    }
+
+    /// Alert for the emissions of a watched event in a block
+    pub fn generate_events(
+        &self,
+        block: &Block,
+        watch: &EventWatchWithContext,
+        events: &[(TxAndPosition, EventFields)],
+        format: MessageFormat,
+    ) -> String {
+        self.renderer.events(block, watch, events, format)
+    }
+
+    /// Alert for the transfers of a report to exchange addresses
+    pub async fn generate_cex_deposits(
+        &mut self,
+        block: &Block,
+        report: &PnlReport,
+        wallet: &WalletWithContext,
+        format: MessageFormat,
+    ) -> eyre::Result<String> {
+        self.load_tokens(&report.token_changes).await;
+        Ok(self.renderer.cex_deposits(block, report, wallet, format))
+    }
+
+    /// Alert for a cluster of wallets that crossed a threshold
+    pub fn generate_cluster(&self, block: &Block, alert: &ClusterAlert, format: MessageFormat) -> String {
+        self.renderer.cluster(block, alert, format)
+    }
+
+    /// Alert for a block the wallet built that was reorged out
+    pub fn generate_orphaned(
+        &self,
+        block: &Orphaned,
+        reward: U256,
+        wallet: &WalletWithContext,
+        format: MessageFormat,
+    ) -> String {
+        self.renderer.orphaned(block, reward, wallet, format)
+    }
+
+    /// One message for the reports of several wallets in a block
+    pub async fn generate_combined(
+        &mut self,
+        block: &Block,
+        reports: &[(WalletWithContext, PnlReport)],
+        format: MessageFormat,
+    ) -> eyre::Result<String> {
+        for (_, report) in reports {
+            self.load_tokens(&report.token_changes).await;
+        }
+
+        Ok(self.renderer.combined(block, reports, format))
+    }
+}
+
+impl Renderer {
+    pub fn new(chain: Chain) -> Self {
+        let mut token_info = HashMap::default();
+
+        if chain == Chain::mainnet() {
+            token_info.insert(
+                address!("9f8F72aA9304c8B593d555F12eF6589cC3A579A2"),
+                ("MKR".to_string(), 18),
+            );
+        }
+
+        Self {
+            native_symbol: chain
+                .named()
+                .and_then(|chain| chain.native_currency_symbol())
+                .unwrap_or("ETH")
+                .to_string(),
+            native_decimals: 18,
+            explorer: utils::default_explorer(chain).to_string(),
+            tx_links: vec![TxLink::Phalcon],
+
+            chain,
+            token_info,
+            token_risk: HashMap::new(),
+        }
+    }
+
+    /// Apply the native currency and explorer set for the chain in the config
+    pub fn with_chain_config(mut self, config: &ChainConfig) -> Self {
+        if let Some(symbol) = &config.native_symbol {
+            self.native_symbol = symbol.clone();
+        }
+
+        if let Some(decimals) = config.native_decimals {
+            self.native_decimals = decimals;
+        }
+
+        if let Some(explorer) = &config.explorer {
+            self.explorer = explorer.trim_end_matches('/').to_string();
+        }
+
+        if let Some(tx_links) = &config.tx_links {
+            self.tx_links = tx_links.clone();
+        }
+
+        self
+    }
+
+    pub fn native_decimals(&self) -> u8 {
+        self.native_decimals
+    }
+
+    /// The message of one wallet's report for a block
+    pub fn report(
+        &self,
+        block: &Block,
+        receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
+        report: &PnlReport,
+        wallet: &WalletWithContext,
+        format: MessageFormat,
+    ) -> eyre::Result<String> {
+        let strings = wallet.language.strings();
+        let mut message = Markup::new(format);
+
+        message
+            .link(
+                &wallet.display_name(),
+                &utils::address_url(&self.explorer, &wallet.address),
+            )
+            .text(&format!(" · {} · ", self.chain_tag()))
+            .link(
+                &block.header.number.to_string(),
+                &utils::block_url(&self.explorer, block.header.number),
+            );
+        if !report.builder_reward.is_zero() {
+            message.text(strings.builder_tag);
+        }
+        message.newline();
+
+        if let Some(time) = format_block_time(block.header.timestamp, wallet.timezone) {
+            message.text(&time).newline();
+        }
+
+        self.write_amounts(
+            &mut message,
+            report.pnl,
+            report.stable_pnl,
+            &report.token_changes,
+            &report.token_values_usd,
+            &report.new_tokens,
+            wallet,
+        );
+        if let Some(pnl_usd) = report.pnl_usd {
+            message
+                .text("≈ ")
+                .bold(&wallet.number_format.amount(&pnl_usd, 18, 2))
+                .text(" USD")
+                .newline();
+        }
+        self.write_vault_assets(&mut message, &report.vault_assets, wallet);
+        self.write_perps(&mut message, &report.perps, wallet);
+        self.write_staking(&mut message, &report.staking, wallet);
+        self.write_cex_deposits(&mut message, &report.cex_deposits, wallet);
+
         if !report.validator_bribe.is_zero() {
-            writeln!(
-                &mut message_content,
-                r#"\VBribe: {pnl}"#,
-                pnl = escape(&format_ether_trimmed(&report.validator_bribe)),
-            )?;
+            message
+                .text(&format!(
+                    "{}: {}",
+                    strings.validator_bribe,
+                    self.format_native(&report.validator_bribe, wallet.number_format)
+                ))
+                .newline();
         }
 
+        let tx_links = wallet.tx_links.as_deref().unwrap_or(&self.tx_links);
         let max_index_length = digit_count(report.txs.iter().map(|tx| tx.index).max().unwrap_or(0));
 
         for tx_and_position in &report.txs {
-            let receipt = receipt_and_traces
+            let (receipt, trace) = receipt_and_traces
                 .get(tx_and_position.index as usize)
-                .map(|(r, _)| r)
                 .with_context(|| {
                     format!(
                         "Failed to find receipt and trace for tx at index {}",
@@ -141,25 +481,776 @@ impl<T: Clone + Transport> MessageGenerator<T> {
                 })?;
 
             let index_indent = " ".repeat(max_index_length - digit_count(tx_and_position.index));
+            let status = if receipt.inner.status() { "✓" } else { "✗" };
+            let short_hash = utils::format_short_hash(&tx_and_position.hash);
+            let tx_url = utils::tx_url(&self.explorer, &tx_and_position.hash);
+
+            match format {
+                MessageFormat::Telegram | MessageFormat::Discord | MessageFormat::Slack => {
+                    message
+                        .text("[")
+                        .code(&format!("{index_indent}{}", tx_and_position.index))
+                        .text(&format!("] {status}"))
+                        .link(&short_hash, &tx_url);
+                    self.write_extra_tx_links(&mut message, tx_links, tx_and_position);
+                }
+
+                MessageFormat::Html => {
+                    message
+                        .text(&format!("[{}] {status}", tx_and_position.index))
+                        .link(&tx_and_position.hash.to_string(), &tx_url);
+                    self.write_extra_tx_links(&mut message, tx_links, tx_and_position);
+                    message
+                        .text(" ")
+                        .link(
+                            &receipt.from.to_string(),
+                            &utils::address_url(&self.explorer, &receipt.from),
+                        )
+                        .text(" → ");
+                    match receipt.to {
+                        Some(to) => message.link(&to.to_string(), &utils::address_url(&self.explorer, &to)),
+                        None => message.text(strings.contract_creation),
+                    };
+                    message.text(&format!(" · {} {}", strings.gas, receipt.gas_used));
+                }
 
-            writeln!(
-                &mut message_content,
-                r#"\[`{index_indent}{index}`\] {status}{tx_link} \[{phalcon_link}\]"#,
-                index = tx_and_position.index,
-                status = if receipt.inner.status() { "✓" } else { "✗" },
-                tx_link = utils::tx_link(
-                    self.chain,
-                    &tx_and_position.hash,
-                    Some(escape(&utils::format_short_hash(&tx_and_position.hash)))
-                ),
-                phalcon_link = utils::phalcon_tx(self.chain, &tx_and_position.hash, Some("Phalcon".to_string())),
-            )?;
+                // Plain text has no links, so show the explorer URL itself
+                MessageFormat::Plain => {
+                    message.text(&format!("[{}] {status}{short_hash} {tx_url}", tx_and_position.index));
+                }
+            }
+
+            if report.airdrop_claims.contains(&tx_and_position.hash) {
+                message.text(&format!(" 🪂 {}", strings.airdrop_claim));
+            }
+            message.newline();
+
+            if !receipt.inner.status() {
+                self.write_failure(&mut message, trace, tx_and_position, strings.reverted);
+            }
+        }
+
+        for warning in &report.warnings {
+            message.text(&format!("⚠️ {warning}")).newline();
+        }
+        if report.truncated {
+            message.text(&format!("⚠️ {}", strings.truncated)).newline();
         }
 
-        Ok(message_content)
+        Ok(message.finish())
+    }
+
+    /// Summarise the reports a throttled wallet held back in one message
+    pub fn rollup(&self, rollup: &RollUp, wallet: &WalletWithContext, format: MessageFormat) -> String {
+        let strings = wallet.language.strings();
+        let mut message = Markup::new(format);
+
+        message
+            .link(
+                &wallet.display_name(),
+                &utils::address_url(&self.explorer, &wallet.address),
+            )
+            .text(&format!(
+                " · {} · {} {}",
+                self.chain_tag(),
+                rollup.blocks.len(),
+                strings.blocks
+            ))
+            .newline();
+
+        self.write_amounts(
+            &mut message,
+            rollup.pnl,
+            rollup.stable_pnl,
+            &rollup.token_changes,
+            &BTreeMap::new(),
+            &[],
+            wallet,
+        );
+        self.write_pnl_by_category(&mut message, &rollup.pnl_by_category, wallet);
+
+        for (block, txs) in &rollup.blocks {
+            message
+                .link(&block.to_string(), &utils::block_url(&self.explorer, *block))
+                .text(": ");
+            self.write_tx_links(&mut message, txs);
+            message.newline();
+        }
+
+        message.finish()
+    }
+
+    /// The tokens of a report the wallet received for the first time, empty
+    /// when there are none
+    pub fn first_seen(
+        &self,
+        block: &Block,
+        report: &PnlReport,
+        wallet: &WalletWithContext,
+        format: MessageFormat,
+    ) -> String {
+        let mut received = report.token_changes.clone();
+        received.retain(|token, change| report.new_tokens.contains(token) && change.is_positive());
+        if received.is_empty() {
+            return String::new();
+        }
+
+        let mut message = Markup::new(format);
+        message
+            .link(
+                &wallet.display_name(),
+                &utils::address_url(&self.explorer, &wallet.address),
+            )
+            .text(&format!(" · {} · ", self.chain_tag()))
+            .link(
+                &block.header.number.to_string(),
+                &utils::block_url(&self.explorer, block.header.number),
+            )
+            .newline()
+            .bold(wallet.language.strings().first_receipt)
+            .newline();
+
+        self.write_token_changes(&mut message, &received, &BTreeMap::new(), &[], wallet, "");
+        self.write_tx_links(&mut message, &report.txs);
+        message.newline();
+
+        message.finish()
+    }
+
+    /// Large transfers in a block, a line per transfer
+    pub fn token_transfers(
+        &self,
+        block: &Block,
+        name: &str,
+        transfers: &[(TxAndPosition, Transfer)],
+        labels: &HashMap<Address, String>,
+        format: MessageFormat,
+    ) -> String {
+        let mut message = Markup::new(format);
+
+        message
+            .text(&format!("{} · {} · ", name, self.chain_tag()))
+            .link(
+                &block.header.number.to_string(),
+                &utils::block_url(&self.explorer, block.header.number),
+            )
+            .newline();
+
+        for (tx, transfer) in transfers {
+            let (symbol, decimals) = self.token_name(&transfer.token);
+            message
+                .link(
+                    &utils::format_short_hash(&tx.hash),
+                    &utils::tx_url(&self.explorer, &tx.hash),
+                )
+                .text(": ");
+            self.write_address(&mut message, &transfer.from, labels);
+            message.text(" → ");
+            self.write_address(&mut message, &transfer.to, labels);
+            message
+                .text(": ")
+                .bold(&format!(
+                    "{} {symbol}",
+                    utils::format_units(transfer.value, decimals, 4)
+                ))
+                .newline();
+        }
+
+        message.finish()
+    }
+
+    /// Emissions of a watched event in a block, each with its decoded fields
+    pub fn events(
+        &self,
+        block: &Block,
+        watch: &EventWatchWithContext,
+        events: &[(TxAndPosition, EventFields)],
+        format: MessageFormat,
+    ) -> String {
+        let mut message = Markup::new(format);
+
+        if watch.high_severity {
+            message.text("🚨 ");
+        }
+        message
+            .text(&format!("{} · {} · ", watch.name, self.chain_tag()))
+            .link(
+                &block.header.number.to_string(),
+                &utils::block_url(&self.explorer, block.header.number),
+            )
+            .newline();
+
+        for (tx, fields) in events {
+            message
+                .link(
+                    &utils::format_short_hash(&tx.hash),
+                    &utils::tx_url(&self.explorer, &tx.hash),
+                )
+                .text(": ")
+                .bold(&watch.event.name)
+                .newline();
+
+            for (name, value) in fields {
+                message.text(&format!("  {name}: ")).code(value).newline();
+            }
+        }
+
+        message.finish()
+    }
+
+    /// An address by its label, or shortened if it has none
+    fn write_address(&self, message: &mut Markup, address: &Address, labels: &HashMap<Address, String>) {
+        let name = labels
+            .get(address)
+            .cloned()
+            .unwrap_or_else(|| format_short_address(address));
+        message.link(&name, &utils::address_url(&self.explorer, address));
+    }
+
+    /// The CEX deposits of a report on their own, empty when there are none
+    pub fn cex_deposits(
+        &self,
+        block: &Block,
+        report: &PnlReport,
+        wallet: &WalletWithContext,
+        format: MessageFormat,
+    ) -> String {
+        if report.cex_deposits.is_empty() {
+            return String::new();
+        }
+
+        let mut message = Markup::new(format);
+        message
+            .link(
+                &wallet.display_name(),
+                &utils::address_url(&self.explorer, &wallet.address),
+            )
+            .text(&format!(" · {} · ", self.chain_tag()))
+            .link(
+                &block.header.number.to_string(),
+                &utils::block_url(&self.explorer, block.header.number),
+            )
+            .newline();
+
+        self.write_cex_deposits(&mut message, &report.cex_deposits, wallet);
+        self.write_tx_links(&mut message, &report.txs);
+        message.newline();
+
+        message.finish()
+    }
+
+    /// A block the wallet built that was reorged out, with the reward reported
+    /// for it that was never earned
+    pub fn orphaned(
+        &self,
+        block: &Orphaned,
+        reward: U256,
+        wallet: &WalletWithContext,
+        format: MessageFormat,
+    ) -> String {
+        let strings = wallet.language.strings();
+        let mut message = Markup::new(format);
+
+        message
+            .link(
+                &wallet.display_name(),
+                &utils::address_url(&self.explorer, &wallet.address),
+            )
+            .text(&format!(" · {} · ", self.chain_tag()))
+            .link(
+                &block.number.to_string(),
+                &utils::block_url(&self.explorer, block.number),
+            )
+            .text(strings.builder_tag)
+            .newline()
+            .text(&format!("⚠️ {} {} {}", strings.orphaned, block.depth, strings.blocks))
+            .newline()
+            .text(&format!("{}: ", self.native_symbol))
+            .bold(&format!("-{}", self.format_native(&reward, wallet.number_format)))
+            .newline();
+
+        message.finish()
+    }
+
+    /// The PnL of each wallet of a channel over a UTC day, one line each, and
+    /// under builders the blocks they lost. Wallets drawn on the chart sent
+    /// with it are marked by their line color
+    pub fn digest(&self, date: &str, days: &[WalletDay], charted: bool, format: MessageFormat) -> String {
+        let Some(first) = days.first() else {
+            return String::new();
+        };
+        let strings = first.wallet.language.strings();
+        let mut message = Markup::new(format);
+
+        message
+            .bold(strings.daily_digest)
+            .text(&format!(" · {} · {date}", self.chain_tag()))
+            .newline();
+
+        for (i, day) in days.iter().enumerate() {
+            let wallet = &day.wallet;
+            let pnl = day.reports.iter().fold(I256::ZERO, |sum, r| sum + r.report.pnl);
+            let built = day.reports.iter().filter(|r| !r.report.builder_reward.is_zero());
+            let (blocks_built, builder_reward) = built.fold((0, U256::ZERO), |(blocks, reward), r| {
+                (blocks + 1, reward + r.report.builder_reward)
+            });
+
+            if charted {
+                message.text(&format!("{} ", digest::marker(i)));
+            }
+            message
+                .link(
+                    &wallet.display_name(),
+                    &utils::address_url(&self.explorer, &wallet.address),
+                )
+                .text(": ")
+                .bold(&self.format_signed_native(pnl, wallet.number_format))
+                .text(&format!(" {}", self.native_symbol));
+            if let Some(pnl_usd) = digest::pnl_usd(&day.reports) {
+                message
+                    .text(" ≈ ")
+                    .bold(&wallet.number_format.amount(&pnl_usd, 18, 2))
+                    .text(" USD");
+            }
+            message.text(&format!(" · {} {}", day.reports.len(), strings.reports));
+            if blocks_built > 0 {
+                message.text(&format!(
+                    " · {} {blocks_built} {}, {} {}",
+                    strings.builder_tag,
+                    strings.blocks,
+                    self.format_native(&builder_reward, wallet.number_format),
+                    self.native_symbol
+                ));
+            }
+            message.newline();
+
+            if let Some(missed) = day
+                .missed
+                .as_ref()
+                .filter(|missed| missed.blocks > 0 || missed.unknown > 0)
+            {
+                message.text(&format!("  {}: {} {}", strings.missed, missed.blocks, strings.blocks));
+                if let Some(profit) = missed.profit {
+                    message
+                        .text(" ≈ ")
+                        .bold(&format!("-{}", self.format_native(&profit, wallet.number_format)))
+                        .text(&format!(" {}", self.native_symbol));
+                }
+                message.text(&format!(
+                    ", {} {} {}",
+                    strings.outbid_by,
+                    self.format_native(&missed.outbid_by, wallet.number_format),
+                    self.native_symbol
+                ));
+                if missed.unknown > 0 {
+                    message.text(&format!(", {} {}", missed.unknown, strings.unknown));
+                }
+                message.newline();
+            }
+        }
+
+        if charted {
+            let unit = if digest::in_usd(days) {
+                "USD"
+            } else {
+                &self.native_symbol
+            };
+            message
+                .text(&format!("📈 {} ({unit})", strings.cumulative_pnl))
+                .newline();
+        }
+
+        message.finish()
+    }
+
+    /// The PnL of a cluster's wallets over its window, with each wallet's part
+    pub fn cluster(&self, block: &Block, alert: &ClusterAlert, format: MessageFormat) -> String {
+        let strings = alert.cluster.language.strings();
+        let mut message = Markup::new(format);
+
+        let verb = match alert.trigger {
+            ClusterTrigger::Loss => strings.cluster_loss,
+            ClusterTrigger::Profit => strings.cluster_profit,
+        };
+
+        message
+            .text(&format!("🚨 {} · {} · ", alert.cluster.name, self.chain_tag()))
+            .link(
+                &block.header.number.to_string(),
+                &utils::block_url(&self.explorer, block.header.number),
+            )
+            .newline()
+            .text(&format!("{verb} "))
+            .bold(&format!(
+                "{} {}",
+                self.format_signed_native(alert.total, alert.cluster.number_format),
+                self.native_symbol
+            ))
+            .text(&format!(
+                " {} {} {}",
+                strings.in_last,
+                alert.cluster.window.as_secs() / 60,
+                strings.minutes
+            ))
+            .newline();
+
+        for (wallet, pnl) in &alert.by_wallet {
+            message
+                .text(&format!("  {wallet}: "))
+                .code(&self.format_signed_native(*pnl, alert.cluster.number_format))
+                .newline();
+        }
+
+        message.finish()
+    }
+
+    /// One message for the reports of several wallets in a block, a line per
+    /// wallet with its token changes below. Language and timezone are taken
+    /// from the first wallet, as they are set per channel.
+    pub fn combined(&self, block: &Block, reports: &[(WalletWithContext, PnlReport)], format: MessageFormat) -> String {
+        let Some((first, _)) = reports.first() else {
+            return String::new();
+        };
+        let strings = first.language.strings();
+        let mut message = Markup::new(format);
+
+        message
+            .text(&format!("{} · ", self.chain_tag()))
+            .link(
+                &block.header.number.to_string(),
+                &utils::block_url(&self.explorer, block.header.number),
+            )
+            .newline();
+
+        if let Some(time) = format_block_time(block.header.timestamp, first.timezone) {
+            message.text(&time).newline();
+        }
+
+        // Most important first. Wallets with a negative priority are folded
+        let mut reports = reports.iter().collect::<Vec<_>>();
+        reports.sort_by_key(|(wallet, _)| std::cmp::Reverse(wallet.priority));
+        let folded_at = reports
+            .iter()
+            .position(|(wallet, _)| wallet.priority < 0)
+            .unwrap_or(reports.len());
+        let (shown, folded) = reports.split_at(folded_at);
+
+        for (wallet, report) in shown {
+            self.write_combined_report(&mut message, wallet, report);
+        }
+
+        if !folded.is_empty() {
+            let mut folded_message = Markup::new(format);
+            for (wallet, report) in folded {
+                self.write_combined_report(&mut folded_message, wallet, report);
+            }
+
+            message.fold(&format!("{} {}", folded.len(), strings.more_wallets), folded_message);
+        }
+
+        message.finish()
+    }
+
+    /// A wallet's line in a combined message, with its token changes below
+    fn write_combined_report(&self, message: &mut Markup, wallet: &WalletWithContext, report: &PnlReport) {
+        message.link(
+            &wallet.display_name(),
+            &utils::address_url(&self.explorer, &wallet.address),
+        );
+        if !report.builder_reward.is_zero() {
+            message.text(&format!(" {}", wallet.language.strings().builder_tag));
+        }
+
+        message
+            .text(": ")
+            .bold(&self.format_signed_native(report.pnl, wallet.number_format))
+            .text(&format!(" {} · ", self.native_symbol));
+        self.write_tx_links(message, &report.txs);
+        message.newline();
+
+        self.write_token_changes(
+            message,
+            &report.token_changes,
+            &report.token_values_usd,
+            &report.new_tokens,
+            wallet,
+            "    ",
+        );
+    }
+
+    /// Native PnL, stablecoin PnL and token change lines
+    fn write_amounts(
+        &self,
+        message: &mut Markup,
+        pnl: I256,
+        stable_pnl: I256,
+        token_changes: &BalanceChange,
+        usd_values: &BTreeMap<Address, I256>,
+        new_tokens: &[Address],
+        wallet: &WalletWithContext,
+    ) {
+        message
+            .text(&format!("{}: ", self.native_symbol))
+            .bold(&self.format_signed_native(pnl, wallet.number_format))
+            .newline();
+
+        if !stable_pnl.is_zero() {
+            message
+                .text("USD: ")
+                .bold(&wallet.number_format.amount(&stable_pnl, 18, 2))
+                .newline();
+        }
+
+        self.write_token_changes(message, token_changes, usd_values, new_tokens, wallet, "");
+    }
+
+    /// `Trading: +0.1 · Gas: -0.002` over the categories with a PnL
+    fn write_pnl_by_category(
+        &self,
+        message: &mut Markup,
+        pnl_by_category: &BTreeMap<PnlCategory, I256>,
+        wallet: &WalletWithContext,
+    ) {
+        let parts = pnl_by_category
+            .iter()
+            .filter(|(_, pnl)| !pnl.is_zero())
+            .map(|(category, pnl)| {
+                format!(
+                    "{}: {}",
+                    wallet.language.strings().category(*category),
+                    self.format_signed_native(*pnl, wallet.number_format)
+                )
+            })
+            .collect::<Vec<_>>();
+
+        if !parts.is_empty() {
+            message.text(&parts.join(" · ")).newline();
+        }
+    }
+
+    fn write_token_changes(
+        &self,
+        message: &mut Markup,
+        token_changes: &BalanceChange,
+        usd_values: &BTreeMap<Address, I256>,
+        new_tokens: &[Address],
+        wallet: &WalletWithContext,
+        indent: &str,
+    ) {
+        for (token, change) in token_changes.iter() {
+            let (symbol, decimals) = self.token_name(token);
+
+            message
+                .text(indent)
+                .link(&symbol, &utils::token_owner_url(&self.explorer, token, &wallet.address))
+                .text(&format!(": {}", wallet.number_format.amount(change, decimals, 8)));
+            if let Some(usd) = usd_values.get(token) {
+                message.text(&format!(" ≈ {} USD", wallet.number_format.amount(usd, 18, 2)));
+            }
+            if new_tokens.contains(token) {
+                message.text(" ").bold(wallet.language.strings().new_token);
+            }
+            if let Some(reason) = self.token_risk.get(token) {
+                message.text(&format!(" ⚠️ {reason}"));
+            }
+            message.newline();
+        }
+    }
+
+    /// `≈ 100 USDC` under the vault shares a report's vault deposits and
+    /// withdrawals changed
+    fn write_vault_assets(&self, message: &mut Markup, vault_assets: &[VaultAssets], wallet: &WalletWithContext) {
+        for vault_assets in vault_assets {
+            let (vault, _) = self.token_name(&vault_assets.vault);
+            let (asset, decimals) = self.token_name(&vault_assets.asset);
+
+            message
+                .text(&format!(
+                    "{vault} ≈ {} {asset}",
+                    wallet.number_format.amount(&vault_assets.assets, decimals, 8)
+                ))
+                .newline();
+        }
+    }
+
+    /// `GMX: PnL 12.5 USD · fees 1.2 USD` per perps protocol
+    fn write_perps(&self, message: &mut Markup, perps: &[PerpsPnl], wallet: &WalletWithContext) {
+        for pnl in perps {
+            message
+                .text(&format!("{}: PnL ", pnl.protocol.name()))
+                .bold(&format!(
+                    "{} USD",
+                    wallet.number_format.amount(&pnl.realized_pnl, 18, 2)
+                ))
+                .text(&format!(
+                    " · {} {} USD",
+                    wallet.language.strings().fees,
+                    wallet.number_format.amount(&pnl.fees, 18, 2)
+                ))
+                .newline();
+        }
+    }
+
+    /// `🥩 Lido deposit: 32 ETH` per staking flow, which is not in the PnL
+    fn write_staking(&self, message: &mut Markup, flows: &[StakingFlow], wallet: &WalletWithContext) {
+        let strings = wallet.language.strings();
+        for flow in flows {
+            let (symbol, decimals) = self.token_name(&flow.token);
+            let kind = match flow.kind {
+                StakingFlowKind::Deposit => strings.staking_deposit,
+                StakingFlowKind::WithdrawalRequest => strings.staking_withdrawal_request,
+                StakingFlowKind::Claim => strings.staking_claim,
+            };
+
+            let amount = wallet.number_format.amount(&I256::from_raw(flow.amount), decimals, 8);
+
+            message
+                .text(&format!("🥩 {} {kind}: {amount} {symbol}", flow.protocol.name()))
+                .newline();
+        }
+    }
+
+    /// `📤 CEX deposit (Binance): 1.5 ETH` per transfer to an exchange
+    fn write_cex_deposits(&self, message: &mut Markup, deposits: &[CexDeposit], wallet: &WalletWithContext) {
+        for deposit in deposits {
+            let (symbol, decimals) = self.token_name(&deposit.token);
+
+            message
+                .text(&format!("📤 {} (", wallet.language.strings().cex_deposit))
+                .link(&deposit.exchange, &utils::address_url(&self.explorer, &deposit.to))
+                .text(&format!(
+                    "): {} {symbol}",
+                    wallet.number_format.amount(&deposit.amount, decimals, 8)
+                ))
+                .newline();
+        }
+    }
+
+    /// Symbol and decimals of a token, or its short address if unknown
+    fn token_name(&self, token: &Address) -> (String, u8) {
+        if *token == NATIVE_TOKEN {
+            return (self.native_symbol.clone(), self.native_decimals);
+        }
+
+        match self.token_info.get(token) {
+            Some((symbol, decimals)) => (TokenName::Symbol(symbol).to_string(), *decimals),
+            None => (TokenName::Address(token).to_string(), 18),
+        }
+    }
+
+    /// Why a tx reverted, with a link opening it in a debugger
+    fn write_failure(&self, message: &mut Markup, trace: &CallFrame, tx: &TxAndPosition, label: &str) {
+        let reason = trace
+            .revert_reason
+            .as_deref()
+            .or(trace.error.as_deref())
+            .unwrap_or("unknown");
+
+        message.text(&format!("    {label}: {reason}"));
+        if message.has_links() {
+            message
+                .text(" [")
+                .link("Debugger", &utils::tenderly_debugger_url(self.chain, &tx.hash))
+                .text("]");
+        }
+        message.newline();
+    }
+
+    /// ` [Phalcon] [Tenderly]` after a tx
+    fn write_extra_tx_links(&self, message: &mut Markup, tx_links: &[TxLink], tx: &TxAndPosition) {
+        for tx_link in tx_links {
+            message
+                .text(" [")
+                .link(tx_link.label(), &tx_link.url(self.chain, &tx.hash))
+                .text("]");
+        }
+    }
+
+    fn write_tx_links(&self, message: &mut Markup, txs: &[TxAndPosition]) {
+        for (i, tx) in txs.iter().enumerate() {
+            if i > 0 {
+                message.text(", ");
+            }
+            message.link(
+                &utils::format_short_hash(&tx.hash),
+                &utils::tx_url(&self.explorer, &tx.hash),
+            );
+        }
+    }
+
+    fn chain_tag(&self) -> String {
+        format!("#{}", self.chain.to_string().to_uppercase())
+    }
+
+    fn format_signed_native(&self, value: I256, format: NumberFormat) -> String {
+        format.signed_native(&value, self.native_decimals)
+    }
+
+    fn format_native(&self, value: &U256, format: NumberFormat) -> String {
+        format.native(value, self.native_decimals)
     }
 }
 
+/// Block time in the timezone of the channel, e.g. `2024-11-15 22:27:59 CST`
+fn format_block_time(timestamp: u64, timezone: Tz) -> Option<String> {
+    let time = DateTime::from_timestamp(timestamp.try_into().ok()?, 0)?.with_timezone(&timezone);
+    Some(time.format("%Y-%m-%d %H:%M:%S %Z").to_string())
+}
+
+/// Write a report page rendered as [`MessageFormat::Html`] under the permalink
+/// directory, returning its public URL. The page is named after a hash of the
+/// report's key and the secret, the same when the report is made again
+async fn write_page(
+    permalink: &PermalinkConfig,
+    chain: Chain,
+    block: u64,
+    wallet: &WalletWithContext,
+    content: &str,
+) -> eyre::Result<String> {
+    let key = keccak256(format!(
+        "{}:{}:{block}:{:#x}",
+        permalink.secret,
+        chain.id(),
+        wallet.address
+    ));
+    let path = format!("{}/{block}-{}.html", chain.id(), hex::encode(&key[..16]));
+
+    let mut title = Markup::new(MessageFormat::Html);
+    title.text(&format!("{} · {chain} · {block}", wallet.display_name()));
+
+    let page = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{title}</title></head>\n<body><pre>\n{content}</pre></body>\n</html>\n",
+        title = title.finish(),
+    );
+
+    let file = std::path::Path::new(&permalink.dir).join(&path);
+    if let Some(parent) = file.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .context("Failed to create permalink directory")?;
+    }
+    tokio::fs::write(&file, page)
+        .await
+        .with_context(|| format!("Failed to write {}", file.display()))?;
+
+    Ok(format!("{}/{path}", permalink.base_url.trim_end_matches('/')))
+}
+
+/// How a message is marked up for the service it is sent to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Telegram MarkdownV2
+    Telegram,
+    /// Discord markdown
+    Discord,
+    /// Slack mrkdwn, sent as the sections of a Block Kit message
+    Slack,
+    /// No markup, for SMS-like services
+    Plain,
+    /// HTML for report pages, with more details than fit in a chat message
+    Html,
+}
+
 fn digit_count(n: u64) -> usize {
     n.to_string().len()
 }
@@ -177,3 +1268,166 @@ impl std::fmt::Display for TokenName<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{config::AlertTo, digest::Missed, processor::REPORT_VERSION, storage::StoredReport},
+        alloy::primitives::B256,
+    };
+
+    const WALLET: Address = address!("1111111111111111111111111111111111111111");
+    const TOKEN: Address = address!("2222222222222222222222222222222222222222");
+
+    /// Compare `rendered` to `testdata/messages/<name>.txt`, or rewrite the
+    /// file with it when `UPDATE_GOLDEN` is set
+    fn assert_golden(name: &str, rendered: &str) {
+        let path = format!("{}/testdata/messages/{name}.txt", env!("CARGO_MANIFEST_DIR"));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, rendered).unwrap();
+            return;
+        }
+
+        let golden = std::fs::read_to_string(&path).unwrap_or_else(|err| panic!("Failed to read {path}: {err}"));
+        assert_eq!(rendered, golden, "{name} changed, UPDATE_GOLDEN=1 rewrites {path}");
+    }
+
+    /// A name with the characters MarkdownV2 reserves for links and italics
+    fn wallet() -> WalletWithContext {
+        WalletWithContext::new(
+            "Searcher_[1]".to_string(),
+            WALLET,
+            None,
+            vec![],
+            false,
+            Arc::new(AlertTo::default()),
+        )
+    }
+
+    /// Thousandths of ether, or of USD, in 18 decimals
+    fn milli(value: i64) -> I256 {
+        I256::try_from(value).unwrap() * I256::from_raw(U256::from(1_000_000_000_000_000_u64))
+    }
+
+    fn report(pnl: I256) -> PnlReport {
+        PnlReport {
+            version: REPORT_VERSION,
+            txs: vec![],
+            pnl,
+            pnl_by_category: Default::default(),
+            stable_pnl: I256::ZERO,
+            pnl_usd: None,
+            token_values_usd: Default::default(),
+            builder_reward: U256::ZERO,
+            validator_bribe: U256::ZERO,
+            token_changes: Default::default(),
+            new_tokens: vec![],
+            airdrop_claims: vec![],
+            vaults: vec![],
+            vault_assets: vec![],
+            perps: vec![],
+            staking: vec![],
+            cex_deposits: vec![],
+            warnings: vec![],
+            truncated: false,
+        }
+    }
+
+    fn stored(block: u64, timestamp: u64, report: PnlReport) -> StoredReport {
+        StoredReport {
+            chain: 1,
+            block,
+            timestamp,
+            wallet: "Searcher_[1]".to_string(),
+            address: WALLET,
+            report,
+        }
+    }
+
+    #[test]
+    fn markup_spans() {
+        let mut message = Markup::new(MessageFormat::Telegram);
+        message
+            .text("1+1=2 #tag {x}|y~ >.!")
+            .newline()
+            .bold("-0.5")
+            .newline()
+            .code("a`b\\c")
+            .newline()
+            .link("Foo_Bar", "https://example.com/a_(b)");
+
+        assert_golden("markup_spans", &message.finish());
+    }
+
+    #[test]
+    fn report_with_tokens_and_warnings() {
+        let mut renderer = Renderer::new(Chain::mainnet());
+        // A symbol that would open bold, italics and a link unescaped
+        renderer.token_info.insert(TOKEN, ("PEPE*_[x]".to_string(), 6));
+
+        let mut block = Block::default();
+        block.header.number = 21_000_000;
+        block.header.timestamp = 1_700_000_000;
+
+        let mut report = report(milli(1500));
+        report.pnl_usd = Some(milli(3_000_000));
+        report.builder_reward = U256::from(100_000_000_000_000_000_u64);
+        report.token_changes.insert(TOKEN, I256::try_from(-2_500_000).unwrap());
+        report.token_values_usd.insert(TOKEN, milli(-2500));
+        report.new_tokens.push(TOKEN);
+        report.warnings.push("Sandwiched (check the bundle!)".to_string());
+
+        let rendered = renderer
+            .report(&block, &[], &report, &wallet(), MessageFormat::Telegram)
+            .unwrap();
+        assert_golden("report", &rendered);
+    }
+
+    #[test]
+    fn orphaned() {
+        let renderer = Renderer::new(Chain::mainnet());
+        let block = Orphaned {
+            number: 21_000_000,
+            hash: B256::ZERO,
+            depth: 2,
+            rewards: vec![],
+        };
+
+        let rendered = renderer.orphaned(
+            &block,
+            U256::from(50_000_000_000_000_000_u64),
+            &wallet(),
+            MessageFormat::Telegram,
+        );
+        assert_golden("orphaned", &rendered);
+    }
+
+    #[test]
+    fn digest() {
+        let renderer = Renderer::new(Chain::mainnet());
+
+        let mut won = report(milli(1500));
+        won.pnl_usd = Some(milli(3_000_000));
+        won.builder_reward = U256::from(100_000_000_000_000_000_u64);
+        let mut lost = report(milli(-500));
+        lost.pnl_usd = Some(milli(-1_000_000));
+
+        let days = [WalletDay {
+            wallet: wallet(),
+            reports: vec![
+                stored(21_000_000, 1_729_728_000, won),
+                stored(21_000_100, 1_729_729_200, lost),
+            ],
+            missed: Some(Missed {
+                blocks: 3,
+                outbid_by: U256::from(20_000_000_000_000_000_u64),
+                profit: Some(U256::from(10_000_000_000_000_000_u64)),
+                unknown: 0,
+            }),
+        }];
+
+        let rendered = renderer.digest("2024-10-24", &days, true, MessageFormat::Telegram);
+        assert_golden("digest", &rendered);
+    }
+}
@@ -0,0 +1,32 @@
+use {
+    crate::processor::{PnlReport, REPORT_VERSION},
+    eyre::{ensure, Context, ContextCompat},
+    serde_json::{Map, Value},
+};
+
+/// Upgrades of a serialized report by the version they upgrade from. Append
+/// one, and bump [`REPORT_VERSION`], whenever a field is renamed or changes
+/// shape. New fields with a default need none.
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[
+    // 0: reports from before versioning, whose shape is the same as version 1
+    |_| {},
+];
+
+/// Read a serialized report of any version, upgrading it to the current one.
+/// Reports without a version are from before versioning.
+pub fn migrate_report(mut value: Value) -> eyre::Result<PnlReport> {
+    let report = value.as_object_mut().context("Report is not an object")?;
+
+    let version = report.get("version").and_then(Value::as_u64).unwrap_or_default() as usize;
+    ensure!(
+        version <= REPORT_VERSION as usize,
+        "Report version {version} is newer than this build's {REPORT_VERSION}"
+    );
+
+    for migration in &MIGRATIONS[version..] {
+        migration(report);
+    }
+    report.insert("version".to_string(), REPORT_VERSION.into());
+
+    serde_json::from_value(value).context("Failed to parse report")
+}
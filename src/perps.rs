@@ -0,0 +1,127 @@
+use {
+    crate::{config::WalletWithContext, contract::GmxVault, utils::primitive_log_decode},
+    alloy::{
+        primitives::{address, Address, B256, I256, U256},
+        rpc::types::AnyTransactionReceipt,
+    },
+    alloy_chains::{Chain, NamedChain},
+    schemars::JsonSchema,
+    serde::{Deserialize, Serialize},
+    std::collections::HashMap,
+};
+
+/// Perps protocols whose position events are decoded into reports. Token
+/// transfers alone only show the margin going in and out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PerpsProtocol {
+    /// GMX v1 on Arbitrum and Avalanche
+    Gmx,
+}
+
+impl PerpsProtocol {
+    pub fn name(self) -> &'static str {
+        match self {
+            PerpsProtocol::Gmx => "GMX",
+        }
+    }
+}
+
+/// Realized PnL and fees of a wallet's positions on a protocol, in USD with 18
+/// decimals. They are already part of the margin in `token_changes`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Eq, PartialEq)]
+pub struct PerpsPnl {
+    pub protocol: PerpsProtocol,
+    #[schemars(with = "String")]
+    pub realized_pnl: I256,
+    #[schemars(with = "String")]
+    pub fees: I256,
+}
+
+/// Position events of the wallet in `receipts` on each of `protocols`
+pub fn decode<'a>(
+    protocols: &[PerpsProtocol],
+    chain: Chain,
+    receipts: impl Iterator<Item = &'a AnyTransactionReceipt> + Clone,
+    wallet: &WalletWithContext,
+) -> Vec<PerpsPnl> {
+    protocols
+        .iter()
+        .filter_map(|protocol| match protocol {
+            PerpsProtocol::Gmx => decode_gmx(gmx_vault(chain)?, receipts.clone(), wallet),
+        })
+        .collect()
+}
+
+fn gmx_vault(chain: Chain) -> Option<Address> {
+    match chain.named()? {
+        NamedChain::Arbitrum => Some(address!("489ee077994B6658eAfA855C308275EAd8097C4A")),
+        NamedChain::Avalanche => Some(address!("9ab2De34A33fB459b538c43f251eB825645e8595")),
+        _ => None,
+    }
+}
+
+fn decode_gmx<'a>(
+    vault: Address,
+    receipts: impl Iterator<Item = &'a AnyTransactionReceipt>,
+    wallet: &WalletWithContext,
+) -> Option<PerpsPnl> {
+    let mut pnl = PerpsPnl {
+        protocol: PerpsProtocol::Gmx,
+        realized_pnl: I256::ZERO,
+        fees: I256::ZERO,
+    };
+    let mut found = false;
+
+    for receipt in receipts {
+        let logs = receipt
+            .inner
+            .inner
+            .logs()
+            .iter()
+            .filter(|log| log.inner.address == vault)
+            .map(|log| &log.inner)
+            .collect::<Vec<_>>();
+
+        // The PnL of a decrease is emitted in its own event, by position key
+        let deltas = logs
+            .iter()
+            .filter_map(|log| primitive_log_decode::<GmxVault::UpdatePnl>(log))
+            .map(|e| {
+                let delta = gmx_usd(e.delta);
+                (e.key, if e.hasProfit { delta } else { -delta })
+            })
+            .collect::<HashMap<B256, I256>>();
+
+        for log in logs {
+            if let Some(e) = primitive_log_decode::<GmxVault::IncreasePosition>(log) {
+                if wallet.involved_wallets().contains(&e.account) {
+                    pnl.fees += gmx_usd(e.fee);
+                    found = true;
+                }
+            } else if let Some(e) = primitive_log_decode::<GmxVault::DecreasePosition>(log) {
+                if wallet.involved_wallets().contains(&e.account) {
+                    pnl.fees += gmx_usd(e.fee);
+                    pnl.realized_pnl += deltas.get(&e.key).copied().unwrap_or_default();
+                    found = true;
+                }
+            } else if let Some(e) = primitive_log_decode::<GmxVault::LiquidatePosition>(log) {
+                if wallet.involved_wallets().contains(&e.account) {
+                    pnl.realized_pnl += e.realisedPnl / gmx_scale();
+                    found = true;
+                }
+            }
+        }
+    }
+
+    found.then_some(pnl)
+}
+
+/// GMX prices USD with 30 decimals
+fn gmx_usd(value: U256) -> I256 {
+    I256::from_raw(value) / gmx_scale()
+}
+
+fn gmx_scale() -> I256 {
+    I256::from_raw(U256::from(10).pow(U256::from(12)))
+}
@@ -0,0 +1,450 @@
+use {
+    crate::{
+        chain_adapter::{self, ChainRules},
+        config::{ChainConfig, PriceConfig, NATIVE_TOKEN},
+        contract::{AggregatorV3::AggregatorV3Instance, ERC20::ERC20Instance},
+        processor::PnlReport,
+        rpc_metrics,
+    },
+    alloy::{
+        eips::BlockId,
+        primitives::{address, Address, I256, U256},
+        providers::Provider,
+        transports::Transport,
+    },
+    alloy_chains::{Chain, NamedChain},
+    eyre::{bail, Context},
+    serde::Deserialize,
+    std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tokio::time::Instant,
+    tracing::debug,
+};
+
+/// Seconds around the block time CoinGecko prices are looked for in
+const COINGECKO_WINDOW: u64 = 60 * 60;
+
+/// Seconds from the block time a price found for another block is reused
+/// within
+const PRICE_TTL: u64 = 5 * 60;
+
+/// How long a token no source had a price for isn't asked about again
+const NEGATIVE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How long a token is left alone after a source failed on it, e.g. when
+/// rate limited
+const ERROR_TTL: Duration = Duration::from_secs(60);
+
+/// Cached prices past which the expired ones are dropped
+const MAX_CACHED_PRICES: usize = 10_000;
+
+/// Days of CoinGecko prices kept across coins, past which they are dropped
+const MAX_CACHED_DAYS: usize = 10_000;
+
+/// When the next CoinGecko request may be made. Shared by every chain, as the
+/// limit is per API key
+static NEXT_COINGECKO_REQUEST: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Where USD prices come from. The oracle asks its sources in turn until one
+/// knows the token.
+#[burberry::async_trait]
+pub trait PriceSource: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// USD price of one whole `token`, [`NATIVE_TOKEN`] for the native one, as
+    /// of `block` mined at `timestamp`. `None` if the source doesn't know it
+    async fn price(&self, chain: Chain, token: Address, block: u64, timestamp: u64) -> eyre::Result<Option<f64>>;
+}
+
+/// Values reports in USD at the prices of their block
+pub struct PriceOracle<T> {
+    chain: Chain,
+    rules: ChainRules,
+    provider: Arc<dyn Provider<T>>,
+    sources: Vec<Box<dyn PriceSource>>,
+    /// Chainlink feeds by token, to read them through another provider
+    chainlink_feeds: HashMap<Address, Address>,
+    native_decimals: u8,
+    decimals: HashMap<Address, u8>,
+    /// Last price looked up by token, reused for blocks close to it
+    prices: HashMap<Address, CachedPrice>,
+}
+
+struct CachedPrice {
+    /// Block time the price is at
+    timestamp: u64,
+    price: Option<f64>,
+    /// Until when a missing price isn't looked up again
+    retry_at: Instant,
+}
+
+impl CachedPrice {
+    fn is_fresh(&self, timestamp: u64, now: Instant) -> bool {
+        match self.price {
+            Some(_) => self.timestamp.abs_diff(timestamp) <= PRICE_TTL,
+            None => now < self.retry_at,
+        }
+    }
+}
+
+impl<T: Clone + Transport> PriceOracle<T> {
+    /// Chainlink feeds first, the known ones of the native token and the
+    /// chain's `price_feeds` by token, then CoinGecko for the native token and
+    /// the tokens of its `coingecko_ids`
+    pub fn new(chain: Chain, provider: Arc<dyn Provider<T>>, config: &PriceConfig, chain_config: &ChainConfig) -> Self {
+        let mut chainlink_feeds = chain_config.price_feeds.clone();
+        if let Some(feed) = native_feed(chain) {
+            chainlink_feeds.entry(NATIVE_TOKEN).or_insert(feed);
+        }
+
+        let sources: Vec<Box<dyn PriceSource>> = vec![
+            Box::new(Chainlink {
+                provider: Arc::clone(&provider),
+                feeds: chainlink_feeds.clone(),
+            }),
+            Box::new(CoinGecko {
+                client: reqwest::Client::new(),
+                url: config.coingecko_url.trim_end_matches('/').to_string(),
+                api_key: config.coingecko_api_key.clone(),
+                ids: chain_config.coingecko_ids.clone(),
+                request_interval: Duration::from_secs(60) / config.coingecko_requests_per_minute.max(1),
+                days: Mutex::new(HashMap::new()),
+            }),
+        ];
+
+        Self {
+            chain,
+            rules: chain_config.rules(),
+            provider,
+            sources,
+            chainlink_feeds,
+            native_decimals: chain_config.native_decimals.unwrap_or(18),
+            decimals: HashMap::new(),
+            prices: HashMap::new(),
+        }
+    }
+
+    /// Make calls through `provider` from now on, e.g. after reconnecting
+    pub fn set_provider(&mut self, provider: Arc<dyn Provider<T>>) {
+        self.sources[0] = Box::new(Chainlink {
+            provider: Arc::clone(&provider),
+            feeds: self.chainlink_feeds.clone(),
+        });
+        self.provider = provider;
+    }
+
+    /// Fill in `pnl_usd` and `token_values_usd` at the prices of `block`,
+    /// mined at `timestamp`. Wrapped native tokens are priced as the native
+    /// one and stablecoins at one USD. Tokens without a price are left out of
+    /// `pnl_usd`, which stays unset when the native token has none.
+    pub async fn value_report(&mut self, block: u64, timestamp: u64, report: &mut PnlReport) {
+        let Some(native_price) = self.price(block, timestamp, NATIVE_TOKEN).await else {
+            return;
+        };
+
+        let stables = self.rules.stables.clone();
+        let wrapped_natives = chain_adapter::wrapped_natives(self.chain, &self.rules);
+
+        let changes = report
+            .token_changes
+            .iter()
+            .map(|(token, change)| (*token, *change))
+            .collect::<Vec<_>>();
+
+        let mut total = usd_value(report.pnl, self.native_decimals, native_price);
+        for (token, change) in changes {
+            let value = if let Some(decimals) = stables.get(&token) {
+                usd_value(change, *decimals, 1.0)
+            } else {
+                let price = if wrapped_natives.contains(&token) {
+                    Some(native_price)
+                } else {
+                    self.price(block, timestamp, token).await
+                };
+                let Some(price) = price else {
+                    continue;
+                };
+                let decimals = match self.decimals(token).await {
+                    Ok(decimals) => decimals,
+                    Err(err) => {
+                        debug!(%token, "Failed to get decimals: {err:#}");
+                        continue;
+                    }
+                };
+                usd_value(change, decimals, price)
+            };
+
+            total += value;
+            report.token_values_usd.insert(token, value);
+        }
+
+        report.pnl_usd = Some(total);
+    }
+
+    /// Price of `token` from the first source that knows it. Prices are
+    /// reused for blocks within [`PRICE_TTL`] of the one they were found at,
+    /// and tokens without one aren't looked up again for a while
+    async fn price(&mut self, block: u64, timestamp: u64, token: Address) -> Option<f64> {
+        let now = Instant::now();
+        if let Some(cached) = self.prices.get(&token).filter(|c| c.is_fresh(timestamp, now)) {
+            return cached.price;
+        }
+
+        let mut price = None;
+        let mut failed = false;
+        for source in &self.sources {
+            match source.price(self.chain, token, block, timestamp).await {
+                Ok(Some(found)) => {
+                    price = Some(found);
+                    break;
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    failed = true;
+                    debug!(source = source.name(), %token, "Failed to get price: {err:#}");
+                }
+            }
+        }
+
+        if self.prices.len() >= MAX_CACHED_PRICES {
+            self.prices
+                .retain(|_, cached| cached.price.is_none() && now < cached.retry_at);
+        }
+        self.prices.insert(
+            token,
+            CachedPrice {
+                timestamp,
+                price,
+                retry_at: now + if failed { ERROR_TTL } else { NEGATIVE_TTL },
+            },
+        );
+        price
+    }
+
+    async fn decimals(&mut self, token: Address) -> eyre::Result<u8> {
+        if let Some(decimals) = self.decimals.get(&token) {
+            return Ok(*decimals);
+        }
+
+        rpc_metrics::record(self.chain, "eth_call", 1);
+        let decimals = ERC20Instance::new(token, self.provider.root())
+            .decimals()
+            .call()
+            .await
+            .context("Failed to get decimals for token")?
+            ._0;
+
+        self.decimals.insert(token, decimals);
+        Ok(decimals)
+    }
+}
+
+/// `amount` of a token with `decimals` at `price`, in USD with 18 decimals
+fn usd_value(amount: I256, decimals: u8, price: f64) -> I256 {
+    let price = I256::from_raw(U256::from((price * 1e18) as u128));
+    let unit = I256::from_raw(U256::from(10).pow(U256::from(decimals)));
+    amount * price / unit
+}
+
+/// Chainlink feeds in USD, read at the block
+struct Chainlink<T> {
+    provider: Arc<dyn Provider<T>>,
+    feeds: HashMap<Address, Address>,
+}
+
+#[burberry::async_trait]
+impl<T: Clone + Transport> PriceSource for Chainlink<T> {
+    fn name(&self) -> &'static str {
+        "chainlink"
+    }
+
+    async fn price(&self, chain: Chain, token: Address, block: u64, _timestamp: u64) -> eyre::Result<Option<f64>> {
+        let Some(feed) = self.feeds.get(&token) else {
+            return Ok(None);
+        };
+
+        rpc_metrics::record(chain, "eth_call", 2);
+        let aggregator = AggregatorV3Instance::new(*feed, self.provider.root());
+        let decimals = aggregator
+            .decimals()
+            .block(BlockId::number(block))
+            .call()
+            .await
+            .context("Failed to get feed decimals")?
+            ._0;
+        let answer = aggregator
+            .latestRoundData()
+            .block(BlockId::number(block))
+            .call()
+            .await
+            .context("Failed to get feed answer")?
+            .answer;
+
+        if answer.is_negative() || answer.is_zero() {
+            return Ok(None);
+        }
+
+        let answer: f64 = answer.to_string().parse().context("Invalid feed answer")?;
+        Ok(Some(answer / 10f64.powi(decimals as i32)))
+    }
+}
+
+/// USD/native feed of the chains it is known for
+fn native_feed(chain: Chain) -> Option<Address> {
+    match chain.named()? {
+        NamedChain::Mainnet => Some(address!("5f4eC3Df9cbd43714FE2740f5E3616155c5b8419")),
+        NamedChain::BinanceSmartChain => Some(address!("0567F2323251f0Aab15c8dFb1967E4e8A7D42aeE")),
+        NamedChain::Polygon => Some(address!("AB594600376Ec9fD91F8e885dADF0CE036862dE0")),
+        NamedChain::Arbitrum => Some(address!("639Fe6ab55C921f74e7fac1ee960C0B6293ba612")),
+        NamedChain::Optimism => Some(address!("13e3Ee699D1909E989722E753853AE30b17e08c5")),
+        NamedChain::Base => Some(address!("71041dddad3595F9CEd3DcCFBe3D1F4b0a16Bb70")),
+        _ => None,
+    }
+}
+
+/// Prices of the hour around the block from the CoinGecko API, for the
+/// native token and the tokens given an id. Requests are spaced out by
+/// `request_interval`. Blocks of a day that is over get the prices of the
+/// whole day at once, kept for the other blocks of that day
+struct CoinGecko {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+    /// CoinGecko coin ids by token
+    ids: HashMap<Address, String>,
+    request_interval: Duration,
+    /// Prices of past days by coin id and day since the epoch, as
+    /// millisecond timestamp and price pairs
+    days: Mutex<HashMap<(String, u64), Vec<(u64, f64)>>>,
+}
+
+impl CoinGecko {
+    /// Wait for the next request slot, and book the one after
+    async fn wait_for_slot(&self) {
+        let slot = {
+            let mut next = NEXT_COINGECKO_REQUEST.lock().unwrap();
+            let now = Instant::now();
+            let slot = next.filter(|t| *t > now).unwrap_or(now);
+            *next = Some(slot + self.request_interval);
+            slot
+        };
+
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+#[derive(Deserialize)]
+struct MarketChart {
+    /// Millisecond timestamp and price pairs
+    prices: Vec<(u64, f64)>,
+}
+
+#[burberry::async_trait]
+impl PriceSource for CoinGecko {
+    fn name(&self) -> &'static str {
+        "coingecko"
+    }
+
+    async fn price(&self, chain: Chain, token: Address, _block: u64, timestamp: u64) -> eyre::Result<Option<f64>> {
+        let id = if token == NATIVE_TOKEN {
+            match native_coin(chain) {
+                Some(id) => id,
+                None => return Ok(None),
+            }
+        } else {
+            match self.ids.get(&token) {
+                Some(id) => id.as_str(),
+                None => return Ok(None),
+            }
+        };
+        // A day's prices don't change once it's over, with the window
+        // after it
+        let day = timestamp / 86400;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let (from, to, day_key) = if (day + 1) * 86400 + COINGECKO_WINDOW <= now {
+            let key = (id.to_string(), day);
+            if let Some(prices) = self.days.lock().unwrap().get(&key) {
+                return Ok(closest(prices, timestamp));
+            }
+            (
+                (day * 86400).saturating_sub(COINGECKO_WINDOW),
+                (day + 1) * 86400 + COINGECKO_WINDOW,
+                Some(key),
+            )
+        } else {
+            (
+                timestamp.saturating_sub(COINGECKO_WINDOW),
+                timestamp + COINGECKO_WINDOW,
+                None,
+            )
+        };
+        let url = format!(
+            "{}/coins/{id}/market_chart/range?vs_currency=usd&from={from}&to={to}",
+            self.url,
+        );
+
+        let mut request = self.client.get(url);
+        if let Some(api_key) = &self.api_key {
+            let header = if self.url.contains("pro-api") {
+                "x-cg-pro-api-key"
+            } else {
+                "x-cg-demo-api-key"
+            };
+            request = request.header(header, api_key);
+        }
+
+        self.wait_for_slot().await;
+        let response = request.send().await.context("Failed to reach CoinGecko")?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            bail!("CoinGecko answered {}", response.status());
+        }
+
+        let body = response.text().await.context("Failed to read CoinGecko response")?;
+        let chart: MarketChart = serde_json::from_str(&body).context("Invalid CoinGecko response")?;
+        let price = closest(&chart.prices, timestamp);
+
+        if let Some(key) = day_key {
+            let mut days = self.days.lock().unwrap();
+            if days.len() >= MAX_CACHED_DAYS {
+                days.clear();
+            }
+            days.insert(key, chart.prices);
+        }
+
+        Ok(price)
+    }
+}
+
+/// The price of `prices` closest to `timestamp`, if one is within
+/// [`COINGECKO_WINDOW`] of it
+fn closest(prices: &[(u64, f64)], timestamp: u64) -> Option<f64> {
+    prices
+        .iter()
+        .map(|(time, price)| ((time / 1000).abs_diff(timestamp), *price))
+        .filter(|(distance, _)| *distance <= COINGECKO_WINDOW)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, price)| price)
+}
+
+/// CoinGecko id of the native coin of a chain
+fn native_coin(chain: Chain) -> Option<&'static str> {
+    match chain.named()? {
+        NamedChain::Mainnet => Some("ethereum"),
+        NamedChain::BinanceSmartChain => Some("binancecoin"),
+        NamedChain::Polygon => Some("polygon-ecosystem-token"),
+        NamedChain::Arbitrum => Some("ethereum"),
+        NamedChain::Optimism => Some("ethereum"),
+        NamedChain::Base => Some("ethereum"),
+        NamedChain::Avalanche => Some("avalanche-2"),
+        _ => None,
+    }
+}
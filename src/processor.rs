@@ -1,44 +1,143 @@
 use {
     crate::{
         balance_changes::{BalanceChange, BalanceChanges},
-        config::{WalletWithContext, NATIVE_TOKEN},
-        contract::{ERC20, WETH9},
-        utils::{is_weth9, primitive_log_decode, U256AsDecimalStr},
+        category::{self, PnlCategory},
+        chain_adapter::{self, ChainRules, ProducerModel, TransferSource},
+        config::{TraceConfig, TraceLimits, WalletWithContext, NATIVE_TOKEN},
+        contract::{AirdropDistributor, ERC20, ERC4626, WETH9},
+        exchanges,
+        perps::PerpsPnl,
+        staking::{self, StakingFlow},
+        utils::{primitive_log_decode, U256AsDecimalStr},
+        vault::VaultAssets,
     },
     alloy::{
         network::ReceiptResponse,
         primitives::{Address, TxHash, I256, U256},
         rpc::types::{
-            trace::geth::{CallConfig, CallFrame, GethDebugBuiltInTracerType, GethDebugTracingOptions},
+            trace::geth::{
+                CallConfig, CallFrame, CallLogFrame, GethDebugBuiltInTracerType, GethDebugTracerType,
+                GethDebugTracingOptions,
+            },
             AnyTransactionReceipt, Header,
         },
+        sol_types::SolCall,
     },
     alloy_chains::Chain,
-    eyre::{eyre, Context, ContextCompat},
+    eyre::{Context, ContextCompat},
+    schemars::JsonSchema,
     serde::{Deserialize, Serialize},
     serde_with::serde_as,
-    std::collections::{HashSet, VecDeque},
-    tracing::{info_span, instrument, trace},
+    std::{
+        collections::{BTreeMap, HashMap, HashSet, VecDeque},
+        time::Duration,
+    },
+    tracing::{info_span, instrument, trace, warn},
 };
 
+/// Version of the serialized [`PnlReport`]. Bump it along with a migration
+/// in [`crate::migration`] when a field is renamed or changes shape
+pub const REPORT_VERSION: u32 = 1;
+
 #[serde_as]
-#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Eq, PartialEq)]
 pub struct PnlReport {
+    /// [`REPORT_VERSION`] when the report was made, 0 if before versioning
+    #[serde(default)]
+    pub version: u32,
+
+    /// `<hash>:<index in block>`
     #[serde_as(as = "Vec<TxAndPositionAsStr>")]
+    #[schemars(with = "Vec<String>")]
     pub txs: Vec<TxAndPosition>,
 
+    #[schemars(with = "String")]
     pub pnl: I256,
 
+    /// `pnl` broken down by what it came from. The parts add up to `pnl`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[schemars(with = "BTreeMap<PnlCategory, String>")]
+    pub pnl_by_category: BTreeMap<PnlCategory, I256>,
+
+    /// Net change of the configured stablecoins at one USD each, in 18
+    /// decimals. They stay in `token_changes` as well.
+    #[serde(default, skip_serializing_if = "I256::is_zero")]
+    #[schemars(with = "String")]
+    pub stable_pnl: I256,
+
+    /// `pnl` and the token changes with a known price, in USD with 18
+    /// decimals at the prices of the block. Only when prices are set up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub pnl_usd: Option<I256>,
+
+    /// USD value of the changes in `token_changes` with a known price, in 18
+    /// decimals
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[schemars(with = "BTreeMap<String, String>")]
+    pub token_values_usd: BTreeMap<Address, I256>,
+
     #[serde(default, skip_serializing_if = "U256::is_zero")]
     #[serde_as(as = "U256AsDecimalStr")]
+    #[schemars(with = "String")]
     pub builder_reward: U256,
 
     #[serde(default, skip_serializing_if = "U256::is_zero")]
     #[serde_as(as = "U256AsDecimalStr")]
+    #[schemars(with = "String")]
     pub validator_bribe: U256,
 
     #[serde(default, skip_serializing_if = "BalanceChange::is_empty")]
     pub token_changes: BalanceChange,
+
+    /// Tokens in `token_changes` the wallet never held before. Only filled in
+    /// when first-seen tracking is on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(with = "Vec<String>")]
+    pub new_tokens: Vec<Address>,
+
+    /// Txs in `txs` where the wallet claimed an airdrop itself. They are
+    /// reported even when they look like a spam airdrop.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(with = "Vec<String>")]
+    pub airdrop_claims: Vec<TxHash>,
+
+    /// ERC-4626 vaults the wallet deposited into or withdrew from. Their
+    /// shares are in `token_changes`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(with = "Vec<String>")]
+    pub vaults: Vec<Address>,
+
+    /// What the share changes of `vaults` are worth in the underlying asset
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vault_assets: Vec<VaultAssets>,
+
+    /// Positions closed on the perps protocols set up for the chain
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub perps: Vec<PerpsPnl>,
+
+    /// Staking deposits, withdrawal requests and claims. What they moved is
+    /// left out of `pnl` and `token_changes`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub staking: Vec<StakingFlow>,
+
+    /// Transfers out of the wallet to known exchange addresses
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cex_deposits: Vec<CexDeposit>,
+
+    /// Problems met while processing the block, e.g. txs skipped for a
+    /// malformed trace. The report may be incomplete when this isn't empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+
+    /// Some trace of the block was cut by the trace limits, so transfers may
+    /// be missing
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub truncated: bool,
+}
+
+fn is_false(v: &bool) -> bool {
+    !v
 }
 
 impl PnlReport {
@@ -49,13 +148,43 @@ impl PnlReport {
 
 pub fn process_block(
     chain: Chain,
+    rules: &ChainRules,
+    header: &Header,
+    receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
+    wallets: &[WalletWithContext],
+) -> eyre::Result<Vec<Option<PnlReport>>> {
+    process_block_inner(chain, rules, header, receipt_and_traces, wallets, false, vec![])
+}
+
+/// Lenient variant of [`process_block`]: a tx whose balance changes can't be
+/// generated is skipped instead of failing the block. `warnings` holds the
+/// problems found before this point; all of them are attached to every report
+/// of the block, as a skipped tx may have involved any wallet.
+pub fn process_block_lenient(
+    chain: Chain,
+    rules: &ChainRules,
+    header: &Header,
+    receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
+    wallets: &[WalletWithContext],
+    warnings: Vec<String>,
+) -> eyre::Result<Vec<Option<PnlReport>>> {
+    process_block_inner(chain, rules, header, receipt_and_traces, wallets, true, warnings)
+}
+
+fn process_block_inner(
+    chain: Chain,
+    rules: &ChainRules,
     header: &Header,
     receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
     wallets: &[WalletWithContext],
+    lenient: bool,
+    mut warnings: Vec<String>,
 ) -> eyre::Result<Vec<Option<PnlReport>>> {
+    let adapter = chain_adapter::for_chain(chain);
     let mut reports = Vec::with_capacity(wallets.len());
 
     let mut balance_changes_all = Vec::with_capacity(receipt_and_traces.len());
+    let exchanges = exchanges::all();
 
     let all_involved_wallets = wallets
         .iter()
@@ -65,14 +194,25 @@ pub fn process_block(
         .collect::<HashSet<_>>();
 
     for (i, (receipt, call_trace)) in receipt_and_traces.iter().enumerate() {
-        let bcs = generate_pnl(chain, receipt, call_trace, None)
-            .with_context(|| format!("Failed to generate balance changes for tx at index {i}"))?;
+        let transfers = match transfers(chain, rules, receipt, call_trace)
+            .with_context(|| format!("Failed to generate balance changes for tx at index {i}"))
+        {
+            Ok(transfers) => transfers,
+            Err(err) if lenient => {
+                warn!(tx = %receipt.transaction_hash, "Skipping tx: {err:#}");
+                warnings.push(format!("{err:#}"));
+                vec![]
+            }
+            Err(err) => return Err(err),
+        };
 
+        let bcs = balance_changes(&transfers, None);
         let filtered_bcs = clone_and_retain_accounts(&bcs, &all_involved_wallets);
 
         balance_changes_all.push(BalanceChangesCache {
             filtered: filtered_bcs,
             full: bcs,
+            native_transfers: transfers.into_iter().filter(|t| t.token == NATIVE_TOKEN).collect(),
         });
     }
 
@@ -80,20 +220,41 @@ pub fn process_block(
         let s = info_span!("by_wallet", wallet = %wallet.address);
         let _g = s.enter();
 
-        let is_builder =
-            chain == Chain::mainnet() && wallet.builder.as_ref().map(|b| header.miner.eq(b)).unwrap_or_default();
+        let producer_model = chain_adapter::producer_model(chain, rules);
+        // A miner may mine to the wallet itself rather than to a builder address
+        let is_producer = match producer_model {
+            ProducerModel::Miner => wallet.is_builder(header.miner) || wallet.address == header.miner,
+            _ => wallet.is_builder(header.miner),
+        };
 
-        let (builder_reward, validator_bribe) = if is_builder {
-            let reward = calculate_builder_reward(
-                header.base_fee_per_gas.expect("Base fee per gas is not set").into(),
-                receipt_and_traces.iter().map(|(r, _)| r),
+        let (builder_reward, validator_bribe) = if is_producer && producer_model != ProducerModel::None {
+            // Tips of the producer's own txs are paid to itself, and left out
+            // of their fee below
+            let fees = calculate_builder_reward(
+                chain_adapter::burnt_base_fee(chain, rules, header),
+                receipt_and_traces.iter().map(|(r, _)| r).filter(|r| {
+                    !adapter.is_system_tx(header, r) &&
+                        !adapter.is_deposit_tx(r) &&
+                        !wallet.involved_wallets().contains(&r.from)
+                }),
             );
 
-            let bribe = find_validator_bribe(&balance_changes_all);
-
-            trace!(builder_reward = ?reward, validate_bribe = %bribe);
-
-            (reward, bribe)
+            match producer_model {
+                ProducerModel::Builder => {
+                    let bribe = find_validator_bribe(header.miner, &balance_changes_all, wallet);
+                    trace!(builder_reward = ?fees, validate_bribe = %bribe);
+                    (fees, bribe)
+                }
+                ProducerModel::Miner => {
+                    let subsidy = chain_adapter::block_subsidy(chain, rules, header.number);
+                    trace!(miner_fees = ?fees, block_subsidy = ?subsidy);
+                    (fees + subsidy, U256::ZERO)
+                }
+                _ => {
+                    trace!(validator_reward = ?fees);
+                    (fees, U256::ZERO)
+                }
+            }
         } else {
             (U256::ZERO, U256::ZERO)
         };
@@ -102,8 +263,16 @@ pub fn process_block(
             .iter()
             .enumerate()
             .filter_map(|(i, bc)| {
-                let involved = bc.filtered.keys().any(|w| wallet.involved_wallets().contains(w));
-                (involved && !is_shitcoin_airdrop(&bc.full)).then_some((receipt_and_traces[i].0.clone(), bc))
+                let (receipt, call_trace) = &receipt_and_traces[i];
+                let involved = bc.filtered.keys().any(|w| wallet.involved_wallets().contains(w)) &&
+                    !chain_adapter::is_excluded_tx(chain, rules, header, receipt);
+                let is_claim = is_airdrop_claim(receipt, call_trace, wallet);
+                (involved && (is_claim || !is_shitcoin_airdrop(&bc.full))).then_some((
+                    receipt.clone(),
+                    call_trace,
+                    bc,
+                    is_claim,
+                ))
             })
             .collect::<Vec<_>>();
 
@@ -114,17 +283,32 @@ pub fn process_block(
 
         let mut total_fee = I256::ZERO;
         let mut token_changes = BalanceChange::default();
+        let mut staking = vec![];
+        let mut pnl_by_category = BTreeMap::<PnlCategory, I256>::new();
 
-        for (receipt, bcs) in &all_involved_txs {
+        for (receipt, call_trace, bcs, _) in &all_involved_txs {
             let mut fee = I256::ZERO;
 
             if wallet.involved_wallets().contains(&receipt.from) {
                 fee = calculate_tx_fee(chain, receipt)?;
+                if is_producer && producer_model != ProducerModel::None {
+                    // The tip of a tx in its own block comes straight back
+                    fee -= I256::from_raw(priority_fee(
+                        chain_adapter::burnt_base_fee(chain, rules, header),
+                        receipt,
+                    ));
+                }
                 total_fee += fee;
             }
 
             let recipient = receipt.from.eq(&wallet.address).then_some(receipt.to).flatten();
-            let bc = merge_accounts(&bcs.filtered, wallet.involved_wallets(), recipient);
+            let mut bc = merge_accounts(&bcs.filtered, wallet.involved_wallets(), recipient);
+
+            let flows = staking::find_flows(chain, std::iter::once(receipt), wallet);
+            staking::exclude(chain, &flows, &mut bc);
+            let category = category::classify(chain, rules, receipt, call_trace, wallet, &flows);
+            *pnl_by_category.entry(category).or_default() += bc.clone().extract_ether(chain, rules);
+            staking.extend(flows);
 
             trace!(
                 tx.index = receipt.transaction_index.unwrap(),
@@ -140,11 +324,16 @@ pub fn process_block(
 
         token_changes.retain_non_zero();
 
-        let ether_pnl = token_changes.extract_ether(chain) - total_fee + I256::from_raw(builder_reward);
+        let ether_pnl = token_changes.extract_ether(chain, rules) - total_fee + I256::from_raw(builder_reward);
+        let stable_pnl = token_changes.sum_stables(&rules.stables);
+
+        *pnl_by_category.entry(PnlCategory::Gas).or_default() -= total_fee;
+        *pnl_by_category.entry(PnlCategory::BlockProduction).or_default() += I256::from_raw(builder_reward);
+        pnl_by_category.retain(|_, pnl| !pnl.is_zero());
 
         let mut txs: Vec<TxAndPosition> = all_involved_txs
             .iter()
-            .map(|(receipt, _)| TxAndPosition {
+            .map(|(receipt, ..)| TxAndPosition {
                 index: receipt.transaction_index.unwrap(),
                 hash: receipt.transaction_hash,
             })
@@ -152,12 +341,39 @@ pub fn process_block(
 
         txs.sort_by_key(|t| t.index);
 
+        let vaults = find_vaults(all_involved_txs.iter().map(|(r, ..)| r), wallet, &token_changes);
+
+        let cex_deposits = all_involved_txs
+            .iter()
+            .flat_map(|(receipt, _, bcs, _)| find_cex_deposits(receipt, &bcs.full, wallet, &exchanges))
+            .collect();
+
+        let airdrop_claims = all_involved_txs
+            .iter()
+            .filter(|(.., is_claim)| *is_claim)
+            .map(|(receipt, ..)| receipt.transaction_hash)
+            .collect();
+
         reports.push(Some(PnlReport {
+            version: REPORT_VERSION,
             txs,
             pnl: ether_pnl,
+            pnl_by_category,
+            stable_pnl,
+            pnl_usd: None,
+            token_values_usd: BTreeMap::new(),
             token_changes,
+            new_tokens: vec![],
+            airdrop_claims,
+            vaults,
+            vault_assets: vec![],
+            perps: vec![],
+            staking,
+            cex_deposits,
             builder_reward,
             validator_bribe,
+            warnings: warnings.clone(),
+            truncated: false,
         }));
     }
 
@@ -167,35 +383,66 @@ pub fn process_block(
 #[instrument(skip_all, fields(tx = %receipt.transaction_hash))]
 pub fn generate_pnl(
     chain: Chain,
+    rules: &ChainRules,
     receipt: &AnyTransactionReceipt,
     call_trace: &CallFrame,
     only_addresses: Option<&HashSet<Address>>,
 ) -> eyre::Result<BalanceChanges> {
+    Ok(balance_changes(
+        &transfers(chain, rules, receipt, call_trace)?,
+        only_addresses,
+    ))
+}
+
+/// Net changes of `transfers`, only of those touching `only_addresses` if set
+fn balance_changes(transfers: &[Transfer], only_addresses: Option<&HashSet<Address>>) -> BalanceChanges {
+    let is_relevant_address = |addr: &Address| only_addresses.map(|set| set.contains(addr)).unwrap_or(true);
+
     let mut bcs = BalanceChanges::default();
+
+    for transfer in transfers {
+        if !is_relevant_address(&transfer.from) && !is_relevant_address(&transfer.to) {
+            continue;
+        }
+
+        bcs.append_transfer(transfer.token, transfer.from, transfer.to, transfer.value);
+    }
+
+    bcs.retain_non_zero();
+
+    bcs
+}
+
+/// A movement of a token, or of the native token as [`NATIVE_TOKEN`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transfer {
+    pub token: Address,
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+}
+
+/// Token transfer logs and value transfers of a tx. Reverted calls, and
+/// failed txs as a whole, move nothing.
+pub fn transfers(
+    chain: Chain,
+    rules: &ChainRules,
+    receipt: &AnyTransactionReceipt,
+    call_trace: &CallFrame,
+) -> eyre::Result<Vec<Transfer>> {
+    if let TransferSource::ReceiptLogs { native_token } = chain_adapter::for_chain(chain).transfer_source() {
+        return Ok(transfers_from_receipt_logs(receipt, native_token));
+    }
+
+    let mut transfers = vec![];
     if !receipt.status() {
-        return Ok(bcs);
+        return Ok(transfers);
     }
 
     let mut stack = VecDeque::with_capacity(1024);
     stack.push_front(call_trace);
 
-    let weth: Address = chain
-        .named()
-        .and_then(|c| c.wrapped_native_token())
-        .context("WETH address not found. Chain is not supported")?
-        .0
-         .0
-        .into();
-
-    macro_rules! is_relevant_address {
-        ($addr:expr) => {
-            only_addresses.is_none() ||
-                only_addresses
-                    .as_ref()
-                    .map(|set| set.contains($addr))
-                    .unwrap_or_default()
-        };
-    }
+    let weth9_tokens = chain_adapter::weth9_tokens(chain, rules);
 
     while let Some(frame) = stack.pop_front() {
         if frame.error.is_some() || frame.revert_reason.is_some() {
@@ -204,32 +451,11 @@ pub fn generate_pnl(
         }
 
         for log in &frame.logs {
-            let log = alloy::primitives::Log::new(
-                log.address.context("Log address is not set")?,
-                log.topics.clone().unwrap_or_default(),
-                log.data.clone().unwrap_or_default(),
-            )
-            .context("Log is invalid")?;
-
-            let (token, from, to, value) = if let Some(transfer) = primitive_log_decode::<ERC20::Transfer>(&log) {
-                (log.address, transfer.from, transfer.to, transfer.value)
-            } else if log.address.as_slice() == weth.as_slice() && is_weth9(chain) {
-                if let Some(withdrawal) = primitive_log_decode::<WETH9::Withdrawal>(&log) {
-                    (weth, withdrawal.src, Address::ZERO, withdrawal.wad)
-                } else if let Some(deposit) = primitive_log_decode::<WETH9::Deposit>(&log) {
-                    (weth, Address::ZERO, deposit.dst, deposit.wad)
-                } else {
-                    continue;
-                }
-            } else {
-                continue;
-            };
+            let log = to_primitive_log(log)?;
 
-            if !is_relevant_address!(&from) && !is_relevant_address!(&to) {
-                continue;
+            if let Some((token, from, to, value)) = decode_transfer(&weth9_tokens, &log) {
+                transfers.push(Transfer { token, from, to, value });
             }
-
-            bcs.append_transfer(token, from, to, value);
         }
 
         stack.extend(frame.calls.iter());
@@ -239,34 +465,160 @@ pub fn generate_pnl(
             continue;
         }
 
-        match frame.typ.as_str() {
-            "CALL" | "CALLCODE" | "CREATE" | "CREATE2" | "SELFDESTRUCT" => {
-                let to = frame.to.unwrap_or_default();
-                let from = frame.from;
+        if let "CALL" | "CALLCODE" | "CREATE" | "CREATE2" | "SELFDESTRUCT" = frame.typ.as_str() {
+            transfers.push(Transfer {
+                token: NATIVE_TOKEN,
+                from: frame.from,
+                to: frame.to.unwrap_or_default(),
+                value,
+            });
+        }
+    }
 
-                if !is_relevant_address!(&from) && !is_relevant_address!(&to) {
-                    continue;
-                }
+    Ok(transfers)
+}
 
-                bcs.append_transfer(NATIVE_TOKEN, from, to, value);
-            }
+/// Transfers from the `Transfer` logs of the receipt, for chains whose tracer
+/// doesn't return logs. See [`TransferSource::ReceiptLogs`].
+fn transfers_from_receipt_logs(receipt: &AnyTransactionReceipt, native_token: Address) -> Vec<Transfer> {
+    receipt
+        .inner
+        .inner
+        .logs()
+        .iter()
+        .filter_map(|log| {
+            let transfer = primitive_log_decode::<ERC20::Transfer>(&log.inner)?;
 
-            _ => continue,
-        };
+            let token = if log.inner.address == native_token {
+                NATIVE_TOKEN
+            } else {
+                log.inner.address
+            };
+
+            Some(Transfer {
+                token,
+                from: transfer.from,
+                to: transfer.to,
+                value: transfer.value,
+            })
+        })
+        .collect()
+}
+
+/// Number of frames and depth of a call trace
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraceSize {
+    pub frames: usize,
+    pub depth: usize,
+}
+
+impl TraceSize {
+    pub fn of(call_trace: &CallFrame) -> Self {
+        let mut size = Self::default();
+        let mut stack = vec![(call_trace, 1)];
+
+        while let Some((frame, depth)) = stack.pop() {
+            size.frames += 1;
+            size.depth = size.depth.max(depth);
+            stack.extend(frame.calls.iter().map(|call| (call, depth + 1)));
+        }
+
+        size
     }
+}
 
-    bcs.retain_non_zero();
+/// Drop the frames of a trace beyond `limits`, returning whether any was
+/// dropped. Frames are kept in call order, so the outer calls survive.
+pub fn truncate_trace(call_trace: &mut CallFrame, limits: &TraceLimits) -> bool {
+    if limits.max_depth.is_none() && limits.max_frames.is_none() {
+        return false;
+    }
 
-    Ok(bcs)
+    let mut frames = 0;
+    truncate_frame(call_trace, 1, limits, &mut frames)
 }
 
-pub fn trace_options() -> GethDebugTracingOptions {
-    GethDebugTracingOptions::default()
-        .with_tracer(GethDebugBuiltInTracerType::CallTracer.into())
-        .with_call_config(CallConfig {
-            only_top_call: Some(false),
-            with_log: Some(true),
-        })
+fn truncate_frame(frame: &mut CallFrame, depth: usize, limits: &TraceLimits, frames: &mut usize) -> bool {
+    *frames += 1;
+
+    if limits.max_depth.is_some_and(|max| depth >= max) {
+        let truncated = !frame.calls.is_empty();
+        frame.calls.clear();
+        return truncated;
+    }
+
+    let mut truncated = false;
+    let mut kept = 0;
+
+    for call in frame.calls.iter_mut() {
+        if limits.max_frames.is_some_and(|max| *frames >= max) {
+            break;
+        }
+
+        truncated |= truncate_frame(call, depth + 1, limits, frames);
+        kept += 1;
+    }
+
+    if kept < frame.calls.len() {
+        frame.calls.truncate(kept);
+        truncated = true;
+    }
+
+    truncated
+}
+
+pub fn to_primitive_log(log: &CallLogFrame) -> eyre::Result<alloy::primitives::Log> {
+    alloy::primitives::Log::new(
+        log.address.context("Log address is not set")?,
+        log.topics.clone().unwrap_or_default(),
+        log.data.clone().unwrap_or_default(),
+    )
+    .context("Log is invalid")
+}
+
+/// Decode a log into `(token, from, to, value)`. Deposits and withdrawals of
+/// `weth9_tokens` are treated as the token minted to / burnt from the account.
+pub fn decode_transfer(
+    weth9_tokens: &[Address],
+    log: &alloy::primitives::Log,
+) -> Option<(Address, Address, Address, U256)> {
+    if let Some(transfer) = primitive_log_decode::<ERC20::Transfer>(log) {
+        return Some((log.address, transfer.from, transfer.to, transfer.value));
+    }
+
+    if !weth9_tokens.contains(&log.address) {
+        return None;
+    }
+
+    let weth = log.address;
+
+    if let Some(withdrawal) = primitive_log_decode::<WETH9::Withdrawal>(log) {
+        Some((weth, withdrawal.src, Address::ZERO, withdrawal.wad))
+    } else {
+        primitive_log_decode::<WETH9::Deposit>(log).map(|deposit| (weth, Address::ZERO, deposit.dst, deposit.wad))
+    }
+}
+
+/// Tracer returning the call tree with the transfer logs and values of each
+/// frame, shaped like a call tracer frame
+const JS_TRACER: &str = include_str!("tracer.js");
+
+pub fn trace_options(config: &TraceConfig) -> GethDebugTracingOptions {
+    let options = if config.js_tracer {
+        GethDebugTracingOptions::default().with_tracer(GethDebugTracerType::JsTracer(JS_TRACER.to_string()))
+    } else {
+        GethDebugTracingOptions::default()
+            .with_tracer(GethDebugBuiltInTracerType::CallTracer.into())
+            .with_call_config(CallConfig {
+                only_top_call: Some(false),
+                with_log: Some(true),
+            })
+    };
+
+    match config.timeout_secs {
+        Some(secs) => options.with_timeout(Duration::from_secs(secs)),
+        None => options,
+    }
 }
 
 fn find_all_receipients<'a>(
@@ -300,6 +652,86 @@ fn clone_and_retain_accounts(bcs: &BalanceChanges, accounts: &HashSet<Address>)
     result
 }
 
+/// Vaults with an ERC-4626 `Deposit` or `Withdraw` for the wallet whose shares
+/// changed
+fn find_vaults<'a>(
+    receipts: impl Iterator<Item = &'a AnyTransactionReceipt>,
+    wallet: &WalletWithContext,
+    token_changes: &BalanceChange,
+) -> Vec<Address> {
+    let mut vaults = vec![];
+
+    for log in receipts.flat_map(|r| r.inner.inner.logs()) {
+        let owner = if let Some(deposit) = primitive_log_decode::<ERC4626::Deposit>(&log.inner) {
+            deposit.owner
+        } else if let Some(withdraw) = primitive_log_decode::<ERC4626::Withdraw>(&log.inner) {
+            withdraw.owner
+        } else {
+            continue;
+        };
+
+        let vault = log.inner.address;
+        if wallet.involved_wallets().contains(&owner) && token_changes.contains_key(&vault) && !vaults.contains(&vault)
+        {
+            vaults.push(vault);
+        }
+    }
+
+    vaults
+}
+
+/// Tokens the wallet sent in a tx that an exchange address received
+fn find_cex_deposits(
+    receipt: &AnyTransactionReceipt,
+    full_bcs: &BalanceChanges,
+    wallet: &WalletWithContext,
+    exchanges: &HashMap<Address, String>,
+) -> Vec<CexDeposit> {
+    let sent = merge_accounts(full_bcs, wallet.involved_wallets(), None);
+    let mut deposits = vec![];
+
+    for (account, bc) in full_bcs.iter() {
+        let Some(exchange) = exchanges.get(account) else {
+            continue;
+        };
+
+        for (token, amount) in bc.iter() {
+            if amount.is_positive() && sent.get(token).is_some_and(|s| s.is_negative()) {
+                deposits.push(CexDeposit {
+                    tx: receipt.transaction_hash,
+                    exchange: exchange.clone(),
+                    to: *account,
+                    token: *token,
+                    amount: *amount,
+                });
+            }
+        }
+    }
+
+    deposits
+}
+
+/// Whether the wallet sent the tx itself to claim from an airdrop distributor,
+/// as opposed to being sent tokens it never asked for
+fn is_airdrop_claim(receipt: &AnyTransactionReceipt, call_trace: &CallFrame, wallet: &WalletWithContext) -> bool {
+    if !wallet.involved_wallets().contains(&receipt.from) {
+        return false;
+    }
+
+    let Some(selector) = call_trace.input.get(..4) else {
+        return false;
+    };
+
+    [
+        AirdropDistributor::claim_0Call::SELECTOR,
+        AirdropDistributor::claim_1Call::SELECTOR,
+        AirdropDistributor::claim_2Call::SELECTOR,
+        AirdropDistributor::claim_3Call::SELECTOR,
+    ]
+    .iter()
+    .any(|s| s.as_slice() == selector)
+}
+
 /// Check the balance changes generated from a tx matched the pattern of a
 /// shitcoin airdrop.
 /// Pattern: multiple tokens are transferred to multiple addresses.
@@ -348,31 +780,56 @@ fn merge_accounts(bcs: &BalanceChanges, accounts: &[Address], recipient: Option<
     bc
 }
 
-fn calculate_tx_fee(chain: Chain, receipt: &AnyTransactionReceipt) -> eyre::Result<I256> {
-    let extra_cost = if chain.is_optimism() {
-        let l1_fee = receipt
-            .other
-            .get("l1Fee")
-            .and_then(|v| v.as_str())
-            .map(|s| s.trim_start_matches("0x").to_string())
-            .unwrap_or_default();
+pub fn calculate_tx_fee(chain: Chain, receipt: &AnyTransactionReceipt) -> eyre::Result<I256> {
+    let adapter = chain_adapter::for_chain(chain);
 
-        U256::from_str_radix(&l1_fee, 16).map_err(|_| eyre!("Failed to parse l1Fee {l1_fee}"))?
-    } else {
-        U256::ZERO
-    };
+    if let TransferSource::ReceiptLogs { .. } = adapter.transfer_source() {
+        // Already in the balance changes as transfers
+        return Ok(I256::ZERO);
+    }
+
+    if adapter.is_deposit_tx(receipt) {
+        // Paid for on the chain it was bridged from
+        return Ok(I256::ZERO);
+    }
+
+    let extra_cost = adapter.extra_fee(receipt)?;
 
-    let fee = U256::from(receipt.gas_used) * U256::from(receipt.effective_gas_price) + extra_cost;
+    // Blob gas is burnt, like the base fee, but priced on its own
+    let blob_fee =
+        U256::from(receipt.blob_gas_used.unwrap_or_default()) * U256::from(receipt.blob_gas_price.unwrap_or_default());
+
+    let fee = U256::from(receipt.gas_used) * U256::from(receipt.effective_gas_price) + blob_fee + extra_cost;
     Ok(I256::from_raw(fee))
 }
 
+/// Priority fees of the txs. Legacy txs pay their whole gas price, so their
+/// tip is what is left above the base fee. A tx priced below the base fee,
+/// e.g. a zero gas price system tx, tips nothing.
 fn calculate_builder_reward<'a>(
     base_fee: u128,
     receipts_iter: impl Iterator<Item = &'a AnyTransactionReceipt>,
 ) -> U256 {
-    receipts_iter
-        .map(|r| U256::from(r.effective_gas_price - base_fee) * U256::from(r.gas_used))
-        .sum()
+    receipts_iter.map(|r| priority_fee(base_fee, r)).sum()
+}
+
+/// Fee of a tx paid to the block producer rather than burnt
+pub fn priority_fee(base_fee: u128, receipt: &AnyTransactionReceipt) -> U256 {
+    U256::from(receipt.effective_gas_price.saturating_sub(base_fee)) * U256::from(receipt.gas_used)
+}
+
+/// A transfer to an exchange address, usually a deposit to the exchange
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Eq, PartialEq)]
+pub struct CexDeposit {
+    #[schemars(with = "String")]
+    pub tx: TxHash,
+    pub exchange: String,
+    #[schemars(with = "String")]
+    pub to: Address,
+    #[schemars(with = "String")]
+    pub token: Address,
+    #[schemars(with = "String")]
+    pub amount: I256,
 }
 
 #[derive(Clone, Eq, PartialEq)]
@@ -431,24 +888,605 @@ impl<'a> std::fmt::Display for PnlReportTxFormatWrapper<'a> {
 struct BalanceChangesCache {
     filtered: BalanceChanges,
     full: BalanceChanges,
+    /// Kept apart from the net changes, which can't tell who paid whom
+    native_transfers: Vec<Transfer>,
 }
 
-fn find_validator_bribe(all_bcs: &[BalanceChangesCache]) -> U256 {
-    let Some(bc) = all_bcs.last() else {
-        return U256::ZERO;
+/// ETH the builder paid out of its payment addresses, the coinbase unless
+/// configured, in its block to anyone but its own wallets. Covers the usual
+/// payment at the end of the block as well as payments mid-block and split
+/// over several txs.
+fn find_validator_bribe(
+    coinbase: Address,
+    balance_changes_all: &[BalanceChangesCache],
+    wallet: &WalletWithContext,
+) -> U256 {
+    let payers = if wallet.payment_addresses.is_empty() {
+        std::slice::from_ref(&coinbase)
+    } else {
+        wallet.payment_addresses.as_slice()
     };
 
-    // Find the largest beneficary
-    bc.full
+    balance_changes_all
         .iter()
-        .filter_map(|(_, bc)| {
-            let ether = match bc.get(&NATIVE_TOKEN) {
-                Some(ether) if ether.is_positive() => ether.into_raw(),
-                _ => return None,
-            };
+        // A tx whose transfers can't be read has already failed the block or
+        // been skipped with a warning on the report
+        .flat_map(|bc| &bc.native_transfers)
+        .filter(|t| payers.contains(&t.from) && !wallet.involved_wallets().contains(&t.to))
+        .map(|t| t.value)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::chain_adapter::FeeMode,
+        alloy::{
+            primitives::{address, B256},
+            sol_types::SolEvent,
+        },
+        alloy_chains::NamedChain,
+        proptest::{collection::vec, prelude::*},
+        serde_json::json,
+    };
+
+    /// A type 2 receipt with no logs, as an RPC returns it
+    fn receipt(from: Address, status: bool, gas_used: u64, effective_gas_price: u128) -> AnyTransactionReceipt {
+        serde_json::from_value(json!({
+            "type": "0x2",
+            "status": if status { "0x1" } else { "0x0" },
+            "cumulativeGasUsed": format!("{gas_used:#x}"),
+            "logs": [],
+            "logsBloom": format!("0x{}", "0".repeat(512)),
+            "transactionHash": TxHash::with_last_byte(1),
+            "transactionIndex": "0x0",
+            "blockHash": TxHash::with_last_byte(2),
+            "blockNumber": "0x1",
+            "from": from,
+            "to": Address::with_last_byte(0xee),
+            "gasUsed": format!("{gas_used:#x}"),
+            "effectiveGasPrice": format!("{effective_gas_price:#x}"),
+            "contractAddress": null,
+        }))
+        .unwrap()
+    }
+
+    /// `receipt` with `fields` set, e.g. a chain's own receipt fields
+    fn with_fields(receipt: AnyTransactionReceipt, fields: serde_json::Value) -> AnyTransactionReceipt {
+        let mut json = serde_json::to_value(receipt).unwrap();
+        for (key, value) in fields.as_object().unwrap() {
+            json[key] = value.clone();
+        }
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn call(from: Address, to: Address, value: u64, reverted: bool, calls: Vec<CallFrame>) -> CallFrame {
+        CallFrame {
+            from,
+            to: Some(to),
+            value: Some(U256::from(value)),
+            typ: "CALL".to_string(),
+            error: reverted.then(|| "execution reverted".to_string()),
+            calls,
+            ..Default::default()
+        }
+    }
+
+    /// Accounts the generated calls move value between
+    fn account() -> impl Strategy<Value = Address> {
+        (1u8..6).prop_map(Address::with_last_byte)
+    }
+
+    /// A tree of value calls, some of them reverted
+    fn call_frame() -> impl Strategy<Value = CallFrame> {
+        let leaf = (account(), account(), 0u64..1_000_000, prop::bool::weighted(0.2))
+            .prop_map(|(from, to, value, reverted)| call(from, to, value, reverted, vec![]));
+
+        leaf.prop_recursive(4, 32, 4, |inner| {
+            (
+                account(),
+                account(),
+                0u64..1_000_000,
+                prop::bool::weighted(0.2),
+                vec(inner, 0..4),
+            )
+                .prop_map(|(from, to, value, reverted, calls)| call(from, to, value, reverted, calls))
+        })
+    }
+
+    /// Logs as tracers return them: transfers, WETH9 deposits and withdrawals,
+    /// some with data too short to decode, and other events. Some have no
+    /// address
+    fn log_frame() -> impl Strategy<Value = CallLogFrame> {
+        let weth = chain_adapter::weth9_tokens(Chain::mainnet(), &ChainRules::default())[0];
+        let wad = prop_oneof![
+            any::<u64>().prop_map(|wad| B256::from(U256::from(wad)).to_vec()),
+            vec(any::<u8>(), 0..32),
+        ];
+        let topics_and_data = prop_oneof![
+            (account(), account(), any::<u64>()).prop_map(|(from, to, value)| (
+                vec![ERC20::Transfer::SIGNATURE_HASH, from.into_word(), to.into_word()],
+                B256::from(U256::from(value)).to_vec(),
+            )),
+            (account(), wad.clone())
+                .prop_map(|(dst, data)| (vec![WETH9::Deposit::SIGNATURE_HASH, dst.into_word()], data)),
+            (account(), wad).prop_map(|(src, data)| (vec![WETH9::Withdrawal::SIGNATURE_HASH, src.into_word()], data)),
+            (
+                vec(any::<[u8; 32]>().prop_map(B256::from), 0..=4),
+                vec(any::<u8>(), 0..64)
+            ),
+        ];
+
+        (
+            prop::option::weighted(0.9, prop_oneof![account(), Just(weth)]),
+            topics_and_data,
+        )
+            .prop_map(|(address, (topics, data))| CallLogFrame {
+                address,
+                topics: Some(topics),
+                data: Some(data.into()),
+                ..Default::default()
+            })
+    }
+
+    /// Call types of the tracer, and unknown ones
+    fn call_type() -> impl Strategy<Value = String> {
+        prop_oneof![
+            prop::sample::select(vec![
+                "CALL",
+                "CALLCODE",
+                "CREATE",
+                "CREATE2",
+                "SELFDESTRUCT",
+                "DELEGATECALL",
+                "STATICCALL"
+            ])
+            .prop_map(str::to_string),
+            "[A-Z]{0,12}",
+        ]
+    }
+
+    /// A tree of calls of any type with logs, some of them reverted
+    fn arbitrary_frame() -> impl Strategy<Value = CallFrame> {
+        let frame = |from, to, value, reverted, typ, logs, calls| CallFrame {
+            typ,
+            logs,
+            ..call(from, to, value, reverted, calls)
+        };
+        let leaf = (
+            account(),
+            account(),
+            0u64..1_000_000,
+            prop::bool::weighted(0.2),
+            call_type(),
+            vec(log_frame(), 0..3),
+        )
+            .prop_map(move |(from, to, value, reverted, typ, logs)| {
+                frame(from, to, value, reverted, typ, logs, vec![])
+            });
+
+        leaf.prop_recursive(4, 32, 4, move |inner| {
+            (
+                account(),
+                account(),
+                0u64..1_000_000,
+                prop::bool::weighted(0.2),
+                call_type(),
+                vec(log_frame(), 0..3),
+                vec(inner, 0..4),
+            )
+                .prop_map(move |(from, to, value, reverted, typ, logs, calls)| {
+                    frame(from, to, value, reverted, typ, logs, calls)
+                })
+        })
+    }
+
+    /// `frame` and all of its subcalls
+    fn frames(frame: &CallFrame) -> Vec<&CallFrame> {
+        std::iter::once(frame)
+            .chain(frame.calls.iter().flat_map(frames))
+            .collect()
+    }
+
+    /// `frame` with it and all of its subcalls of type `typ`
+    fn with_type(frame: &CallFrame, typ: &str) -> CallFrame {
+        CallFrame {
+            typ: typ.to_string(),
+            calls: frame.calls.iter().map(|call| with_type(call, typ)).collect(),
+            ..frame.clone()
+        }
+    }
 
-            Some(ether)
+    /// `frame` with its reverted calls and their subcalls taken out
+    fn without_reverted(frame: &CallFrame) -> Option<CallFrame> {
+        if frame.error.is_some() {
+            return None;
+        }
+
+        Some(CallFrame {
+            calls: frame.calls.iter().filter_map(without_reverted).collect(),
+            ..frame.clone()
         })
-        .max()
-        .unwrap_or_default()
+    }
+
+    #[test]
+    fn builder_tips_to_itself_net_out() {
+        const GWEI: u128 = 1_000_000_000;
+        let (searcher, builder, proposer) = (
+            Address::with_last_byte(0xaa),
+            Address::with_last_byte(0xbb),
+            Address::with_last_byte(0xcc),
+        );
+        let wallet = WalletWithContext::new(
+            "builder".to_string(),
+            searcher,
+            Some(builder),
+            vec![],
+            false,
+            Default::default(),
+        );
+        let header = Header {
+            number: 1,
+            miner: builder,
+            base_fee_per_gas: Some(10 * GWEI),
+            ..Default::default()
+        };
+
+        let receipt_and_traces = [
+            // Someone else's tx, tipping 5 gwei a gas to the builder
+            (
+                receipt(Address::with_last_byte(1), true, 21000, 15 * GWEI),
+                call(Address::with_last_byte(1), Address::with_last_byte(2), 0, false, vec![]),
+            ),
+            // The builder paying the proposer, tipping 2 gwei a gas to itself
+            (
+                receipt(builder, true, 21000, 12 * GWEI),
+                call(builder, proposer, 1000, false, vec![]),
+            ),
+        ];
+
+        let report = process_block(
+            Chain::mainnet(),
+            &ChainRules::default(),
+            &header,
+            &receipt_and_traces,
+            &[wallet],
+        )
+        .unwrap()
+        .remove(0)
+        .unwrap();
+
+        let gwei = |value: u128| I256::from_raw(U256::from(value * GWEI));
+        // Only the other tx's tip, and only the base fee of its own tx
+        assert_eq!(report.builder_reward, U256::from(21000 * 5 * GWEI));
+        assert_eq!(report.pnl_by_category[&PnlCategory::Gas], -gwei(21000 * 10));
+        assert_eq!(report.pnl_by_category[&PnlCategory::BlockProduction], gwei(21000 * 5));
+        assert_eq!(report.validator_bribe, U256::from(1000));
+        assert_eq!(
+            report.pnl,
+            gwei(21000 * 5) - gwei(21000 * 10) - I256::from_raw(U256::from(1000))
+        );
+    }
+
+    #[test]
+    fn bsc_validator_keeps_the_whole_gas_price_without_base_fee() {
+        const GWEI: u128 = 1_000_000_000;
+        let (validator, sender) = (Address::with_last_byte(0xbb), Address::with_last_byte(1));
+        let wallet = WalletWithContext::new(
+            "validator".to_string(),
+            Address::with_last_byte(0xaa),
+            Some(validator),
+            vec![],
+            false,
+            Default::default(),
+        );
+        // Before the London fork
+        let header = Header {
+            number: 1,
+            miner: validator,
+            base_fee_per_gas: None,
+            ..Default::default()
+        };
+
+        let receipt_and_traces = [
+            (
+                receipt(sender, true, 21000, 3 * GWEI),
+                call(sender, Address::with_last_byte(2), 0, false, vec![]),
+            ),
+            // The validator handing the fees over to the validator set
+            (
+                receipt(validator, true, 30000, 0),
+                call(
+                    validator,
+                    Address::with_last_byte(0x10),
+                    21000 * 3 * GWEI as u64,
+                    false,
+                    vec![],
+                ),
+            ),
+        ];
+
+        let report = process_block(
+            Chain::from(NamedChain::BinanceSmartChain),
+            &ChainRules::default(),
+            &header,
+            &receipt_and_traces,
+            &[wallet],
+        )
+        .unwrap()
+        .remove(0)
+        .unwrap();
+
+        let reward = I256::from_raw(U256::from(21000 * 3 * GWEI));
+        assert_eq!(report.builder_reward, U256::from(21000 * 3 * GWEI));
+        assert_eq!(report.validator_bribe, U256::ZERO);
+        assert_eq!(report.pnl, reward);
+        assert!(report.txs.is_empty());
+    }
+
+    #[test]
+    fn bsc_txs_priced_below_the_base_fee_tip_nothing() {
+        const GWEI: u128 = 1_000_000_000;
+        let bsc = Chain::from(NamedChain::BinanceSmartChain);
+        let header = Header {
+            number: 1,
+            miner: Address::with_last_byte(0xbb),
+            base_fee_per_gas: Some(5 * GWEI),
+            ..Default::default()
+        };
+        let receipt = receipt(Address::with_last_byte(1), true, 21000, GWEI);
+
+        // Nothing is burnt, so the validator gets the whole gas price
+        let base_fee = chain_adapter::burnt_base_fee(bsc, &ChainRules::default(), &header);
+        assert_eq!(priority_fee(base_fee, &receipt), U256::from(21000 * GWEI));
+
+        // Burning the base fee leaves no tip rather than underflowing
+        let rules = ChainRules {
+            fee_mode: Some(FeeMode::Eip1559),
+            ..Default::default()
+        };
+        let base_fee = chain_adapter::burnt_base_fee(bsc, &rules, &header);
+        assert_eq!(base_fee, 5 * GWEI);
+        assert_eq!(priority_fee(base_fee, &receipt), U256::ZERO);
+        assert_eq!(
+            calculate_builder_reward(base_fee, std::iter::once(&receipt)),
+            U256::ZERO
+        );
+    }
+
+    #[test]
+    fn zksync_fees_are_native_transfers() {
+        const GWEI: u128 = 1_000_000_000;
+        let zksync = Chain::from(NamedChain::ZkSync);
+        let (sender, bootloader) = (
+            Address::with_last_byte(0xaa),
+            address!("0000000000000000000000000000000000008001"),
+        );
+        let transfer = |from: Address, to: Address, value: u128| {
+            json!({
+                "address": address!("000000000000000000000000000000000000800a"),
+                "topics": [ERC20::Transfer::SIGNATURE_HASH, from.into_word(), to.into_word()],
+                "data": B256::from(U256::from(value)),
+            })
+        };
+
+        // The fee is paid up front for the gas limit, and the unused part refunded
+        let receipt = with_fields(
+            receipt(sender, true, 100_000, GWEI / 4),
+            json!({
+                "logs": [
+                    transfer(sender, bootloader, 150_000 * GWEI / 4),
+                    transfer(bootloader, sender, 50_000 * GWEI / 4),
+                ],
+            }),
+        );
+
+        let fee = I256::from_raw(U256::from(100_000 * GWEI / 4));
+        let bcs = generate_pnl(zksync, &ChainRules::default(), &receipt, &CallFrame::default(), None).unwrap();
+        assert_eq!(bcs[&sender][&NATIVE_TOKEN], -fee);
+        assert_eq!(bcs[&bootloader][&NATIVE_TOKEN], fee);
+        // Counting the fee again would charge it twice
+        assert_eq!(calculate_tx_fee(zksync, &receipt).unwrap(), I256::ZERO);
+
+        let wallet = WalletWithContext::new("sender".to_string(), sender, None, vec![], false, Default::default());
+        let report = process_block(
+            zksync,
+            &ChainRules::default(),
+            &Header::default(),
+            &[(receipt, CallFrame::default())],
+            &[wallet],
+        )
+        .unwrap()
+        .remove(0)
+        .unwrap();
+        assert_eq!(report.pnl, -fee);
+    }
+
+    #[test]
+    fn op_deposits_are_excluded_as_configured() {
+        let optimism = Chain::from(NamedChain::Optimism);
+        let (depositor, wallet) = (Address::with_last_byte(0xdd), Address::with_last_byte(0xaa));
+        let header = Header {
+            number: 1,
+            ..Default::default()
+        };
+        let deposit = |from: Address| with_fields(receipt(from, true, 50_000, 0), json!({ "type": "0x7e" }));
+        let skip_deposit_txs = ChainRules {
+            skip_deposit_txs: true,
+            ..Default::default()
+        };
+
+        // The L1 attributes deposit starting every block is never a wallet's
+        let l1_attributes = deposit(address!("DeaDDEaDDeAdDeAdDEAdDEaddeAddEAdDEAd0001"));
+        assert!(chain_adapter::is_excluded_tx(
+            optimism,
+            &ChainRules::default(),
+            &header,
+            &l1_attributes
+        ));
+        assert_eq!(calculate_tx_fee(optimism, &l1_attributes).unwrap(), I256::ZERO);
+
+        // A user deposit only if so configured, else it moves funds like any tx
+        let receipt_and_traces = [(deposit(depositor), call(depositor, wallet, 1000, false, vec![]))];
+        let wallets = [WalletWithContext::new(
+            "wallet".to_string(),
+            wallet,
+            None,
+            vec![],
+            false,
+            Default::default(),
+        )];
+        assert!(!chain_adapter::is_excluded_tx(
+            optimism,
+            &ChainRules::default(),
+            &header,
+            &receipt_and_traces[0].0
+        ));
+        assert!(chain_adapter::is_excluded_tx(
+            optimism,
+            &skip_deposit_txs,
+            &header,
+            &receipt_and_traces[0].0
+        ));
+        assert_eq!(
+            calculate_tx_fee(optimism, &receipt_and_traces[0].0).unwrap(),
+            I256::ZERO
+        );
+
+        let report = process_block(optimism, &ChainRules::default(), &header, &receipt_and_traces, &wallets)
+            .unwrap()
+            .remove(0)
+            .unwrap();
+        assert_eq!(report.pnl, I256::from_raw(U256::from(1000)));
+
+        let reports = process_block(optimism, &skip_deposit_txs, &header, &receipt_and_traces, &wallets).unwrap();
+        assert_eq!(reports, [None]);
+    }
+
+    #[test]
+    fn op_fees_include_the_l1_and_operator_fees() {
+        const GWEI: u128 = 1_000_000_000;
+        let optimism = Chain::from(NamedChain::Optimism);
+        let sender = Address::with_last_byte(0xaa);
+
+        // An Ecotone receipt, whose L1 fee is priced off the L1 blob base fee
+        let receipt = with_fields(
+            receipt(sender, true, 100_000, GWEI / 1000),
+            json!({
+                "l1Fee": "0x2540be400",
+                "l1GasPrice": "0x3b9aca00",
+                "l1GasUsed": "0x640",
+                "l1BlobBaseFee": "0x1",
+                "l1BaseFeeScalar": "0x558",
+                "l1BlobBaseFeeScalar": "0xc5fc5",
+            }),
+        );
+        let l2_fee = U256::from(100_000 * GWEI / 1000);
+        let l1_fee = U256::from(10_000_000_000u64);
+        assert_eq!(
+            calculate_tx_fee(optimism, &receipt).unwrap(),
+            I256::from_raw(l2_fee + l1_fee)
+        );
+
+        // Isthmus adds the operator fee, per million gas plus a constant
+        let receipt = with_fields(
+            receipt,
+            json!({
+                "operatorFeeScalar": format!("{:#x}", 2_000_000),
+                "operatorFeeConstant": format!("{:#x}", 500),
+            }),
+        );
+        let operator_fee = U256::from(100_000 * 2 + 500);
+        assert_eq!(
+            calculate_tx_fee(optimism, &receipt).unwrap(),
+            I256::from_raw(l2_fee + l1_fee + operator_fee)
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn value_calls_net_to_zero(trace in call_frame()) {
+            let receipt = receipt(Address::ZERO, true, 21000, 1);
+            let bcs = generate_pnl(Chain::mainnet(), &ChainRules::default(), &receipt, &trace, None).unwrap();
+
+            let total = bcs
+                .values()
+                .filter_map(|bc| bc.get(&NATIVE_TOKEN))
+                .fold(I256::ZERO, |sum, change| sum + *change);
+            prop_assert_eq!(total, I256::ZERO);
+            prop_assert!(bcs.values().all(|bc| !bc.is_empty() && bc.values().all(|change| !change.is_zero())));
+        }
+
+        #[test]
+        fn reverted_calls_move_nothing(trace in call_frame()) {
+            let (receipt, rules) = (receipt(Address::ZERO, true, 21000, 1), ChainRules::default());
+            let bcs = generate_pnl(Chain::mainnet(), &rules, &receipt, &trace, None).unwrap();
+
+            let expected = match without_reverted(&trace) {
+                Some(pruned) => generate_pnl(Chain::mainnet(), &rules, &receipt, &pruned, None).unwrap(),
+                None => BalanceChanges::default(),
+            };
+            prop_assert_eq!(&*bcs, &*expected);
+        }
+
+        #[test]
+        fn failed_txs_move_nothing(trace in call_frame()) {
+            let receipt = receipt(Address::ZERO, false, 21000, 1);
+            let bcs = generate_pnl(Chain::mainnet(), &ChainRules::default(), &receipt, &trace, None).unwrap();
+
+            prop_assert!(bcs.is_empty());
+        }
+
+        #[test]
+        fn only_addresses_keep_their_own_changes(trace in call_frame(), only in vec(account(), 0..4)) {
+            let (receipt, rules) = (receipt(Address::ZERO, true, 21000, 1), ChainRules::default());
+            let only = only.into_iter().collect::<HashSet<_>>();
+            let all = generate_pnl(Chain::mainnet(), &rules, &receipt, &trace, None).unwrap();
+            let filtered = generate_pnl(Chain::mainnet(), &rules, &receipt, &trace, Some(&only)).unwrap();
+
+            for address in &only {
+                prop_assert_eq!(filtered.get(address), all.get(address));
+            }
+        }
+
+        #[test]
+        fn only_logs_without_address_fail(trace in arbitrary_frame()) {
+            let receipt = receipt(Address::ZERO, true, 21000, 1);
+            let result = generate_pnl(Chain::mainnet(), &ChainRules::default(), &receipt, &trace, None);
+
+            // Logs of reverted calls are never read
+            let addressless = without_reverted(&trace)
+                .is_some_and(|pruned| frames(&pruned).iter().any(|f| f.logs.iter().any(|l| l.address.is_none())));
+            prop_assert_eq!(result.is_err(), addressless);
+        }
+
+        #[test]
+        fn arbitrary_frames_net_to_zero_but_weth(trace in arbitrary_frame()) {
+            let (receipt, rules) = (receipt(Address::ZERO, true, 21000, 1), ChainRules::default());
+            let Ok(bcs) = generate_pnl(Chain::mainnet(), &rules, &receipt, &trace, None) else {
+                return Ok(());
+            };
+
+            // Deposits and withdrawals mint and burn WETH, everything else moves
+            let weth = chain_adapter::weth9_tokens(Chain::mainnet(), &rules)[0];
+            let mut totals = HashMap::<Address, I256>::new();
+            for (token, change) in bcs.values().flat_map(|bc| bc.iter()) {
+                *totals.entry(*token).or_default() += *change;
+            }
+            totals.retain(|token, total| *token != weth && !total.is_zero());
+            prop_assert!(totals.is_empty(), "{:?}", totals);
+        }
+
+        #[test]
+        fn unknown_call_types_move_no_value(trace in arbitrary_frame(), typ in "[a-z]{0,12}") {
+            let receipt = receipt(Address::ZERO, true, 21000, 1);
+            let trace = with_type(&trace, &typ);
+            let Ok(bcs) = generate_pnl(Chain::mainnet(), &ChainRules::default(), &receipt, &trace, None) else {
+                return Ok(());
+            };
+
+            prop_assert!(bcs.values().all(|bc| !bc.contains_key(&NATIVE_TOKEN)));
+        }
+    }
 }
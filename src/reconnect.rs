@@ -0,0 +1,204 @@
+use {
+    crate::{
+        config::BlockSource,
+        log_collector::{self, TransferLogCollector},
+        utils::new_pubsub_provider,
+    },
+    alloy::{primitives::Address, providers::Provider, pubsub::PubSubFrontend, rpc::types::Block},
+    alloy_chains::Chain,
+    burberry::{async_trait, collector::BlockCollector, Collector, CollectorStream},
+    futures::{channel::mpsc, SinkExt, StreamExt},
+    std::{sync::Arc, time::Duration},
+    tokio::sync::watch,
+    tracing::{error, info, warn},
+};
+
+/// Wait before the first reconnection attempt, doubled after each failure
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Blocks missed while disconnected or stopped that are processed on
+/// reconnection or start. Older ones are left to `scan`
+const MAX_MISSED_BLOCKS: u64 = 1000;
+
+/// The same with transfer logs, whose missed blocks are found with
+/// `eth_getLogs` rather than fetched one by one
+const MAX_MISSED_LOG_BLOCKS: u64 = 100_000;
+
+/// Collects blocks from `source` like the collector it wraps, but connects to
+/// the RPC again when its subscription ends, and emits the blocks missed in
+/// the meantime before the new ones. Each new provider is sent to `providers`.
+/// With a checkpoint, the blocks after it are caught up on at start too
+pub struct ReconnectingCollector {
+    chain: Chain,
+    rpc: String,
+    provider: Arc<dyn Provider<PubSubFrontend>>,
+    source: BlockSource,
+    /// Transfer log subscriptions are for these
+    addresses: Vec<Address>,
+    providers: watch::Sender<Arc<dyn Provider<PubSubFrontend>>>,
+    /// Last block processed before the watcher was stopped
+    checkpoint: Option<u64>,
+}
+
+impl ReconnectingCollector {
+    pub fn new(
+        chain: Chain,
+        rpc: String,
+        provider: Arc<dyn Provider<PubSubFrontend>>,
+        source: BlockSource,
+        addresses: Vec<Address>,
+        providers: watch::Sender<Arc<dyn Provider<PubSubFrontend>>>,
+    ) -> Self {
+        Self {
+            chain,
+            rpc,
+            provider,
+            source,
+            addresses,
+            providers,
+            checkpoint: None,
+        }
+    }
+
+    pub fn with_checkpoint(mut self, block: Option<u64>) -> Self {
+        self.checkpoint = block;
+        self
+    }
+}
+
+#[async_trait]
+impl Collector<Block> for ReconnectingCollector {
+    fn name(&self) -> &str {
+        "ReconnectingCollector"
+    }
+
+    async fn get_event_stream(&self) -> eyre::Result<CollectorStream<'_, Block>> {
+        let (mut tx, rx) = mpsc::unbounded();
+        let (chain, rpc, source) = (self.chain, self.rpc.clone(), self.source);
+        let addresses = self.addresses.clone();
+        let providers = self.providers.clone();
+        let mut provider = Arc::clone(&self.provider);
+        let checkpoint = self.checkpoint;
+
+        tokio::spawn(async move {
+            let mut last_block: Option<u64> = checkpoint;
+            // Blocks since the checkpoint are caught up on like after a
+            // reconnection
+            let mut reconnected = checkpoint.is_some();
+            let mut backoff = MIN_BACKOFF;
+
+            loop {
+                let collector: Box<dyn Collector<Block>> = match source {
+                    BlockSource::Blocks => Box::new(BlockCollector::new(Arc::clone(&provider))),
+                    BlockSource::TransferLogs => {
+                        Box::new(TransferLogCollector::new(Arc::clone(&provider), addresses.clone()))
+                    }
+                };
+
+                match collector.get_event_stream().await {
+                    Ok(mut blocks) => {
+                        while let Some(block) = blocks.next().await {
+                            backoff = MIN_BACKOFF;
+                            let number = block.header.number;
+
+                            if let (true, Some(last)) = (reconnected, last_block) {
+                                let missed =
+                                    missed_blocks(chain, provider.as_ref(), source, &addresses, last, number).await;
+                                for missed in missed {
+                                    if tx.send(missed).await.is_err() {
+                                        return;
+                                    }
+                                }
+                            }
+                            reconnected = false;
+
+                            last_block = Some(last_block.map_or(number, |last| last.max(number)));
+                            if tx.send(block).await.is_err() {
+                                return;
+                            }
+                        }
+                        warn!(%chain, "Block subscription ended");
+                    }
+                    Err(err) => warn!(%chain, "Failed to subscribe to blocks: {err:#}"),
+                }
+
+                provider = loop {
+                    warn!(%chain, "Reconnecting in {backoff:?}");
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+
+                    match new_pubsub_provider(&rpc).await {
+                        Ok(provider) => break provider.into(),
+                        Err(err) => warn!(%chain, "Failed to reconnect: {err:#}"),
+                    }
+                };
+                info!(%chain, "Reconnected");
+                reconnected = true;
+                let _ = providers.send(Arc::clone(&provider));
+            }
+        });
+
+        Ok(Box::pin(rx))
+    }
+}
+
+/// Blocks after `last` and before `head`, the most recent
+/// [`MAX_MISSED_BLOCKS`] of them. With transfer logs, only the blocks with a
+/// transfer of `addresses` among the most recent [`MAX_MISSED_LOG_BLOCKS`].
+/// The ones failing to load are skipped
+async fn missed_blocks(
+    chain: Chain,
+    provider: &dyn Provider<PubSubFrontend>,
+    source: BlockSource,
+    addresses: &[Address],
+    last: u64,
+    head: u64,
+) -> Vec<Block> {
+    let max_missed = match source {
+        BlockSource::Blocks => MAX_MISSED_BLOCKS,
+        BlockSource::TransferLogs => MAX_MISSED_LOG_BLOCKS,
+    };
+    let first = (last + 1).max(head.saturating_sub(max_missed));
+    if first >= head {
+        return vec![];
+    }
+    if first > last + 1 {
+        error!(
+            %chain,
+            from_block = last + 1,
+            to_block = first - 1,
+            "Too many blocks missed, scan them to get their reports"
+        );
+    }
+    info!(%chain, from_block = first, to_block = head - 1, "Catching up on missed blocks");
+
+    let numbers = match source {
+        BlockSource::Blocks => (first..head).collect::<Vec<_>>(),
+        BlockSource::TransferLogs => {
+            match log_collector::blocks_with_transfers(chain, provider, addresses, first, head - 1).await {
+                Ok(numbers) => numbers.into_iter().collect(),
+                Err(err) => {
+                    error!(
+                        %chain,
+                        from_block = first,
+                        to_block = head - 1,
+                        "Failed to find the missed blocks with transfers, scan them to get their reports: {err:#}"
+                    );
+                    return vec![];
+                }
+            }
+        }
+    };
+
+    let mut blocks = vec![];
+    for number in numbers {
+        match provider.get_block_by_number(number.into(), false).await {
+            Ok(Some(block)) => blocks.push(block),
+            Ok(None) => warn!(%chain, block = number, "Missed block not found"),
+            Err(err) => warn!(%chain, block = number, "Failed to get missed block: {err:#}"),
+        }
+    }
+
+    blocks
+}
@@ -0,0 +1,220 @@
+use {
+    crate::utils::U256AsDecimalStr,
+    alloy::primitives::U256,
+    eyre::Context,
+    futures::{stream, StreamExt},
+    serde::Deserialize,
+    serde_with::{serde_as, DisplayFromStr},
+    std::collections::HashMap,
+    tracing::debug,
+};
+
+/// Blocks looked up on the relays at once
+const MAX_CONCURRENT_BLOCKS: usize = 8;
+
+/// Delivered payloads asked for per request, the most relays return
+const PAGE_SIZE: usize = 200;
+
+/// A bid as the relay data API lists them, both the delivered payloads and
+/// the blocks builders submitted
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct BidTrace {
+    #[serde_as(as = "DisplayFromStr")]
+    slot: u64,
+    #[serde_as(as = "DisplayFromStr")]
+    block_number: u64,
+    builder_pubkey: String,
+    /// Paid to the proposer, in wei
+    #[serde_as(as = "U256AsDecimalStr")]
+    value: U256,
+}
+
+/// How a builder fared on the relays over a range of blocks
+#[derive(Debug, Clone, Default)]
+pub struct BuilderDay {
+    /// Paid to proposers for the blocks it won, by block
+    pub won: HashMap<u64, U256>,
+    /// Blocks it bid on that another builder won
+    pub lost: u64,
+    /// Sum of its best bid on each lost block
+    pub lost_bids: U256,
+    /// Sum over the lost blocks of what the winning bid was above its best
+    pub outbid_by: U256,
+    /// Blocks the relays failed to tell about, so neither won nor lost
+    pub unknown: u64,
+}
+
+/// Client of the data APIs of MEV-Boost relays
+pub struct RelayClient {
+    client: reqwest::Client,
+    relays: Vec<String>,
+}
+
+impl RelayClient {
+    pub fn new(relays: &[String]) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            relays: relays.iter().map(|url| url.trim_end_matches('/').to_string()).collect(),
+        }
+    }
+
+    /// The blocks from `from_block` to `to_block` the builder bidding with
+    /// `pubkeys` won, and the ones it lost. A block none of the relays
+    /// delivered, e.g. one built locally by its proposer, counts as neither,
+    /// and one the relays failed to tell about as unknown
+    pub async fn builder_day(&self, pubkeys: &[String], from_block: u64, to_block: u64) -> eyre::Result<BuilderDay> {
+        let mut day = BuilderDay::default();
+        for relay in &self.relays {
+            for pubkey in pubkeys {
+                for payload in self.delivered_by(relay, pubkey, from_block, to_block).await? {
+                    day.won.insert(payload.block_number, payload.value);
+                }
+            }
+        }
+
+        let others = (from_block..=to_block)
+            .filter(|block| !day.won.contains_key(block))
+            .collect::<Vec<_>>();
+        let blocks = stream::iter(others)
+            .map(|block| async move { (block, self.lost(pubkeys, block).await) })
+            .buffer_unordered(MAX_CONCURRENT_BLOCKS)
+            .collect::<Vec<_>>()
+            .await;
+
+        for (block, lost) in blocks {
+            match lost {
+                Ok(Some(Lost { best_bid, winning_bid })) => {
+                    day.lost += 1;
+                    day.lost_bids += best_bid;
+                    day.outbid_by += winning_bid.saturating_sub(best_bid);
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    debug!(block, "Failed to get block from the relays: {err:#}");
+                    day.unknown += 1;
+                }
+            }
+        }
+
+        Ok(day)
+    }
+
+    /// Payloads built by `builder_pubkey` that `relay` delivered from
+    /// `from_block` to `to_block`, paged back from the latest by slot
+    async fn delivered_by(
+        &self,
+        relay: &str,
+        builder_pubkey: &str,
+        from_block: u64,
+        to_block: u64,
+    ) -> eyre::Result<Vec<BidTrace>> {
+        let url = format!("{relay}/relay/v1/data/bidtraces/proposer_payload_delivered");
+        let mut payloads = vec![];
+        let mut cursor = None;
+
+        loop {
+            let mut query = vec![
+                ("builder_pubkey", builder_pubkey.to_string()),
+                ("limit", PAGE_SIZE.to_string()),
+            ];
+            if let Some(slot) = cursor {
+                query.push(("cursor", slot.to_string()));
+            }
+
+            let page = self.get(&url, &query).await?;
+            // Pages are ordered by slot, latest first
+            let Some((oldest_slot, oldest_block)) = page.last().map(|p| (p.slot, p.block_number)) else {
+                break;
+            };
+            let is_last = page.len() < PAGE_SIZE || oldest_block < from_block || oldest_slot == 0;
+
+            payloads.extend(
+                page.into_iter()
+                    .filter(|p| (from_block..=to_block).contains(&p.block_number)),
+            );
+
+            if is_last {
+                break;
+            }
+            cursor = Some(oldest_slot - 1);
+        }
+
+        Ok(payloads)
+    }
+
+    /// Whether another builder won `block` over the best bid of `pubkeys`
+    async fn lost(&self, pubkeys: &[String], block: u64) -> eyre::Result<Option<Lost>> {
+        let mut delivered = None;
+        for relay in &self.relays {
+            let url = format!("{relay}/relay/v1/data/bidtraces/proposer_payload_delivered");
+            if let Some(payload) = self
+                .get(&url, &[("block_number", block.to_string())])
+                .await?
+                .into_iter()
+                .find(|payload| payload.block_number == block)
+            {
+                delivered = Some(payload);
+                break;
+            }
+        }
+        let Some(delivered) = delivered else {
+            return Ok(None);
+        };
+
+        if pubkeys
+            .iter()
+            .any(|pubkey| pubkey.eq_ignore_ascii_case(&delivered.builder_pubkey))
+        {
+            return Ok(None);
+        }
+
+        let mut best_bid = None;
+        for relay in &self.relays {
+            let url = format!("{relay}/relay/v1/data/bidtraces/builder_blocks_received");
+            for pubkey in pubkeys {
+                let bids = self
+                    .get(
+                        &url,
+                        &[("block_number", block.to_string()), ("builder_pubkey", pubkey.clone())],
+                    )
+                    .await?;
+                best_bid = bids
+                    .into_iter()
+                    .filter(|bid| bid.block_number == block)
+                    .map(|bid| bid.value)
+                    .chain(best_bid)
+                    .max();
+            }
+        }
+
+        Ok(best_bid.map(|best_bid| Lost {
+            best_bid,
+            winning_bid: delivered.value,
+        }))
+    }
+
+    async fn get(&self, url: &str, query: &[(&str, String)]) -> eyre::Result<Vec<BidTrace>> {
+        serde_json::from_str(
+            &self
+                .client
+                .get(url)
+                .query(query)
+                .send()
+                .await
+                .with_context(|| format!("Failed to query {url}"))?
+                .error_for_status()
+                .with_context(|| format!("{url} rejected the query"))?
+                .text()
+                .await
+                .context("Failed to read relay response")?,
+        )
+        .with_context(|| format!("Invalid response from {url}"))
+    }
+}
+
+/// A block another builder won
+struct Lost {
+    best_bid: U256,
+    winning_bid: U256,
+}
@@ -0,0 +1,60 @@
+use {
+    crate::config::{Config, WalletWithContext},
+    std::{collections::HashMap, time::SystemTime},
+    tokio::{
+        sync::watch,
+        time::{interval, Duration},
+    },
+    tracing::{info, warn},
+};
+
+/// How often the config file is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Wallets to watch by chain name
+pub type WalletsByChain = HashMap<String, Vec<WalletWithContext>>;
+
+/// Poll the config at `path` and send its wallets to `sender` each time the
+/// file changes into a config that still validates. Only wallets are
+/// reloaded, other changes wait for a restart
+pub async fn watch_config(path: String, chains: Vec<String>, sender: watch::Sender<WalletsByChain>) {
+    let mut last_modified = modified(&path);
+    let mut ticks = interval(POLL_INTERVAL);
+
+    loop {
+        ticks.tick().await;
+
+        let current = modified(&path);
+        if current == last_modified {
+            continue;
+        }
+        last_modified = current;
+
+        let config = match Config::from_file(&path) {
+            Ok(config) => config,
+            Err(err) => {
+                warn!(
+                    %path,
+                    "Config changed but is invalid, keeping the wallets watched: {err:#}"
+                );
+                continue;
+            }
+        };
+
+        if config.chains.len() != chains.len() || !config.chains.keys().all(|name| chains.contains(name)) {
+            warn!(%path, "Chains changed in the config, restart to apply");
+        }
+
+        let wallets = config.to_wallet_with_context_by_chain();
+        let count = wallets.values().map(Vec::len).sum::<usize>();
+        info!(%path, wallets = count, "Config reloaded");
+
+        if sender.send(wallets).is_err() {
+            return;
+        }
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
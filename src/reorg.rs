@@ -0,0 +1,88 @@
+use {
+    alloy::{
+        primitives::{B256, U256},
+        rpc::types::Header,
+    },
+    std::collections::{BTreeMap, HashMap},
+};
+
+/// Blocks kept to notice reorgs, deeper ones go unnoticed
+const KEPT_BLOCKS: u64 = 64;
+
+/// Notices blocks replaced by a reorg, so builder income reported for them
+/// can be flagged as never earned
+#[derive(Debug, Default)]
+pub struct ReorgTracker {
+    /// Canonical hash of recent blocks as last seen
+    hashes: BTreeMap<u64, B256>,
+    /// Builder rewards reported for a block, by wallet index
+    built: HashMap<B256, Vec<(usize, U256)>>,
+}
+
+/// A block built by a watched wallet that is no longer canonical
+#[derive(Debug)]
+pub struct Orphaned {
+    pub number: u64,
+    pub hash: B256,
+    /// Blocks replaced by the reorg orphaning this one
+    pub depth: usize,
+    /// Builder rewards reported for the block, by wallet index
+    pub rewards: Vec<(usize, U256)>,
+}
+
+impl ReorgTracker {
+    /// Take in a new head, returning the built blocks it orphaned
+    pub fn observe(&mut self, header: &Header) -> Vec<Orphaned> {
+        let mut replaced = self.hashes.split_off(&header.number);
+
+        // The new head doesn't build on the block we have at its parent height
+        if let Some((&number, &hash)) = self.hashes.last_key_value() {
+            if number + 1 == header.number && hash != header.parent_hash {
+                self.hashes.remove(&number);
+                replaced.insert(number, hash);
+            }
+        }
+
+        replaced.retain(|_, hash| *hash != header.hash);
+        self.hashes.insert(header.number, header.hash);
+
+        while self.hashes.len() as u64 > KEPT_BLOCKS {
+            self.hashes.pop_first();
+        }
+
+        let depth = replaced.len();
+        let orphaned = replaced
+            .into_iter()
+            .filter_map(|(number, hash)| {
+                self.built.remove(&hash).map(|rewards| Orphaned {
+                    number,
+                    hash,
+                    depth,
+                    rewards,
+                })
+            })
+            .collect();
+
+        // Built blocks too old to be reorged out any more
+        let hashes = &self.hashes;
+        self.built.retain(|hash, _| hashes.values().any(|h| h == hash));
+
+        orphaned
+    }
+
+    /// Move the rewards to the wallets' indices in a new wallet list, by old
+    /// index, dropping those of wallets no longer watched
+    pub fn remap_wallets(&mut self, indices: &HashMap<usize, usize>) {
+        for rewards in self.built.values_mut() {
+            *rewards = rewards
+                .iter()
+                .filter_map(|(index, reward)| indices.get(index).map(|new| (*new, *reward)))
+                .collect();
+        }
+    }
+
+    /// Remember the reward reported for a wallet building `hash`
+    pub fn record_built(&mut self, hash: B256, wallet_index: usize, reward: U256) {
+        self.built.entry(hash).or_default().push((wallet_index, reward));
+    }
+}
@@ -0,0 +1,143 @@
+use {
+    crate::{
+        archive::{ArchiveWriter, Entry},
+        config::RetentionConfig,
+        storage::{PruneSink, Storage, StoredReport},
+    },
+    chrono::{DateTime, Utc},
+    eyre::Context,
+    std::{
+        path::Path,
+        sync::Arc,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tracing::{error, info, warn},
+};
+
+/// How often the store is checked for data past retention
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+const DAY: u64 = 86400;
+
+/// Prune `storage` by `retention` now and then every [`PRUNE_INTERVAL`].
+/// Failures are logged and tried again on the next round
+pub async fn prune_every(storage: Arc<dyn Storage>, retention: RetentionConfig) {
+    let mut ticks = tokio::time::interval(PRUNE_INTERVAL);
+
+    loop {
+        ticks.tick().await;
+
+        if let Err(err) = prune(storage.as_ref(), &retention).await {
+            error!("Failed to prune storage: {err:#}");
+        }
+    }
+}
+
+/// Delete the reports and processed blocks older than the retention, from the
+/// start of the UTC day `days` ago, after archiving the reports when
+/// `archive_dir` is set. Then the oldest days left, one at a time, while the
+/// store is over `max_size_gb`
+pub async fn prune(storage: &dyn Storage, retention: &RetentionConfig) -> eyre::Result<()> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let today = now / DAY * DAY;
+    let mut cutoff = (now / DAY).saturating_sub(retention.days as u64) * DAY;
+
+    prune_before(storage, retention, cutoff, now).await?;
+
+    let Some(max_size_gb) = retention.max_size_gb else {
+        return Ok(());
+    };
+    let max_size = (max_size_gb * 1e9) as u64;
+
+    let mut size = storage.size().await?;
+    while size > max_size && cutoff < today {
+        cutoff += DAY;
+        prune_before(storage, retention, cutoff, now).await?;
+        size = storage.size().await?;
+    }
+    if size > max_size {
+        warn!(size, max_size, "Storage is over max_size_gb with only today left");
+    }
+
+    Ok(())
+}
+
+async fn prune_before(storage: &dyn Storage, retention: &RetentionConfig, cutoff: u64, now: u64) -> eyre::Result<()> {
+    let (reports, blocks) = match &retention.archive_dir {
+        Some(dir) => {
+            let mut archive = Archive::new(dir, cutoff, now);
+            storage.prune(cutoff, Some(&mut archive)).await?
+        }
+        None => storage.prune(cutoff, None).await?,
+    };
+
+    if reports > 0 || blocks > 0 {
+        info!(reports, blocks, before = cutoff, "Storage pruned");
+    }
+
+    Ok(())
+}
+
+/// The reports of blocks mined before `cutoff`, written to a new archive in
+/// `dir` that `state import` can load back. No file is made when there are
+/// none
+struct Archive {
+    dir: String,
+    path: String,
+    out: Option<ArchiveWriter>,
+    archived: u64,
+}
+
+impl Archive {
+    fn new(dir: &str, cutoff: u64, now: u64) -> Self {
+        let day = DateTime::<Utc>::from_timestamp(cutoff as i64, 0)
+            .unwrap_or_default()
+            .format("%Y-%m-%d");
+        let path = Path::new(dir)
+            .join(format!("reports-before-{day}-{now}.jsonl.zst"))
+            .to_string_lossy()
+            .into_owned();
+
+        Self {
+            dir: dir.to_string(),
+            path,
+            out: None,
+            archived: 0,
+        }
+    }
+}
+
+impl PruneSink for Archive {
+    fn write(&mut self, reports: Vec<StoredReport>) -> eyre::Result<()> {
+        if reports.is_empty() {
+            return Ok(());
+        }
+
+        let out = match self.out.take() {
+            Some(out) => out,
+            None => {
+                std::fs::create_dir_all(&self.dir).with_context(|| format!("Failed to create {}", self.dir))?;
+                ArchiveWriter::create(&self.path)?
+            }
+        };
+        let out = self.out.insert(out);
+        for report in reports {
+            out.write(&Entry::Report(report))?;
+            self.archived += 1;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> eyre::Result<()> {
+        if let Some(out) = self.out.take() {
+            out.finish()?;
+            info!(reports = self.archived, path = %self.path, "Reports archived");
+        }
+
+        Ok(())
+    }
+}
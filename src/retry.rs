@@ -0,0 +1,73 @@
+use {
+    alloy::rpc::types::Block,
+    burberry::{async_trait, Collector, CollectorStream},
+    eyre::ContextCompat,
+    futures::{channel::mpsc, SinkExt},
+    std::{collections::BTreeMap, sync::Mutex},
+    tokio::{sync::mpsc as tokio_mpsc, time::Instant},
+};
+
+/// Where the strategy sends a failed block with when to try it again
+pub type RetrySender = tokio_mpsc::UnboundedSender<(Block, Instant)>;
+
+/// Emits each block sent to it once its retry is due, so that a failed block
+/// is tried again on time rather than when the next block arrives
+pub struct RetryCollector {
+    retries: Mutex<Option<tokio_mpsc::UnboundedReceiver<(Block, Instant)>>>,
+}
+
+impl RetryCollector {
+    pub fn new() -> (RetrySender, Self) {
+        let (sender, receiver) = tokio_mpsc::unbounded_channel();
+        let collector = Self {
+            retries: Mutex::new(Some(receiver)),
+        };
+
+        (sender, collector)
+    }
+}
+
+#[async_trait]
+impl Collector<Block> for RetryCollector {
+    fn name(&self) -> &str {
+        "RetryCollector"
+    }
+
+    async fn get_event_stream(&self) -> eyre::Result<CollectorStream<'_, Block>> {
+        let mut retries = self
+            .retries
+            .lock()
+            .unwrap()
+            .take()
+            .context("Retry collector already started")?;
+        let (mut tx, rx) = mpsc::unbounded();
+
+        tokio::spawn(async move {
+            // Blocks by when they are due, in the order they failed
+            let mut pending: BTreeMap<Instant, Vec<Block>> = BTreeMap::new();
+
+            loop {
+                let next = pending.keys().next().copied();
+                tokio::select! {
+                    retry = retries.recv() => match retry {
+                        Some((block, due)) => pending.entry(due).or_default().push(block),
+                        None => return,
+                    },
+                    // The deadline is only waited for with a retry pending
+                    _ = tokio::time::sleep_until(next.unwrap_or_else(Instant::now)), if next.is_some() => {
+                        let Some((_, blocks)) = pending.pop_first() else {
+                            continue;
+                        };
+                        for block in blocks {
+                            if tx.send(block).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Box::pin(rx))
+    }
+}
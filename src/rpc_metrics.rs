@@ -0,0 +1,107 @@
+use {
+    alloy_chains::Chain,
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::{BTreeMap, HashMap},
+        sync::{LazyLock, Mutex, RwLock},
+        time::Duration,
+    },
+    tracing::info,
+};
+
+/// Calls made since start by chain, then method
+static CALLS: Mutex<BTreeMap<u64, BTreeMap<&'static str, u64>>> = Mutex::new(BTreeMap::new());
+
+/// Compute units of a call by chain, then method
+static COSTS: LazyLock<RwLock<HashMap<u64, HashMap<String, u64>>>> = LazyLock::new(Default::default);
+
+/// RPC providers whose compute unit prices are known
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RpcProvider {
+    Alchemy,
+}
+
+impl RpcProvider {
+    /// Compute units of the methods the watcher calls, from the provider's
+    /// published pricing
+    fn costs(self) -> &'static [(&'static str, u64)] {
+        match self {
+            RpcProvider::Alchemy => &[
+                ("eth_getBlockReceipts", 500),
+                ("debug_traceBlockByNumber", 497),
+                ("debug_traceTransaction", 309),
+                ("eth_call", 26),
+                ("eth_getBlockByNumber", 16),
+                ("eth_getLogs", 75),
+            ],
+        }
+    }
+}
+
+/// Price the calls of `chain` at `provider`'s compute units, with `overrides`
+/// by method on top
+pub fn register_costs(chain: Chain, provider: Option<RpcProvider>, overrides: &HashMap<String, u64>) {
+    let mut costs: HashMap<String, u64> = provider
+        .map(|p| p.costs().iter().map(|(method, cu)| (method.to_string(), *cu)).collect())
+        .unwrap_or_default();
+    costs.extend(overrides.iter().map(|(method, cu)| (method.clone(), *cu)));
+
+    COSTS.write().unwrap().insert(chain.id(), costs);
+}
+
+/// Count `calls` calls of `method` made on `chain`. A batch counts each of
+/// its calls.
+pub fn record(chain: Chain, method: &'static str, calls: u64) {
+    if calls == 0 {
+        return;
+    }
+
+    *CALLS
+        .lock()
+        .unwrap()
+        .entry(chain.id())
+        .or_default()
+        .entry(method)
+        .or_default() += calls;
+}
+
+/// Calls of each method of `chain` since start, with their compute units if
+/// priced
+pub fn usage(chain: Chain) -> Vec<(&'static str, u64, Option<u64>)> {
+    let calls = CALLS.lock().unwrap().get(&chain.id()).cloned().unwrap_or_default();
+    let costs = COSTS.read().unwrap();
+    let costs = costs.get(&chain.id());
+
+    calls
+        .into_iter()
+        .map(|(method, count)| {
+            let cu = costs.and_then(|c| c.get(method)).map(|cu| cu * count);
+            (method, count, cu)
+        })
+        .collect()
+}
+
+/// Log the RPC usage of `chain` every `interval`
+pub async fn log_usage_every(chain: Chain, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+
+        let usage = usage(chain);
+        if usage.is_empty() {
+            continue;
+        }
+
+        let calls = usage
+            .iter()
+            .map(|(method, count, _)| format!("{method}={count}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let compute_units = usage.iter().filter_map(|(.., cu)| *cu).sum::<u64>();
+
+        info!(%chain, compute_units, "RPC calls since start: {calls}");
+    }
+}
@@ -0,0 +1,204 @@
+use {
+    crate::{
+        balance_changes::BalanceChange,
+        config::{WalletWithContext, NATIVE_TOKEN},
+        contract::{EigenLayerStrategyManager, EigenLayerStrategyManagerV2, Lido, LidoWithdrawalQueue, ERC20},
+        utils::{primitive_log_decode, U256AsDecimalStr},
+    },
+    alloy::{
+        primitives::{address, Address, TxHash, I256, U256},
+        rpc::types::AnyTransactionReceipt,
+    },
+    alloy_chains::{Chain, NamedChain},
+    schemars::JsonSchema,
+    serde::{Deserialize, Serialize},
+    serde_with::serde_as,
+};
+
+/// stETH moves a wei or two less than asked, as balances are kept in shares
+const STETH_ROUNDING: u64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StakingProtocol {
+    Lido,
+    EigenLayer,
+}
+
+impl StakingProtocol {
+    pub fn name(self) -> &'static str {
+        match self {
+            StakingProtocol::Lido => "Lido",
+            StakingProtocol::EigenLayer => "EigenLayer",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StakingFlowKind {
+    /// Staked into the protocol
+    Deposit,
+    /// Queued for withdrawal, to be claimed later
+    WithdrawalRequest,
+    /// A finished withdrawal paid out
+    Claim,
+}
+
+/// What a wallet staked or got back from a staking protocol in a tx
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Eq, PartialEq)]
+pub struct StakingFlow {
+    #[schemars(with = "String")]
+    pub tx: TxHash,
+    pub protocol: StakingProtocol,
+    pub kind: StakingFlowKind,
+    /// What left or reached the wallet, the zero address for the native token
+    #[schemars(with = "String")]
+    pub token: Address,
+    #[serde_as(as = "U256AsDecimalStr")]
+    #[schemars(with = "String")]
+    pub amount: U256,
+}
+
+/// Staking contracts of a chain
+struct Contracts {
+    steth: Address,
+    withdrawal_queue: Address,
+    eigenlayer_strategy_manager: Address,
+}
+
+fn contracts(chain: Chain) -> Option<Contracts> {
+    match chain.named()? {
+        NamedChain::Mainnet => Some(Contracts {
+            steth: address!("ae7ab96520DE3A18E5e111B5EaAb095312D7fE84"),
+            withdrawal_queue: address!("889edC2eDab5f40e902b864aD4d7AdE8E412F9B1"),
+            eigenlayer_strategy_manager: address!("858646372CC42E1A627fcE94aa7A7033e7CF075A"),
+        }),
+        _ => None,
+    }
+}
+
+/// Lido deposits, withdrawal requests and claims, and EigenLayer deposits of
+/// the wallet in `receipts`
+pub fn find_flows<'a>(
+    chain: Chain,
+    receipts: impl Iterator<Item = &'a AnyTransactionReceipt>,
+    wallet: &WalletWithContext,
+) -> Vec<StakingFlow> {
+    let Some(contracts) = contracts(chain) else {
+        return vec![];
+    };
+    let involved = wallet.involved_wallets();
+    let mut flows = vec![];
+
+    for receipt in receipts {
+        let logs = receipt.inner.inner.logs();
+        let flow = |protocol, kind, token, amount| StakingFlow {
+            tx: receipt.transaction_hash,
+            protocol,
+            kind,
+            token,
+            amount,
+        };
+
+        for log in logs {
+            let emitter = log.inner.address;
+
+            if emitter == contracts.steth {
+                if let Some(submitted) = primitive_log_decode::<Lido::Submitted>(&log.inner) {
+                    if involved.contains(&submitted.sender) {
+                        flows.push(flow(
+                            StakingProtocol::Lido,
+                            StakingFlowKind::Deposit,
+                            NATIVE_TOKEN,
+                            submitted.amount,
+                        ));
+                    }
+                }
+            } else if emitter == contracts.withdrawal_queue {
+                if let Some(requested) = primitive_log_decode::<LidoWithdrawalQueue::WithdrawalRequested>(&log.inner) {
+                    if involved.contains(&requested.requestor) {
+                        flows.push(flow(
+                            StakingProtocol::Lido,
+                            StakingFlowKind::WithdrawalRequest,
+                            contracts.steth,
+                            requested.amountOfStETH,
+                        ));
+                    }
+                } else if let Some(claimed) = primitive_log_decode::<LidoWithdrawalQueue::WithdrawalClaimed>(&log.inner)
+                {
+                    if involved.contains(&claimed.receiver) {
+                        flows.push(flow(
+                            StakingProtocol::Lido,
+                            StakingFlowKind::Claim,
+                            NATIVE_TOKEN,
+                            claimed.amountOfETH,
+                        ));
+                    }
+                }
+            } else if emitter == contracts.eigenlayer_strategy_manager {
+                let (staker, strategy) = if let Some(deposit) =
+                    primitive_log_decode::<EigenLayerStrategyManager::Deposit>(&log.inner)
+                {
+                    (deposit.staker, deposit.strategy)
+                } else if let Some(deposit) = primitive_log_decode::<EigenLayerStrategyManagerV2::Deposit>(&log.inner) {
+                    (deposit.staker, deposit.strategy)
+                } else {
+                    continue;
+                };
+                if !involved.contains(&staker) {
+                    continue;
+                }
+
+                // The event only has the shares, the tokens are in the
+                // transfer to the strategy
+                let deposited = logs.iter().find_map(|log| {
+                    let transfer = primitive_log_decode::<ERC20::Transfer>(&log.inner)?;
+                    (transfer.from == staker && transfer.to == strategy).then_some((log.inner.address, transfer.value))
+                });
+                if let Some((token, amount)) = deposited {
+                    flows.push(flow(
+                        StakingProtocol::EigenLayer,
+                        StakingFlowKind::Deposit,
+                        token,
+                        amount,
+                    ));
+                }
+            }
+        }
+    }
+
+    flows
+}
+
+/// Take what `flows` moved out of `token_changes`, so staking doesn't show as
+/// a loss or a gain. The stETH minted for a Lido deposit goes too.
+pub fn exclude(chain: Chain, flows: &[StakingFlow], token_changes: &mut BalanceChange) {
+    let Some(contracts) = contracts(chain) else {
+        return;
+    };
+
+    for flow in flows {
+        let amount = I256::from_raw(flow.amount);
+        match flow.kind {
+            StakingFlowKind::Deposit | StakingFlowKind::WithdrawalRequest => {
+                *token_changes.entry(flow.token).or_default() += amount;
+            }
+            StakingFlowKind::Claim => {
+                *token_changes.entry(flow.token).or_default() -= amount;
+            }
+        }
+
+        if flow.protocol == StakingProtocol::Lido && flow.kind == StakingFlowKind::Deposit {
+            *token_changes.entry(contracts.steth).or_default() -= amount;
+        }
+    }
+
+    let lido = flows.iter().any(|flow| flow.protocol == StakingProtocol::Lido);
+    if let (true, Some(change)) = (lido, token_changes.get(&contracts.steth)) {
+        if change.unsigned_abs() <= U256::from(STETH_ROUNDING) {
+            token_changes.remove(&contracts.steth);
+        }
+    }
+}
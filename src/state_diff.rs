@@ -0,0 +1,168 @@
+use {
+    crate::{
+        chain_adapter::{self, ChainRules, TransferSource},
+        config::{WalletWithContext, NATIVE_TOKEN},
+        processor::{self, PnlReport},
+        rpc_metrics,
+    },
+    alloy::{
+        primitives::{Address, TxHash, I256, U256},
+        providers::Provider,
+        rpc::{
+            client::BatchRequest,
+            types::{
+                trace::geth::{
+                    CallFrame, GethDebugBuiltInTracerType, GethDebugTracingOptions, GethTrace, PreStateConfig,
+                    PreStateFrame,
+                },
+                AnyTransactionReceipt, Header,
+            },
+        },
+        transports::Transport,
+    },
+    alloy_chains::Chain,
+    eyre::{bail, Context},
+    std::{
+        collections::{HashMap, HashSet},
+        sync::atomic::{AtomicU64, Ordering},
+    },
+    tracing::warn,
+};
+
+/// Txs whose native balance changes from call frames disagreed with the state
+/// diff, since start
+static DISAGREEMENTS: AtomicU64 = AtomicU64::new(0);
+
+/// Check the native PnL of a report against the state diff of each of its
+/// txs, and go with the state diff where they disagree. Call frames miss ETH
+/// moved by `SELFDESTRUCT` to an existing account and ETH consumed by some
+/// precompiles.
+pub async fn verify<T: Clone + Transport>(
+    provider: &dyn Provider<T>,
+    chain: Chain,
+    rules: &ChainRules,
+    header: &Header,
+    receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
+    wallet: &WalletWithContext,
+    report: &mut PnlReport,
+) -> eyre::Result<()> {
+    if let TransferSource::ReceiptLogs { .. } = chain_adapter::for_chain(chain).transfer_source() {
+        // Balance changes come from the node's own transfer logs already
+        return Ok(());
+    }
+
+    let txs = report
+        .txs
+        .iter()
+        .filter_map(|tx| receipt_and_traces.iter().find(|(r, _)| r.transaction_hash == tx.hash))
+        .collect::<Vec<_>>();
+
+    rpc_metrics::record(chain, "debug_traceTransaction", txs.len() as u64);
+    let diffs = native_deltas(provider, txs.iter().map(|(r, _)| r.transaction_hash)).await?;
+    let involved = wallet.involved_wallets().iter().copied().collect::<HashSet<_>>();
+
+    for ((receipt, call_trace), state) in txs.into_iter().zip(diffs) {
+        let frames = processor::generate_pnl(chain, rules, receipt, call_trace, Some(&involved))?;
+
+        let mut from_frames = I256::ZERO;
+        let mut from_state = I256::ZERO;
+
+        for address in wallet.involved_wallets() {
+            from_frames += frames
+                .get(address)
+                .and_then(|bc| bc.get(&NATIVE_TOKEN))
+                .copied()
+                .unwrap_or_default();
+
+            // Gas is paid and tipped in the state diff, but is accounted for on
+            // its own in reports
+            let mut delta = state.get(address).copied().unwrap_or_default();
+            if *address == receipt.from {
+                delta += processor::calculate_tx_fee(chain, receipt)?;
+            }
+            if *address == header.miner {
+                delta -= I256::from_raw(tip(chain, rules, header, receipt));
+            }
+            from_state += delta;
+        }
+
+        if from_frames != from_state {
+            let total = DISAGREEMENTS.fetch_add(1, Ordering::Relaxed) + 1;
+            warn!(
+                tx = %receipt.transaction_hash,
+                wallet = %wallet.name,
+                %from_frames,
+                %from_state,
+                total,
+                "Native balance change disagrees with the state diff"
+            );
+
+            report.pnl += from_state - from_frames;
+            report.warnings.push(format!(
+                "Native balance change of tx {:#x} corrected from the state diff",
+                receipt.transaction_hash
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Native balance change of each account touched by each tx, from the
+/// prestate tracer in diff mode
+pub async fn native_deltas<T: Clone + Transport>(
+    provider: &dyn Provider<T>,
+    txs: impl Iterator<Item = TxHash>,
+) -> eyre::Result<Vec<HashMap<Address, I256>>> {
+    let options = GethDebugTracingOptions::default()
+        .with_tracer(GethDebugBuiltInTracerType::PreStateTracer.into())
+        .with_prestate_config(PreStateConfig {
+            diff_mode: Some(true),
+            ..Default::default()
+        });
+
+    let mut batch = BatchRequest::new(provider.client());
+    let calls = txs
+        .map(|hash| {
+            batch
+                .add_call::<_, GethTrace>("debug_traceTransaction", &(hash, options.clone()))
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    batch.await.context("Failed to send batch request")?;
+
+    let mut deltas = Vec::with_capacity(calls.len());
+    for call in calls {
+        let trace = call.await.context("Failed to trace state diff")?;
+        let GethTrace::PreStateTracer(PreStateFrame::Diff(diff)) = trace else {
+            bail!("Trace result is not a state diff");
+        };
+
+        let mut delta = HashMap::new();
+        for (address, pre) in &diff.pre {
+            let before = pre.balance.unwrap_or_default();
+            // An account left out of `post` was destroyed, one in `post`
+            // without a balance kept it
+            let after = match diff.post.get(address) {
+                Some(post) => post.balance.unwrap_or(before),
+                None => U256::ZERO,
+            };
+            delta.insert(*address, I256::from_raw(after) - I256::from_raw(before));
+        }
+        for (address, post) in &diff.post {
+            if !diff.pre.contains_key(address) {
+                delta.insert(*address, I256::from_raw(post.balance.unwrap_or_default()));
+            }
+        }
+
+        deltas.push(delta);
+    }
+
+    Ok(deltas)
+}
+
+/// Priority fee of a tx, paid to the block producer
+pub fn tip(chain: Chain, rules: &ChainRules, header: &Header, receipt: &AnyTransactionReceipt) -> U256 {
+    processor::priority_fee(chain_adapter::burnt_base_fee(chain, rules, header), receipt)
+}
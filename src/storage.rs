@@ -0,0 +1,932 @@
+use {
+    crate::{executor::ReportEvent, migration, processor::PnlReport},
+    alloy::primitives::Address,
+    eyre::{bail, Context},
+    serde::{Deserialize, Deserializer, Serialize},
+    sqlx::{
+        postgres::{PgConnection, PgPool},
+        sqlite::{SqliteConnectOptions, SqlitePool},
+        Connection,
+    },
+    std::{str::FromStr, sync::Arc},
+};
+
+const SQLITE_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS pnl_reports (
+    chain INTEGER NOT NULL,
+    block INTEGER NOT NULL,
+    address TEXT NOT NULL,
+    wallet TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    pnl TEXT NOT NULL,
+    report TEXT NOT NULL,
+    PRIMARY KEY (chain, block, address)
+)";
+
+const POSTGRES_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS pnl_reports (
+    chain BIGINT NOT NULL,
+    block BIGINT NOT NULL,
+    address TEXT NOT NULL,
+    wallet TEXT NOT NULL,
+    timestamp BIGINT NOT NULL,
+    pnl NUMERIC(78, 0) NOT NULL,
+    report JSONB NOT NULL,
+    PRIMARY KEY (chain, block, address)
+)";
+
+/// Block ranges already backfilled, by chain and wallet address
+const SQLITE_BACKFILLS_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS backfills (
+    chain INTEGER NOT NULL,
+    address TEXT NOT NULL,
+    from_block INTEGER NOT NULL,
+    to_block INTEGER NOT NULL,
+    PRIMARY KEY (chain, address)
+)";
+
+const POSTGRES_BACKFILLS_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS backfills (
+    chain BIGINT NOT NULL,
+    address TEXT NOT NULL,
+    from_block BIGINT NOT NULL,
+    to_block BIGINT NOT NULL,
+    PRIMARY KEY (chain, address)
+)";
+
+/// Last block processed by chain
+const SQLITE_CHECKPOINTS_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS checkpoints (
+    chain INTEGER PRIMARY KEY,
+    block INTEGER NOT NULL
+)";
+
+const POSTGRES_CHECKPOINTS_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS checkpoints (
+    chain BIGINT PRIMARY KEY,
+    block BIGINT NOT NULL
+)";
+
+/// How each block went, for `health`
+const SQLITE_PROCESSED_BLOCKS_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS processed_blocks (
+    chain INTEGER NOT NULL,
+    block INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    elapsed_ms INTEGER NOT NULL,
+    attempts INTEGER NOT NULL,
+    error TEXT,
+    PRIMARY KEY (chain, block)
+)";
+
+const POSTGRES_PROCESSED_BLOCKS_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS processed_blocks (
+    chain BIGINT NOT NULL,
+    block BIGINT NOT NULL,
+    timestamp BIGINT NOT NULL,
+    elapsed_ms BIGINT NOT NULL,
+    attempts BIGINT NOT NULL,
+    error TEXT,
+    PRIMARY KEY (chain, block)
+)";
+
+/// A report of a wallet in a block, as kept in the store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredReport {
+    pub chain: u64,
+    pub block: u64,
+    pub timestamp: u64,
+    /// Name of the wallet in the config when the report was made
+    pub wallet: String,
+    pub address: Address,
+    /// Upgraded to the current version when read
+    #[serde(deserialize_with = "migrated_report")]
+    pub report: PnlReport,
+}
+
+fn migrated_report<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PnlReport, D::Error> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    migration::migrate_report(value).map_err(|err| serde::de::Error::custom(format!("{err:#}")))
+}
+
+impl From<ReportEvent> for StoredReport {
+    fn from(event: ReportEvent) -> Self {
+        Self {
+            chain: event.chain,
+            block: event.block,
+            timestamp: event.timestamp,
+            wallet: event.wallet,
+            address: event.address,
+            report: event.report,
+        }
+    }
+}
+
+/// Blocks whose reports were saved for a wallet that was new to the store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backfill {
+    pub chain: u64,
+    pub address: Address,
+    pub from_block: u64,
+    pub to_block: u64,
+}
+
+/// The last block processed on a chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub chain: u64,
+    pub block: u64,
+}
+
+/// How the last attempt at a block went
+#[derive(Debug, Clone)]
+pub struct ProcessedBlock {
+    pub chain: u64,
+    pub block: u64,
+    pub timestamp: u64,
+    pub elapsed_ms: u64,
+    pub attempts: u32,
+    /// What the block failed with, after its last retry
+    pub error: Option<String>,
+}
+
+/// Blocks of a chain over a time range
+#[derive(Debug, Clone)]
+pub struct ChainStats {
+    pub chain: u64,
+    pub processed: u64,
+    /// Given up on after their retries
+    pub failed: u64,
+    pub first_block: u64,
+    pub last_block: u64,
+    pub avg_elapsed_ms: f64,
+    pub max_elapsed_ms: u64,
+    /// Attempts beyond the first, over every block
+    pub retries: u64,
+}
+
+/// Which stored reports to read. Unset fields don't filter, and the ranges
+/// are inclusive
+#[derive(Debug, Clone, Default)]
+pub struct ReportQuery {
+    pub chain: Option<u64>,
+    pub address: Option<Address>,
+    pub from_block: Option<u64>,
+    pub to_block: Option<u64>,
+    pub from_timestamp: Option<u64>,
+    pub to_timestamp: Option<u64>,
+    /// Page of the matching reports, unbounded when unset
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+/// Reports read at once while pruning
+const PRUNE_PAGE_SIZE: i64 = 1000;
+
+/// Where [`Storage::prune`] hands the reports it is about to delete
+pub trait PruneSink: Send {
+    fn write(&mut self, reports: Vec<StoredReport>) -> eyre::Result<()>;
+
+    /// Called once every report was written, before any is deleted
+    fn finish(&mut self) -> eyre::Result<()>;
+}
+
+/// First key of the advisory locks taken on chains, the second being the
+/// chain id, so they don't clash with other users of the database
+const CHAIN_LOCK_NAMESPACE: i32 = 0x5757;
+
+/// The lock of a chain, held while this process watches it. Released when
+/// dropped, or when its connection is lost
+pub struct ChainLock {
+    connection: PgConnection,
+}
+
+impl ChainLock {
+    /// Whether the lock is still held, i.e. its connection is alive
+    pub async fn held(&mut self) -> bool {
+        self.connection.ping().await.is_ok()
+    }
+}
+
+/// Columns of a stored report, in the order they are selected
+type ReportRow = (i64, i64, String, String, i64, String);
+
+type BackfillRow = (i64, String, i64, i64);
+
+type CheckpointRow = (i64, i64);
+
+type ChainStatsRow = (i64, i64, i64, i64, i64, f64, i64, i64);
+
+/// Keeps every report, one per chain, block and wallet address. A report
+/// saved again for the same key, e.g. after a reorg, replaces the old one
+#[burberry::async_trait]
+pub trait Storage: Send + Sync {
+    async fn save(&self, report: &StoredReport) -> eyre::Result<()>;
+
+    /// Reports matching `query`, oldest first
+    async fn reports(&self, query: &ReportQuery) -> eyre::Result<Vec<StoredReport>>;
+
+    /// Whether the history of the wallet at `address` was backfilled
+    async fn backfilled(&self, chain: u64, address: Address) -> eyre::Result<bool>;
+
+    async fn record_backfill(&self, chain: u64, address: Address, from_block: u64, to_block: u64) -> eyre::Result<()>;
+
+    /// Every backfill recorded
+    async fn backfills(&self) -> eyre::Result<Vec<Backfill>>;
+
+    /// Record that `block` of `chain` was processed. A checkpoint never moves
+    /// back
+    async fn save_checkpoint(&self, chain: u64, block: u64) -> eyre::Result<()>;
+
+    async fn checkpoint(&self, chain: u64) -> eyre::Result<Option<u64>>;
+
+    /// Every checkpoint recorded
+    async fn checkpoints(&self) -> eyre::Result<Vec<Checkpoint>>;
+
+    async fn record_block(&self, block: &ProcessedBlock) -> eyre::Result<()>;
+
+    /// Blocks of `chain` from `from_block` to `to_block` that were recorded,
+    /// with whether they failed, in order
+    async fn recorded_blocks(&self, chain: u64, from_block: u64, to_block: u64) -> eyre::Result<Vec<(u64, bool)>>;
+
+    /// Blocks of each chain mined from `from_timestamp` to `to_timestamp`
+    async fn chain_stats(&self, from_timestamp: u64, to_timestamp: u64) -> eyre::Result<Vec<ChainStats>>;
+
+    /// Blocks failed by chain and error over the same range, most frequent
+    /// first
+    async fn block_errors(&self, from_timestamp: u64, to_timestamp: u64) -> eyre::Result<Vec<(u64, String, u64)>>;
+
+    /// Reports by chain and wallet name over the same range
+    async fn report_counts(&self, from_timestamp: u64, to_timestamp: u64) -> eyre::Result<Vec<(u64, String, u64)>>;
+
+    /// Delete the reports and processed blocks of blocks mined before
+    /// `timestamp`, writing the reports to `sink` first. Reading and deleting
+    /// them is one transaction, so exactly the reports written are deleted,
+    /// and none if the sink fails. Returns how many reports and blocks were
+    /// deleted
+    async fn prune(&self, timestamp: u64, sink: Option<&mut dyn PruneSink>) -> eyre::Result<(u64, u64)>;
+
+    /// Bytes taken by the stored data, without the space deleted rows leave
+    /// until a vacuum
+    async fn size(&self) -> eyre::Result<u64>;
+
+    /// Take the lock of `chain`, so only one of the instances sharing the
+    /// store watches it. `None` while another instance holds it
+    async fn lock_chain(&self, chain: u64) -> eyre::Result<Option<ChainLock>>;
+}
+
+/// The store at `url`, `sqlite://<path>` or `postgres://...`. The table is
+/// created if missing
+pub async fn connect(url: &str) -> eyre::Result<Arc<dyn Storage>> {
+    if url.starts_with("sqlite:") {
+        Ok(Arc::new(SqliteStorage::connect(url).await?))
+    } else if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        Ok(Arc::new(PostgresStorage::connect(url).await?))
+    } else {
+        bail!("unsupported storage url, expected sqlite:// or postgres://")
+    }
+}
+
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    pub async fn connect(url: &str) -> eyre::Result<Self> {
+        let options = SqliteConnectOptions::from_str(url)
+            .context("Invalid SQLite url")?
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options)
+            .await
+            .context("Failed to open SQLite database")?;
+
+        sqlx::query(SQLITE_SCHEMA)
+            .execute(&pool)
+            .await
+            .context("Failed to create the reports table")?;
+        sqlx::query(SQLITE_BACKFILLS_SCHEMA)
+            .execute(&pool)
+            .await
+            .context("Failed to create the backfills table")?;
+        sqlx::query(SQLITE_CHECKPOINTS_SCHEMA)
+            .execute(&pool)
+            .await
+            .context("Failed to create the checkpoints table")?;
+        sqlx::query(SQLITE_PROCESSED_BLOCKS_SCHEMA)
+            .execute(&pool)
+            .await
+            .context("Failed to create the processed blocks table")?;
+
+        Ok(Self { pool })
+    }
+}
+
+#[burberry::async_trait]
+impl Storage for SqliteStorage {
+    async fn save(&self, stored: &StoredReport) -> eyre::Result<()> {
+        let report = serde_json::to_string(&stored.report).context("Failed to serialize report")?;
+
+        sqlx::query(
+            "INSERT INTO pnl_reports (chain, block, address, wallet, timestamp, pnl, report)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (chain, block, address) DO UPDATE SET
+                wallet = excluded.wallet, timestamp = excluded.timestamp, pnl = excluded.pnl, report = excluded.report",
+        )
+        .bind(stored.chain as i64)
+        .bind(stored.block as i64)
+        .bind(format!("{:#x}", stored.address))
+        .bind(&stored.wallet)
+        .bind(stored.timestamp as i64)
+        .bind(stored.report.pnl.to_string())
+        .bind(report)
+        .execute(&self.pool)
+        .await
+        .context("Failed to save report")?;
+
+        Ok(())
+    }
+
+    async fn reports(&self, query: &ReportQuery) -> eyre::Result<Vec<StoredReport>> {
+        let rows: Vec<ReportRow> = sqlx::query_as(
+            "SELECT chain, block, address, wallet, timestamp, report FROM pnl_reports
+            WHERE (?1 IS NULL OR chain = ?1) AND (?2 IS NULL OR address = ?2)
+                AND (?3 IS NULL OR block >= ?3) AND (?4 IS NULL OR block <= ?4)
+                AND (?5 IS NULL OR timestamp >= ?5) AND (?6 IS NULL OR timestamp <= ?6)
+            ORDER BY timestamp, chain, block, address
+            LIMIT COALESCE(?7, -1) OFFSET COALESCE(?8, 0)",
+        )
+        .bind(query.chain.map(|chain| chain as i64))
+        .bind(query.address.map(|address| format!("{address:#x}")))
+        .bind(query.from_block.map(|block| block as i64))
+        .bind(query.to_block.map(|block| block as i64))
+        .bind(query.from_timestamp.map(|timestamp| timestamp as i64))
+        .bind(query.to_timestamp.map(|timestamp| timestamp as i64))
+        .bind(query.limit.map(|limit| limit as i64))
+        .bind(query.offset.map(|offset| offset as i64))
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to read reports")?;
+
+        rows.into_iter().map(stored_report).collect()
+    }
+
+    async fn backfilled(&self, chain: u64, address: Address) -> eyre::Result<bool> {
+        let found: Option<(i64,)> = sqlx::query_as("SELECT to_block FROM backfills WHERE chain = ? AND address = ?")
+            .bind(chain as i64)
+            .bind(format!("{address:#x}"))
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to read backfills")?;
+
+        Ok(found.is_some())
+    }
+
+    async fn record_backfill(&self, chain: u64, address: Address, from_block: u64, to_block: u64) -> eyre::Result<()> {
+        sqlx::query(
+            "INSERT INTO backfills (chain, address, from_block, to_block) VALUES (?, ?, ?, ?)
+            ON CONFLICT (chain, address) DO UPDATE SET from_block = excluded.from_block, to_block = excluded.to_block",
+        )
+        .bind(chain as i64)
+        .bind(format!("{address:#x}"))
+        .bind(from_block as i64)
+        .bind(to_block as i64)
+        .execute(&self.pool)
+        .await
+        .context("Failed to record backfill")?;
+
+        Ok(())
+    }
+
+    async fn backfills(&self) -> eyre::Result<Vec<Backfill>> {
+        let rows: Vec<BackfillRow> = sqlx::query_as("SELECT chain, address, from_block, to_block FROM backfills")
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to read backfills")?;
+
+        rows.into_iter().map(backfill).collect()
+    }
+    async fn save_checkpoint(&self, chain: u64, block: u64) -> eyre::Result<()> {
+        sqlx::query(
+            "INSERT INTO checkpoints (chain, block) VALUES (?, ?)
+            ON CONFLICT (chain) DO UPDATE SET block = max(block, excluded.block)",
+        )
+        .bind(chain as i64)
+        .bind(block as i64)
+        .execute(&self.pool)
+        .await
+        .context("Failed to save checkpoint")?;
+
+        Ok(())
+    }
+
+    async fn checkpoint(&self, chain: u64) -> eyre::Result<Option<u64>> {
+        let found: Option<(i64,)> = sqlx::query_as("SELECT block FROM checkpoints WHERE chain = ?")
+            .bind(chain as i64)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to read checkpoint")?;
+
+        Ok(found.map(|(block,)| block as u64))
+    }
+
+    async fn checkpoints(&self) -> eyre::Result<Vec<Checkpoint>> {
+        let rows: Vec<CheckpointRow> = sqlx::query_as("SELECT chain, block FROM checkpoints")
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to read checkpoints")?;
+
+        Ok(rows.into_iter().map(checkpoint).collect())
+    }
+    async fn record_block(&self, block: &ProcessedBlock) -> eyre::Result<()> {
+        sqlx::query(
+            "INSERT INTO processed_blocks (chain, block, timestamp, elapsed_ms, attempts, error)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT (chain, block) DO UPDATE SET
+                timestamp = excluded.timestamp, elapsed_ms = excluded.elapsed_ms,
+                attempts = excluded.attempts, error = excluded.error",
+        )
+        .bind(block.chain as i64)
+        .bind(block.block as i64)
+        .bind(block.timestamp as i64)
+        .bind(block.elapsed_ms as i64)
+        .bind(block.attempts as i64)
+        .bind(&block.error)
+        .execute(&self.pool)
+        .await
+        .context("Failed to record block")?;
+
+        Ok(())
+    }
+
+    async fn recorded_blocks(&self, chain: u64, from_block: u64, to_block: u64) -> eyre::Result<Vec<(u64, bool)>> {
+        let rows: Vec<(i64, bool)> = sqlx::query_as(
+            "SELECT block, error IS NOT NULL FROM processed_blocks
+            WHERE chain = ? AND block BETWEEN ? AND ? ORDER BY block",
+        )
+        .bind(chain as i64)
+        .bind(from_block as i64)
+        .bind(to_block as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to read processed blocks")?;
+
+        Ok(rows.into_iter().map(|(block, failed)| (block as u64, failed)).collect())
+    }
+
+    async fn chain_stats(&self, from_timestamp: u64, to_timestamp: u64) -> eyre::Result<Vec<ChainStats>> {
+        let rows: Vec<ChainStatsRow> = sqlx::query_as(
+            "SELECT chain, SUM(error IS NULL), SUM(error IS NOT NULL), MIN(block), MAX(block),
+                AVG(elapsed_ms), MAX(elapsed_ms), SUM(attempts - 1)
+            FROM processed_blocks WHERE timestamp BETWEEN ? AND ?
+            GROUP BY chain ORDER BY chain",
+        )
+        .bind(from_timestamp as i64)
+        .bind(to_timestamp as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to read processed blocks")?;
+
+        Ok(rows.into_iter().map(chain_stats).collect())
+    }
+
+    async fn block_errors(&self, from_timestamp: u64, to_timestamp: u64) -> eyre::Result<Vec<(u64, String, u64)>> {
+        let rows: Vec<(i64, String, i64)> = sqlx::query_as(
+            "SELECT chain, error, COUNT(*) FROM processed_blocks
+            WHERE error IS NOT NULL AND timestamp BETWEEN ? AND ?
+            GROUP BY chain, error ORDER BY COUNT(*) DESC",
+        )
+        .bind(from_timestamp as i64)
+        .bind(to_timestamp as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to read block errors")?;
+
+        Ok(rows.into_iter().map(count_row).collect())
+    }
+
+    async fn report_counts(&self, from_timestamp: u64, to_timestamp: u64) -> eyre::Result<Vec<(u64, String, u64)>> {
+        let rows: Vec<(i64, String, i64)> = sqlx::query_as(
+            "SELECT chain, wallet, COUNT(*) FROM pnl_reports WHERE timestamp BETWEEN ? AND ?
+            GROUP BY chain, wallet ORDER BY chain, wallet",
+        )
+        .bind(from_timestamp as i64)
+        .bind(to_timestamp as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to count reports")?;
+
+        Ok(rows.into_iter().map(count_row).collect())
+    }
+
+    async fn prune(&self, timestamp: u64, sink: Option<&mut dyn PruneSink>) -> eyre::Result<(u64, u64)> {
+        // A write by another connection after the first read fails the
+        // delete, rather than deleting what wasn't read
+        let mut tx = self.pool.begin().await.context("Failed to start pruning")?;
+
+        if let Some(sink) = sink {
+            let mut offset = 0;
+            loop {
+                let rows: Vec<ReportRow> = sqlx::query_as(
+                    "SELECT chain, block, address, wallet, timestamp, report FROM pnl_reports
+                    WHERE timestamp < ?1
+                    ORDER BY timestamp, chain, block, address
+                    LIMIT ?2 OFFSET ?3",
+                )
+                .bind(timestamp as i64)
+                .bind(PRUNE_PAGE_SIZE)
+                .bind(offset)
+                .fetch_all(&mut *tx)
+                .await
+                .context("Failed to read reports to prune")?;
+
+                let last_page = (rows.len() as i64) < PRUNE_PAGE_SIZE;
+                offset += rows.len() as i64;
+                sink.write(rows.into_iter().map(stored_report).collect::<eyre::Result<_>>()?)?;
+                if last_page {
+                    break;
+                }
+            }
+            sink.finish()?;
+        }
+
+        let reports = sqlx::query("DELETE FROM pnl_reports WHERE timestamp < ?")
+            .bind(timestamp as i64)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to prune reports")?;
+        let blocks = sqlx::query("DELETE FROM processed_blocks WHERE timestamp < ?")
+            .bind(timestamp as i64)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to prune processed blocks")?;
+        tx.commit().await.context("Failed to commit pruning")?;
+
+        Ok((reports.rows_affected(), blocks.rows_affected()))
+    }
+
+    async fn size(&self) -> eyre::Result<u64> {
+        let (size,): (i64,) = sqlx::query_as(
+            "SELECT (page_count - freelist_count) * page_size
+            FROM pragma_page_count(), pragma_freelist_count(), pragma_page_size()",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to get the database size")?;
+
+        Ok(size as u64)
+    }
+
+    async fn lock_chain(&self, _chain: u64) -> eyre::Result<Option<ChainLock>> {
+        bail!("SQLite storage is local to one host, chain locks need Postgres")
+    }
+}
+
+pub struct PostgresStorage {
+    pool: PgPool,
+}
+
+impl PostgresStorage {
+    pub async fn connect(url: &str) -> eyre::Result<Self> {
+        let pool = PgPool::connect(url).await.context("Failed to connect to Postgres")?;
+
+        sqlx::query(POSTGRES_SCHEMA)
+            .execute(&pool)
+            .await
+            .context("Failed to create the reports table")?;
+        sqlx::query(POSTGRES_BACKFILLS_SCHEMA)
+            .execute(&pool)
+            .await
+            .context("Failed to create the backfills table")?;
+        sqlx::query(POSTGRES_CHECKPOINTS_SCHEMA)
+            .execute(&pool)
+            .await
+            .context("Failed to create the checkpoints table")?;
+        sqlx::query(POSTGRES_PROCESSED_BLOCKS_SCHEMA)
+            .execute(&pool)
+            .await
+            .context("Failed to create the processed blocks table")?;
+
+        Ok(Self { pool })
+    }
+}
+
+#[burberry::async_trait]
+impl Storage for PostgresStorage {
+    async fn save(&self, stored: &StoredReport) -> eyre::Result<()> {
+        let report = serde_json::to_string(&stored.report).context("Failed to serialize report")?;
+
+        sqlx::query(
+            "INSERT INTO pnl_reports (chain, block, address, wallet, timestamp, pnl, report)
+            VALUES ($1, $2, $3, $4, $5, $6::NUMERIC, $7::JSONB)
+            ON CONFLICT (chain, block, address) DO UPDATE SET
+                wallet = excluded.wallet, timestamp = excluded.timestamp, pnl = excluded.pnl, report = excluded.report",
+        )
+        .bind(stored.chain as i64)
+        .bind(stored.block as i64)
+        .bind(format!("{:#x}", stored.address))
+        .bind(&stored.wallet)
+        .bind(stored.timestamp as i64)
+        .bind(stored.report.pnl.to_string())
+        .bind(report)
+        .execute(&self.pool)
+        .await
+        .context("Failed to save report")?;
+
+        Ok(())
+    }
+
+    async fn reports(&self, query: &ReportQuery) -> eyre::Result<Vec<StoredReport>> {
+        let rows: Vec<ReportRow> = sqlx::query_as(
+            "SELECT chain, block, address, wallet, timestamp, report::TEXT FROM pnl_reports
+            WHERE ($1::BIGINT IS NULL OR chain = $1) AND ($2::TEXT IS NULL OR address = $2)
+                AND ($3::BIGINT IS NULL OR block >= $3) AND ($4::BIGINT IS NULL OR block <= $4)
+                AND ($5::BIGINT IS NULL OR timestamp >= $5) AND ($6::BIGINT IS NULL OR timestamp <= $6)
+            ORDER BY timestamp, chain, block, address
+            LIMIT $7::BIGINT OFFSET COALESCE($8::BIGINT, 0)",
+        )
+        .bind(query.chain.map(|chain| chain as i64))
+        .bind(query.address.map(|address| format!("{address:#x}")))
+        .bind(query.from_block.map(|block| block as i64))
+        .bind(query.to_block.map(|block| block as i64))
+        .bind(query.from_timestamp.map(|timestamp| timestamp as i64))
+        .bind(query.to_timestamp.map(|timestamp| timestamp as i64))
+        .bind(query.limit.map(|limit| limit as i64))
+        .bind(query.offset.map(|offset| offset as i64))
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to read reports")?;
+
+        rows.into_iter().map(stored_report).collect()
+    }
+
+    async fn backfilled(&self, chain: u64, address: Address) -> eyre::Result<bool> {
+        let found: Option<(i64,)> = sqlx::query_as("SELECT to_block FROM backfills WHERE chain = $1 AND address = $2")
+            .bind(chain as i64)
+            .bind(format!("{address:#x}"))
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to read backfills")?;
+
+        Ok(found.is_some())
+    }
+
+    async fn record_backfill(&self, chain: u64, address: Address, from_block: u64, to_block: u64) -> eyre::Result<()> {
+        sqlx::query(
+            "INSERT INTO backfills (chain, address, from_block, to_block) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (chain, address) DO UPDATE SET from_block = excluded.from_block, to_block = excluded.to_block",
+        )
+        .bind(chain as i64)
+        .bind(format!("{address:#x}"))
+        .bind(from_block as i64)
+        .bind(to_block as i64)
+        .execute(&self.pool)
+        .await
+        .context("Failed to record backfill")?;
+
+        Ok(())
+    }
+
+    async fn backfills(&self) -> eyre::Result<Vec<Backfill>> {
+        let rows: Vec<BackfillRow> = sqlx::query_as("SELECT chain, address, from_block, to_block FROM backfills")
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to read backfills")?;
+
+        rows.into_iter().map(backfill).collect()
+    }
+    async fn save_checkpoint(&self, chain: u64, block: u64) -> eyre::Result<()> {
+        sqlx::query(
+            "INSERT INTO checkpoints (chain, block) VALUES ($1, $2)
+            ON CONFLICT (chain) DO UPDATE SET block = GREATEST(checkpoints.block, excluded.block)",
+        )
+        .bind(chain as i64)
+        .bind(block as i64)
+        .execute(&self.pool)
+        .await
+        .context("Failed to save checkpoint")?;
+
+        Ok(())
+    }
+
+    async fn checkpoint(&self, chain: u64) -> eyre::Result<Option<u64>> {
+        let found: Option<(i64,)> = sqlx::query_as("SELECT block FROM checkpoints WHERE chain = $1")
+            .bind(chain as i64)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to read checkpoint")?;
+
+        Ok(found.map(|(block,)| block as u64))
+    }
+
+    async fn checkpoints(&self) -> eyre::Result<Vec<Checkpoint>> {
+        let rows: Vec<CheckpointRow> = sqlx::query_as("SELECT chain, block FROM checkpoints")
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to read checkpoints")?;
+
+        Ok(rows.into_iter().map(checkpoint).collect())
+    }
+    async fn record_block(&self, block: &ProcessedBlock) -> eyre::Result<()> {
+        sqlx::query(
+            "INSERT INTO processed_blocks (chain, block, timestamp, elapsed_ms, attempts, error)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (chain, block) DO UPDATE SET
+                timestamp = excluded.timestamp, elapsed_ms = excluded.elapsed_ms,
+                attempts = excluded.attempts, error = excluded.error",
+        )
+        .bind(block.chain as i64)
+        .bind(block.block as i64)
+        .bind(block.timestamp as i64)
+        .bind(block.elapsed_ms as i64)
+        .bind(block.attempts as i64)
+        .bind(&block.error)
+        .execute(&self.pool)
+        .await
+        .context("Failed to record block")?;
+
+        Ok(())
+    }
+
+    async fn recorded_blocks(&self, chain: u64, from_block: u64, to_block: u64) -> eyre::Result<Vec<(u64, bool)>> {
+        let rows: Vec<(i64, bool)> = sqlx::query_as(
+            "SELECT block, error IS NOT NULL FROM processed_blocks
+            WHERE chain = $1 AND block BETWEEN $2 AND $3 ORDER BY block",
+        )
+        .bind(chain as i64)
+        .bind(from_block as i64)
+        .bind(to_block as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to read processed blocks")?;
+
+        Ok(rows.into_iter().map(|(block, failed)| (block as u64, failed)).collect())
+    }
+
+    async fn chain_stats(&self, from_timestamp: u64, to_timestamp: u64) -> eyre::Result<Vec<ChainStats>> {
+        let rows: Vec<ChainStatsRow> = sqlx::query_as(
+            "SELECT chain, COUNT(*) FILTER (WHERE error IS NULL), COUNT(*) FILTER (WHERE error IS NOT NULL),
+                MIN(block), MAX(block), AVG(elapsed_ms)::DOUBLE PRECISION, MAX(elapsed_ms),
+                SUM(attempts - 1)::BIGINT
+            FROM processed_blocks WHERE timestamp BETWEEN $1 AND $2
+            GROUP BY chain ORDER BY chain",
+        )
+        .bind(from_timestamp as i64)
+        .bind(to_timestamp as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to read processed blocks")?;
+
+        Ok(rows.into_iter().map(chain_stats).collect())
+    }
+
+    async fn block_errors(&self, from_timestamp: u64, to_timestamp: u64) -> eyre::Result<Vec<(u64, String, u64)>> {
+        let rows: Vec<(i64, String, i64)> = sqlx::query_as(
+            "SELECT chain, error, COUNT(*) FROM processed_blocks
+            WHERE error IS NOT NULL AND timestamp BETWEEN $1 AND $2
+            GROUP BY chain, error ORDER BY COUNT(*) DESC",
+        )
+        .bind(from_timestamp as i64)
+        .bind(to_timestamp as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to read block errors")?;
+
+        Ok(rows.into_iter().map(count_row).collect())
+    }
+
+    async fn report_counts(&self, from_timestamp: u64, to_timestamp: u64) -> eyre::Result<Vec<(u64, String, u64)>> {
+        let rows: Vec<(i64, String, i64)> = sqlx::query_as(
+            "SELECT chain, wallet, COUNT(*) FROM pnl_reports WHERE timestamp BETWEEN $1 AND $2
+            GROUP BY chain, wallet ORDER BY chain, wallet",
+        )
+        .bind(from_timestamp as i64)
+        .bind(to_timestamp as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to count reports")?;
+
+        Ok(rows.into_iter().map(count_row).collect())
+    }
+
+    async fn prune(&self, timestamp: u64, sink: Option<&mut dyn PruneSink>) -> eyre::Result<(u64, u64)> {
+        // Reads and deletes see one snapshot: a report saved since is left
+        // alone, and one saved again since fails the delete
+        let mut tx = self.pool.begin().await.context("Failed to start pruning")?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+            .execute(&mut *tx)
+            .await
+            .context("Failed to start pruning")?;
+
+        if let Some(sink) = sink {
+            let mut offset = 0;
+            loop {
+                let rows: Vec<ReportRow> = sqlx::query_as(
+                    "SELECT chain, block, address, wallet, timestamp, report::TEXT FROM pnl_reports
+                    WHERE timestamp < $1
+                    ORDER BY timestamp, chain, block, address
+                    LIMIT $2 OFFSET $3",
+                )
+                .bind(timestamp as i64)
+                .bind(PRUNE_PAGE_SIZE)
+                .bind(offset)
+                .fetch_all(&mut *tx)
+                .await
+                .context("Failed to read reports to prune")?;
+
+                let last_page = (rows.len() as i64) < PRUNE_PAGE_SIZE;
+                offset += rows.len() as i64;
+                sink.write(rows.into_iter().map(stored_report).collect::<eyre::Result<_>>()?)?;
+                if last_page {
+                    break;
+                }
+            }
+            sink.finish()?;
+        }
+
+        let reports = sqlx::query("DELETE FROM pnl_reports WHERE timestamp < $1")
+            .bind(timestamp as i64)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to prune reports")?;
+        let blocks = sqlx::query("DELETE FROM processed_blocks WHERE timestamp < $1")
+            .bind(timestamp as i64)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to prune processed blocks")?;
+        tx.commit().await.context("Failed to commit pruning")?;
+
+        Ok((reports.rows_affected(), blocks.rows_affected()))
+    }
+
+    async fn size(&self) -> eyre::Result<u64> {
+        // The rows themselves, as the table files only shrink on a full vacuum
+        let (size,): (i64,) = sqlx::query_as(
+            "SELECT ((SELECT COALESCE(SUM(pg_column_size(r.*)), 0) FROM pnl_reports r)
+                + (SELECT COALESCE(SUM(pg_column_size(b.*)), 0) FROM processed_blocks b))::BIGINT",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to get the database size")?;
+
+        Ok(size as u64)
+    }
+
+    async fn lock_chain(&self, chain: u64) -> eyre::Result<Option<ChainLock>> {
+        // A session lock, held by a connection of its own for as long as
+        // it's open rather than returned to the pool
+        let mut connection = self
+            .pool
+            .acquire()
+            .await
+            .context("Failed to connect to Postgres")?
+            .detach();
+        let (locked,): (bool,) = sqlx::query_as("SELECT pg_try_advisory_lock($1, $2)")
+            .bind(CHAIN_LOCK_NAMESPACE)
+            .bind(chain as u32 as i32)
+            .fetch_one(&mut connection)
+            .await
+            .context("Failed to take chain lock")?;
+
+        Ok(locked.then_some(ChainLock { connection }))
+    }
+}
+
+fn stored_report((chain, block, address, wallet, timestamp, report): ReportRow) -> eyre::Result<StoredReport> {
+    Ok(StoredReport {
+        chain: chain as u64,
+        block: block as u64,
+        timestamp: timestamp as u64,
+        wallet,
+        address: address.parse().context("Invalid stored address")?,
+        report: serde_json::from_str(&report)
+            .context("Invalid stored report")
+            .and_then(migration::migrate_report)?,
+    })
+}
+
+fn backfill((chain, address, from_block, to_block): BackfillRow) -> eyre::Result<Backfill> {
+    Ok(Backfill {
+        chain: chain as u64,
+        address: address.parse().context("Invalid stored address")?,
+        from_block: from_block as u64,
+        to_block: to_block as u64,
+    })
+}
+
+fn checkpoint((chain, block): CheckpointRow) -> Checkpoint {
+    Checkpoint {
+        chain: chain as u64,
+        block: block as u64,
+    }
+}
+
+fn chain_stats(
+    (chain, processed, failed, first_block, last_block, avg_elapsed_ms, max_elapsed_ms, retries): ChainStatsRow,
+) -> ChainStats {
+    ChainStats {
+        chain: chain as u64,
+        processed: processed as u64,
+        failed: failed as u64,
+        first_block: first_block as u64,
+        last_block: last_block as u64,
+        avg_elapsed_ms,
+        max_elapsed_ms: max_elapsed_ms as u64,
+        retries: retries as u64,
+    }
+}
+
+fn count_row((chain, key, count): (i64, String, i64)) -> (u64, String, u64) {
+    (chain as u64, key, count as u64)
+}
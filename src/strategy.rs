@@ -1,33 +1,156 @@
 use {
     crate::{
-        config::WalletWithContext,
-        message::MessageGenerator,
-        processor::{self},
+        chain_adapter::ChainRules,
+        cluster::ClusterTracker,
+        config::{
+            AggregateConfig, AlertTo, BlockSource, ChainConfig, ClusterWatchWithContext, ErrorAlertTo,
+            EventWatchWithContext, FirstSeenConfig, LargeTransferWatchWithContext, PermalinkConfig, PriceConfig,
+            RetryConfig, TokenRiskConfig, TokenWatchWithContext, TraceConfig, TraceLimits, TraceMode,
+            WalletWithContext,
+        },
+        delivery::{delivery_id, Deliveries},
+        event_watch, exchanges,
+        executor::{dune::WalletTransfer, Action, Alert, AlertEvent, AlertTransfer, Emission, ReportEvent},
+        explorer::{self, ExplorerClient},
+        first_seen::SeenTokens,
+        heal,
+        message::{MessageFormat, MessageGenerator},
+        perps::{self, PerpsProtocol},
+        price::PriceOracle,
+        processor::{self, PnlReport, TraceSize},
+        reorg::ReorgTracker,
+        retry::RetrySender,
+        rpc_metrics, state_diff,
+        storage::{ProcessedBlock, Storage, StoredReport},
+        throttle::{Release, RollUp, Throttle},
+        token_watch,
+        trace_mode::TraceModeSelector,
+        tui::{DashboardEvent, DashboardSender},
         utils::{self},
+        vault,
     },
-    alloy::{providers::Provider, rpc::types::Block, transports::Transport},
-    alloy_chains::Chain,
-    burberry::{
-        executor::telegram_message::{Message, MessageBuilder},
-        ActionSubmitter, Strategy,
+    alloy::{
+        primitives::{Address, B256},
+        providers::Provider,
+        rpc::types::{trace::geth::CallFrame, AnyTransactionReceipt, Block},
+        transports::Transport,
     },
+    alloy_chains::Chain,
+    burberry::{executor::telegram_message::escape, ActionSubmitter, Strategy},
     eyre::Context,
-    std::sync::Arc,
-    tokio::time::Instant,
-    tracing::{error, info, instrument},
+    redis::aio::ConnectionManager,
+    std::{
+        collections::{BTreeMap, HashMap, HashSet},
+        sync::Arc,
+    },
+    tokio::{
+        sync::watch,
+        time::{Duration, Instant},
+    },
+    tracing::{debug, error, info, instrument, warn},
 };
 
+/// How often the blocks `storage` has as failed or missing are processed
+/// again
+const HEAL_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Blocks before the current one looked at for gaps
+const HEAL_LOOKBACK: u64 = 1000;
+
+/// Gaps processed again at once, so that live blocks aren't held back long.
+/// The rest wait for the next round
+const MAX_HEALED_BLOCKS: usize = 50;
+
 pub struct WalletWatcher<T: Clone + Transport> {
     pub provider: Arc<dyn Provider<T>>,
     pub chain: Chain,
+    /// What the chain's config adds to its adapter, see [`ChainRules`]
+    pub rules: ChainRules,
     pub wallets: Vec<WalletWithContext>,
     pub message_generator: MessageGenerator<T>,
+    pub dashboard: Option<DashboardSender>,
+    pub lenient_traces: bool,
+    pub error_channel: Option<ErrorAlertTo>,
+    pub retry: RetryConfig,
+    pub trace_limits: TraceLimits,
+    pub trace_config: TraceConfig,
+    trace_modes: TraceModeSelector,
+    explorer: Option<ExplorerClient>,
+    prices: Option<PriceOracle<T>>,
+    /// Failed blocks are sent here to come back as events when due
+    retries: Option<RetrySender>,
+    /// Failed blocks waiting for their next attempt, by hash
+    retrying: HashMap<B256, PendingRetry>,
+    /// By wallet index, for throttled wallets that have had a report
+    throttles: HashMap<usize, Throttle>,
+    pub aggregate: Option<AggregateConfig>,
+    /// Reports of the current window by wallet index
+    aggregated: HashMap<usize, RollUp>,
+    /// First block of the current window and when it started
+    window_start: Option<(u64, Instant)>,
+    seen_tokens: Option<SeenTokens>,
+    first_seen_alert: bool,
+    cex_alert: bool,
+    reorgs: ReorgTracker,
+    deliveries: Deliveries,
+    pub perps: Vec<PerpsProtocol>,
+    pub token_watches: Vec<TokenWatchWithContext>,
+    pub event_watches: Vec<EventWatchWithContext>,
+    pub large_transfer_watches: Vec<LargeTransferWatchWithContext>,
+    clusters: ClusterTracker,
+    storage: Option<Arc<dyn Storage>>,
+    block_source: BlockSource,
+    last_heal: Instant,
+    /// Gaps processed again in this run, which aren't tried twice
+    healed: HashSet<u64>,
+    /// Wallets to watch from the next block on, when the config is reloaded
+    wallet_updates: Option<watch::Receiver<Vec<WalletWithContext>>>,
+    /// Provider to use from the next block on, when the RPC was reconnected
+    provider_updates: Option<watch::Receiver<Arc<dyn Provider<T>>>>,
+}
+
+/// A failed block waiting for its next attempt
+struct PendingRetry {
+    number: u64,
+    attempt: u32,
 }
 
 impl<T: Clone + Transport> WalletWatcher<T> {
     pub fn new(chain: Chain, provider: Arc<dyn Provider<T>>, wallets: Vec<WalletWithContext>) -> Self {
         Self {
             message_generator: MessageGenerator::new(chain, Arc::clone(&provider)),
+            rules: ChainRules::default(),
+            dashboard: None,
+            lenient_traces: false,
+            error_channel: None,
+            retry: RetryConfig::default(),
+            trace_limits: TraceLimits::default(),
+            trace_config: TraceConfig::default(),
+            trace_modes: TraceModeSelector::default(),
+            explorer: None,
+            prices: None,
+            retries: None,
+            retrying: HashMap::new(),
+            throttles: HashMap::new(),
+            aggregate: None,
+            aggregated: HashMap::new(),
+            window_start: None,
+            seen_tokens: None,
+            first_seen_alert: false,
+            cex_alert: false,
+            reorgs: ReorgTracker::default(),
+            deliveries: Deliveries::default(),
+            perps: vec![],
+            token_watches: vec![],
+            event_watches: vec![],
+            large_transfer_watches: vec![],
+            clusters: ClusterTracker::default(),
+            storage: None,
+            block_source: BlockSource::default(),
+            last_heal: Instant::now(),
+            healed: HashSet::new(),
+            wallet_updates: None,
+            provider_updates: None,
 
             chain,
             provider,
@@ -35,26 +158,844 @@ impl<T: Clone + Transport> WalletWatcher<T> {
         }
     }
 
+    pub fn with_chain_config(mut self, config: &ChainConfig) -> Self {
+        self.message_generator = self.message_generator.with_chain_config(config);
+        self.rules = config.rules();
+        self.aggregate = config.aggregate;
+        self.trace_config = config.trace;
+        self.explorer = config.explorer_api.clone().map(ExplorerClient::new);
+        self.perps = config.perps.clone();
+        self.block_source = config.block_source;
+        self
+    }
+
+    pub fn with_permalink(mut self, permalink: PermalinkConfig) -> Self {
+        self.message_generator = self.message_generator.with_permalink(permalink);
+        self
+    }
+
+    pub fn with_token_risk(mut self, config: TokenRiskConfig) -> Self {
+        self.message_generator = self.message_generator.with_token_risk(config);
+        self
+    }
+
+    /// Value reports in USD, with the Chainlink feeds and CoinGecko ids of
+    /// `chain_config`
+    pub fn with_prices(mut self, config: &PriceConfig, chain_config: &ChainConfig) -> Self {
+        self.prices = Some(PriceOracle::new(
+            self.chain,
+            Arc::clone(&self.provider),
+            config,
+            chain_config,
+        ));
+        self
+    }
+
+    /// Track the tokens each wallet has held, keeping them in `cache` if given
+    pub fn with_first_seen(mut self, config: FirstSeenConfig, cache: Option<ConnectionManager>) -> Self {
+        self.seen_tokens = Some(SeenTokens::new(self.chain, cache));
+        self.first_seen_alert = config.alert;
+        self
+    }
+
+    pub fn with_token_watches(mut self, watches: Vec<TokenWatchWithContext>) -> Self {
+        self.token_watches = watches;
+        self
+    }
+
+    pub fn with_event_watches(mut self, watches: Vec<EventWatchWithContext>) -> Self {
+        self.event_watches = watches;
+        self
+    }
+
+    pub fn with_large_transfer_watches(mut self, watches: Vec<LargeTransferWatchWithContext>) -> Self {
+        self.large_transfer_watches = watches;
+        self
+    }
+
+    /// Send a separate alert for each report with a CEX deposit
+    pub fn with_cluster_watches(mut self, watches: Vec<ClusterWatchWithContext>) -> Self {
+        self.clusters = ClusterTracker::new(watches);
+        self
+    }
+
+    pub fn with_cex_alert(mut self, alert: bool) -> Self {
+        self.cex_alert = alert;
+        self
+    }
+
+    pub fn with_token_cache(mut self, cache: ConnectionManager) -> Self {
+        self.message_generator = self.message_generator.with_token_cache(cache);
+        self
+    }
+
+    /// Use the symbol and decimals of tokens from a token list
+    pub fn with_listed_tokens(mut self, tokens: &HashMap<Address, (String, u8)>) -> Self {
+        self.message_generator = self.message_generator.with_listed_tokens(tokens);
+        self
+    }
+
+    /// Remember delivered reports in `store`, so they aren't sent again after
+    /// a restart
+    pub fn with_delivery_store(mut self, store: ConnectionManager) -> Self {
+        self.deliveries = Deliveries::new(Some(store));
+        self
+    }
+
+    /// Save every report into `storage`
+    pub fn with_storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Swap in the wallets sent to `updates`, keeping the subscriptions
+    pub fn with_wallet_updates(mut self, updates: watch::Receiver<Vec<WalletWithContext>>) -> Self {
+        self.wallet_updates = Some(updates);
+        self
+    }
+
+    /// Switch to the providers sent to `updates`
+    pub fn with_provider_updates(mut self, updates: watch::Receiver<Arc<dyn Provider<T>>>) -> Self {
+        self.provider_updates = Some(updates);
+        self
+    }
+
+    /// Make calls through the provider last sent to the updates, if it changed
+    fn apply_provider_updates(&mut self) {
+        let Some(updates) = self.provider_updates.as_mut() else {
+            return;
+        };
+        if !updates.has_changed().unwrap_or(false) {
+            return;
+        }
+        let provider = Arc::clone(&updates.borrow_and_update());
+
+        self.message_generator.set_provider(Arc::clone(&provider));
+        if let Some(prices) = self.prices.as_mut() {
+            prices.set_provider(Arc::clone(&provider));
+        }
+        self.provider = provider;
+    }
+
+    /// Watch the wallets last sent to the updates, if they changed. State kept
+    /// by wallet index follows the wallets still watched, by name and address,
+    /// and is dropped for the removed ones
+    fn apply_wallet_updates(&mut self) {
+        let Some(updates) = self.wallet_updates.as_mut() else {
+            return;
+        };
+        if !updates.has_changed().unwrap_or(false) {
+            return;
+        }
+        let wallets = updates.borrow_and_update().clone();
+
+        let indices = self
+            .wallets
+            .iter()
+            .enumerate()
+            .filter_map(|(old, wallet)| {
+                let new = wallets
+                    .iter()
+                    .position(|w| w.name == wallet.name && w.address == wallet.address);
+                if new.is_none() {
+                    info!(chain = %self.chain, wallet = %wallet.name, "Wallet removed, dropping its pending roll-ups");
+                }
+                new.map(|new| (old, new))
+            })
+            .collect::<HashMap<_, _>>();
+
+        self.throttles = std::mem::take(&mut self.throttles)
+            .into_iter()
+            .filter_map(|(index, throttle)| indices.get(&index).map(|new| (*new, throttle)))
+            .collect();
+        self.aggregated = std::mem::take(&mut self.aggregated)
+            .into_iter()
+            .filter_map(|(index, rollup)| indices.get(&index).map(|new| (*new, rollup)))
+            .collect();
+        self.reorgs.remap_wallets(&indices);
+
+        info!(chain = %self.chain, wallets = wallets.len(), "Wallets reloaded");
+        self.wallets = wallets;
+    }
+
+    pub fn with_dashboard(mut self, dashboard: DashboardSender) -> Self {
+        self.dashboard = Some(dashboard);
+        self
+    }
+
+    pub fn with_lenient_traces(mut self, lenient: bool) -> Self {
+        self.lenient_traces = lenient;
+        self
+    }
+
+    pub fn with_error_channel(mut self, channel: ErrorAlertTo) -> Self {
+        self.error_channel = Some(channel);
+        self
+    }
+
+    /// Retry failed blocks by `retry`, sending them to `retries` to get them
+    /// back when due. Failed blocks are given up on at once otherwise
+    pub fn with_retry(mut self, retry: RetryConfig, retries: RetrySender) -> Self {
+        self.retry = retry;
+        self.retries = Some(retries);
+        self
+    }
+
+    pub fn with_trace_limits(mut self, limits: TraceLimits) -> Self {
+        self.trace_limits = limits;
+        self
+    }
+
+    /// Cut the traces of a block down to the trace limits, logging their size
+    /// so the limits can be tuned. Returns whether any trace was cut.
+    fn truncate_traces(&self, block: u64, receipt_and_traces: &mut [(AnyTransactionReceipt, CallFrame)]) -> bool {
+        let mut frames = 0;
+        let mut largest = TraceSize::default();
+        let mut truncated = false;
+
+        for (_, trace) in receipt_and_traces.iter_mut() {
+            let size = TraceSize::of(trace);
+            frames += size.frames;
+            largest.frames = largest.frames.max(size.frames);
+            largest.depth = largest.depth.max(size.depth);
+
+            truncated |= processor::truncate_trace(trace, &self.trace_limits);
+        }
+
+        info!(
+            block,
+            frames,
+            max_tx_frames = largest.frames,
+            max_depth = largest.depth,
+            truncated,
+            "Trace size"
+        );
+
+        truncated
+    }
+
+    fn alert_error(&self, submitter: &dyn ActionSubmitter<Action>, message: String) {
+        match &self.error_channel {
+            Some(ErrorAlertTo::Telegram(target)) => {
+                submitter.submit(Action::Telegram(target.clone(), escape(&message)))
+            }
+            Some(ErrorAlertTo::Webhook(target)) => submitter.submit(Action::Webhook(target.clone(), message)),
+            None => {}
+        }
+    }
+
+    /// Send the message of a report to a chat output, rolled up with earlier
+    /// reports when the wallet is throttled
+    async fn send_message(
+        &mut self,
+        wallet_index: usize,
+        block: &Block,
+        receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
+        report: &PnlReport,
+        submitter: &dyn ActionSubmitter<Action>,
+    ) -> eyre::Result<()> {
+        if self.aggregate.is_some() {
+            self.aggregated
+                .entry(wallet_index)
+                .or_default()
+                .add(block.header.number, report);
+            return Ok(());
+        }
+
+        let wallet = self.wallets[wallet_index].clone();
+        let format = message_format(&wallet.alert_to);
+
+        let release = match wallet.throttle {
+            Some(window) => self
+                .throttles
+                .entry(wallet_index)
+                .or_insert_with(|| Throttle::new(window))
+                .offer(block.header.number, report, Instant::now()),
+            None => Release::Report,
+        };
+
+        let message = match release {
+            Release::Report => {
+                self.message_generator
+                    .generate(block, receipt_and_traces, report, &wallet, format)
+                    .await?
+            }
+            Release::RollUp(rollup) => self.message_generator.generate_rollup(&rollup, &wallet, format).await?,
+            Release::Hold => return Ok(()),
+        };
+
+        submit_message(submitter, &wallet.alert_to, message);
+        Ok(())
+    }
+
+    /// What data outputs get of a report
+    fn report_event(&self, block: &Block, wallet: &WalletWithContext, report: PnlReport) -> ReportEvent {
+        ReportEvent::new(self.chain, block, wallet, report)
+            .with_native_decimals(self.message_generator.native_decimals())
+    }
+
+    /// Send a report to its wallet's output. Chat outputs go through the
+    /// wallet's throttle and the chain's aggregation
+    async fn deliver_report(
+        &mut self,
+        wallet_index: usize,
+        block: &Block,
+        receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
+        report: PnlReport,
+        submitter: &dyn ActionSubmitter<Action>,
+    ) -> eyre::Result<()> {
+        let wallet = self.wallets[wallet_index].clone();
+
+        match wallet.alert_to.as_ref() {
+            AlertTo::Telegram(_) |
+            AlertTo::Signal(_) |
+            AlertTo::Twilio(_) |
+            AlertTo::Discord(_) |
+            AlertTo::Slack(_) => {
+                self.send_message(wallet_index, block, receipt_and_traces, &report, submitter)
+                    .await?;
+            }
+
+            AlertTo::Clickhouse(target) => {
+                let event = self.report_event(block, &wallet, report);
+                submitter.submit(Action::Clickhouse(target.clone(), event));
+            }
+
+            AlertTo::Nats(target) => {
+                let event = self.report_event(block, &wallet, report);
+                submitter.submit(Action::Nats(target.clone(), event));
+            }
+
+            AlertTo::Redis(target) => {
+                let event = self.report_event(block, &wallet, report);
+                submitter.submit(Action::Redis(target.clone(), event));
+            }
+
+            AlertTo::Mqtt(target) => {
+                let event = self.report_event(block, &wallet, report);
+                submitter.submit(Action::Mqtt(target.clone(), event));
+            }
+
+            AlertTo::Push(target) => {
+                let event = self.report_event(block, &wallet, report);
+                submitter.submit(Action::Push(target.clone(), event));
+            }
+
+            AlertTo::Dune(target) => {
+                let transfers = self.wallet_transfers(&wallet, receipt_and_traces, &report);
+                let event = self.report_event(block, &wallet, report);
+                submitter.submit(Action::Dune(target.clone(), event, transfers));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send a made-up report of a wallet without txs the way a real one of
+    /// the block would be, to check the wiring of its channel
+    pub async fn send_test_report(
+        &mut self,
+        wallet_index: usize,
+        block: &Block,
+        report: PnlReport,
+        submitter: &dyn ActionSubmitter<Action>,
+    ) -> eyre::Result<()> {
+        let wallet = self.wallets[wallet_index].clone();
+
+        if let (Some(_), true) = (wallet.combined_channel, wallet.alert_to.is_chat()) {
+            let message = self
+                .message_generator
+                .generate_combined(block, &[(wallet.clone(), report)], message_format(&wallet.alert_to))
+                .await?;
+            submit_message(submitter, &wallet.alert_to, message);
+            return Ok(());
+        }
+
+        self.deliver_report(wallet_index, block, &[], report, submitter).await
+    }
+
+    /// Send the roll-ups of throttled wallets whose window has passed
+    async fn flush_rollups(&mut self, submitter: &dyn ActionSubmitter<Action>) {
+        let now = Instant::now();
+        let due = self
+            .throttles
+            .iter_mut()
+            .filter_map(|(i, throttle)| throttle.take_due(now).map(|rollup| (*i, rollup)))
+            .collect::<Vec<_>>();
+
+        for (wallet_index, rollup) in due {
+            let wallet = self.wallets[wallet_index].clone();
+
+            match self
+                .message_generator
+                .generate_rollup(&rollup, &wallet, message_format(&wallet.alert_to))
+                .await
+            {
+                Ok(message) => submit_message(submitter, &wallet.alert_to, message),
+                Err(err) => error!(wallet = %wallet.name, "Failed to generate roll-up: {err:#}"),
+            }
+        }
+    }
+
+    /// Send the reports merged over the current window once it has closed
+    async fn flush_aggregated(&mut self, block: u64, submitter: &dyn ActionSubmitter<Action>) {
+        let Some(aggregate) = self.aggregate else {
+            return;
+        };
+
+        let (start_block, started) = *self.window_start.get_or_insert((block, Instant::now()));
+        if !aggregate.is_due(block.saturating_sub(start_block) + 1, started.elapsed()) {
+            return;
+        }
+
+        self.window_start = None;
+
+        for (wallet_index, rollup) in std::mem::take(&mut self.aggregated) {
+            let wallet = self.wallets[wallet_index].clone();
+
+            match self
+                .message_generator
+                .generate_rollup(&rollup, &wallet, message_format(&wallet.alert_to))
+                .await
+            {
+                Ok(message) => submit_message(submitter, &wallet.alert_to, message),
+                Err(err) => error!(wallet = %wallet.name, "Failed to generate aggregated message: {err:#}"),
+            }
+        }
+    }
+
+    /// Tell builder wallets about their blocks a new head reorged out, as the
+    /// reward reported for them was never earned
+    fn alert_orphaned(&mut self, block: &Block, submitter: &dyn ActionSubmitter<Action>) {
+        for orphaned in self.reorgs.observe(&block.header) {
+            warn!(
+                chain = %self.chain,
+                block = orphaned.number,
+                hash = %orphaned.hash,
+                depth = orphaned.depth,
+                "Built block was orphaned"
+            );
+
+            for (wallet_index, reward) in &orphaned.rewards {
+                let wallet = &self.wallets[*wallet_index];
+                if !wallet.alert_to.is_chat() {
+                    continue;
+                }
+
+                let message = self.message_generator.generate_orphaned(
+                    &orphaned,
+                    *reward,
+                    wallet,
+                    message_format(&wallet.alert_to),
+                );
+                submit_message(submitter, &wallet.alert_to, message);
+            }
+        }
+    }
+
+    /// Alert on the transfers of watched tokens above their threshold
+    async fn alert_token_transfers(
+        &mut self,
+        block: &Block,
+        receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
+        submitter: &dyn ActionSubmitter<Action>,
+    ) {
+        for watch in self.token_watches.clone() {
+            let min_value = match self.message_generator.load_symbol_and_decimal(&watch.token).await {
+                Ok((_, decimals)) => token_watch::to_min_value(watch.min_amount, *decimals),
+                Err(err) => Err(err),
+            };
+            let min_value = match min_value {
+                Ok(min_value) => min_value,
+                Err(err) => {
+                    warn!(watch = %watch.name, "Failed to get the threshold of the token watch: {err:#}");
+                    continue;
+                }
+            };
+
+            let transfers =
+                token_watch::find_transfers(self.chain, &self.rules, receipt_and_traces, watch.token, min_value);
+            if transfers.is_empty() {
+                continue;
+            }
+
+            if !watch.alert_to.is_chat() {
+                let alert = Alert::TokenTransfers {
+                    transfers: transfers.iter().map(AlertTransfer::from).collect(),
+                };
+                let event = AlertEvent::new(self.chain, block, &watch.name, alert);
+                submit_alert(submitter, &watch.alert_to, event);
+                continue;
+            }
+
+            let message = self.message_generator.generate_token_transfers(
+                block,
+                &watch,
+                &transfers,
+                &self.address_labels(),
+                message_format(&watch.alert_to),
+            );
+            submit_message(submitter, &watch.alert_to, message);
+        }
+    }
+
+    /// Alert on any transfer on the chain above a scanner's thresholds
+    async fn alert_large_transfers(
+        &mut self,
+        block: &Block,
+        receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
+        submitter: &dyn ActionSubmitter<Action>,
+    ) {
+        for watch in self.large_transfer_watches.clone() {
+            let thresholds = match token_watch::large_transfer_thresholds(
+                self.chain,
+                &self.rules,
+                &watch,
+                self.message_generator.native_decimals(),
+            ) {
+                Ok(thresholds) => thresholds,
+                Err(err) => {
+                    warn!(watch = %watch.name, "Failed to get the thresholds of the large transfer watch: {err:#}");
+                    continue;
+                }
+            };
+
+            let transfers = token_watch::find_large_transfers(self.chain, &self.rules, receipt_and_traces, &thresholds);
+            if transfers.is_empty() {
+                continue;
+            }
+
+            if !watch.alert_to.is_chat() {
+                let alert = Alert::LargeTransfers {
+                    transfers: transfers.iter().map(AlertTransfer::from).collect(),
+                };
+                let event = AlertEvent::new(self.chain, block, &watch.name, alert);
+                submit_alert(submitter, &watch.alert_to, event);
+                continue;
+            }
+
+            let labels = self.address_labels();
+            let message = self
+                .message_generator
+                .generate_large_transfers(block, &watch, &transfers, &labels, message_format(&watch.alert_to))
+                .await;
+            submit_message(submitter, &watch.alert_to, message);
+        }
+    }
+
+    /// Alert on the clusters whose wallets together crossed a threshold
+    fn alert_clusters(&mut self, block: &Block, submitter: &dyn ActionSubmitter<Action>) {
+        let alerts = self
+            .clusters
+            .check(block.header.timestamp, self.message_generator.native_decimals());
+
+        for alert in alerts {
+            warn!(cluster = %alert.cluster.name, total = %alert.total, "Cluster crossed a threshold");
+            if !alert.cluster.alert_to.is_chat() {
+                let event = AlertEvent::new(self.chain, block, &alert.cluster.name, Alert::cluster(&alert));
+                submit_alert(submitter, &alert.cluster.alert_to, event);
+                continue;
+            }
+
+            let message =
+                self.message_generator
+                    .generate_cluster(block, &alert, message_format(&alert.cluster.alert_to));
+            submit_message(submitter, &alert.cluster.alert_to, message);
+        }
+    }
+
+    /// Alert on the emissions of watched contract events
+    fn alert_events(
+        &self,
+        block: &Block,
+        receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
+        submitter: &dyn ActionSubmitter<Action>,
+    ) {
+        for watch in &self.event_watches {
+            let events = event_watch::find_events(receipt_and_traces, watch.address, &watch.event);
+            if events.is_empty() {
+                continue;
+            }
+
+            if !watch.alert_to.is_chat() {
+                let alert = Alert::Events {
+                    address: watch.address,
+                    event: watch.event.name.clone(),
+                    high_severity: watch.high_severity,
+                    emissions: events.iter().map(Emission::from).collect(),
+                };
+                let event = AlertEvent::new(self.chain, block, &watch.name, alert);
+                submit_alert(submitter, &watch.alert_to, event);
+                continue;
+            }
+
+            let message =
+                self.message_generator
+                    .generate_events(block, watch, &events, message_format(&watch.alert_to));
+            submit_message(submitter, &watch.alert_to, message);
+        }
+    }
+
+    /// Transfers from or to `wallet` in the txs of its report
+    fn wallet_transfers(
+        &self,
+        wallet: &WalletWithContext,
+        receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
+        report: &PnlReport,
+    ) -> Vec<WalletTransfer> {
+        report
+            .txs
+            .iter()
+            .filter_map(|tx| receipt_and_traces.get(tx.index as usize))
+            .flat_map(|(receipt, call_trace)| {
+                processor::transfers(self.chain, &self.rules, receipt, call_trace)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|t| {
+                        wallet.involved_wallets().contains(&t.from) || wallet.involved_wallets().contains(&t.to)
+                    })
+                    .map(|t| WalletTransfer {
+                        tx: receipt.transaction_hash,
+                        token: t.token,
+                        from: t.from,
+                        to: t.to,
+                        value: t.value,
+                    })
+            })
+            .collect()
+    }
+
+    /// Names of the watched wallets and known exchanges by address
+    fn address_labels(&self) -> HashMap<Address, String> {
+        let mut labels = exchanges::all();
+        for wallet in &self.wallets {
+            for address in wallet.involved_wallets() {
+                labels.insert(*address, wallet.name.clone());
+            }
+        }
+
+        labels
+    }
+
+    /// Addresses whose txs alone are traced in `block`, empty to trace every
+    /// tx. A watched builder's block is traced in full, as payments to its
+    /// coinbase from contracts don't show in receipts.
+    fn trace_scope(&self, config: &TraceConfig, block: &Block) -> Vec<Address> {
+        if !config.wallet_scoped ||
+            matches!(config.mode, TraceMode::LogsOnly | TraceMode::Explorer) ||
+            self.produced_by_watched(block)
+        {
+            return vec![];
+        }
+
+        self.watched_addresses()
+    }
+
+    /// Addresses of the watched wallets and the builder payment addresses
+    fn watched_addresses(&self) -> Vec<Address> {
+        self.wallets
+            .iter()
+            .flat_map(|w| w.involved_wallets().iter().chain(&w.payment_addresses))
+            .copied()
+            .collect()
+    }
+
+    fn produced_by_watched(&self, block: &Block) -> bool {
+        self.wallets
+            .iter()
+            .any(|w| w.is_builder(block.header.miner) || w.address == block.header.miner)
+    }
+
+    /// Whether `block` may hold anything watched: a log of a watched token or
+    /// contract, or naming a watched wallet, in its logs bloom, or a tx sent
+    /// from or to a watched wallet, as plain transfers emit no log. Blocks of
+    /// a watched builder always may, as do all blocks while large transfers
+    /// are watched.
+    async fn may_be_relevant(&self, block: &Block) -> eyre::Result<bool> {
+        if !self.large_transfer_watches.is_empty() || self.produced_by_watched(block) {
+            return Ok(true);
+        }
+
+        let addresses = self.watched_addresses();
+        let emitters = self
+            .token_watches
+            .iter()
+            .map(|w| w.token)
+            .chain(self.event_watches.iter().map(|w| w.address))
+            .collect::<Vec<_>>();
+        if utils::bloom_may_involve(&block.header.logs_bloom, &addresses, &emitters) {
+            return Ok(true);
+        }
+
+        rpc_metrics::record(self.chain, "eth_getBlockByNumber", 1);
+        let block = self
+            .provider
+            .get_block_by_number(block.header.number.into(), true)
+            .await
+            .context("Failed to get block transactions")?
+            .context("Block not found")?;
+
+        Ok(block
+            .transactions
+            .txns()
+            .any(|tx| addresses.contains(&tx.from) || tx.to.is_some_and(|to| addresses.contains(&to))))
+    }
+
+    /// Count the calls fetching a block of `tx_count` txs in `mode` makes.
+    /// Traces of a scoped block are counted once fetched, by
+    /// [`Self::record_scoped_traces`].
+    fn record_trace_calls(&self, mode: TraceMode, tx_count: usize, scoped: bool) {
+        rpc_metrics::record(self.chain, "eth_getBlockReceipts", 1);
+        if scoped {
+            return;
+        }
+
+        match mode {
+            TraceMode::Block => rpc_metrics::record(self.chain, "debug_traceBlockByNumber", 1),
+            TraceMode::PerTx => rpc_metrics::record(self.chain, "debug_traceTransaction", tx_count as u64),
+            TraceMode::LogsOnly | TraceMode::Explorer => rpc_metrics::record(self.chain, "eth_getBlockByNumber", 1),
+        }
+    }
+
+    fn record_scoped_traces(&self, scope: &[Address], receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)]) {
+        if scope.is_empty() {
+            return;
+        }
+
+        let traced = receipt_and_traces
+            .iter()
+            .filter(|(receipt, _)| utils::may_involve(receipt, scope))
+            .count();
+        rpc_metrics::record(self.chain, "debug_traceTransaction", traced as u64);
+    }
+
+    /// In the explorer mode, add the native transfers the explorer knows of
+    /// to the frames read from logs
+    async fn add_explorer_transfers(
+        &self,
+        mode: TraceMode,
+        block: u64,
+        receipt_and_traces: &mut [(AnyTransactionReceipt, CallFrame)],
+    ) -> eyre::Result<()> {
+        let (TraceMode::Explorer, Some(explorer)) = (mode, &self.explorer) else {
+            return Ok(());
+        };
+
+        let addresses = self.watched_addresses();
+        explorer::add_internal_txs(explorer, block, &addresses, receipt_and_traces)
+            .await
+            .context("Failed to get internal txs from the explorer")
+    }
+
+    fn notify_dashboard(&self, event: DashboardEvent) {
+        if let Some(dashboard) = &self.dashboard {
+            let _ = dashboard.send(event);
+        }
+    }
+
     #[instrument(skip_all, fields(chain = %self.chain, block = block.header.number))]
-    pub async fn process_block<A: From<Message> + Send + Sync + Clone + 'static>(
+    pub async fn process_block(
         &mut self,
-        block: Block,
-        submitter: Arc<dyn ActionSubmitter<A>>,
+        mut block: Block,
+        submitter: Arc<dyn ActionSubmitter<Action>>,
     ) -> eyre::Result<()> {
-        let receipt_and_traces = utils::get_receipt_and_trace(self.provider.as_ref(), block.header.number)
+        utils::fill_block_producer(self.provider.as_ref(), self.chain, &mut block.header).await?;
+
+        if self.trace_config.bloom_prescreen && !self.may_be_relevant(&block).await? {
+            debug!("Nothing watched in block, skipping");
+            return Ok(());
+        }
+
+        let trace_config = TraceConfig {
+            mode: self.trace_modes.select(&self.trace_config, &block),
+            ..self.trace_config
+        };
+        if trace_config.mode != self.trace_config.mode {
+            info!(mode = ?trace_config.mode, "Fetching block in a lighter trace mode");
+        }
+        let scope = self.trace_scope(&trace_config, &block);
+        self.record_trace_calls(trace_config.mode, block.transactions.len(), !scope.is_empty());
+
+        let fetch_start = Instant::now();
+        let (receipt_and_traces, reports, truncated) = if self.lenient_traces {
+            let (mut receipt_and_traces, warnings) = utils::get_receipt_and_trace_lenient(
+                self.provider.as_ref(),
+                block.header.number,
+                &trace_config,
+                &scope,
+            )
             .await
             .context("Failed to get receipt and traces")?;
+            self.trace_modes.record_latency(fetch_start.elapsed());
+            self.record_scoped_traces(&scope, &receipt_and_traces);
+            self.add_explorer_transfers(trace_config.mode, block.header.number, &mut receipt_and_traces)
+                .await?;
+            let truncated = self.truncate_traces(block.header.number, &mut receipt_and_traces);
+
+            let reports = processor::process_block_lenient(
+                self.chain,
+                &self.rules,
+                &block.header,
+                &receipt_and_traces,
+                &self.wallets,
+                warnings,
+            )
+            .context("Failed to generate balance changes")?;
+
+            (receipt_and_traces, reports, truncated)
+        } else {
+            let mut receipt_and_traces =
+                utils::get_receipt_and_trace(self.provider.as_ref(), block.header.number, &trace_config, &scope)
+                    .await
+                    .context("Failed to get receipt and traces")?;
+            self.trace_modes.record_latency(fetch_start.elapsed());
+            self.record_scoped_traces(&scope, &receipt_and_traces);
+            self.add_explorer_transfers(trace_config.mode, block.header.number, &mut receipt_and_traces)
+                .await?;
+            let truncated = self.truncate_traces(block.header.number, &mut receipt_and_traces);
 
-        let reports = processor::process_block(self.chain, &block.header, receipt_and_traces.as_slice(), &self.wallets)
+            let reports = processor::process_block(
+                self.chain,
+                &self.rules,
+                &block.header,
+                &receipt_and_traces,
+                &self.wallets,
+            )
             .context("Failed to generate balance changes")?;
 
+            (receipt_and_traces, reports, truncated)
+        };
+
+        self.alert_token_transfers(&block, &receipt_and_traces, submitter.as_ref())
+            .await;
+        self.alert_large_transfers(&block, &receipt_and_traces, submitter.as_ref())
+            .await;
+        self.alert_events(&block, &receipt_and_traces, submitter.as_ref());
+
         let report_and_wallet_index = reports
             .into_iter()
             .enumerate()
-            .filter_map(|(i, r)| r.map(|r| (i, r)))
+            .filter_map(|(i, r)| r.map(|r| (i, PnlReport { truncated, ..r })))
+            .map(|(i, mut r)| {
+                match (trace_config.mode, &self.explorer) {
+                    (TraceMode::LogsOnly, _) => r
+                        .warnings
+                        .push("Block read from logs only, native transfers by contracts are missing".to_string()),
+                    (TraceMode::Explorer, Some(explorer)) => r.warnings.push(format!(
+                        "Explorer-sourced: internal transfers from {}, amounts may be approximate",
+                        explorer.host()
+                    )),
+                    _ => {}
+                }
+                (i, r)
+            })
             .collect::<Vec<_>>();
 
-        for (wallet_index, report) in report_and_wallet_index {
+        // Channel index to the reports sharing its message, in wallet order
+        let mut combined: BTreeMap<usize, Vec<(WalletWithContext, PnlReport)>> = BTreeMap::new();
+        let mut combined_ids: BTreeMap<usize, Vec<B256>> = BTreeMap::new();
+
+        for (wallet_index, mut report) in report_and_wallet_index {
             info!(
                 wallet = format_args!("{}-{:#x}", self.wallets[wallet_index].name, self.wallets[wallet_index].address),
                 pnl = ?report.pnl,
@@ -62,24 +1003,139 @@ impl<T: Clone + Transport> WalletWatcher<T> {
                 tx = %report.tx_formatter(),
             );
 
-            let wallet = &self.wallets[wallet_index];
+            let wallet = self.wallets[wallet_index].clone();
+
+            if !report.builder_reward.is_zero() {
+                self.reorgs
+                    .record_built(block.header.hash, wallet_index, report.builder_reward);
+            }
+
+            if !self.perps.is_empty() {
+                let receipts = report
+                    .txs
+                    .iter()
+                    .filter_map(|tx| receipt_and_traces.get(tx.index as usize))
+                    .map(|(receipt, _)| receipt);
+                report.perps = perps::decode(&self.perps, self.chain, receipts, &wallet);
+            }
+
+            if self.trace_config.verify_state_diff {
+                if let Err(err) = state_diff::verify(
+                    self.provider.as_ref(),
+                    self.chain,
+                    &self.rules,
+                    &block.header,
+                    &receipt_and_traces,
+                    &wallet,
+                    &mut report,
+                )
+                .await
+                {
+                    warn!(wallet = %wallet.name, "Failed to verify against the state diff: {err:#}");
+                    report
+                        .warnings
+                        .push(format!("Failed to verify against the state diff: {err:#}"));
+                }
+            }
+
+            if !report.vaults.is_empty() {
+                if let Err(err) = vault::value_shares(
+                    self.provider.as_ref(),
+                    self.chain,
+                    &self.rules,
+                    block.header.number,
+                    &mut report,
+                )
+                .await
+                {
+                    warn!(wallet = %wallet.name, "Failed to value vault shares: {err:#}");
+                    report.warnings.push(format!("Failed to value vault shares: {err:#}"));
+                }
+            }
+
+            if let Some(prices) = self.prices.as_mut() {
+                prices
+                    .value_report(block.header.number, block.header.timestamp, &mut report)
+                    .await;
+            }
+
+            if let Some(storage) = &self.storage {
+                let stored = StoredReport {
+                    chain: self.chain.id(),
+                    block: block.header.number,
+                    timestamp: block.header.timestamp,
+                    wallet: wallet.name.clone(),
+                    address: wallet.address,
+                    report: report.clone(),
+                };
+                if let Err(err) = storage.save(&stored).await {
+                    error!(wallet = %wallet.name, "Failed to save report: {err:#}");
+                }
+            }
+
+            let id = delivery_id(self.chain, block.header.hash, wallet.address);
+            if !self.deliveries.claim(id).await {
+                info!(wallet = %wallet.name, "Report already delivered, skipping");
+                continue;
+            }
+
+            if let Some(seen_tokens) = self.seen_tokens.as_mut() {
+                report.new_tokens = seen_tokens
+                    .record(wallet.address, report.token_changes.keys().copied())
+                    .await;
+            }
+
+            self.notify_dashboard(DashboardEvent::Report {
+                chain: self.chain,
+                wallet: wallet.display_name(),
+                block: block.header.number,
+                timestamp: block.header.timestamp,
+                pnl: report.pnl,
+            });
+            self.clusters.record(&wallet.name, block.header.timestamp, report.pnl);
+
+            if self.first_seen_alert && !report.new_tokens.is_empty() && wallet.alert_to.is_chat() {
+                let message = self
+                    .message_generator
+                    .generate_first_seen(&block, &report, &wallet, message_format(&wallet.alert_to))
+                    .await?;
+                if !message.is_empty() {
+                    submit_message(submitter.as_ref(), &wallet.alert_to, message);
+                }
+            }
+
+            if self.cex_alert && !report.cex_deposits.is_empty() && wallet.alert_to.is_chat() {
+                let message = self
+                    .message_generator
+                    .generate_cex_deposits(&block, &report, &wallet, message_format(&wallet.alert_to))
+                    .await?;
+                submit_message(submitter.as_ref(), &wallet.alert_to, message);
+            }
+
+            if let (Some(channel), true) = (wallet.combined_channel, wallet.alert_to.is_chat()) {
+                combined.entry(channel).or_default().push((wallet, report));
+                combined_ids.entry(channel).or_default().push(id);
+                continue;
+            }
+
+            self.deliver_report(wallet_index, &block, &receipt_and_traces, report, submitter.as_ref())
+                .await?;
+            self.deliveries.confirm(id);
+        }
+
+        self.alert_clusters(&block, submitter.as_ref());
 
+        for (channel, reports) in combined {
+            let alert_to = Arc::clone(&reports[0].0.alert_to);
             let message = self
                 .message_generator
-                .generate(&block, &receipt_and_traces, &report, wallet)
+                .generate_combined(&block, &reports, message_format(&alert_to))
                 .await?;
 
-            let mut mb = MessageBuilder::default()
-                .bot_token(wallet.alert_to.bot_token.clone())
-                .chat_id(wallet.alert_to.chat_id.clone())
-                .text(message)
-                .disable_link_preview(true);
-
-            if let Some(thread_id) = &wallet.alert_to.thread_id {
-                mb = mb.thread_id(thread_id.clone());
+            submit_message(submitter.as_ref(), &alert_to, message);
+            for id in combined_ids.remove(&channel).unwrap_or_default() {
+                self.deliveries.confirm(id);
             }
-
-            submitter.submit(mb.build().into());
         }
 
         Ok(())
@@ -87,35 +1143,273 @@ impl<T: Clone + Transport> WalletWatcher<T> {
 }
 
 #[burberry::async_trait]
-impl<T, E, A> Strategy<E, A> for WalletWatcher<T>
+impl<T, E> Strategy<E, Action> for WalletWatcher<T>
 where
     T: Clone + Transport,
     E: TryInto<Block> + Send + Sync + Clone + 'static,
-    A: From<Message> + Send + Sync + Clone + 'static,
 {
-    async fn process_event(&mut self, event: E, submitter: Arc<dyn ActionSubmitter<A>>) {
+    async fn process_event(&mut self, event: E, submitter: Arc<dyn ActionSubmitter<Action>>) {
         let Ok(block) = event.try_into() else {
             return;
         };
 
+        self.apply_provider_updates();
+        self.apply_wallet_updates();
+
+        // A failed block back from the retry collector, not a new head
+        if let Some(retry) = self.retrying.remove(&block.header.hash) {
+            self.handle_block(block, retry.attempt, submitter).await;
+            return;
+        }
+
+        self.flush_rollups(submitter.as_ref()).await;
+        self.alert_orphaned(&block, submitter.as_ref());
+
+        let block_num = block.header.number;
+        self.handle_block(block, 0, submitter.clone()).await;
+        if self.storage.is_some() && self.last_heal.elapsed() >= HEAL_INTERVAL {
+            self.last_heal = Instant::now();
+            self.heal_gaps(block_num, submitter.clone()).await;
+        }
+        self.flush_aggregated(block_num, submitter.as_ref()).await;
+    }
+}
+
+/// Markup used by a chat output
+fn message_format(alert_to: &AlertTo) -> MessageFormat {
+    match alert_to {
+        AlertTo::Telegram(_) => MessageFormat::Telegram,
+        AlertTo::Discord(_) => MessageFormat::Discord,
+        AlertTo::Slack(_) => MessageFormat::Slack,
+        _ => MessageFormat::Plain,
+    }
+}
+
+fn submit_message(submitter: &dyn ActionSubmitter<Action>, alert_to: &AlertTo, message: String) {
+    match alert_to {
+        AlertTo::Telegram(target) => submitter.submit(Action::Telegram(target.clone(), message)),
+        AlertTo::Signal(target) => submitter.submit(Action::Signal(target.clone(), message)),
+        AlertTo::Twilio(target) => submitter.submit(Action::Twilio(target.clone(), message)),
+        AlertTo::Discord(target) => submitter.submit(Action::Discord(target.clone(), message)),
+        AlertTo::Slack(target) => submitter.submit(Action::Slack(target.clone(), message)),
+        // Data outputs get the report itself
+        _ => {}
+    }
+}
+
+/// Send a watch alert to a message bus as the alert itself. Other data
+/// outputs can't have watches, see [`crate::config::Config::validate`]
+fn submit_alert(submitter: &dyn ActionSubmitter<Action>, alert_to: &AlertTo, event: AlertEvent) {
+    match alert_to {
+        AlertTo::Nats(target) => submitter.submit(Action::NatsAlert(target.clone(), event)),
+        AlertTo::Redis(target) => submitter.submit(Action::RedisAlert(target.clone(), event)),
+        _ => {}
+    }
+}
+
+/// Marks the chat messages of a block processed after the fact as late
+struct LateSubmitter(Arc<dyn ActionSubmitter<Action>>);
+
+impl ActionSubmitter<Action> for LateSubmitter {
+    fn submit(&self, action: Action) {
+        let action = match action {
+            Action::Telegram(target, message) => Action::Telegram(target, format!("{} {message}", escape("(late)"))),
+            Action::Signal(target, message) => Action::Signal(target, format!("(late) {message}")),
+            Action::Twilio(target, message) => Action::Twilio(target, format!("(late) {message}")),
+            Action::Discord(target, message) => Action::Discord(target, format!("(late) {message}")),
+            Action::Slack(target, message) => Action::Slack(target, format!("(late) {message}")),
+            action => action,
+        };
+        self.0.submit(action);
+    }
+}
+
+impl<T: Clone + Transport> WalletWatcher<T> {
+    /// Keep how the last attempt at a block went in `storage`, for `health`
+    async fn record_block(&self, block: u64, timestamp: u64, elapsed: Duration, attempt: u32, error: Option<String>) {
+        let Some(storage) = &self.storage else {
+            return;
+        };
+
+        let record = ProcessedBlock {
+            chain: self.chain.id(),
+            block,
+            timestamp,
+            elapsed_ms: elapsed.as_millis() as u64,
+            attempts: attempt + 1,
+            error,
+        };
+        if let Err(err) = storage.record_block(&record).await {
+            warn!(chain = %self.chain, block, "Failed to record block: {err:#}");
+        }
+    }
+
+    /// Process a block again after it failed or was missed, its messages
+    /// marked late. It's recorded like a live block, but not retried
+    pub async fn heal_block(&mut self, block: Block, submitter: Arc<dyn ActionSubmitter<Action>>) -> eyre::Result<()> {
+        let block_num = block.header.number;
+        let block_timestamp = block.header.timestamp;
+
+        let start = Instant::now();
+        let result = self.process_block(block, Arc::new(LateSubmitter(submitter))).await;
+        if result.is_err() {
+            self.deliveries.release_unconfirmed().await;
+        }
+        let error = result.as_ref().err().map(|err| err.to_string());
+        self.record_block(block_num, block_timestamp, start.elapsed(), 0, error)
+            .await;
+
+        result
+    }
+
+    /// Process again the blocks of the last [`HEAL_LOOKBACK`] before `head`
+    /// that `storage` has as failed or missing, except the ones waiting for a
+    /// retry or already healed in this run
+    async fn heal_gaps(&mut self, head: u64, submitter: Arc<dyn ActionSubmitter<Action>>) {
+        let Some(storage) = self.storage.clone() else {
+            return;
+        };
+
+        let from_block = head.saturating_sub(HEAL_LOOKBACK);
+        self.healed.retain(|block| *block >= from_block);
+        let gaps = match heal::find_gaps(
+            storage.as_ref(),
+            self.chain.id(),
+            self.block_source,
+            from_block,
+            head.saturating_sub(1),
+        )
+        .await
+        {
+            Ok(gaps) => gaps,
+            Err(err) => {
+                warn!(chain = %self.chain, "Failed to look for gaps: {err:#}");
+                return;
+            }
+        };
+
+        let retrying = self.retrying.values().map(|retry| retry.number).collect::<HashSet<_>>();
+        let gaps = gaps
+            .into_iter()
+            .filter(|block| !retrying.contains(block) && self.healed.insert(*block))
+            .take(MAX_HEALED_BLOCKS)
+            .collect::<Vec<_>>();
+        if gaps.is_empty() {
+            return;
+        }
+        info!(chain = %self.chain, blocks = ?gaps, "Processing gaps again");
+
+        for number in gaps {
+            let block = match self.provider.get_block_by_number(number.into(), false).await {
+                Ok(Some(block)) => block,
+                Ok(None) => {
+                    warn!(chain = %self.chain, block = number, "Gap block not found");
+                    continue;
+                }
+                Err(err) => {
+                    warn!(chain = %self.chain, block = number, "Failed to get gap block: {err:#}");
+                    continue;
+                }
+            };
+
+            match self.heal_block(block, submitter.clone()).await {
+                Ok(()) => info!(chain = %self.chain, block = number, "Gap processed"),
+                Err(err) => warn!(chain = %self.chain, block = number, "Gap failed again: {err:#}"),
+            }
+        }
+    }
+
+    /// Process a block, queueing it for another attempt on failure until
+    /// retries are exhausted
+    async fn handle_block(&mut self, block: Block, attempt: u32, submitter: Arc<dyn ActionSubmitter<Action>>) {
         let block_num = block.header.number;
+        let block_timestamp = block.header.timestamp;
 
         let start = Instant::now();
-        let result = self.process_block(block, submitter).await;
+        let result = self.process_block(block.clone(), submitter.clone()).await;
         let elapsed = start.elapsed();
 
-        if let Err(err) = result {
-            error!(
-                chain = %self.chain,
-                block = block_num,
-                ?elapsed,
-                "Failed to processed block: {err:#}");
-        } else {
-            info!(
-                chain = %self.chain,
-                block = block_num,
-                ?elapsed,
-                "Processed block");
+        // Reports claimed before the failure but never sent go out on retry
+        if result.is_err() {
+            self.deliveries.release_unconfirmed().await;
+        }
+
+        match result {
+            Ok(()) => {
+                info!(
+                    chain = %self.chain,
+                    block = block_num,
+                    attempt,
+                    ?elapsed,
+                    "Processed block");
+
+                if let Some(storage) = &self.storage {
+                    if let Err(err) = storage.save_checkpoint(self.chain.id(), block_num).await {
+                        warn!(chain = %self.chain, block = block_num, "Failed to save checkpoint: {err:#}");
+                    }
+                }
+                self.record_block(block_num, block_timestamp, elapsed, attempt, None)
+                    .await;
+
+                self.notify_dashboard(DashboardEvent::Processed {
+                    chain: self.chain,
+                    block: block_num,
+                    timestamp: block_timestamp,
+                    elapsed,
+                });
+            }
+
+            Err(err) if attempt < self.retry.attempts && self.retries.is_some() => {
+                let delay = self.retry.delay(attempt + 1);
+
+                warn!(
+                    chain = %self.chain,
+                    block = block_num,
+                    attempt,
+                    ?elapsed,
+                    ?delay,
+                    "Failed to process block, will retry: {err:#}");
+
+                self.retrying.insert(
+                    block.header.hash,
+                    PendingRetry {
+                        number: block_num,
+                        attempt: attempt + 1,
+                    },
+                );
+                if let Some(retries) = &self.retries {
+                    let _ = retries.send((block, Instant::now() + delay));
+                }
+            }
+
+            Err(err) => {
+                error!(
+                    chain = %self.chain,
+                    block = block_num,
+                    attempt,
+                    ?elapsed,
+                    gap = true,
+                    "Failed to processed block: {err:#}");
+
+                // The outermost context names the step that failed, so blocks
+                // group by it
+                self.record_block(block_num, block_timestamp, elapsed, attempt, Some(err.to_string()))
+                    .await;
+
+                self.alert_error(
+                    submitter.as_ref(),
+                    format!(
+                        "Block {block_num} on chain {} failed after {} attempts: {err:#}",
+                        self.chain,
+                        attempt + 1
+                    ),
+                );
+
+                self.notify_dashboard(DashboardEvent::Error {
+                    chain: self.chain,
+                    message: format!("Block {block_num}: {err:#}"),
+                });
+            }
         }
     }
 }
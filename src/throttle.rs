@@ -0,0 +1,97 @@
+use {
+    crate::{
+        balance_changes::BalanceChange,
+        category::PnlCategory,
+        processor::{PnlReport, TxAndPosition},
+    },
+    alloy::primitives::I256,
+    std::{collections::BTreeMap, time::Duration},
+    tokio::time::Instant,
+};
+
+/// Limits a wallet to one message per window. Reports arriving in between are
+/// held back and sent as a single roll-up once the window has passed.
+pub struct Throttle {
+    window: Duration,
+    last_sent: Option<Instant>,
+    pending: RollUp,
+}
+
+/// Reports of a throttled wallet held back since its last message
+#[derive(Debug, Default)]
+pub struct RollUp {
+    pub blocks: Vec<(u64, Vec<TxAndPosition>)>,
+    pub pnl: I256,
+    pub pnl_by_category: BTreeMap<PnlCategory, I256>,
+    pub stable_pnl: I256,
+    pub token_changes: BalanceChange,
+}
+
+impl RollUp {
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn add(&mut self, block: u64, report: &PnlReport) {
+        self.blocks.push((block, report.txs.clone()));
+        self.pnl += report.pnl;
+        for (category, pnl) in &report.pnl_by_category {
+            *self.pnl_by_category.entry(*category).or_default() += *pnl;
+        }
+        self.stable_pnl += report.stable_pnl;
+        self.token_changes.extend(&report.token_changes);
+        self.token_changes.retain_non_zero();
+    }
+}
+
+/// What to send for a report of a throttled wallet
+pub enum Release {
+    /// The report itself, as nothing is held back
+    Report,
+    /// Everything held back, this report included
+    RollUp(RollUp),
+    /// Nothing yet, the report is held back
+    Hold,
+}
+
+impl Throttle {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last_sent: None,
+            pending: RollUp::default(),
+        }
+    }
+
+    fn is_open(&self, now: Instant) -> bool {
+        self.last_sent
+            .map_or(true, |last| now.duration_since(last) >= self.window)
+    }
+
+    pub fn offer(&mut self, block: u64, report: &PnlReport, now: Instant) -> Release {
+        if !self.is_open(now) {
+            self.pending.add(block, report);
+            return Release::Hold;
+        }
+
+        self.last_sent = Some(now);
+
+        if self.pending.is_empty() {
+            Release::Report
+        } else {
+            self.pending.add(block, report);
+            Release::RollUp(std::mem::take(&mut self.pending))
+        }
+    }
+
+    /// Take the reports held back once the window has passed, so a wallet
+    /// going quiet still gets its roll-up
+    pub fn take_due(&mut self, now: Instant) -> Option<RollUp> {
+        if self.pending.is_empty() || !self.is_open(now) {
+            return None;
+        }
+
+        self.last_sent = Some(now);
+        Some(std::mem::take(&mut self.pending))
+    }
+}
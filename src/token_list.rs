@@ -0,0 +1,66 @@
+use {
+    alloy::primitives::Address,
+    eyre::{bail, Context},
+    serde::Deserialize,
+    std::collections::HashMap,
+    tracing::{info, warn},
+};
+
+/// Symbol and decimals of listed tokens, by chain id and address
+pub type ListedTokens = HashMap<u64, HashMap<Address, (String, u8)>>;
+
+/// A token list in the Uniswap format, of which only the tokens are read
+#[derive(Deserialize)]
+struct TokenList {
+    tokens: Vec<ListedToken>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListedToken {
+    chain_id: u64,
+    address: Address,
+    symbol: String,
+    decimals: u8,
+}
+
+/// Read the token lists at `sources`, URLs or file paths. A token in several
+/// lists keeps the metadata of the first one. Lists that fail to load are
+/// logged and skipped, their tokens are then looked up on chain
+pub async fn load(sources: &[String]) -> ListedTokens {
+    let mut listed = ListedTokens::new();
+
+    for source in sources {
+        match read(source).await {
+            Ok(list) => {
+                info!(%source, tokens = list.tokens.len(), "Token list loaded");
+                for token in list.tokens {
+                    listed
+                        .entry(token.chain_id)
+                        .or_default()
+                        .entry(token.address)
+                        .or_insert((token.symbol, token.decimals));
+                }
+            }
+            Err(err) => warn!(%source, "Failed to load token list: {err:#}"),
+        }
+    }
+
+    listed
+}
+
+async fn read(source: &str) -> eyre::Result<TokenList> {
+    let body = if source.starts_with("http://") || source.starts_with("https://") {
+        let response = reqwest::get(source).await.context("Failed to fetch token list")?;
+        if !response.status().is_success() {
+            bail!("token list server answered {}", response.status());
+        }
+        response.text().await.context("Failed to read token list")?
+    } else {
+        tokio::fs::read_to_string(source)
+            .await
+            .context("Failed to read token list")?
+    };
+
+    serde_json::from_str(&body).context("Invalid token list")
+}
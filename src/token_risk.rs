@@ -0,0 +1,89 @@
+use {
+    crate::{
+        chain_adapter,
+        config::TokenRiskConfig,
+        contract::{FactoryV2::FactoryV2Instance, Ownable::OwnableInstance, ERC20::ERC20Instance},
+        rpc_metrics,
+    },
+    alloy::{
+        primitives::{address, Address, U256},
+        providers::Provider,
+        transports::Transport,
+    },
+    alloy_chains::{Chain, NamedChain},
+    eyre::Context,
+};
+
+/// Why a token looks like a honeypot, or `None` if it doesn't. A token is
+/// suspicious when its pool against the wrapped native token on the chain's
+/// main V2 DEX is missing or holds less than `min_liquidity`. Chains without a
+/// known DEX aren't checked.
+pub async fn check<T: Clone + Transport>(
+    provider: &dyn Provider<T>,
+    chain: Chain,
+    token: Address,
+    config: &TokenRiskConfig,
+) -> eyre::Result<Option<String>> {
+    let (Some(factory), Some(weth)) = (
+        v2_factory(chain),
+        chain_adapter::for_chain(chain).wrapped_native_token(chain),
+    ) else {
+        return Ok(None);
+    };
+
+    if token == weth {
+        return Ok(None);
+    }
+
+    rpc_metrics::record(chain, "eth_call", 1);
+    let pair = FactoryV2Instance::new(factory, provider.root())
+        .getPair(token, weth)
+        .call()
+        .await
+        .context("Failed to get pair")?
+        .pair;
+
+    let liquidity = if pair.is_zero() {
+        U256::ZERO
+    } else {
+        rpc_metrics::record(chain, "eth_call", 1);
+        ERC20Instance::new(weth, provider.root())
+            .balanceOf(pair)
+            .call()
+            .await
+            .context("Failed to get pair liquidity")?
+            .balance
+    };
+
+    if liquidity >= config.min_liquidity_wei() {
+        return Ok(None);
+    }
+
+    let mut reason = if pair.is_zero() {
+        "no liquidity"
+    } else {
+        "thin liquidity"
+    }
+    .to_string();
+
+    // Tokens without an owner function are treated as renounced
+    rpc_metrics::record(chain, "eth_call", 1);
+    let owner = OwnableInstance::new(token, provider.root()).owner().call().await;
+    if owner.is_ok_and(|owner| !owner._0.is_zero()) {
+        reason.push_str(", owner not renounced");
+    }
+
+    Ok(Some(reason))
+}
+
+/// Uniswap V2 style factory of the main DEX of a chain
+fn v2_factory(chain: Chain) -> Option<Address> {
+    match chain.named()? {
+        NamedChain::Mainnet => Some(address!("5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f")),
+        NamedChain::BinanceSmartChain => Some(address!("cA143Ce32Fe78f1f7019d7d551a6402fC5350c73")),
+        NamedChain::Polygon => Some(address!("5757371414417b8C6CAad45bAeF941aBc7d3Ab32")),
+        NamedChain::Arbitrum => Some(address!("f1D7CC64Fb4452F05c498126312eBE29f30Fbcf9")),
+        NamedChain::Base => Some(address!("8909Dc15e40173Ff4699343b6eB8132c65e18eC6")),
+        _ => None,
+    }
+}
@@ -0,0 +1,103 @@
+use {
+    crate::{
+        chain_adapter::{self, ChainRules},
+        config::{LargeTransferWatchWithContext, NATIVE_TOKEN},
+        processor::{self, Transfer, TxAndPosition},
+    },
+    alloy::{
+        primitives::{utils::parse_units, Address, U256},
+        rpc::types::{trace::geth::CallFrame, AnyTransactionReceipt},
+    },
+    alloy_chains::Chain,
+    eyre::Context,
+    std::collections::HashMap,
+    tracing::warn,
+};
+
+/// Transfers of `token` of at least `min_value` in a block, whoever makes them
+pub fn find_transfers(
+    chain: Chain,
+    rules: &ChainRules,
+    receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
+    token: Address,
+    min_value: U256,
+) -> Vec<(TxAndPosition, Transfer)> {
+    find_transfers_by(chain, rules, receipt_and_traces, |t| {
+        t.token == token && t.value >= min_value
+    })
+}
+
+/// Transfers in a block of at least the threshold of their token, for tokens
+/// in `thresholds`
+pub fn find_large_transfers(
+    chain: Chain,
+    rules: &ChainRules,
+    receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
+    thresholds: &HashMap<Address, U256>,
+) -> Vec<(TxAndPosition, Transfer)> {
+    find_transfers_by(chain, rules, receipt_and_traces, |t| {
+        thresholds.get(&t.token).is_some_and(|min_value| t.value >= *min_value)
+    })
+}
+
+/// Thresholds of a large transfer watch by token: the native token and its
+/// wrapped versions at `min_native`, stablecoins at `min_usd`
+pub fn large_transfer_thresholds(
+    chain: Chain,
+    rules: &ChainRules,
+    watch: &LargeTransferWatchWithContext,
+    native_decimals: u8,
+) -> eyre::Result<HashMap<Address, U256>> {
+    let mut thresholds = HashMap::new();
+
+    if let Some(min_native) = watch.min_native {
+        let min_value = to_min_value(min_native, native_decimals)?;
+        thresholds.insert(NATIVE_TOKEN, min_value);
+        for token in chain_adapter::wrapped_natives(chain, rules) {
+            thresholds.insert(token, min_value);
+        }
+    }
+
+    if let Some(min_usd) = watch.min_usd {
+        for (token, decimals) in &rules.stables {
+            thresholds.insert(*token, to_min_value(min_usd, *decimals)?);
+        }
+    }
+
+    Ok(thresholds)
+}
+
+fn find_transfers_by(
+    chain: Chain,
+    rules: &ChainRules,
+    receipt_and_traces: &[(AnyTransactionReceipt, CallFrame)],
+    filter: impl Fn(&Transfer) -> bool,
+) -> Vec<(TxAndPosition, Transfer)> {
+    let mut found = vec![];
+
+    for (receipt, trace) in receipt_and_traces {
+        let transfers = match processor::transfers(chain, rules, receipt, trace) {
+            Ok(transfers) => transfers,
+            Err(err) => {
+                warn!(tx = %receipt.transaction_hash, "Failed to read transfers: {err:#}");
+                continue;
+            }
+        };
+
+        let tx = TxAndPosition {
+            index: receipt.transaction_index.unwrap_or_default(),
+            hash: receipt.transaction_hash,
+        };
+
+        found.extend(transfers.into_iter().filter(&filter).map(|t| (tx.clone(), t)));
+    }
+
+    found
+}
+
+/// `amount` whole tokens in the token's smallest unit
+pub fn to_min_value(amount: f64, decimals: u8) -> eyre::Result<U256> {
+    Ok(parse_units(&amount.to_string(), decimals)
+        .with_context(|| format!("Invalid amount {amount}"))?
+        .get_absolute())
+}
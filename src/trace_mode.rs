@@ -0,0 +1,67 @@
+use {
+    crate::config::{AdaptiveTrace, TraceConfig, TraceMode},
+    alloy::rpc::types::Block,
+    std::time::Duration,
+};
+
+/// Weight of the latest block in the average latency
+const LATENCY_WEIGHT: f64 = 0.2;
+
+/// Picks the trace mode of each block of a chain from its size and how long
+/// recent blocks took to fetch
+#[derive(Debug, Default)]
+pub struct TraceModeSelector {
+    /// Moving average of the time to fetch a block, in milliseconds
+    latency_ms: Option<f64>,
+}
+
+impl TraceModeSelector {
+    /// The mode to fetch `block` in: the configured one, or a lighter one
+    /// picked by `adaptive`
+    pub fn select(&self, config: &TraceConfig, block: &Block) -> TraceMode {
+        let Some(adaptive) = &config.adaptive else {
+            return config.mode;
+        };
+
+        let by_size = size_mode(adaptive, block.header.gas_used as u64, block.transactions.len());
+        let mut mode = config.mode.max(by_size);
+
+        if let (Some(max_latency_ms), Some(latency_ms)) = (adaptive.max_latency_ms, self.latency_ms) {
+            if latency_ms > max_latency_ms as f64 {
+                mode = lighter(mode);
+            }
+        }
+
+        mode
+    }
+
+    pub fn record_latency(&mut self, elapsed: Duration) {
+        let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+        self.latency_ms = Some(match self.latency_ms {
+            Some(average) => average + LATENCY_WEIGHT * (elapsed_ms - average),
+            None => elapsed_ms,
+        });
+    }
+}
+
+fn size_mode(adaptive: &AdaptiveTrace, gas_used: u64, tx_count: usize) -> TraceMode {
+    if adaptive.logs_only_gas.is_some_and(|gas| gas_used >= gas) ||
+        adaptive.logs_only_txs.is_some_and(|txs| tx_count >= txs)
+    {
+        TraceMode::LogsOnly
+    } else if adaptive.per_tx_gas.is_some_and(|gas| gas_used >= gas) ||
+        adaptive.per_tx_txs.is_some_and(|txs| tx_count >= txs)
+    {
+        TraceMode::PerTx
+    } else {
+        TraceMode::Block
+    }
+}
+
+fn lighter(mode: TraceMode) -> TraceMode {
+    match mode {
+        TraceMode::Block => TraceMode::PerTx,
+        TraceMode::PerTx | TraceMode::LogsOnly => TraceMode::LogsOnly,
+        TraceMode::Explorer => TraceMode::Explorer,
+    }
+}
@@ -0,0 +1,92 @@
+// Minimal replacement of the call tracer with logs: the call tree with values
+// and errors, and only the logs PnL is made of (ERC20 `Transfer`, WETH9
+// `Deposit` and `Withdrawal`). The output is shaped like a call tracer frame.
+{
+    calls: [{ calls: [], logs: [] }],
+
+    topics: {
+        "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef": true,
+        "e1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c": true,
+        "7fcf532c15f0a6db0bd6d0e038bea71d30d808c7d98cb3bf7268a95bf5081b65": true,
+    },
+
+    word: function (value) {
+        var hex = value.toString(16);
+        while (hex.length < 64) {
+            hex = "0" + hex;
+        }
+        return hex;
+    },
+
+    step: function (log) {
+        // LOG2 and LOG3 only
+        var op = log.op.toNumber();
+        if (op !== 0xa2 && op !== 0xa3) {
+            return;
+        }
+
+        var topic0 = this.word(log.stack.peek(2));
+        if (!this.topics[topic0]) {
+            return;
+        }
+
+        var topics = [];
+        for (var i = 0; i < op - 0xa0; i++) {
+            topics.push("0x" + this.word(log.stack.peek(2 + i)));
+        }
+
+        var offset = log.stack.peek(0).valueOf();
+        var size = log.stack.peek(1).valueOf();
+
+        this.calls[this.calls.length - 1].logs.push({
+            address: toHex(log.contract.getAddress()),
+            topics: topics,
+            data: toHex(log.memory.slice(offset, offset + size)),
+        });
+    },
+
+    enter: function (frame) {
+        var value = frame.getValue();
+
+        this.calls.push({
+            type: frame.getType(),
+            from: toHex(frame.getFrom()),
+            to: toHex(frame.getTo()),
+            value: value === undefined ? undefined : "0x" + value.toString(16),
+            gas: "0x0",
+            gasUsed: "0x0",
+            input: "0x",
+            calls: [],
+            logs: [],
+        });
+    },
+
+    exit: function (result) {
+        var call = this.calls.pop();
+        var error = result.getError();
+        if (error !== undefined) {
+            call.error = error;
+        }
+
+        this.calls[this.calls.length - 1].calls.push(call);
+    },
+
+    fault: function () {},
+
+    result: function (ctx) {
+        var top = this.calls[0];
+
+        top.type = ctx.type;
+        top.from = toHex(ctx.from);
+        top.to = toHex(ctx.to);
+        top.value = "0x" + ctx.value.toString(16);
+        top.gas = "0x0";
+        top.gasUsed = "0x0";
+        top.input = "0x";
+        if (ctx.error !== undefined) {
+            top.error = ctx.error;
+        }
+
+        return top;
+    },
+}
@@ -0,0 +1,271 @@
+use {
+    crate::{
+        executor::telegram,
+        utils::format::{NumberFormat, ThousandsSeparator},
+    },
+    alloy::primitives::I256,
+    alloy_chains::Chain,
+    ratatui::{
+        crossterm::event::{self, Event, KeyCode},
+        layout::{Constraint, Layout},
+        widgets::{Block, List, Row, Table},
+        Frame,
+    },
+    std::{
+        collections::{BTreeMap, VecDeque},
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+};
+
+const MAX_REPORTS: usize = 100;
+const MAX_ERRORS: usize = 50;
+const PNL_WINDOW: u64 = 24 * 60 * 60;
+
+/// What strategies tell the dashboard
+#[derive(Debug, Clone)]
+pub enum DashboardEvent {
+    Processed {
+        chain: Chain,
+        block: u64,
+        timestamp: u64,
+        elapsed: Duration,
+    },
+    Report {
+        chain: Chain,
+        wallet: String,
+        block: u64,
+        timestamp: u64,
+        pnl: I256,
+    },
+    Error {
+        chain: Chain,
+        message: String,
+    },
+}
+
+pub type DashboardSender = UnboundedSender<DashboardEvent>;
+
+/// Start rendering on a blocking thread. The process exits when `q` is
+/// pressed.
+pub fn spawn() -> DashboardSender {
+    let (sender, receiver) = unbounded_channel();
+
+    tokio::task::spawn_blocking(move || {
+        let result = run(receiver);
+        ratatui::restore();
+
+        if let Err(err) = result {
+            eprintln!("Dashboard failed: {err:#}");
+            std::process::exit(-1);
+        }
+
+        std::process::exit(0);
+    });
+
+    sender
+}
+
+fn run(mut receiver: UnboundedReceiver<DashboardEvent>) -> eyre::Result<()> {
+    let mut terminal = ratatui::init();
+    let mut state = State::default();
+
+    loop {
+        while let Ok(event) = receiver.try_recv() {
+            state.apply(event);
+        }
+        state.prune();
+
+        terminal.draw(|frame| state.render(frame))?;
+
+        if event::poll(Duration::from_millis(250))? {
+            if let Event::Key(key) = event::read()? {
+                if key.code == KeyCode::Char('q') {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+struct ChainStatus {
+    block: u64,
+    timestamp: u64,
+    elapsed: Duration,
+    processed: u64,
+}
+
+struct ReportLine {
+    chain: Chain,
+    wallet: String,
+    block: u64,
+    timestamp: u64,
+    pnl: I256,
+}
+
+#[derive(Default)]
+struct State {
+    chains: BTreeMap<String, ChainStatus>,
+    reports: VecDeque<ReportLine>,
+    errors: VecDeque<String>,
+
+    /// (chain, wallet) to the timestamp and PnL of reports within the window
+    pnl_window: BTreeMap<(String, String), VecDeque<(u64, I256)>>,
+}
+
+impl State {
+    fn apply(&mut self, event: DashboardEvent) {
+        match event {
+            DashboardEvent::Processed {
+                chain,
+                block,
+                timestamp,
+                elapsed,
+            } => {
+                let status = self.chains.entry(chain.to_string()).or_insert(ChainStatus {
+                    block,
+                    timestamp,
+                    elapsed,
+                    processed: 0,
+                });
+
+                status.block = block;
+                status.timestamp = timestamp;
+                status.elapsed = elapsed;
+                status.processed += 1;
+            }
+
+            DashboardEvent::Report {
+                chain,
+                wallet,
+                block,
+                timestamp,
+                pnl,
+            } => {
+                self.pnl_window
+                    .entry((chain.to_string(), wallet.clone()))
+                    .or_default()
+                    .push_back((timestamp, pnl));
+
+                self.reports.push_front(ReportLine {
+                    chain,
+                    wallet,
+                    block,
+                    timestamp,
+                    pnl,
+                });
+                self.reports.truncate(MAX_REPORTS);
+            }
+
+            DashboardEvent::Error { chain, message } => {
+                self.errors.push_front(format!("[{chain}] {message}"));
+                self.errors.truncate(MAX_ERRORS);
+            }
+        }
+    }
+
+    fn prune(&mut self) {
+        let cutoff = now().saturating_sub(PNL_WINDOW);
+
+        for entries in self.pnl_window.values_mut() {
+            while entries
+                .front()
+                .map(|(timestamp, _)| *timestamp < cutoff)
+                .unwrap_or_default()
+            {
+                entries.pop_front();
+            }
+        }
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let [top, middle, bottom] = Layout::vertical([
+            Constraint::Length(self.chains.len() as u16 + 3),
+            Constraint::Min(8),
+            Constraint::Length(8),
+        ])
+        .areas(frame.area());
+
+        let [reports_area, pnl_area] =
+            Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)]).areas(middle);
+
+        let now = now();
+
+        let chains = Table::new(
+            self.chains.iter().map(|(chain, status)| {
+                Row::new(vec![
+                    chain.clone(),
+                    status.block.to_string(),
+                    format!("{}s", now.saturating_sub(status.timestamp)),
+                    format!("{:.2?}", status.elapsed),
+                    status.processed.to_string(),
+                ])
+            }),
+            [Constraint::Ratio(1, 5); 5],
+        )
+        .header(Row::new(vec!["Chain", "Block", "Head lag", "Processing", "Processed"]))
+        .block(Block::bordered().title(" Chains (q to quit) "));
+
+        let reports = Table::new(
+            self.reports.iter().map(|r| {
+                Row::new(vec![
+                    format!("{}s ago", now.saturating_sub(r.timestamp)),
+                    r.chain.to_string(),
+                    r.wallet.clone(),
+                    r.block.to_string(),
+                    format_pnl(r.pnl),
+                ])
+            }),
+            [
+                Constraint::Length(10),
+                Constraint::Length(10),
+                Constraint::Min(12),
+                Constraint::Length(10),
+                Constraint::Min(12),
+            ],
+        )
+        .header(Row::new(vec!["Time", "Chain", "Wallet", "Block", "PnL"]))
+        .block(Block::bordered().title(" Recent reports "));
+
+        let pnl = Table::new(
+            self.pnl_window.iter().map(|((chain, wallet), entries)| {
+                let pnl = entries
+                    .iter()
+                    .filter(|(timestamp, _)| now.saturating_sub(*timestamp) <= PNL_WINDOW)
+                    .fold(I256::ZERO, |acc, (_, pnl)| acc + *pnl);
+
+                Row::new(vec![chain.clone(), wallet.clone(), format_pnl(pnl)])
+            }),
+            [Constraint::Length(10), Constraint::Min(12), Constraint::Min(12)],
+        )
+        .header(Row::new(vec!["Chain", "Wallet", "PnL"]))
+        .block(Block::bordered().title(" Rolling 24h PnL "));
+
+        let errors = List::new(self.errors.iter().map(String::as_str)).block(
+            Block::bordered()
+                .title(" Errors ")
+                .title_bottom(format!(" Telegram: {} ", telegram::stats())),
+        );
+
+        frame.render_widget(chains, top);
+        frame.render_widget(reports, reports_area);
+        frame.render_widget(pnl, pnl_area);
+        frame.render_widget(errors, bottom);
+    }
+}
+
+fn format_pnl(pnl: I256) -> String {
+    let format = NumberFormat {
+        separator: Some(ThousandsSeparator::Comma),
+        ..Default::default()
+    };
+
+    format.signed_native(&pnl, 18)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
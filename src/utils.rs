@@ -1,27 +1,113 @@
 use {
-    crate::processor::trace_options,
+    crate::{
+        chain_adapter,
+        config::{TraceConfig, TraceMode},
+        processor::trace_options,
+        rpc_metrics,
+    },
     alloy::{
         hex,
-        primitives::{Address, B256, I256, U256},
+        primitives::{Address, Bloom, BloomInput, B256, I256, U256},
         providers::{IpcConnect, Provider, ProviderBuilder, WsConnect},
         pubsub::PubSubFrontend,
         rpc::{
             client::BatchRequest,
             types::{
-                trace::geth::{CallFrame, TraceResult},
-                AnyTransactionReceipt,
+                trace::geth::{CallFrame, CallLogFrame, GethTrace, TraceResult},
+                AnyTransactionReceipt, Header,
             },
         },
         transports::Transport,
     },
     alloy_chains::{Chain, NamedChain},
-    eyre::{bail, ensure, Context},
+    eyre::{bail, ensure, eyre, Context},
+    serde::Deserialize,
+    tracing::warn,
 };
 
+pub mod format;
+
+/// Receipts and call traces of a block. With a non-empty `scope`, only the
+/// txs that may involve one of its addresses are traced, see [`may_involve`].
 pub async fn get_receipt_and_trace<T: Clone + Transport>(
     provider: &dyn Provider<T>,
     block: u64,
+    config: &TraceConfig,
+    scope: &[Address],
 ) -> eyre::Result<Vec<(AnyTransactionReceipt, CallFrame)>> {
+    fetch_receipt_and_trace(provider, block, config, scope)
+        .await?
+        .into_iter()
+        .map(|(receipt, trace)| trace.map(|trace| (receipt, trace)))
+        .collect()
+}
+
+/// Like [`get_receipt_and_trace`], but a tx whose trace is unusable doesn't
+/// fail the whole block. Its trace is replaced by an empty errored frame, so it
+/// carries no balance changes, and the reason is returned as a warning.
+pub async fn get_receipt_and_trace_lenient<T: Clone + Transport>(
+    provider: &dyn Provider<T>,
+    block: u64,
+    config: &TraceConfig,
+    scope: &[Address],
+) -> eyre::Result<(Vec<(AnyTransactionReceipt, CallFrame)>, Vec<String>)> {
+    let mut warnings = vec![];
+
+    let receipt_and_traces = fetch_receipt_and_trace(provider, block, config, scope)
+        .await?
+        .into_iter()
+        .map(|(receipt, trace)| {
+            let trace = trace.unwrap_or_else(|err| {
+                warn!(tx = %receipt.transaction_hash, "Skipping tx with malformed trace: {err:#}");
+                warnings.push(format!(
+                    "Skipped tx at index {}: {err:#}",
+                    receipt.transaction_index.unwrap_or_default()
+                ));
+
+                CallFrame {
+                    error: Some(format!("{err:#}")),
+                    ..Default::default()
+                }
+            });
+
+            (receipt, trace)
+        })
+        .collect();
+
+    Ok((receipt_and_traces, warnings))
+}
+
+async fn fetch_receipt_and_trace<T: Clone + Transport>(
+    provider: &dyn Provider<T>,
+    block: u64,
+    config: &TraceConfig,
+    scope: &[Address],
+) -> eyre::Result<Vec<(AnyTransactionReceipt, eyre::Result<CallFrame>)>> {
+    match config.mode {
+        TraceMode::Block if scope.is_empty() => trace_block(provider, block, config).await,
+        TraceMode::Block | TraceMode::PerTx => trace_txs(provider, block, config, scope).await,
+        // Explorer data is added on top by the strategy
+        TraceMode::LogsOnly | TraceMode::Explorer => frames_from_logs(provider, block).await,
+    }
+}
+
+async fn trace_block<T: Clone + Transport>(
+    provider: &dyn Provider<T>,
+    block: u64,
+    config: &TraceConfig,
+) -> eyre::Result<Vec<(AnyTransactionReceipt, eyre::Result<CallFrame>)>> {
+    let (receipts, traces) = get_receipts_and_trace_results(provider, block, config).await?;
+
+    zip_traces(receipts, traces)
+}
+
+/// Receipts and `debug_traceBlockByNumber` results of a block as the node
+/// returns them
+pub async fn get_receipts_and_trace_results<T: Clone + Transport>(
+    provider: &dyn Provider<T>,
+    block: u64,
+    config: &TraceConfig,
+) -> eyre::Result<(Vec<AnyTransactionReceipt>, Vec<TraceResult>)> {
     let mut batch = BatchRequest::new(provider.client());
     let block_num_hex = format!("{:#x}", block);
 
@@ -29,29 +115,90 @@ pub async fn get_receipt_and_trace<T: Clone + Transport>(
         .add_call::<_, Vec<AnyTransactionReceipt>>("eth_getBlockReceipts", &(block_num_hex.clone(),))
         .unwrap();
     let traces = batch
-        .add_call::<_, Vec<TraceResult>>("debug_traceBlockByNumber", &(block_num_hex, trace_options()))
+        .add_call::<_, Vec<TraceResult>>("debug_traceBlockByNumber", &(block_num_hex, trace_options(config)))
         .unwrap();
 
     batch.await.context("Failed to send batch request")?;
 
     let receipts = receipts.await.context("Failed to get transaction receipt")?;
     let traces = traces.await.context("Failed to trace transaction")?;
+
+    Ok((receipts, traces))
+}
+
+/// Pair the receipts of a block with the results of its
+/// `debug_traceBlockByNumber`, in block order
+pub fn zip_traces(
+    receipts: Vec<AnyTransactionReceipt>,
+    traces: Vec<TraceResult>,
+) -> eyre::Result<Vec<(AnyTransactionReceipt, eyre::Result<CallFrame>)>> {
     ensure!(
         receipts.len() == traces.len(),
         "Receipts and traces have different lengths"
     );
 
-    let mut receipt_and_traces = Vec::with_capacity(receipts.len());
+    let receipt_and_traces = receipts
+        .into_iter()
+        .zip(traces)
+        .map(|(receipt, trace_result)| {
+            let trace = match trace_result {
+                TraceResult::Success { result, .. } => into_call_frame(result)
+                    .with_context(|| format!("Trace result {:#x} is not a call frame", receipt.transaction_hash)),
+                TraceResult::Error { error, tx_hash } => Err(eyre!("Failed to trace tx {tx_hash:?}: {error}")),
+            };
+
+            (receipt, trace)
+        })
+        .collect();
 
-    for (receipt, trace_result) in receipts.into_iter().zip(traces.into_iter()) {
-        let trace = match trace_result {
-            TraceResult::Success { result, .. } => result,
-            TraceResult::Error { error, tx_hash } => bail!("Failed to trace tx {tx_hash:?}: {error}"),
-        };
+    Ok(receipt_and_traces)
+}
+
+/// Trace each tx of the block on its own, in one batch. Slower than
+/// [`trace_block`], but a node that times out on a whole block can still
+/// trace its txs. With a non-empty `scope`, txs that can't involve it are
+/// left untraced, as a top-level frame with their logs.
+async fn trace_txs<T: Clone + Transport>(
+    provider: &dyn Provider<T>,
+    block: u64,
+    config: &TraceConfig,
+    scope: &[Address],
+) -> eyre::Result<Vec<(AnyTransactionReceipt, eyre::Result<CallFrame>)>> {
+    let receipts: Vec<AnyTransactionReceipt> = provider
+        .client()
+        .request("eth_getBlockReceipts", (format!("{:#x}", block),))
+        .await
+        .context("Failed to get transaction receipt")?;
+
+    if receipts.is_empty() {
+        return Ok(vec![]);
+    }
 
-        let trace = trace
-            .try_into_call_frame()
-            .with_context(|| format!("Trace result {:#x} is not a call frame", receipt.transaction_hash))?;
+    let mut batch = BatchRequest::new(provider.client());
+    let options = trace_options(config);
+    let traces = receipts
+        .iter()
+        .map(|receipt| {
+            (scope.is_empty() || may_involve(receipt, scope)).then(|| {
+                batch
+                    .add_call::<_, GethTrace>("debug_traceTransaction", &(receipt.transaction_hash, &options))
+                    .unwrap()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    batch.await.context("Failed to send batch request")?;
+
+    let mut receipt_and_traces = Vec::with_capacity(receipts.len());
+    for (receipt, trace) in receipts.into_iter().zip(traces) {
+        let trace = match trace {
+            Some(trace) => match trace.await {
+                Ok(trace) => into_call_frame(trace)
+                    .with_context(|| format!("Trace result {:#x} is not a call frame", receipt.transaction_hash)),
+                Err(err) => Err(eyre!("Failed to trace tx {:#x}: {err}", receipt.transaction_hash)),
+            },
+            None => Ok(top_level_frame(&receipt, None)),
+        };
 
         receipt_and_traces.push((receipt, trace));
     }
@@ -59,6 +206,157 @@ pub async fn get_receipt_and_trace<T: Clone + Transport>(
     Ok(receipt_and_traces)
 }
 
+#[derive(Deserialize)]
+struct BlockWithValues {
+    transactions: Vec<TxValue>,
+}
+
+#[derive(Deserialize)]
+struct TxValue {
+    hash: B256,
+    #[serde(default)]
+    value: U256,
+}
+
+/// Without any trace, each tx becomes a single top-level frame carrying its
+/// value and its receipt's logs. Token transfers are all there, but native
+/// transfers made by contracts are not.
+async fn frames_from_logs<T: Clone + Transport>(
+    provider: &dyn Provider<T>,
+    block: u64,
+) -> eyre::Result<Vec<(AnyTransactionReceipt, eyre::Result<CallFrame>)>> {
+    let mut batch = BatchRequest::new(provider.client());
+    let block_num_hex = format!("{:#x}", block);
+
+    let receipts = batch
+        .add_call::<_, Vec<AnyTransactionReceipt>>("eth_getBlockReceipts", &(block_num_hex.clone(),))
+        .unwrap();
+    let block = batch
+        .add_call::<_, BlockWithValues>("eth_getBlockByNumber", &(block_num_hex, true))
+        .unwrap();
+
+    batch.await.context("Failed to send batch request")?;
+
+    let receipts = receipts.await.context("Failed to get transaction receipt")?;
+    let block = block.await.context("Failed to get block")?;
+    ensure!(
+        receipts.len() == block.transactions.len(),
+        "Receipts and transactions have different lengths"
+    );
+
+    let receipt_and_frames = receipts
+        .into_iter()
+        .zip(block.transactions)
+        .map(|(receipt, tx)| {
+            let frame = if receipt.transaction_hash == tx.hash {
+                Ok(top_level_frame(&receipt, Some(tx.value)))
+            } else {
+                Err(eyre!("Receipt {:#x} is out of order", receipt.transaction_hash))
+            };
+
+            (receipt, frame)
+        })
+        .collect();
+
+    Ok(receipt_and_frames)
+}
+
+/// Whether a tx may touch one of `addresses`, judged from its receipt alone:
+/// as its sender, recipient or created contract, as the emitter of a log, or in
+/// a log topic or data word. Native transfers made to an address by a contract
+/// leave no trace in the receipt and are missed.
+pub fn may_involve(receipt: &AnyTransactionReceipt, addresses: &[Address]) -> bool {
+    let is_watched =
+        |word: &[u8]| word[..12].iter().all(|b| *b == 0) && addresses.contains(&Address::from_slice(&word[12..]));
+
+    addresses.contains(&receipt.from) ||
+        receipt.to.is_some_and(|to| addresses.contains(&to)) ||
+        receipt
+            .contract_address
+            .is_some_and(|created| addresses.contains(&created)) ||
+        receipt.inner.inner.logs().iter().any(|log| {
+            addresses.contains(&log.address()) ||
+                log.topics().iter().any(|topic| is_watched(topic.as_slice())) ||
+                log.data().data.chunks_exact(32).any(is_watched)
+        })
+}
+
+/// Whether a logs bloom may hold a log emitted by one of `emitters` or
+/// `addresses`, or one naming an address of `addresses` in a topic, as the
+/// `from` and `to` of token transfers do. Addresses only in log data are
+/// missed.
+pub fn bloom_may_involve(bloom: &Bloom, addresses: &[Address], emitters: &[Address]) -> bool {
+    addresses
+        .iter()
+        .chain(emitters)
+        .any(|a| bloom.contains_input(BloomInput::Raw(a.as_slice()))) ||
+        addresses
+            .iter()
+            .any(|a| bloom.contains_input(BloomInput::Raw(a.into_word().as_slice())))
+}
+
+/// The tx as a single frame with its receipt's logs. `value` is left unset
+/// when unknown.
+fn top_level_frame(receipt: &AnyTransactionReceipt, value: Option<U256>) -> CallFrame {
+    let logs = receipt
+        .inner
+        .inner
+        .logs()
+        .iter()
+        .map(|log| CallLogFrame {
+            address: Some(log.address()),
+            topics: Some(log.topics().to_vec()),
+            data: Some(log.data().data.clone()),
+            ..Default::default()
+        })
+        .collect();
+
+    CallFrame {
+        from: receipt.from,
+        to: receipt.to.or(receipt.contract_address),
+        value,
+        typ: if receipt.to.is_some() { "CALL" } else { "CREATE" }.to_string(),
+        gas_used: U256::from(receipt.gas_used),
+        error: (!receipt.inner.status()).then(|| "execution reverted".to_string()),
+        logs,
+        ..Default::default()
+    }
+}
+
+/// Call frame of a trace, from the call tracer or the JS tracer mimicking it
+pub fn into_call_frame(trace: GethTrace) -> eyre::Result<CallFrame> {
+    match trace {
+        GethTrace::JS(value) => Ok(serde_json::from_value(value)?),
+        trace => Ok(trace.try_into_call_frame()?),
+    }
+}
+
+/// Some chains (Polygon) leave the coinbase empty and pay the fees to the block
+/// signer instead. Fill the signer in, so the producer can be matched against
+/// `miner` as on other chains.
+pub async fn fill_block_producer<T: Clone + Transport>(
+    provider: &dyn Provider<T>,
+    chain: Chain,
+    header: &mut Header,
+) -> eyre::Result<()> {
+    let Some(method) = chain_adapter::for_chain(chain).block_author_method() else {
+        return Ok(());
+    };
+
+    if !header.miner.is_zero() {
+        return Ok(());
+    }
+
+    rpc_metrics::record(chain, method, 1);
+    header.miner = provider
+        .client()
+        .request(method, (format!("{:#x}", header.number),))
+        .await
+        .context("Failed to get block author")?;
+
+    Ok(())
+}
+
 pub fn format_units(value: U256, decimals: u8, keep_decimal: u8) -> String {
     let formatted = alloy::primitives::utils::format_units(value, decimals).unwrap();
 
@@ -98,18 +396,6 @@ pub fn primitive_log_decode<T: ::alloy::sol_types::SolEvent>(
     T::decode_log(log, true).ok()
 }
 
-pub fn is_weth9(chain: Chain) -> bool {
-    matches!(
-        chain.named(),
-        Some(NamedChain::Mainnet) |
-            Some(NamedChain::BinanceSmartChain) |
-            Some(NamedChain::Polygon) |
-            Some(NamedChain::Optimism) |
-            Some(NamedChain::Base) |
-            Some(NamedChain::Blast)
-    )
-}
-
 pub async fn new_provider(rpc: &str) -> eyre::Result<Box<dyn Provider>> {
     let p = if rpc.starts_with("http://") || rpc.starts_with("https://") {
         ProviderBuilder::new()
@@ -136,6 +422,14 @@ pub async fn new_pubsub_provider(rpc: &str) -> eyre::Result<Box<dyn Provider<Pub
     Ok(Box::new(p))
 }
 
+/// `name` after `emoji`, if any
+pub fn display_name(emoji: Option<&str>, name: &str) -> String {
+    match emoji {
+        Some(emoji) => format!("{emoji} {name}"),
+        None => name.to_string(),
+    }
+}
+
 pub fn format_ether_trimmed(value: &U256) -> String {
     use std::fmt::Write;
 
@@ -166,57 +460,46 @@ pub fn format_short_address(address: &Address) -> String {
     )
 }
 
-fn prefix(chain: Chain) -> &'static str {
+/// Etherscan-compatible explorer of a known chain
+pub fn default_explorer(chain: Chain) -> &'static str {
     chain
         .etherscan_urls()
         .map(|(_, url)| url)
         .unwrap_or("unsupported-chain")
 }
 
-pub fn tx_link(chain: Chain, hash: &B256, tag: Option<String>) -> String {
-    format!(
-        "[{tag_str}]({prefix}/tx/{hash})",
-        tag_str = tag.unwrap_or_else(|| format!("{}", hash)),
-        prefix = prefix(chain),
-        hash = hash,
-    )
+pub fn tx_url(explorer: &str, hash: &B256) -> String {
+    format!("{explorer}/tx/{hash}")
 }
 
-pub fn address_link(chain: Chain, address: &Address, address_tag: Option<String>) -> String {
-    format!(
-        "[{tag}]({prefix}/address/{address})",
-        tag = address_tag.unwrap_or_else(|| format!("{}", address)),
-        address = address,
-        prefix = prefix(chain),
-    )
+pub fn address_url(explorer: &str, address: &Address) -> String {
+    format!("{explorer}/address/{address}")
 }
 
-pub fn block_link(chain: Chain, block: u64) -> String {
-    format!(
-        "[{block}]({prefix}/block/{block})",
-        block = block,
-        prefix = prefix(chain)
-    )
+pub fn block_url(explorer: &str, block: u64) -> String {
+    format!("{explorer}/block/{block}")
 }
 
-pub fn token_owner_link(chain: Chain, token: &Address, owner: &Address, owner_tag: Option<String>) -> String {
-    format!(
-        "[{tag}]({prefix}/token/{token}?a={owner})",
-        tag = owner_tag.unwrap_or_else(|| format!("{owner}")),
-        owner = owner,
-        token = token,
-        prefix = prefix(chain),
-    )
+pub fn token_owner_url(explorer: &str, token: &Address, owner: &Address) -> String {
+    format!("{explorer}/token/{token}?a={owner}")
 }
 
-pub fn phalcon_tx(chain: Chain, hash: &B256, tag: Option<String>) -> String {
+pub fn phalcon_tx_url(chain: Chain, hash: &B256) -> String {
     format!(
-        "[{tag}](https://app.blocksec.com/explorer/tx/{chain_tag}/{hash})",
-        tag = tag.unwrap_or_else(|| format!("{}", hash)),
+        "https://app.blocksec.com/explorer/tx/{chain_tag}/{hash}",
         chain_tag = to_phalcon_chain_tag(chain),
     )
 }
 
+pub fn tenderly_tx_url(chain: Chain, hash: &B256) -> String {
+    format!("https://dashboard.tenderly.co/tx/{}/{hash}", chain.id())
+}
+
+/// Tenderly's debugger of a tx, stepping through its trace
+pub fn tenderly_debugger_url(chain: Chain, hash: &B256) -> String {
+    format!("{}/debugger", tenderly_tx_url(chain, hash))
+}
+
 fn to_phalcon_chain_tag(chain: Chain) -> &'static str {
     match chain.named() {
         Some(NamedChain::Mainnet) => "eth",
@@ -0,0 +1,189 @@
+use {
+    super::{format_token_amount, format_units},
+    alloy::primitives::{I256, U256},
+    serde::{Deserialize, Serialize},
+};
+
+/// Significant digits after the first one in scientific notation
+const SCIENTIFIC_DECIMALS: usize = 4;
+
+/// Decimals kept on an amount shortened with a unit suffix
+const SUFFIX_DECIMALS: usize = 2;
+
+/// Unit suffixes by power of a thousand
+const SUFFIXES: [&str; 4] = ["K", "M", "B", "T"];
+
+/// How token amounts are written in messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AmountFormat {
+    /// A few decimals, and dust of tokens with more than 9 decimals in wei
+    #[default]
+    Compact,
+    /// Every decimal up to the last significant one
+    Precise,
+    /// Five significant digits and a power of ten, e.g. `1.2345e-7`
+    Scientific,
+}
+
+/// Character grouping the digits of amounts by thousands. The decimal mark
+/// is a comma when the separator is a period, and a period otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThousandsSeparator {
+    Comma,
+    Period,
+    Space,
+    Apostrophe,
+}
+
+impl ThousandsSeparator {
+    fn char(self) -> char {
+        match self {
+            ThousandsSeparator::Comma => ',',
+            ThousandsSeparator::Period => '.',
+            // Narrow no-break space, so an amount isn't split over two lines
+            ThousandsSeparator::Space => '\u{202f}',
+            ThousandsSeparator::Apostrophe => '\'',
+        }
+    }
+
+    fn decimal_mark(self) -> char {
+        match self {
+            ThousandsSeparator::Period => ',',
+            _ => '.',
+        }
+    }
+}
+
+/// How a channel writes amounts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub amounts: AmountFormat,
+    pub separator: Option<ThousandsSeparator>,
+    /// Shorten amounts of a thousand and more with K, M, B or T
+    pub suffixes: bool,
+}
+
+impl NumberFormat {
+    /// `value` of a token with `decimals`, keeping `keep_decimal` decimals
+    /// when compact
+    pub fn amount(self, value: &I256, decimals: u8, keep_decimal: u8) -> String {
+        let formatted = match self.amounts {
+            AmountFormat::Compact => format_token_amount(value, decimals, keep_decimal),
+            AmountFormat::Precise => format_signed(value, |abs| format_units(abs, decimals, decimals)),
+            AmountFormat::Scientific => format_signed(value, |abs| format_scientific(abs, decimals)),
+        };
+
+        self.style(&formatted)
+    }
+
+    /// `value` of the native token, precise unless scientific
+    pub fn native(self, value: &U256, decimals: u8) -> String {
+        let formatted = match self.amounts {
+            AmountFormat::Scientific => format_scientific(*value, decimals),
+            AmountFormat::Compact | AmountFormat::Precise => format_units(*value, decimals, decimals),
+        };
+
+        self.style(&formatted)
+    }
+
+    pub fn signed_native(self, value: &I256, decimals: u8) -> String {
+        format_signed(value, |abs| self.native(&abs, decimals))
+    }
+
+    /// Apply the suffix and separators to a plain decimal number. Anything
+    /// else, like `12 wei` or `1.2e-7`, is left as is.
+    pub fn style(self, formatted: &str) -> String {
+        let (sign, number) = match formatted.strip_prefix('-') {
+            Some(number) => ("-", number),
+            None => ("", formatted),
+        };
+        let (integer, decimal) = number.split_once('.').unwrap_or((number, ""));
+
+        let is_plain = !integer.is_empty() &&
+            integer.bytes().all(|b| b.is_ascii_digit()) &&
+            decimal.bytes().all(|b| b.is_ascii_digit());
+        if !is_plain {
+            return formatted.to_string();
+        }
+
+        let (integer, decimal, suffix) = if self.suffixes {
+            with_suffix(integer, decimal)
+        } else {
+            (integer.to_string(), decimal.to_string(), "")
+        };
+
+        let (integer, decimal_mark) = match self.separator {
+            Some(separator) => (group(&integer, separator.char()), separator.decimal_mark()),
+            None => (integer, '.'),
+        };
+
+        if decimal.is_empty() {
+            format!("{sign}{integer}{suffix}")
+        } else {
+            format!("{sign}{integer}{decimal_mark}{decimal}{suffix}")
+        }
+    }
+}
+
+/// Move the digits of whole thousands past the decimal mark, e.g. `1234567`
+/// to `1.23` and `M`
+fn with_suffix(integer: &str, decimal: &str) -> (String, String, &'static str) {
+    let integer = integer.trim_start_matches('0');
+    let thousands = (integer.len().saturating_sub(1) / 3).min(SUFFIXES.len());
+    if thousands == 0 {
+        return (integer_or_zero(integer), decimal.to_string(), "");
+    }
+
+    let (head, tail) = integer.split_at(integer.len() - thousands * 3);
+    let decimal = format!("{tail}{decimal}");
+    let decimal = decimal[..SUFFIX_DECIMALS.min(decimal.len())].trim_end_matches('0');
+
+    (head.to_string(), decimal.to_string(), SUFFIXES[thousands - 1])
+}
+
+fn integer_or_zero(integer: &str) -> String {
+    if integer.is_empty() {
+        "0".to_string()
+    } else {
+        integer.to_string()
+    }
+}
+
+/// Digits grouped by three from the right
+fn group(integer: &str, separator: char) -> String {
+    let mut grouped = String::with_capacity(integer.len() + integer.len() / 3);
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(separator);
+        }
+        grouped.push(digit);
+    }
+
+    grouped
+}
+
+fn format_signed(value: &I256, format: impl FnOnce(U256) -> String) -> String {
+    let (sign, abs) = value.into_sign_and_abs();
+    format!("{}{}", if sign.is_positive() { "" } else { "-" }, format(abs))
+}
+
+/// `value` with `decimals` as `d.dddde<exponent>`, truncated to
+/// [`SCIENTIFIC_DECIMALS`] decimals
+fn format_scientific(value: U256, decimals: u8) -> String {
+    if value.is_zero() {
+        return "0".to_string();
+    }
+
+    let digits = value.to_string();
+    let exponent = digits.len() as i64 - 1 - decimals as i64;
+    let (first, rest) = digits.split_at(1);
+    let mantissa = rest[..rest.len().min(SCIENTIFIC_DECIMALS)].trim_end_matches('0');
+
+    if mantissa.is_empty() {
+        format!("{first}e{exponent}")
+    } else {
+        format!("{first}.{mantissa}e{exponent}")
+    }
+}
@@ -0,0 +1,74 @@
+use {
+    crate::{
+        balance_changes::BalanceChange, chain_adapter::ChainRules, contract::ERC4626::ERC4626Instance,
+        processor::PnlReport, rpc_metrics,
+    },
+    alloy::{
+        eips::BlockId,
+        primitives::{Address, I256},
+        providers::Provider,
+        transports::Transport,
+    },
+    alloy_chains::Chain,
+    eyre::Context,
+    schemars::JsonSchema,
+    serde::{Deserialize, Serialize},
+};
+
+/// Underlying assets a change of vault shares is worth
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Eq, PartialEq)]
+pub struct VaultAssets {
+    #[schemars(with = "String")]
+    pub vault: Address,
+    #[schemars(with = "String")]
+    pub asset: Address,
+    #[schemars(with = "String")]
+    pub assets: I256,
+}
+
+/// Value the vault share changes of a report in their underlying asset, as of
+/// the end of `block`. Assets that are the wrapped native token or a
+/// stablecoin are added to `pnl` or `stable_pnl`, so a deposit doesn't show
+/// as a loss.
+pub async fn value_shares<T: Clone + Transport>(
+    provider: &dyn Provider<T>,
+    chain: Chain,
+    rules: &ChainRules,
+    block: u64,
+    report: &mut PnlReport,
+) -> eyre::Result<()> {
+    for vault in report.vaults.clone() {
+        let Some(shares) = report.token_changes.get(&vault).copied() else {
+            continue;
+        };
+
+        rpc_metrics::record(chain, "eth_call", 2);
+        let erc4626 = ERC4626Instance::new(vault, provider.root());
+        let asset = erc4626
+            .asset()
+            .block(BlockId::number(block))
+            .call()
+            .await
+            .context("Failed to get vault asset")?
+            ._0;
+
+        let (sign, shares) = shares.into_sign_and_abs();
+        let assets = erc4626
+            .convertToAssets(shares)
+            .block(BlockId::number(block))
+            .call()
+            .await
+            .context("Failed to convert vault shares")?
+            ._0;
+        let assets = I256::checked_from_sign_and_abs(sign, assets).context("Vault assets overflow")?;
+
+        let mut underlying = BalanceChange::default();
+        underlying.insert(asset, assets);
+        report.stable_pnl += underlying.sum_stables(&rules.stables);
+        report.pnl += underlying.extract_ether(chain, rules);
+
+        report.vault_assets.push(VaultAssets { vault, asset, assets });
+    }
+
+    Ok(())
+}